@@ -0,0 +1,27 @@
+CREATE TABLE IF NOT EXISTS payment_events (
+  event_id UUID NOT NULL PRIMARY KEY,
+  payment_id UUID NOT NULL,
+  data_version INTEGER NOT NULL,
+  event_type VARCHAR(64) NOT NULL,
+  event_source VARCHAR(32) NOT NULL,
+  payload_ref TEXT,
+  previous_state VARCHAR(32),
+  new_state VARCHAR(32) NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL,
+  CONSTRAINT fk_payment
+    FOREIGN KEY(payment_id)
+    REFERENCES payments(payment_id)
+);
+
+CREATE INDEX IF NOT EXISTS payment_events_payment_id_idx
+  ON payment_events (payment_id, created_at);
+
+CREATE OR REPLACE FUNCTION payment_events_append_only() RETURNS TRIGGER AS $$
+BEGIN
+  RAISE EXCEPTION 'payment_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER payment_events_append_only
+  BEFORE UPDATE OR DELETE ON payment_events
+  FOR EACH ROW EXECUTE FUNCTION payment_events_append_only();
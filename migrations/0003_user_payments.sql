@@ -0,0 +1,18 @@
+-- A payment can be linked to both its payer and its payee, so the link is
+-- keyed by user and role rather than by payment alone.
+ALTER TABLE user_payments DROP CONSTRAINT user_payments_pkey;
+ALTER TABLE user_payments
+  DROP COLUMN data_version,
+  DROP COLUMN updated_at,
+  DROP COLUMN payment_data,
+  ADD COLUMN role VARCHAR(16) NOT NULL,
+  ADD PRIMARY KEY (payment_id, user_id, role),
+  ADD CONSTRAINT fk_payment
+    FOREIGN KEY(payment_id)
+    REFERENCES payments(payment_id),
+  ADD CONSTRAINT fk_user
+    FOREIGN KEY(user_id)
+    REFERENCES users(user_id);
+
+CREATE INDEX IF NOT EXISTS user_payments_user_id_idx
+  ON user_payments (user_id, role);
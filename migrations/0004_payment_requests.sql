@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS payment_requests (
+  request_id UUID NOT NULL PRIMARY KEY,
+  user_id UUID NOT NULL,
+  data_version INTEGER NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL,
+  updated_at TIMESTAMPTZ NOT NULL,
+  request_data JSONB NOT NULL DEFAULT '{}',
+  CONSTRAINT fk_user
+    FOREIGN KEY(user_id)
+    REFERENCES users(user_id)
+);
+
+CREATE INDEX IF NOT EXISTS payment_requests_user_id_idx
+  ON payment_requests (user_id, created_at DESC);
@@ -0,0 +1,20 @@
+CREATE TABLE IF NOT EXISTS scheduled_transfers (
+  schedule_id UUID NOT NULL PRIMARY KEY,
+  user_id UUID NOT NULL,
+  data_version INTEGER NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL,
+  updated_at TIMESTAMPTZ NOT NULL,
+  -- NULL unless the schedule is active, mirrors schedule_data for the scheduler
+  next_run_at TIMESTAMPTZ,
+  schedule_data JSONB NOT NULL DEFAULT '{}',
+  CONSTRAINT fk_user
+    FOREIGN KEY(user_id)
+    REFERENCES users(user_id)
+);
+
+CREATE INDEX IF NOT EXISTS scheduled_transfers_user_id_idx
+  ON scheduled_transfers (user_id, created_at DESC);
+
+CREATE INDEX IF NOT EXISTS scheduled_transfers_next_run_at_idx
+  ON scheduled_transfers (next_run_at)
+  WHERE next_run_at IS NOT NULL;
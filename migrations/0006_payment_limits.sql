@@ -0,0 +1,6 @@
+-- lookups of recent payments by party for the transfer limits
+CREATE INDEX IF NOT EXISTS payments_payer_email_idx
+  ON payments (lower(payment_data->>'payer_email'), created_at);
+
+CREATE INDEX IF NOT EXISTS payments_payee_email_idx
+  ON payments (lower(payment_data->>'payee_email'), created_at);
@@ -0,0 +1,6 @@
+-- payments waiting on an admin decision
+CREATE INDEX IF NOT EXISTS payments_held_for_review_idx
+  ON payments (created_at)
+  WHERE payment_data->'payment_statuses'->>'held_for_review_at' IS NOT NULL
+    AND payment_data->'payment_statuses'->>'review_released_at' IS NULL
+    AND payment_data->>'refund_data' IS NULL;
@@ -0,0 +1,4 @@
+-- payments still stored in the V1 shape, drained by the rewrite job
+CREATE INDEX IF NOT EXISTS payments_data_v1_idx
+  ON payments (payment_id)
+  WHERE payment_data->>'version' = 'V1';
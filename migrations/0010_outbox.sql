@@ -0,0 +1,24 @@
+-- side effects of payment updates, written in the same transaction as the
+-- update and delivered by the dispatcher
+CREATE TABLE IF NOT EXISTS outbox (
+  message_id UUID NOT NULL PRIMARY KEY,
+  payment_id UUID,
+  -- pending, delivered or dead
+  status VARCHAR(16) NOT NULL,
+  attempts INTEGER NOT NULL,
+  last_error TEXT,
+  next_attempt_at TIMESTAMPTZ NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL,
+  updated_at TIMESTAMPTZ NOT NULL,
+  message_data JSONB NOT NULL,
+  CONSTRAINT fk_payment
+    FOREIGN KEY(payment_id)
+    REFERENCES payments(payment_id)
+);
+
+CREATE INDEX IF NOT EXISTS outbox_pending_idx
+  ON outbox (next_attempt_at)
+  WHERE status = 'pending';
+
+CREATE INDEX IF NOT EXISTS outbox_status_idx
+  ON outbox (status, created_at DESC);
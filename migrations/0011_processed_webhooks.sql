@@ -0,0 +1,12 @@
+-- TrueLayer webhook events already applied, written in the same transaction
+-- as the payment update they made so a redelivered event is applied once
+CREATE TABLE IF NOT EXISTS processed_webhooks (
+  event_id UUID NOT NULL PRIMARY KEY,
+  payment_id UUID NOT NULL,
+  event_type VARCHAR(64) NOT NULL,
+  event_version INTEGER NOT NULL,
+  processed_at TIMESTAMPTZ NOT NULL,
+  CONSTRAINT fk_payment
+    FOREIGN KEY(payment_id)
+    REFERENCES payments(payment_id)
+);
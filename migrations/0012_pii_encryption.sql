@@ -0,0 +1,45 @@
+-- personal data is encrypted field by field and looked up by blind indexes,
+-- keyed hashes of its lowercase form. rows stored before are indexed with
+-- plain hashes until the rotation job encrypts them
+ALTER TABLE payments
+  ADD COLUMN IF NOT EXISTS pii_key_id VARCHAR(64),
+  ADD COLUMN IF NOT EXISTS payer_email_index BYTEA,
+  ADD COLUMN IF NOT EXISTS payee_email_index BYTEA;
+
+UPDATE payments SET
+  payer_email_index = sha256(convert_to(lower(payment_data->>'payer_email'), 'UTF8')),
+  payee_email_index = sha256(convert_to(lower(payment_data->>'payee_email'), 'UTF8'));
+
+ALTER TABLE payments
+  ALTER COLUMN payer_email_index SET NOT NULL,
+  ALTER COLUMN payee_email_index SET NOT NULL;
+
+DROP INDEX IF EXISTS payments_payer_email_idx;
+DROP INDEX IF EXISTS payments_payee_email_idx;
+
+CREATE INDEX IF NOT EXISTS payments_payer_email_index_idx
+  ON payments (payer_email_index, created_at);
+
+CREATE INDEX IF NOT EXISTS payments_payee_email_index_idx
+  ON payments (payee_email_index, created_at);
+
+CREATE INDEX IF NOT EXISTS payments_pii_key_id_idx
+  ON payments (pii_key_id);
+
+-- the email is kept as a json value, a string until encrypted
+ALTER TABLE users
+  ADD COLUMN IF NOT EXISTS pii_key_id VARCHAR(64),
+  ADD COLUMN IF NOT EXISTS email_index BYTEA;
+
+UPDATE users SET
+  email_index = sha256(convert_to(lower(email), 'UTF8'));
+
+ALTER TABLE users
+  ALTER COLUMN email_index SET NOT NULL,
+  ALTER COLUMN email TYPE JSONB USING to_jsonb(email);
+
+CREATE INDEX IF NOT EXISTS users_email_index_idx
+  ON users (email_index);
+
+CREATE INDEX IF NOT EXISTS users_pii_key_id_idx
+  ON users (pii_key_id);
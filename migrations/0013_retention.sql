@@ -0,0 +1,4 @@
+-- payments whose personal data the retention job has yet to erase
+CREATE INDEX IF NOT EXISTS payments_retention_idx
+  ON payments (state, updated_at)
+  WHERE payment_data->>'personal_data_erased_at' IS NULL;
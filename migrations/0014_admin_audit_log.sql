@@ -0,0 +1,40 @@
+-- every request an admin made and every change they made, appended to only
+CREATE TABLE IF NOT EXISTS admin_audit_log (
+  entry_id UUID NOT NULL PRIMARY KEY,
+  actor VARCHAR(255) NOT NULL,
+  action VARCHAR(64) NOT NULL,
+  -- payment, user or outbox_message
+  target_type VARCHAR(32),
+  target_id UUID,
+  -- method and path of a request entry
+  request TEXT,
+  before JSONB,
+  after JSONB,
+  ip VARCHAR(64),
+  created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS admin_audit_log_created_at_idx
+  ON admin_audit_log (created_at);
+
+CREATE INDEX IF NOT EXISTS admin_audit_log_actor_idx
+  ON admin_audit_log (actor, created_at);
+
+CREATE INDEX IF NOT EXISTS admin_audit_log_target_idx
+  ON admin_audit_log (target_id, created_at);
+
+CREATE OR REPLACE FUNCTION admin_audit_log_append_only() RETURNS trigger AS $$
+BEGIN
+  RAISE EXCEPTION 'admin_audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS admin_audit_log_append_only ON admin_audit_log;
+CREATE TRIGGER admin_audit_log_append_only
+  BEFORE UPDATE OR DELETE ON admin_audit_log
+  FOR EACH ROW EXECUTE FUNCTION admin_audit_log_append_only();
+
+DROP TRIGGER IF EXISTS admin_audit_log_no_truncate ON admin_audit_log;
+CREATE TRIGGER admin_audit_log_no_truncate
+  BEFORE TRUNCATE ON admin_audit_log
+  FOR EACH STATEMENT EXECUTE FUNCTION admin_audit_log_append_only();
@@ -0,0 +1,18 @@
+-- the plain indexes computed by 0012 lowercased emails in sql, which does not
+-- always agree with the gateway. rows still holding one are re-indexed by the
+-- rotation job, after which no email is indexed by a plain hash
+ALTER TABLE payments
+  ADD COLUMN IF NOT EXISTS legacy_email_index BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE payments SET legacy_email_index = TRUE WHERE pii_key_id IS NULL;
+
+CREATE INDEX IF NOT EXISTS payments_legacy_email_index_idx
+  ON payments (legacy_email_index) WHERE legacy_email_index;
+
+ALTER TABLE users
+  ADD COLUMN IF NOT EXISTS legacy_email_index BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE users SET legacy_email_index = TRUE WHERE pii_key_id IS NULL;
+
+CREATE INDEX IF NOT EXISTS users_legacy_email_index_idx
+  ON users (legacy_email_index) WHERE legacy_email_index;
@@ -0,0 +1,42 @@
+//! Embeds the workspace `migrations/` directory into the crate, one
+//! `(version, description, sql)` entry per `<VERSION>_<DESCRIPTION>.sql` file.
+
+use std::{env, fs, path::Path};
+
+fn main() {
+    let migrations_dir =
+        Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("../../migrations");
+    println!("cargo:rerun-if-changed={}", migrations_dir.display());
+
+    let mut migrations = fs::read_dir(&migrations_dir)
+        .expect("migrations directory")
+        .map(|entry| entry.unwrap().path())
+        .filter_map(|path| {
+            let file_name = path.file_name()?.to_str()?.to_owned();
+            let (version, description) = file_name.strip_suffix(".sql")?.split_once('_')?;
+            let version: i64 = version.parse().ok()?;
+            Some((
+                version,
+                description.replace('_', " "),
+                path.canonicalize().unwrap(),
+            ))
+        })
+        .collect::<Vec<_>>();
+    migrations.sort_by_key(|(version, ..)| *version);
+
+    let entries = migrations
+        .iter()
+        .map(|(version, description, path)| {
+            format!(
+                "    ({version}, {description:?}, include_str!({:?})),\n",
+                path.display().to_string()
+            )
+        })
+        .collect::<String>();
+
+    fs::write(
+        Path::new(&env::var("OUT_DIR").unwrap()).join("migrations.rs"),
+        format!("&[\n{entries}]\n"),
+    )
+    .unwrap();
+}
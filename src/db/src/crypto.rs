@@ -0,0 +1,406 @@
+use std::collections::HashMap;
+
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Nonce,
+};
+use anyhow::{anyhow, ensure, Context};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+const NONCE_LEN: usize = 12;
+
+/// Keys of the personal data stored in payments and users.
+#[derive(Deserialize, Debug, Clone)]
+pub struct EncryptionConfig {
+    /// Id of the key new data is encrypted with.
+    pub active_key_id: String,
+    /// Base64 256 bit keys by id. A retired key has to stay listed until the
+    /// rotation job has re-encrypted every row using it.
+    pub keys: HashMap<String, String>,
+    /// Base64 key of the blind indexes emails are looked up by. Unlike the
+    /// encryption keys it cannot be changed once in use.
+    pub index_key: String,
+    /// Whether emails are also looked up by the plain indexes of rows stored
+    /// before encryption. Turn off once the rotation job has rewritten every
+    /// row, so no lookup matches a plain hash again.
+    #[serde(default = "plain_index_lookups")]
+    pub plain_index_lookups: bool,
+}
+
+const fn plain_index_lookups() -> bool {
+    true
+}
+
+/// The form of an email its blind indexes are computed from, and two emails
+/// are the same by.
+pub fn normalize_email(email: &str) -> String {
+    email.to_lowercase()
+}
+
+/// An encrypted value. The value is encrypted with a data key of its own,
+/// which is stored wrapped by the configured key `kid`. Both are base64 of the
+/// nonce followed by the ciphertext.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Sealed {
+    kid: String,
+    dek: String,
+    ct: String,
+}
+
+/// Envelope encryption of single values of a row, and the blind indexes they
+/// are looked up by. Without keys values are stored in plaintext.
+pub(crate) struct Cipher {
+    active_key_id: Option<String>,
+    keys: HashMap<String, Aes256Gcm>,
+    index: BlindIndex,
+    plain_index_lookups: bool,
+}
+
+/// Blind indexes of emails: an HMAC of the normalized email under the index
+/// key, or a plain SHA-256 without one. They identify an email without
+/// revealing it, in the database and in logs.
+#[derive(Debug, Clone, Default)]
+pub struct BlindIndex {
+    key: Option<Vec<u8>>,
+}
+
+impl BlindIndex {
+    pub fn new(config: Option<&EncryptionConfig>) -> anyhow::Result<Self> {
+        let Some(config) = config else {
+            return Ok(BlindIndex::default());
+        };
+        let key = STANDARD
+            .decode(&config.index_key)
+            .context("invalid base64 index key")?;
+        ensure!(key.len() >= 32, "index key is shorter than 256 bits");
+        Ok(BlindIndex { key: Some(key) })
+    }
+
+    /// The blind index of `value` to store.
+    pub fn of(&self, value: &str) -> Vec<u8> {
+        let value = normalize_email(value);
+        match &self.key {
+            Some(key) => {
+                let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("any key length");
+                mac.update(value.as_bytes());
+                mac.finalize().into_bytes().to_vec()
+            }
+            None => Sha256::digest(value.as_bytes()).to_vec(),
+        }
+    }
+}
+
+impl Cipher {
+    pub(crate) fn new(config: Option<&EncryptionConfig>) -> anyhow::Result<Self> {
+        let Some(config) = config else {
+            return Ok(Cipher {
+                active_key_id: None,
+                keys: HashMap::new(),
+                index: BlindIndex::default(),
+                plain_index_lookups: false,
+            });
+        };
+
+        let keys = config
+            .keys
+            .iter()
+            .map(|(kid, key)| {
+                let key = STANDARD
+                    .decode(key)
+                    .with_context(|| format!("invalid base64 encryption key {kid}"))?;
+                let cipher = Aes256Gcm::new_from_slice(&key)
+                    .map_err(|_| anyhow!("encryption key {kid} is not 256 bits"))?;
+                Ok((kid.clone(), cipher))
+            })
+            .collect::<anyhow::Result<HashMap<_, _>>>()?;
+        ensure!(
+            keys.contains_key(&config.active_key_id),
+            "active encryption key {} is not configured",
+            config.active_key_id
+        );
+
+        Ok(Cipher {
+            active_key_id: Some(config.active_key_id.clone()),
+            keys,
+            index: BlindIndex::new(Some(config))?,
+            plain_index_lookups: config.plain_index_lookups,
+        })
+    }
+
+    /// The key new values are encrypted with, none when they are stored in
+    /// plaintext.
+    pub(crate) fn active_key_id(&self) -> Option<&str> {
+        self.active_key_id.as_deref()
+    }
+
+    /// Encrypts the `fields` of the object `data`, stored in the row `row_id`.
+    pub(crate) fn seal_fields(
+        &self,
+        data: &mut Value,
+        row_id: Uuid,
+        fields: &[&str],
+    ) -> anyhow::Result<()> {
+        for field in fields {
+            if let Some(value) = data.get_mut(*field) {
+                *value = self.seal(value.take(), row_id, field)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Decrypts the `fields` of the object `data`, read from the row `row_id`.
+    pub(crate) fn open_fields(
+        &self,
+        data: &mut Value,
+        row_id: Uuid,
+        fields: &[&str],
+    ) -> anyhow::Result<()> {
+        for field in fields {
+            if let Some(value) = data.get_mut(*field) {
+                *value = self.open(value.take(), row_id, field)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Encrypts `value` with the active key. The ciphertext is bound to the
+    /// row and field it is stored in, so it cannot be moved to another one.
+    /// Nulls are left as they are.
+    pub(crate) fn seal(&self, value: Value, row_id: Uuid, field: &str) -> anyhow::Result<Value> {
+        let Some(kid) = &self.active_key_id else {
+            return Ok(value);
+        };
+        if value.is_null() {
+            return Ok(value);
+        }
+
+        let dek = Aes256Gcm::generate_key(OsRng);
+        let plaintext = serde_json::to_vec(&value)?;
+        let ct = encrypt(
+            &Aes256Gcm::new(&dek),
+            &plaintext,
+            aad(row_id, field).as_bytes(),
+        )?;
+        let dek = encrypt(&self.keys[kid], &dek, kid.as_bytes())?;
+
+        Ok(serde_json::to_value(Sealed {
+            kid: kid.clone(),
+            dek: STANDARD.encode(dek),
+            ct: STANDARD.encode(ct),
+        })?)
+    }
+
+    /// Decrypts `value` if it was encrypted, with whichever key it names.
+    pub(crate) fn open(&self, value: Value, row_id: Uuid, field: &str) -> anyhow::Result<Value> {
+        let Ok(sealed) = Sealed::deserialize(&value) else {
+            return Ok(value);
+        };
+
+        let kek = self
+            .keys
+            .get(&sealed.kid)
+            .with_context(|| format!("encryption key {} is not configured", sealed.kid))?;
+        let dek = decrypt(kek, &STANDARD.decode(sealed.dek)?, sealed.kid.as_bytes())
+            .with_context(|| format!("data key of {row_id} {field}"))?;
+        let dek = Aes256Gcm::new_from_slice(&dek).map_err(|_| anyhow!("invalid data key"))?;
+        let plaintext = decrypt(
+            &dek,
+            &STANDARD.decode(sealed.ct)?,
+            aad(row_id, field).as_bytes(),
+        )
+        .with_context(|| format!("{row_id} {field}"))?;
+
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    /// The blind index of `value` to store, see [`BlindIndex`]. Rows stored
+    /// before encryption have plain SHA-256 indexes.
+    pub(crate) fn blind_index(&self, value: &str) -> Vec<u8> {
+        self.index.of(value)
+    }
+
+    /// The blind indexes a row holding `value` may have: the one it is stored
+    /// with now and, while plain index lookups are on, the plain one rows
+    /// stored in plaintext have.
+    pub(crate) fn blind_indexes(&self, value: &str) -> Vec<Vec<u8>> {
+        let mut indexes = vec![self.blind_index(value)];
+        if self.plain_index_lookups {
+            indexes.push(BlindIndex::default().of(value));
+        }
+        indexes
+    }
+}
+
+fn aad(row_id: Uuid, field: &str) -> String {
+    format!("{row_id}/{field}")
+}
+
+fn encrypt(cipher: &Aes256Gcm, plaintext: &[u8], aad: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ct = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
+        .map_err(|_| anyhow!("encryption failed"))?;
+    Ok([nonce.as_slice(), &ct].concat())
+}
+
+fn decrypt(cipher: &Aes256Gcm, sealed: &[u8], aad: &[u8]) -> anyhow::Result<Vec<u8>> {
+    ensure!(sealed.len() > NONCE_LEN, "ciphertext is too short");
+    let (nonce, ct) = sealed.split_at(NONCE_LEN);
+    cipher
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: ct, aad })
+        .map_err(|_| anyhow!("decryption failed"))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn key(byte: u8) -> String {
+        STANDARD.encode([byte; 32])
+    }
+
+    fn config(active_key_id: &str, kids: &[&str]) -> EncryptionConfig {
+        EncryptionConfig {
+            active_key_id: active_key_id.into(),
+            keys: kids
+                .iter()
+                .enumerate()
+                .map(|(i, kid)| (kid.to_string(), key(i as u8 + 1)))
+                .collect(),
+            index_key: key(0xaa),
+            plain_index_lookups: true,
+        }
+    }
+
+    fn cipher(active_key_id: &str, kids: &[&str]) -> Cipher {
+        Cipher::new(Some(&config(active_key_id, kids))).unwrap()
+    }
+
+    #[test]
+    fn seal_and_open_roundtrip() {
+        let cipher = cipher("k1", &["k1"]);
+        let row_id = Uuid::new_v4();
+        let value = json!({ "iban": "GB33BUKB20201555555555" });
+
+        let sealed = cipher
+            .seal(value.clone(), row_id, "payout_account")
+            .unwrap();
+        assert_ne!(sealed, value);
+        assert_eq!(sealed["kid"], "k1");
+        assert_eq!(
+            cipher.open(sealed, row_id, "payout_account").unwrap(),
+            value
+        );
+    }
+
+    #[test]
+    fn seal_leaves_nulls_and_plaintext_ciphers_alone() {
+        let row_id = Uuid::new_v4();
+        let cipher = cipher("k1", &["k1"]);
+        assert_eq!(
+            cipher.seal(Value::Null, row_id, "message").unwrap(),
+            Value::Null
+        );
+
+        let plaintext = Cipher::new(None).unwrap();
+        let value = json!("payer@example.com");
+        assert_eq!(
+            plaintext.seal(value.clone(), row_id, "email").unwrap(),
+            value
+        );
+        assert_eq!(
+            plaintext.open(value.clone(), row_id, "email").unwrap(),
+            value
+        );
+    }
+
+    #[test]
+    fn open_fails_for_another_row_or_field() {
+        let cipher = cipher("k1", &["k1"]);
+        let row_id = Uuid::new_v4();
+        let sealed = cipher
+            .seal(json!("secret"), row_id, "security_answer")
+            .unwrap();
+
+        assert!(cipher
+            .open(sealed.clone(), Uuid::new_v4(), "security_answer")
+            .is_err());
+        assert!(cipher.open(sealed, row_id, "message").is_err());
+    }
+
+    #[test]
+    fn open_uses_the_key_a_value_names() {
+        let row_id = Uuid::new_v4();
+        let old = cipher("k1", &["k1"]);
+        let sealed = old.seal(json!("secret"), row_id, "email").unwrap();
+
+        // k1 keeps its material while k2 becomes the active key
+        let rotated = cipher("k2", &["k1", "k2"]);
+        assert_eq!(rotated.active_key_id(), Some("k2"));
+        assert_eq!(
+            rotated.open(sealed.clone(), row_id, "email").unwrap(),
+            json!("secret")
+        );
+        let resealed = rotated.seal(json!("secret"), row_id, "email").unwrap();
+        assert_eq!(resealed["kid"], "k2");
+
+        let retired = cipher("k2", &["k2"]);
+        assert!(retired.open(sealed, row_id, "email").is_err());
+    }
+
+    #[test]
+    fn new_rejects_an_unconfigured_active_key() {
+        assert!(Cipher::new(Some(&config("k2", &["k1"]))).is_err());
+    }
+
+    #[test]
+    fn blind_index_ignores_case_and_depends_on_the_key() {
+        let keyed = BlindIndex::new(Some(&config("k1", &["k1"]))).unwrap();
+        assert_eq!(keyed.of("Payee@Example.com"), keyed.of("payee@example.com"));
+        assert_ne!(keyed.of("payee@example.com"), keyed.of("payer@example.com"));
+
+        let plain = BlindIndex::default();
+        assert_eq!(
+            plain.of("Payee@Example.com"),
+            Sha256::digest("payee@example.com").to_vec()
+        );
+        assert_ne!(keyed.of("payee@example.com"), plain.of("payee@example.com"));
+    }
+
+    #[test]
+    fn blind_indexes_include_the_plain_index_while_looked_up() {
+        let email = "payee@example.com";
+        let cipher = cipher("k1", &["k1"]);
+        assert_eq!(
+            cipher.blind_indexes(email),
+            [cipher.blind_index(email), BlindIndex::default().of(email)]
+        );
+
+        let config = EncryptionConfig {
+            plain_index_lookups: false,
+            ..config("k1", &["k1"])
+        };
+        let cipher = Cipher::new(Some(&config)).unwrap();
+        assert_eq!(cipher.blind_indexes(email), [cipher.blind_index(email)]);
+
+        let plaintext = Cipher::new(None).unwrap();
+        assert_eq!(
+            plaintext.blind_indexes(email),
+            [BlindIndex::default().of(email)]
+        );
+    }
+}
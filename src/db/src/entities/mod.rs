@@ -1,11 +1,18 @@
 pub mod v1;
+pub mod v2;
 
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::Context;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use tokio_postgres::types::Json;
 use uuid::Uuid;
 
-use crate::entities::v1::UserDataV1;
+use crate::entities::{
+    v1::{PaymentDataV1, UserDataV1},
+    v2::PaymentDataV2,
+};
 
 ////////////////////////////////////////////////////////////////////////////////
 // Payment
@@ -21,24 +28,36 @@ pub struct Payment {
 pub struct UserPayment {
     pub payment_id: Uuid,
     pub user_id: Uuid,
-    pub payment_data: Json<PaymentData>,
+    pub role: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "version")]
 pub enum PaymentData {
-    V1 {
-        payer_full_name: String,
-        payer_email: String,
-        payee_full_name: String,
-        payee_email: String,
-        amount: u32,
-        security_question: String,
-        security_answer: String,
-        payment_statuses: PaymentStatuses,
-        payout_data: Option<PayoutData>,
-        refund_data: Option<RefundData>,
-    },
+    V1(PaymentDataV1),
+    V2(PaymentDataV2),
+}
+
+impl PaymentData {
+    /// The data in the latest shape, upcasting rows written by older
+    /// versions. Such rows are stored in the latest shape on their next
+    /// update.
+    pub fn into_latest(self) -> PaymentDataV2 {
+        match self {
+            PaymentData::V1(data) => data.into(),
+            PaymentData::V2(data) => data,
+        }
+    }
+}
+
+/// ISO-4217 currency of a payment amount. Payments stored before multi
+/// currency support carry no currency and are read back as GBP.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Currency {
+    #[default]
+    Gbp,
+    Eur,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -74,6 +93,313 @@ pub struct PaymentStatuses {
     pub inbound_executed_at: Option<DateTime<Utc>>,
     pub inbound_settled_at: Option<DateTime<Utc>>,
     pub inbound_failed_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub expired_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub cancelled_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub held_for_review_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub review_released_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub payout_requested_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub refund_requested_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RiskAssessment {
+    pub score: u32,
+    pub reasons: Vec<String>,
+    pub assessed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancellationRequest {
+    pub token_hash: String,
+    pub requested_at: DateTime<Utc>,
+}
+
+/// How many payments a party made or received over a period and their
+/// summed amount.
+#[derive(Debug, Clone, Copy)]
+pub struct PaymentTotals {
+    pub count: i64,
+    pub amount_in_minor: i64,
+}
+
+/// An entry of the append-only `payment_events` history, written alongside
+/// every payment snapshot.
+#[derive(Debug, Clone)]
+pub struct PaymentEventRecord {
+    pub event_id: Uuid,
+    pub payment_id: Uuid,
+    pub event_type: String,
+    pub event_source: String,
+    pub payload_ref: Option<String>,
+    pub previous_state: Option<String>,
+    pub new_state: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Records written in the same transaction as a payment snapshot, so none is
+/// kept unless the snapshot is.
+#[derive(Debug, Clone, Default)]
+pub struct PaymentWrites {
+    pub outbox: Vec<OutboxMessage>,
+    /// A payout created for the payment, registered so it can be looked up by
+    /// its id.
+    pub payout_id: Option<Uuid>,
+    /// A refund created for the payment, registered so it can be looked up by
+    /// its id.
+    pub refund_id: Option<Uuid>,
+    /// The webhook the update was made from. A second update from the same
+    /// webhook fails with [`DbError::DuplicateWebhook`](crate::error::DbError).
+    pub webhook: Option<ProcessedWebhook>,
+}
+
+/// A TrueLayer webhook event applied to a payment.
+#[derive(Debug, Clone)]
+pub struct ProcessedWebhook {
+    pub event_id: Uuid,
+    pub event_type: String,
+    pub event_version: u32,
+    pub processed_at: DateTime<Utc>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Payment Query
+////////////////////////////////////////////////////////////////////////////////
+
+/// A page of payments matching `filter`, in `sort` order, starting after the
+/// `after` cursor returned with the previous page.
+#[derive(Debug, Clone, Default)]
+pub struct PaymentQuery {
+    pub filter: PaymentFilter,
+    pub sort: PaymentSort,
+    pub direction: SortDirection,
+    pub after: Option<PaymentCursor>,
+    pub limit: i64,
+}
+
+/// Which payments a [`PaymentQuery`] returns. Unset fields match every
+/// payment. Amounts are in minor units and include both bounds, time ranges
+/// include their start and exclude their end.
+#[derive(Debug, Clone, Default)]
+pub struct PaymentFilter {
+    /// Matches payments in any of these states, or in any state when empty.
+    pub states: Vec<String>,
+    /// Matched ignoring case.
+    pub payer_email: Option<String>,
+    /// Matched ignoring case.
+    pub payee_email: Option<String>,
+    pub min_amount: Option<i64>,
+    pub max_amount: Option<i64>,
+    pub created_from: Option<DateTime<Utc>>,
+    pub created_to: Option<DateTime<Utc>>,
+    pub updated_from: Option<DateTime<Utc>>,
+    pub updated_to: Option<DateTime<Utc>>,
+}
+
+/// The column a [`PaymentQuery`] orders by. Payments with equal values are
+/// ordered by id, so every payment has a single place in the order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PaymentSort {
+    #[default]
+    CreatedAt,
+    UpdatedAt,
+    Amount,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortDirection {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// The place of the last payment of a page in the order of its query: the
+/// sorted value and the payment id. Times are kept in microseconds since the
+/// epoch, the precision postgres stores them in.
+///
+/// Written as `<value>.<payment_id>` for use in links.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PaymentCursor {
+    pub sort_value: i64,
+    pub payment_id: Uuid,
+}
+
+impl Display for PaymentCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.sort_value, self.payment_id)
+    }
+}
+
+impl FromStr for PaymentCursor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (sort_value, payment_id) = s
+            .split_once('.')
+            .with_context(|| format!("Invalid payment cursor: {s}"))?;
+        Ok(PaymentCursor {
+            sort_value: sort_value.parse().context("cursor value")?,
+            payment_id: payment_id.parse().context("cursor payment id")?,
+        })
+    }
+}
+
+/// Payments matching a [`PaymentQuery`], with the cursor of the following
+/// page if there is one.
+#[derive(Debug, Clone)]
+pub struct PaymentPage<T> {
+    pub payments: Vec<(T, u32)>,
+    pub next: Option<PaymentCursor>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Outbox
+////////////////////////////////////////////////////////////////////////////////
+
+/// A side effect waiting to be delivered, or the record of its delivery.
+#[derive(Debug, Clone)]
+pub struct OutboxMessage {
+    pub message_id: Uuid,
+    pub payment_id: Option<Uuid>,
+    pub status: String,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub next_attempt_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub message_data: Json<OutboxMessageData>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum OutboxMessageData {
+    Email {
+        to: String,
+        subject: String,
+        body: String,
+    },
+    Payout {
+        payment_id: Uuid,
+    },
+    Refund {
+        payment_id: Uuid,
+    },
+    Settle {
+        payment_id: Uuid,
+        payer_accounts: Vec<v1::AccountIdentifier>,
+    },
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Admin Audit
+////////////////////////////////////////////////////////////////////////////////
+
+/// An entry of the append-only admin audit log.
+#[derive(Debug, Clone)]
+pub struct AdminAuditEntry {
+    pub entry_id: Uuid,
+    pub actor: String,
+    pub action: String,
+    pub target_type: Option<String>,
+    pub target_id: Option<Uuid>,
+    pub request: Option<String>,
+    pub before: Option<Json<serde_json::Value>>,
+    pub after: Option<Json<serde_json::Value>>,
+    pub ip: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Which audit entries to return, newest first. Unset fields match every
+/// entry, the time range includes its start and excludes its end.
+#[derive(Debug, Clone, Default)]
+pub struct AdminAuditQuery {
+    pub actor: Option<String>,
+    pub target_id: Option<Uuid>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Payment Request
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone)]
+pub struct PaymentRequest {
+    pub request_id: Uuid,
+    pub user_id: Uuid,
+    pub request_data: Json<PaymentRequestData>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "version")]
+pub enum PaymentRequestData {
+    V1 {
+        payer_email: String,
+        amount: u32,
+        currency: Currency,
+        note: String,
+        created_at: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+        payment_id: Option<Uuid>,
+        paid_at: Option<DateTime<Utc>>,
+        declined_at: Option<DateTime<Utc>>,
+        #[serde(default)]
+        claimed_at: Option<DateTime<Utc>>,
+    },
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Scheduled Transfer
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone)]
+pub struct ScheduledTransfer {
+    pub schedule_id: Uuid,
+    pub user_id: Uuid,
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub schedule_data: Json<ScheduledTransferData>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "version")]
+pub enum ScheduledTransferData {
+    V1 {
+        payee_full_name: String,
+        payee_email: String,
+        amount: u32,
+        currency: Currency,
+        security_question: String,
+        security_answer: String,
+        frequency: ScheduleFrequency,
+        starts_at: DateTime<Utc>,
+        occurrence: u32,
+        status: ScheduleStatus,
+        last_payment_id: Option<Uuid>,
+        last_run_at: Option<DateTime<Utc>>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleFrequency {
+    Once,
+    Weekly,
+    Monthly,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleStatus {
+    Active,
+    Paused,
+    Cancelled,
+    Completed,
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -1,10 +1,43 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::entities::{
+    CancellationRequest, Currency, PaymentStatuses, PayoutData, RefundData, RiskAssessment,
+};
 
 ////////////////////////////////////////////////////////////////////////////////
 // Payment
 ////////////////////////////////////////////////////////////////////////////////
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaymentDataV1 {
+    pub payer_full_name: String,
+    pub payer_email: String,
+    pub payee_full_name: String,
+    pub payee_email: String,
+    pub amount: u32,
+    #[serde(default)]
+    pub currency: Currency,
+    pub security_question: String,
+    pub security_answer: String,
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub failed_security_answer_attempts: u32,
+    #[serde(default)]
+    pub deposit_locked_until: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub cancellation_request: Option<CancellationRequest>,
+    #[serde(default)]
+    pub payment_request_id: Option<Uuid>,
+    #[serde(default)]
+    pub risk_assessment: Option<RiskAssessment>,
+    pub payment_statuses: PaymentStatuses,
+    pub payout_data: Option<PayoutData>,
+    pub refund_data: Option<RefundData>,
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 // User
 ////////////////////////////////////////////////////////////////////////////////
@@ -21,6 +54,31 @@ pub enum UserDataV1 {
     Registered {
         first_name: String,
         last_name: String,
+        #[serde(default)]
+        payout_account: Option<PayoutAccount>,
+    },
+    /// A user erased on request. Only the id is kept, so the payments linked
+    /// to them stay linked.
+    Erased { erased_at: DateTime<Utc> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PayoutAccount {
+    pub display_name: String,
+    pub currency: Currency,
+    pub account_identifier: AccountIdentifier,
+    pub verified_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AccountIdentifier {
+    Iban {
+        iban: String,
+    },
+    SortCodeAccountNumber {
+        sort_code: String,
+        account_number: String,
     },
 }
 
@@ -29,6 +87,7 @@ impl UserDataV1 {
         match self {
             UserDataV1::Registering { first_name, .. } => first_name,
             UserDataV1::Registered { first_name, .. } => first_name,
+            UserDataV1::Erased { .. } => "",
         }
     }
 
@@ -36,6 +95,7 @@ impl UserDataV1 {
         match self {
             UserDataV1::Registering { last_name, .. } => last_name,
             UserDataV1::Registered { last_name, .. } => last_name,
+            UserDataV1::Erased { .. } => "",
         }
     }
 }
@@ -0,0 +1,92 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::entities::{
+    v1::{AccountIdentifier, PaymentDataV1},
+    CancellationRequest, Currency, PaymentStatuses, PayoutData, RefundData, RiskAssessment,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+// Payment
+////////////////////////////////////////////////////////////////////////////////
+
+/// Fields read by queries and indexes (`payer_email`, `payee_email`, `amount`,
+/// `currency`, `payment_statuses`, `payout_data` and `refund_data`) keep their
+/// V1 names and place, so both shapes answer the same queries.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaymentDataV2 {
+    pub payer_full_name: String,
+    pub payer_email: String,
+    pub payee_full_name: String,
+    pub payee_email: String,
+    pub amount: u32,
+    pub currency: Currency,
+    pub message: Option<String>,
+    pub security_question: String,
+    pub security_answer: String,
+    pub deposit_lock: DepositLock,
+    pub cancellation_request: Option<CancellationRequest>,
+    pub payment_request_id: Option<Uuid>,
+    pub risk_assessment: Option<RiskAssessment>,
+    pub failure: Option<PaymentFailure>,
+    pub payment_statuses: PaymentStatuses,
+    pub payout_data: Option<PayoutData>,
+    pub refund_data: Option<RefundData>,
+    /// Where a requested payout goes, until TrueLayer created it.
+    #[serde(default)]
+    pub payout_destination: Option<PayoutDestination>,
+    /// When the names, emails and security answer were scrubbed from the
+    /// payment, leaving only its financial record.
+    #[serde(default)]
+    pub personal_data_erased_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepositLock {
+    pub failed_attempts: u32,
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PayoutDestination {
+    pub account_holder_name: String,
+    pub account_identifier: AccountIdentifier,
+}
+
+/// Why the inbound payment failed, as reported by TrueLayer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaymentFailure {
+    pub stage: String,
+    pub reason: String,
+}
+
+impl From<PaymentDataV1> for PaymentDataV2 {
+    fn from(value: PaymentDataV1) -> Self {
+        PaymentDataV2 {
+            payer_full_name: value.payer_full_name,
+            payer_email: value.payer_email,
+            payee_full_name: value.payee_full_name,
+            payee_email: value.payee_email,
+            amount: value.amount,
+            currency: value.currency,
+            message: value.message,
+            security_question: value.security_question,
+            security_answer: value.security_answer,
+            deposit_lock: DepositLock {
+                failed_attempts: value.failed_security_answer_attempts,
+                locked_until: value.deposit_locked_until,
+            },
+            cancellation_request: value.cancellation_request,
+            payment_request_id: value.payment_request_id,
+            risk_assessment: value.risk_assessment,
+            // V1 did not keep failure reasons
+            failure: None,
+            payment_statuses: value.payment_statuses,
+            payout_data: value.payout_data,
+            refund_data: value.refund_data,
+            payout_destination: None,
+            personal_data_erased_at: None,
+        }
+    }
+}
@@ -2,8 +2,18 @@
 pub enum DbError {
     #[error(transparent)]
     Postgres(#[from] tokio_postgres::Error),
+    #[error(transparent)]
+    Pool(#[from] deadpool_postgres::PoolError),
     #[error("Concurrent update error: version clash")]
     ConcurrentUpdate,
+    #[error("Webhook event {0} was already processed")]
+    DuplicateWebhook(uuid::Uuid),
+    #[error("Migration {0} was changed after it was applied")]
+    MigrationChanged(i64),
+    #[error("Migration {0} was applied but is missing from this build")]
+    MigrationMissing(i64),
+    #[error("Migration {0} previously failed and must be resolved by hand")]
+    MigrationFailed(i64),
     #[error(transparent)]
     Unknown(#[from] anyhow::Error),
 }
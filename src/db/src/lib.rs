@@ -1,16 +1,30 @@
+mod crypto;
 pub mod entities;
 pub mod error;
+mod memory;
+mod migrate;
+mod postgres;
+pub mod repository;
 
-use anyhow::Context;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
 use serde::Deserialize;
-use tokio_postgres::{Config, NoTls, Row};
 use uuid::Uuid;
 
 use self::{
-    entities::{Payment, User},
+    entities::{
+        AdminAuditEntry, AdminAuditQuery, OutboxMessage, Payment, PaymentEventRecord, PaymentPage,
+        PaymentQuery, PaymentRequest, PaymentTotals, PaymentWrites, ProcessedWebhook,
+        ScheduledTransfer, User, UserPayment,
+    },
     error::DbError,
+    repository::{AuditRepository, PaymentRepository, UserRepository},
 };
 
+pub use crypto::{normalize_email, BlindIndex, EncryptionConfig};
+pub use memory::MemoryStore;
+pub use postgres::PgClient;
 pub use tokio_postgres::types::Json;
 
 #[derive(Deserialize, Debug, Clone)]
@@ -20,68 +34,225 @@ pub struct DbConfig {
     pub port: u16,
     pub username: String,
     pub password: String,
+    /// Most connections the pool keeps open at once.
+    #[serde(default = "default_pool_size")]
+    pub pool_size: usize,
+    /// How long a query waits for a free connection, or for a new one to
+    /// connect, before failing.
+    #[serde(default = "default_acquire_timeout_secs")]
+    pub acquire_timeout_secs: u64,
+    /// Keys the personal data in payments and users is encrypted with. Left
+    /// out, it is stored in plaintext.
+    #[serde(default)]
+    pub encryption: Option<EncryptionConfig>,
+}
+
+fn default_pool_size() -> usize {
+    16
 }
 
+fn default_acquire_timeout_secs() -> u64 {
+    5
+}
+
+/// The storage used by the app, backed by postgres or held in memory. Reads
+/// convert the stored entities into whatever model the caller asks for.
+#[derive(Clone)]
 pub struct DbClient {
-    inner: tokio_postgres::Client,
+    payments: Arc<dyn PaymentRepository>,
+    users: Arc<dyn UserRepository>,
+    audit: Arc<dyn AuditRepository>,
 }
 
 impl DbClient {
+    pub fn new(
+        payments: Arc<dyn PaymentRepository>,
+        users: Arc<dyn UserRepository>,
+        audit: Arc<dyn AuditRepository>,
+    ) -> Self {
+        DbClient {
+            payments,
+            users,
+            audit,
+        }
+    }
+
     pub async fn connect(db_config: DbConfig) -> Result<Self, DbError> {
-        let (client, connection) = Config::new()
-            .dbname(&db_config.name)
-            .host(&db_config.host)
-            .port(db_config.port)
-            .user(&db_config.username)
-            .password(db_config.password)
-            .connect(NoTls)
-            .await?;
+        Ok(Self::postgres(PgClient::connect(db_config).await?))
+    }
+
+    pub fn postgres(client: PgClient) -> Self {
+        let client = Arc::new(client);
+        DbClient::new(client.clone(), client.clone(), client)
+    }
 
-        tokio::spawn(async move {
-            if let Err(e) = connection.await {
-                eprintln!("Connection error: {}", e);
-            }
-        });
+    /// Storage that lives and dies with the process, for running the app
+    /// without postgres.
+    pub fn in_memory() -> Self {
+        let store = Arc::new(MemoryStore::default());
+        DbClient::new(store.clone(), store.clone(), store)
+    }
 
-        Ok(DbClient { inner: client })
+    pub async fn upsert_payment<T, E>(
+        &self,
+        payment: T,
+        version: u32,
+        event: E,
+    ) -> Result<(), DbError>
+    where
+        T: Into<Payment>,
+        E: Into<PaymentEventRecord>,
+    {
+        self.payments
+            .upsert_payment(
+                payment.into(),
+                version,
+                event.into(),
+                PaymentWrites::default(),
+            )
+            .await
     }
 
-    pub async fn upsert_payment<T>(&self, payment: T, version: u32) -> Result<(), DbError>
+    /// Like [`DbClient::upsert_payment`], also queueing the side effects of
+    /// the update in the outbox. The messages are kept only if the update is.
+    pub async fn upsert_payment_with_outbox<T, E, M>(
+        &self,
+        payment: T,
+        version: u32,
+        event: E,
+        outbox: impl IntoIterator<Item = M>,
+    ) -> Result<(), DbError>
     where
         T: Into<Payment>,
+        E: Into<PaymentEventRecord>,
+        M: Into<OutboxMessage>,
     {
-        let payment = payment.into();
-        let version: i32 = version.try_into().context("version overflow")?;
-        let affected_rows = self
-            .inner
-            .execute(
-                r#"
-                INSERT INTO payments (
-                    payment_id,
-                    data_version,
-                    created_at,
-                    updated_at,
-                    payment_data
-                )
-                VALUES($1, $2, NOW(), NOW(), $3)
-                ON CONFLICT (payment_id) DO UPDATE SET
-                    data_version = $2,
-                    payment_data = $3,
-                    updated_at = NOW()
-                WHERE payments.data_version = $2 - 1
-                "#,
-                &[&payment.payment_id, &version, &payment.payment_data],
+        self.payments
+            .upsert_payment(
+                payment.into(),
+                version,
+                event.into(),
+                PaymentWrites {
+                    outbox: outbox.into_iter().map(Into::into).collect(),
+                    ..Default::default()
+                },
             )
-            .await?;
-
-        match affected_rows {
-            0 => Err(DbError::ConcurrentUpdate),
-            1 => Ok(()),
-            n => Err(DbError::Unknown(anyhow::anyhow!(
-                "More than one({}) row was updated",
-                n
-            ))),
-        }
+            .await
+    }
+
+    /// Like [`DbClient::upsert_payment_with_outbox`], also registering the
+    /// payout id of the payment, so its payout webhooks find the payment as
+    /// soon as the payout is stored.
+    pub async fn upsert_payment_with_payout<T, E, M>(
+        &self,
+        payment: T,
+        version: u32,
+        event: E,
+        payout_id: impl AsRef<Uuid>,
+        outbox: impl IntoIterator<Item = M>,
+    ) -> Result<(), DbError>
+    where
+        T: Into<Payment>,
+        E: Into<PaymentEventRecord>,
+        M: Into<OutboxMessage>,
+    {
+        self.payments
+            .upsert_payment(
+                payment.into(),
+                version,
+                event.into(),
+                PaymentWrites {
+                    outbox: outbox.into_iter().map(Into::into).collect(),
+                    payout_id: Some(*payout_id.as_ref()),
+                    ..Default::default()
+                },
+            )
+            .await
+    }
+
+    /// Like [`DbClient::upsert_payment_with_outbox`], also registering the
+    /// refund id of the payment, so its refund webhooks find the payment as
+    /// soon as the refund is stored.
+    pub async fn upsert_payment_with_refund<T, E, M>(
+        &self,
+        payment: T,
+        version: u32,
+        event: E,
+        refund_id: impl AsRef<Uuid>,
+        outbox: impl IntoIterator<Item = M>,
+    ) -> Result<(), DbError>
+    where
+        T: Into<Payment>,
+        E: Into<PaymentEventRecord>,
+        M: Into<OutboxMessage>,
+    {
+        self.payments
+            .upsert_payment(
+                payment.into(),
+                version,
+                event.into(),
+                PaymentWrites {
+                    outbox: outbox.into_iter().map(Into::into).collect(),
+                    refund_id: Some(*refund_id.as_ref()),
+                    ..Default::default()
+                },
+            )
+            .await
+    }
+
+    /// Like [`DbClient::upsert_payment`], also recording that the update was
+    /// made from `webhook` and queueing `outbox` with it. Fails with
+    /// [`DbError::DuplicateWebhook`], storing nothing, if an update was
+    /// already made from it.
+    pub async fn upsert_payment_from_webhook<T, E, M>(
+        &self,
+        payment: T,
+        version: u32,
+        event: E,
+        webhook: ProcessedWebhook,
+        outbox: impl IntoIterator<Item = M>,
+    ) -> Result<(), DbError>
+    where
+        T: Into<Payment>,
+        E: Into<PaymentEventRecord>,
+        M: Into<OutboxMessage>,
+    {
+        self.payments
+            .upsert_payment(
+                payment.into(),
+                version,
+                event.into(),
+                PaymentWrites {
+                    webhook: Some(webhook),
+                    outbox: outbox.into_iter().map(Into::into).collect(),
+                    ..Default::default()
+                },
+            )
+            .await
+    }
+
+    pub async fn rotate_payment_keys(&self, limit: i64) -> Result<usize, DbError> {
+        self.payments.rotate_payment_keys(limit).await
+    }
+
+    pub async fn is_webhook_processed(&self, event_id: Uuid) -> Result<bool, DbError> {
+        self.payments.is_webhook_processed(event_id).await
+    }
+
+    pub async fn get_payment_events<T>(
+        &self,
+        payment_id: impl AsRef<Uuid>,
+    ) -> Result<Vec<(T, u32)>, DbError>
+    where
+        T: From<PaymentEventRecord>,
+    {
+        Ok(self
+            .payments
+            .get_payment_events(*payment_id.as_ref())
+            .await?
+            .into_iter()
+            .map(|(value, version)| (T::from(value), version))
+            .collect())
     }
 
     pub async fn get_payment<T>(
@@ -91,22 +262,11 @@ impl DbClient {
     where
         T: From<Payment>,
     {
-        let row = self
-            .inner
-            .query_opt(
-                r#"
-                SELECT
-                    payment_id,
-                    data_version,
-                    payment_data
-                FROM payments
-                WHERE payment_id = $1
-                "#,
-                &[payment_id.as_ref()],
-            )
-            .await?;
-
-        row.map(payment_from_row).transpose()
+        Ok(self
+            .payments
+            .get_payment(*payment_id.as_ref())
+            .await?
+            .map(|(value, version)| (T::from(value), version)))
     }
 
     pub async fn get_payment_by_payout_id<T>(
@@ -116,46 +276,33 @@ impl DbClient {
     where
         T: From<Payment>,
     {
-        let row = self
-            .inner
-            .query_opt(
-                r#"
-                SELECT
-                    p.payment_id,
-                    p.data_version,
-                    p.payment_data
-                FROM 
-                    payout_id_to_payment_id pid
-                JOIN 
-                    payments p ON pid.payment_id = p.payment_id
-                WHERE 
-                    pid.payout_id = $1
-                "#,
-                &[payout_id.as_ref()],
-            )
-            .await?;
+        Ok(self
+            .payments
+            .get_payment_by_payout_id(*payout_id.as_ref())
+            .await?
+            .map(|(value, version)| (T::from(value), version)))
+    }
 
-        row.map(payment_from_row).transpose()
+    pub async fn get_payer_totals(
+        &self,
+        payer_email: &str,
+        currency: &str,
+        since: DateTime<Utc>,
+    ) -> Result<PaymentTotals, DbError> {
+        self.payments
+            .get_payer_totals(payer_email, currency, since)
+            .await
     }
 
-    pub async fn register_payout_id(
+    pub async fn get_payee_totals(
         &self,
-        payout_id: impl AsRef<Uuid>,
-        payment_id: impl AsRef<Uuid>,
-    ) -> Result<(), DbError> {
-        self.inner
-            .execute(
-                r#"
-                    INSERT INTO payments (
-                        payout_id,
-                        payment_id
-                    )
-                    VALUES($1, $2, NOW())
-                "#,
-                &[payout_id.as_ref(), payment_id.as_ref()],
-            )
-            .await?;
-        Ok(())
+        payee_email: &str,
+        currency: &str,
+        since: DateTime<Utc>,
+    ) -> Result<PaymentTotals, DbError> {
+        self.payments
+            .get_payee_totals(payee_email, currency, since)
+            .await
     }
 
     pub async fn get_payment_by_refund_id<T>(
@@ -165,187 +312,357 @@ impl DbClient {
     where
         T: From<Payment>,
     {
-        let row = self
-            .inner
-            .query_opt(
-                r#"
-                SELECT
-                    payment_id,
-                    data_version,
-                    payment_data
-                FROM payments
-                FROM 
-                    refund_id_to_payment_id pid
-                JOIN 
-                    payments p ON pid.payment_id = p.payment_id
-                WHERE 
-                    pid.refund_id = $1
-                "#,
-                &[refund_id.as_ref()],
-            )
-            .await?;
+        Ok(self
+            .payments
+            .get_payment_by_refund_id(*refund_id.as_ref())
+            .await?
+            .map(|(value, version)| (T::from(value), version)))
+    }
+
+    pub async fn get_unclaimed_payments<T>(
+        &self,
+        settled_before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<(T, u32)>, DbError>
+    where
+        T: From<Payment>,
+    {
+        Ok(self
+            .payments
+            .get_unclaimed_payments(settled_before, limit)
+            .await?
+            .into_iter()
+            .map(|(value, version)| (T::from(value), version))
+            .collect())
+    }
 
-        row.map(payment_from_row).transpose()
+    pub async fn get_held_payments<T>(
+        &self,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<(T, u32)>, DbError>
+    where
+        T: From<Payment>,
+    {
+        Ok(self
+            .payments
+            .get_held_payments(limit, offset)
+            .await?
+            .into_iter()
+            .map(|(value, version)| (T::from(value), version))
+            .collect())
     }
 
-    pub async fn get_payments<T>(&self, limit: i64, offset: i64) -> Result<Vec<(T, u32)>, DbError>
+    pub async fn get_payments_to_erase<T>(
+        &self,
+        state: &str,
+        updated_before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<(T, u32)>, DbError>
     where
         T: From<Payment>,
     {
-        let rows = self
-            .inner
-            .query(
-                r#"
-                SELECT
-                    payment_id,
-                    data_version,
-                    payment_data
-                FROM payments
-                LIMIT $1 
-                OFFSET $2
-                "#,
-                &[&limit, &offset],
-            )
-            .await?;
+        Ok(self
+            .payments
+            .get_payments_to_erase(state, updated_before, limit)
+            .await?
+            .into_iter()
+            .map(|(value, version)| (T::from(value), version))
+            .collect())
+    }
 
-        rows.into_iter()
-            .map(payment_from_row)
-            .collect::<Result<_, _>>()
+    pub async fn query_payments<T>(&self, query: &PaymentQuery) -> Result<PaymentPage<T>, DbError>
+    where
+        T: From<Payment>,
+    {
+        let page = self.payments.query_payments(query).await?;
+        Ok(PaymentPage {
+            payments: page
+                .payments
+                .into_iter()
+                .map(|(value, version)| (T::from(value), version))
+                .collect(),
+            next: page.next,
+        })
+    }
+
+    pub async fn upcast_payments(&self, limit: i64) -> Result<usize, DbError> {
+        self.payments.upcast_payments(limit).await
     }
 
     pub async fn upsert_user<T>(&self, user: T, version: u32) -> Result<(), DbError>
     where
         T: Into<User>,
     {
-        let user = user.into();
-        let version: i32 = version.try_into().context("version overflow")?;
-        let affected_rows = self
-            .inner
-            .execute(
-                r#"
-                INSERT INTO users (
-                    user_id,
-                    email,
-                    data_version,
-                    created_at,
-                    updated_at,
-                    user_data
-                )
-                VALUES($1, $2, $3, NOW(), NOW(), $4)
-                ON CONFLICT (user_id) DO UPDATE SET
-                    data_version = $3,
-                    user_data = $4,
-                    updated_at = NOW()
-                WHERE users.data_version = $3 - 1
-                "#,
-                &[&user.user_id, &user.email, &version, &user.user_data],
-            )
-            .await?;
-
-        match affected_rows {
-            0 => Err(DbError::ConcurrentUpdate),
-            1 => Ok(()),
-            n => Err(DbError::Unknown(anyhow::anyhow!(
-                "More than one({}) row was updated",
-                n
-            ))),
-        }
+        self.users.upsert_user(user.into(), version).await
     }
 
     pub async fn get_user<T>(&self, user_id: Uuid) -> Result<Option<(T, u32)>, DbError>
     where
         T: From<User>,
     {
-        let row = self
-            .inner
-            .query_opt(
-                r#"
-                SELECT
-                    user_id,
-                    email,
-                    data_version,
-                    user_data
-                FROM users
-                WHERE user_id = $1
-                "#,
-                &[&user_id],
-            )
-            .await?;
-
-        row.map(user_from_row).transpose()
+        Ok(self
+            .users
+            .get_user(user_id)
+            .await?
+            .map(|(value, version)| (T::from(value), version)))
     }
 
     pub async fn get_user_by_email<T>(&self, email: &str) -> Result<Option<(T, u32)>, DbError>
     where
         T: From<User>,
     {
-        let row = self
-            .inner
-            .query_opt(
-                r#"
-                SELECT
-                    user_id,
-                    email,
-                    data_version,
-                    user_data
-                FROM users
-                WHERE email = $1
-                "#,
-                &[&email],
-            )
-            .await?;
-
-        row.map(user_from_row).transpose()
+        Ok(self
+            .users
+            .get_user_by_email(email)
+            .await?
+            .map(|(value, version)| (T::from(value), version)))
     }
 
     pub async fn get_users<T>(&self, limit: i64, offset: i64) -> Result<Vec<(T, u32)>, DbError>
     where
         T: From<User>,
     {
-        let rows = self
-            .inner
-            .query(
-                r#"
-                SELECT
-                    user_id,
-                    email,
-                    data_version,
-                    user_data
-                FROM users
-                LIMIT $1 
-                OFFSET $2
-                "#,
-                &[&limit, &offset],
-            )
-            .await?;
+        Ok(self
+            .users
+            .get_users(limit, offset)
+            .await?
+            .into_iter()
+            .map(|(value, version)| (T::from(value), version))
+            .collect())
+    }
 
-        rows.into_iter()
-            .map(user_from_row)
-            .collect::<Result<_, _>>()
+    pub async fn link_user_payment<T>(&self, user_payment: T) -> Result<(), DbError>
+    where
+        T: Into<UserPayment>,
+    {
+        self.users.link_user_payment(user_payment.into()).await
     }
-}
 
-fn user_from_row<T>(row: Row) -> Result<(T, u32), DbError>
-where
-    T: From<User>,
-{
-    let user = User {
-        user_id: row.try_get(0)?,
-        email: row.try_get(1)?,
-        user_data: row.try_get(3)?,
-    };
-    let version: i32 = row.try_get(2)?;
-    Ok((T::from(user), version as _))
-}
+    pub async fn rotate_user_keys(&self, limit: i64) -> Result<usize, DbError> {
+        self.users.rotate_user_keys(limit).await
+    }
+
+    pub async fn link_user_payments_by_email(
+        &self,
+        user_id: impl AsRef<Uuid>,
+        email: &str,
+        payer_role: &str,
+        payee_role: &str,
+    ) -> Result<u64, DbError> {
+        self.users
+            .link_user_payments_by_email(*user_id.as_ref(), email, payer_role, payee_role)
+            .await
+    }
+
+    pub async fn get_user_payments<T>(
+        &self,
+        user_id: impl AsRef<Uuid>,
+        role: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<(T, u32)>, DbError>
+    where
+        T: From<Payment>,
+    {
+        Ok(self
+            .users
+            .get_user_payments(*user_id.as_ref(), role, limit, offset)
+            .await?
+            .into_iter()
+            .map(|(value, version)| (T::from(value), version))
+            .collect())
+    }
+
+    pub async fn insert_outbox_messages<M>(
+        &self,
+        messages: impl IntoIterator<Item = M>,
+    ) -> Result<(), DbError>
+    where
+        M: Into<OutboxMessage>,
+    {
+        self.payments
+            .insert_outbox_messages(messages.into_iter().map(Into::into).collect())
+            .await
+    }
+
+    pub async fn claim_outbox_messages<T>(
+        &self,
+        now: DateTime<Utc>,
+        lease_until: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<T>, DbError>
+    where
+        T: From<OutboxMessage>,
+    {
+        Ok(self
+            .payments
+            .claim_outbox_messages(now, lease_until, limit)
+            .await?
+            .into_iter()
+            .map(T::from)
+            .collect())
+    }
+
+    pub async fn update_outbox_message<T>(&self, message: T) -> Result<(), DbError>
+    where
+        T: Into<OutboxMessage>,
+    {
+        self.payments.update_outbox_message(message.into()).await
+    }
+
+    pub async fn get_outbox_message<T>(
+        &self,
+        message_id: impl AsRef<Uuid>,
+    ) -> Result<Option<T>, DbError>
+    where
+        T: From<OutboxMessage>,
+    {
+        Ok(self
+            .payments
+            .get_outbox_message(*message_id.as_ref())
+            .await?
+            .map(T::from))
+    }
+
+    pub async fn get_outbox_messages<T>(
+        &self,
+        status: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<T>, DbError>
+    where
+        T: From<OutboxMessage>,
+    {
+        Ok(self
+            .payments
+            .get_outbox_messages(status, limit, offset)
+            .await?
+            .into_iter()
+            .map(T::from)
+            .collect())
+    }
+
+    pub async fn upsert_payment_request<T>(&self, request: T, version: u32) -> Result<(), DbError>
+    where
+        T: Into<PaymentRequest>,
+    {
+        self.payments
+            .upsert_payment_request(request.into(), version)
+            .await
+    }
+
+    pub async fn get_payment_request<T>(
+        &self,
+        request_id: impl AsRef<Uuid>,
+    ) -> Result<Option<(T, u32)>, DbError>
+    where
+        T: From<PaymentRequest>,
+    {
+        Ok(self
+            .payments
+            .get_payment_request(*request_id.as_ref())
+            .await?
+            .map(|(value, version)| (T::from(value), version)))
+    }
+
+    pub async fn get_user_payment_requests<T>(
+        &self,
+        user_id: impl AsRef<Uuid>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<(T, u32)>, DbError>
+    where
+        T: From<PaymentRequest>,
+    {
+        Ok(self
+            .payments
+            .get_user_payment_requests(*user_id.as_ref(), limit, offset)
+            .await?
+            .into_iter()
+            .map(|(value, version)| (T::from(value), version))
+            .collect())
+    }
+
+    pub async fn upsert_scheduled_transfer<T>(
+        &self,
+        schedule: T,
+        version: u32,
+    ) -> Result<(), DbError>
+    where
+        T: Into<ScheduledTransfer>,
+    {
+        self.payments
+            .upsert_scheduled_transfer(schedule.into(), version)
+            .await
+    }
+
+    pub async fn get_scheduled_transfer<T>(
+        &self,
+        schedule_id: impl AsRef<Uuid>,
+    ) -> Result<Option<(T, u32)>, DbError>
+    where
+        T: From<ScheduledTransfer>,
+    {
+        Ok(self
+            .payments
+            .get_scheduled_transfer(*schedule_id.as_ref())
+            .await?
+            .map(|(value, version)| (T::from(value), version)))
+    }
 
-fn payment_from_row<T>(row: Row) -> Result<(T, u32), DbError>
-where
-    T: From<Payment>,
-{
-    let payment = Payment {
-        payment_id: row.try_get(0)?,
-        payment_data: row.try_get(2)?,
-    };
-    let version: i32 = row.try_get(1)?;
-    Ok((T::from(payment), version as _))
+    pub async fn get_user_scheduled_transfers<T>(
+        &self,
+        user_id: impl AsRef<Uuid>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<(T, u32)>, DbError>
+    where
+        T: From<ScheduledTransfer>,
+    {
+        Ok(self
+            .payments
+            .get_user_scheduled_transfers(*user_id.as_ref(), limit, offset)
+            .await?
+            .into_iter()
+            .map(|(value, version)| (T::from(value), version))
+            .collect())
+    }
+
+    pub async fn get_due_scheduled_transfers<T>(
+        &self,
+        now: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<(T, u32)>, DbError>
+    where
+        T: From<ScheduledTransfer>,
+    {
+        Ok(self
+            .payments
+            .get_due_scheduled_transfers(now, limit)
+            .await?
+            .into_iter()
+            .map(|(value, version)| (T::from(value), version))
+            .collect())
+    }
+
+    pub async fn insert_audit_entry<T>(&self, entry: T) -> Result<(), DbError>
+    where
+        T: Into<AdminAuditEntry>,
+    {
+        self.audit.insert_audit_entry(entry.into()).await
+    }
+
+    pub async fn query_audit_entries<T>(&self, query: &AdminAuditQuery) -> Result<Vec<T>, DbError>
+    where
+        T: From<AdminAuditEntry>,
+    {
+        Ok(self
+            .audit
+            .query_audit_entries(query)
+            .await?
+            .into_iter()
+            .map(T::from)
+            .collect())
+    }
 }
@@ -0,0 +1,1330 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard},
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::{
+    entities::{
+        v2::PaymentDataV2, AdminAuditEntry, AdminAuditQuery, Currency, OutboxMessage, Payment,
+        PaymentCursor, PaymentData, PaymentEventRecord, PaymentPage, PaymentQuery, PaymentRequest,
+        PaymentSort, PaymentTotals, PaymentWrites, ScheduledTransfer, SortDirection, User,
+        UserPayment,
+    },
+    error::DbError,
+    normalize_email,
+    repository::{AuditRepository, PaymentRepository, UserRepository},
+    Json,
+};
+
+/// Payments and users held in process memory, answering the same queries as
+/// postgres. Meant for tests and local runs, nothing survives a restart.
+#[derive(Default)]
+pub struct MemoryStore {
+    tables: Mutex<Tables>,
+}
+
+#[derive(Default)]
+struct Tables {
+    payments: HashMap<Uuid, Stored<Payment>>,
+    payment_states: HashMap<Uuid, String>,
+    payment_events: Vec<(PaymentEventRecord, u32)>,
+    outbox: Vec<OutboxMessage>,
+    processed_webhooks: HashMap<Uuid, Uuid>,
+    payout_ids: HashMap<Uuid, Uuid>,
+    refund_ids: HashMap<Uuid, Uuid>,
+    payment_requests: HashMap<Uuid, Stored<PaymentRequest>>,
+    scheduled_transfers: HashMap<Uuid, Stored<ScheduledTransfer>>,
+    users: HashMap<Uuid, Stored<User>>,
+    user_payments: Vec<(UserPayment, DateTime<Utc>)>,
+    audit_log: Vec<AdminAuditEntry>,
+}
+
+impl Tables {
+    /// Links the payment to the user unless already linked, returning whether
+    /// a link was added.
+    fn link_user_payment(&mut self, user_payment: UserPayment) -> bool {
+        let linked = self.user_payments.iter().any(|(linked, _)| {
+            linked.payment_id == user_payment.payment_id
+                && linked.user_id == user_payment.user_id
+                && linked.role == user_payment.role
+        });
+        if !linked {
+            self.user_payments.push((user_payment, Utc::now()));
+        }
+        !linked
+    }
+}
+
+struct Stored<T> {
+    value: T,
+    version: u32,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl MemoryStore {
+    fn tables(&self) -> MutexGuard<'_, Tables> {
+        // a panic while holding the lock leaves the tables as they were
+        self.tables.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// Stores `value` like the versioned upserts in postgres: inserted if new,
+/// otherwise only over the previous version.
+fn upsert<T>(
+    table: &mut HashMap<Uuid, Stored<T>>,
+    id: Uuid,
+    value: T,
+    version: u32,
+) -> Result<(), DbError> {
+    match table.get_mut(&id) {
+        Some(stored) if stored.version + 1 == version => {
+            stored.value = value;
+            stored.version = version;
+            stored.updated_at = Utc::now();
+            Ok(())
+        }
+        Some(_) => Err(DbError::ConcurrentUpdate),
+        None => {
+            let now = Utc::now();
+            table.insert(
+                id,
+                Stored {
+                    value,
+                    version,
+                    created_at: now,
+                    updated_at: now,
+                },
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Applies `offset` and `limit` to rows sorted with `cmp`.
+fn page<T: Clone>(
+    mut rows: Vec<&Stored<T>>,
+    cmp: impl FnMut(&&Stored<T>, &&Stored<T>) -> std::cmp::Ordering,
+    limit: i64,
+    offset: i64,
+) -> Vec<(T, u32)> {
+    rows.sort_by(cmp);
+    rows.into_iter()
+        .skip(offset.max(0) as usize)
+        .take(limit.max(0) as usize)
+        .map(|stored| (stored.value.clone(), stored.version))
+        .collect()
+}
+
+fn payment_data(payment: &Payment) -> PaymentDataV2 {
+    payment.payment_data.0.clone().into_latest()
+}
+
+/// Whether two emails are the same, as their blind indexes in postgres are.
+fn same_email(a: &str, b: &str) -> bool {
+    normalize_email(a) == normalize_email(b)
+}
+
+fn currency_code(currency: Currency) -> &'static str {
+    match currency {
+        Currency::Gbp => "GBP",
+        Currency::Eur => "EUR",
+    }
+}
+
+fn totals<'a>(
+    payments: impl Iterator<Item = &'a Stored<Payment>>,
+    email: impl Fn(&PaymentDataV2) -> &str,
+    party_email: &str,
+    currency: &str,
+    since: DateTime<Utc>,
+) -> PaymentTotals {
+    payments
+        .filter(|stored| stored.created_at >= since)
+        .map(|stored| payment_data(&stored.value))
+        .filter(|data| {
+            same_email(email(data), party_email)
+                && currency_code(data.currency) == currency
+                && data.payment_statuses.inbound_failed_at.is_none()
+        })
+        .fold(
+            PaymentTotals {
+                count: 0,
+                amount_in_minor: 0,
+            },
+            |totals, data| PaymentTotals {
+                count: totals.count + 1,
+                amount_in_minor: totals.amount_in_minor + i64::from(data.amount),
+            },
+        )
+}
+
+#[async_trait]
+impl PaymentRepository for MemoryStore {
+    async fn upsert_payment(
+        &self,
+        payment: Payment,
+        version: u32,
+        event: PaymentEventRecord,
+        writes: PaymentWrites,
+    ) -> Result<(), DbError> {
+        let mut tables = self.tables();
+        if let Some(webhook) = &writes.webhook {
+            if tables.processed_webhooks.contains_key(&webhook.event_id) {
+                return Err(DbError::DuplicateWebhook(webhook.event_id));
+            }
+        }
+        if let Some(payout_id) = writes.payout_id {
+            if tables.payout_ids.contains_key(&payout_id) {
+                return Err(DbError::Unknown(anyhow::anyhow!(
+                    "payout id {payout_id} already registered"
+                )));
+            }
+        }
+        if let Some(refund_id) = writes.refund_id {
+            if tables.refund_ids.contains_key(&refund_id) {
+                return Err(DbError::Unknown(anyhow::anyhow!(
+                    "refund id {refund_id} already registered"
+                )));
+            }
+        }
+        let payment_id = payment.payment_id;
+        upsert(&mut tables.payments, payment_id, payment, version)?;
+        if let Some(payout_id) = writes.payout_id {
+            tables.payout_ids.insert(payout_id, payment_id);
+        }
+        if let Some(refund_id) = writes.refund_id {
+            tables.refund_ids.insert(refund_id, payment_id);
+        }
+        if let Some(webhook) = writes.webhook {
+            tables
+                .processed_webhooks
+                .insert(webhook.event_id, payment_id);
+        }
+        tables.outbox.extend(writes.outbox);
+        tables
+            .payment_states
+            .insert(event.payment_id, event.new_state.clone());
+        tables.payment_events.push((event, version));
+        Ok(())
+    }
+
+    async fn get_payment_events(
+        &self,
+        payment_id: Uuid,
+    ) -> Result<Vec<(PaymentEventRecord, u32)>, DbError> {
+        let mut events = self
+            .tables()
+            .payment_events
+            .iter()
+            .filter(|(event, _)| event.payment_id == payment_id)
+            .cloned()
+            .collect::<Vec<_>>();
+        events.sort_by_key(|(event, version)| (*version, event.created_at));
+        Ok(events)
+    }
+
+    async fn get_payment(&self, payment_id: Uuid) -> Result<Option<(Payment, u32)>, DbError> {
+        Ok(self
+            .tables()
+            .payments
+            .get(&payment_id)
+            .map(|stored| (stored.value.clone(), stored.version)))
+    }
+
+    async fn get_payment_by_payout_id(
+        &self,
+        payout_id: Uuid,
+    ) -> Result<Option<(Payment, u32)>, DbError> {
+        let tables = self.tables();
+        Ok(tables
+            .payout_ids
+            .get(&payout_id)
+            .and_then(|payment_id| tables.payments.get(payment_id))
+            .map(|stored| (stored.value.clone(), stored.version)))
+    }
+
+    async fn get_payer_totals(
+        &self,
+        payer_email: &str,
+        currency: &str,
+        since: DateTime<Utc>,
+    ) -> Result<PaymentTotals, DbError> {
+        Ok(totals(
+            self.tables().payments.values(),
+            |data| &data.payer_email,
+            payer_email,
+            currency,
+            since,
+        ))
+    }
+
+    async fn get_payee_totals(
+        &self,
+        payee_email: &str,
+        currency: &str,
+        since: DateTime<Utc>,
+    ) -> Result<PaymentTotals, DbError> {
+        Ok(totals(
+            self.tables().payments.values(),
+            |data| &data.payee_email,
+            payee_email,
+            currency,
+            since,
+        ))
+    }
+
+    async fn get_payment_by_refund_id(
+        &self,
+        refund_id: Uuid,
+    ) -> Result<Option<(Payment, u32)>, DbError> {
+        let tables = self.tables();
+        Ok(tables
+            .refund_ids
+            .get(&refund_id)
+            .and_then(|payment_id| tables.payments.get(payment_id))
+            .map(|stored| (stored.value.clone(), stored.version)))
+    }
+
+    async fn get_unclaimed_payments(
+        &self,
+        settled_before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<(Payment, u32)>, DbError> {
+        let tables = self.tables();
+        let mut unclaimed = tables
+            .payments
+            .values()
+            .filter(|stored| tables.payment_states[&stored.value.payment_id] == "inbound_settled")
+            .filter_map(|stored| {
+                let settled_at = payment_data(&stored.value)
+                    .payment_statuses
+                    .inbound_settled_at
+                    .filter(|at| *at < settled_before)?;
+                Some((settled_at, stored.value.clone(), stored.version))
+            })
+            .collect::<Vec<_>>();
+        unclaimed.sort_by_key(|(settled_at, ..)| *settled_at);
+        Ok(unclaimed
+            .into_iter()
+            .take(limit.max(0) as usize)
+            .map(|(_, payment, version)| (payment, version))
+            .collect())
+    }
+
+    async fn get_held_payments(
+        &self,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<(Payment, u32)>, DbError> {
+        let tables = self.tables();
+        let held = tables
+            .payments
+            .values()
+            .filter(|stored| {
+                let data = payment_data(&stored.value);
+                data.payment_statuses.held_for_review_at.is_some()
+                    && data.payment_statuses.review_released_at.is_none()
+                    && data.refund_data.is_none()
+            })
+            .collect();
+        Ok(page(
+            held,
+            |a, b| a.created_at.cmp(&b.created_at),
+            limit,
+            offset,
+        ))
+    }
+
+    async fn get_payments_to_erase(
+        &self,
+        state: &str,
+        updated_before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<(Payment, u32)>, DbError> {
+        let tables = self.tables();
+        let due = tables
+            .payments
+            .values()
+            .filter(|stored| {
+                tables.payment_states[&stored.value.payment_id] == state
+                    && stored.updated_at < updated_before
+                    && payment_data(&stored.value)
+                        .personal_data_erased_at
+                        .is_none()
+            })
+            .collect();
+        Ok(page(due, |a, b| a.updated_at.cmp(&b.updated_at), limit, 0))
+    }
+
+    async fn query_payments(&self, query: &PaymentQuery) -> Result<PaymentPage<Payment>, DbError> {
+        let tables = self.tables();
+        let filter = &query.filter;
+        let mut payments = tables
+            .payments
+            .values()
+            .filter(|stored| {
+                let data = payment_data(&stored.value);
+                let amount = i64::from(data.amount);
+                let state = &tables.payment_states[&stored.value.payment_id];
+                (filter.states.is_empty() || filter.states.contains(state))
+                    && filter
+                        .payer_email
+                        .as_ref()
+                        .is_none_or(|email| same_email(&data.payer_email, email))
+                    && filter
+                        .payee_email
+                        .as_ref()
+                        .is_none_or(|email| same_email(&data.payee_email, email))
+                    && filter.min_amount.is_none_or(|min| amount >= min)
+                    && filter.max_amount.is_none_or(|max| amount <= max)
+                    && filter
+                        .created_from
+                        .is_none_or(|from| stored.created_at >= from)
+                    && filter.created_to.is_none_or(|to| stored.created_at < to)
+                    && filter
+                        .updated_from
+                        .is_none_or(|from| stored.updated_at >= from)
+                    && filter.updated_to.is_none_or(|to| stored.updated_at < to)
+            })
+            .map(|stored| {
+                let sort_value = match query.sort {
+                    PaymentSort::CreatedAt => stored.created_at.timestamp_micros(),
+                    PaymentSort::UpdatedAt => stored.updated_at.timestamp_micros(),
+                    PaymentSort::Amount => i64::from(payment_data(&stored.value).amount),
+                };
+                let cursor = PaymentCursor {
+                    sort_value,
+                    payment_id: stored.value.payment_id,
+                };
+                (cursor, stored)
+            })
+            .filter(|(cursor, _)| {
+                query.after.is_none_or(|after| match query.direction {
+                    SortDirection::Asc => *cursor > after,
+                    SortDirection::Desc => *cursor < after,
+                })
+            })
+            .collect::<Vec<_>>();
+        payments.sort_by_key(|(cursor, _)| *cursor);
+        if query.direction == SortDirection::Desc {
+            payments.reverse();
+        }
+
+        let limit = query.limit.max(0) as usize;
+        let next = payments
+            .get(limit)
+            .and(limit.checked_sub(1))
+            .map(|last| payments[last].0);
+        Ok(PaymentPage {
+            payments: payments
+                .into_iter()
+                .take(limit)
+                .map(|(_, stored)| (stored.value.clone(), stored.version))
+                .collect(),
+            next,
+        })
+    }
+
+    async fn upcast_payments(&self, limit: i64) -> Result<usize, DbError> {
+        let mut tables = self.tables();
+        let mut upcast = 0;
+        for stored in tables.payments.values_mut() {
+            if upcast as i64 >= limit {
+                break;
+            }
+            if let PaymentData::V1(data) = &stored.value.payment_data.0 {
+                stored.value.payment_data = Json(PaymentData::V2(data.clone().into()));
+                upcast += 1;
+            }
+        }
+        Ok(upcast)
+    }
+
+    /// Nothing is encrypted in memory.
+    async fn rotate_payment_keys(&self, _limit: i64) -> Result<usize, DbError> {
+        Ok(0)
+    }
+
+    async fn is_webhook_processed(&self, event_id: Uuid) -> Result<bool, DbError> {
+        Ok(self.tables().processed_webhooks.contains_key(&event_id))
+    }
+
+    async fn insert_outbox_messages(&self, messages: Vec<OutboxMessage>) -> Result<(), DbError> {
+        self.tables().outbox.extend(messages);
+        Ok(())
+    }
+
+    async fn claim_outbox_messages(
+        &self,
+        now: DateTime<Utc>,
+        lease_until: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<OutboxMessage>, DbError> {
+        let mut tables = self.tables();
+        let mut due = tables
+            .outbox
+            .iter_mut()
+            .filter(|message| message.status == "pending" && message.next_attempt_at <= now)
+            .collect::<Vec<_>>();
+        due.sort_by_key(|message| message.next_attempt_at);
+        let mut claimed = due
+            .into_iter()
+            .take(limit.max(0) as usize)
+            .map(|message| {
+                message.next_attempt_at = lease_until;
+                message.clone()
+            })
+            .collect::<Vec<_>>();
+        claimed.sort_by_key(|message| message.created_at);
+        Ok(claimed)
+    }
+
+    async fn update_outbox_message(&self, message: OutboxMessage) -> Result<(), DbError> {
+        let mut tables = self.tables();
+        if let Some(stored) = tables
+            .outbox
+            .iter_mut()
+            .find(|stored| stored.message_id == message.message_id)
+        {
+            stored.status = message.status;
+            stored.attempts = message.attempts;
+            stored.last_error = message.last_error;
+            stored.next_attempt_at = message.next_attempt_at;
+        }
+        Ok(())
+    }
+
+    async fn get_outbox_message(&self, message_id: Uuid) -> Result<Option<OutboxMessage>, DbError> {
+        Ok(self
+            .tables()
+            .outbox
+            .iter()
+            .find(|message| message.message_id == message_id)
+            .cloned())
+    }
+
+    async fn get_outbox_messages(
+        &self,
+        status: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<OutboxMessage>, DbError> {
+        let mut messages = self
+            .tables()
+            .outbox
+            .iter()
+            .filter(|message| message.status == status)
+            .cloned()
+            .collect::<Vec<_>>();
+        messages.sort_by_key(|message| std::cmp::Reverse(message.created_at));
+        Ok(messages
+            .into_iter()
+            .skip(offset.max(0) as usize)
+            .take(limit.max(0) as usize)
+            .collect())
+    }
+
+    async fn upsert_payment_request(
+        &self,
+        request: PaymentRequest,
+        version: u32,
+    ) -> Result<(), DbError> {
+        upsert(
+            &mut self.tables().payment_requests,
+            request.request_id,
+            request,
+            version,
+        )
+    }
+
+    async fn get_payment_request(
+        &self,
+        request_id: Uuid,
+    ) -> Result<Option<(PaymentRequest, u32)>, DbError> {
+        Ok(self
+            .tables()
+            .payment_requests
+            .get(&request_id)
+            .map(|stored| (stored.value.clone(), stored.version)))
+    }
+
+    async fn get_user_payment_requests(
+        &self,
+        user_id: Uuid,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<(PaymentRequest, u32)>, DbError> {
+        let tables = self.tables();
+        let requests = tables
+            .payment_requests
+            .values()
+            .filter(|stored| stored.value.user_id == user_id)
+            .collect();
+        Ok(page(
+            requests,
+            |a, b| b.created_at.cmp(&a.created_at),
+            limit,
+            offset,
+        ))
+    }
+
+    async fn upsert_scheduled_transfer(
+        &self,
+        schedule: ScheduledTransfer,
+        version: u32,
+    ) -> Result<(), DbError> {
+        upsert(
+            &mut self.tables().scheduled_transfers,
+            schedule.schedule_id,
+            schedule,
+            version,
+        )
+    }
+
+    async fn get_scheduled_transfer(
+        &self,
+        schedule_id: Uuid,
+    ) -> Result<Option<(ScheduledTransfer, u32)>, DbError> {
+        Ok(self
+            .tables()
+            .scheduled_transfers
+            .get(&schedule_id)
+            .map(|stored| (stored.value.clone(), stored.version)))
+    }
+
+    async fn get_user_scheduled_transfers(
+        &self,
+        user_id: Uuid,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<(ScheduledTransfer, u32)>, DbError> {
+        let tables = self.tables();
+        let schedules = tables
+            .scheduled_transfers
+            .values()
+            .filter(|stored| stored.value.user_id == user_id)
+            .collect();
+        Ok(page(
+            schedules,
+            |a, b| b.created_at.cmp(&a.created_at),
+            limit,
+            offset,
+        ))
+    }
+
+    async fn get_due_scheduled_transfers(
+        &self,
+        now: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<(ScheduledTransfer, u32)>, DbError> {
+        let tables = self.tables();
+        let due = tables
+            .scheduled_transfers
+            .values()
+            .filter(|stored| stored.value.next_run_at.is_some_and(|at| at <= now))
+            .collect();
+        Ok(page(
+            due,
+            |a, b| a.value.next_run_at.cmp(&b.value.next_run_at),
+            limit,
+            0,
+        ))
+    }
+}
+
+#[async_trait]
+impl UserRepository for MemoryStore {
+    async fn upsert_user(&self, user: User, version: u32) -> Result<(), DbError> {
+        upsert(&mut self.tables().users, user.user_id, user, version)
+    }
+
+    async fn get_user(&self, user_id: Uuid) -> Result<Option<(User, u32)>, DbError> {
+        Ok(self
+            .tables()
+            .users
+            .get(&user_id)
+            .map(|stored| (stored.value.clone(), stored.version)))
+    }
+
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<(User, u32)>, DbError> {
+        Ok(self
+            .tables()
+            .users
+            .values()
+            .find(|stored| same_email(&stored.value.email, email))
+            .map(|stored| (stored.value.clone(), stored.version)))
+    }
+
+    async fn get_users(&self, limit: i64, offset: i64) -> Result<Vec<(User, u32)>, DbError> {
+        let tables = self.tables();
+        let users = tables.users.values().collect();
+        Ok(page(
+            users,
+            |a, b| a.created_at.cmp(&b.created_at),
+            limit,
+            offset,
+        ))
+    }
+
+    /// Nothing is encrypted in memory.
+    async fn rotate_user_keys(&self, _limit: i64) -> Result<usize, DbError> {
+        Ok(0)
+    }
+
+    async fn link_user_payment(&self, user_payment: UserPayment) -> Result<(), DbError> {
+        self.tables().link_user_payment(user_payment);
+        Ok(())
+    }
+
+    async fn link_user_payments_by_email(
+        &self,
+        user_id: Uuid,
+        email: &str,
+        payer_role: &str,
+        payee_role: &str,
+    ) -> Result<u64, DbError> {
+        let mut tables = self.tables();
+        let links = tables
+            .payments
+            .values()
+            .flat_map(|stored| {
+                let data = payment_data(&stored.value);
+                let payment_id = stored.value.payment_id;
+                [
+                    same_email(&data.payer_email, email).then_some((payment_id, payer_role)),
+                    same_email(&data.payee_email, email).then_some((payment_id, payee_role)),
+                ]
+            })
+            .flatten()
+            .collect::<Vec<_>>();
+
+        let linked = links
+            .into_iter()
+            .filter(|(payment_id, role)| {
+                tables.link_user_payment(UserPayment {
+                    payment_id: *payment_id,
+                    user_id,
+                    role: (*role).to_owned(),
+                })
+            })
+            .count();
+        Ok(linked as u64)
+    }
+
+    async fn get_user_payments(
+        &self,
+        user_id: Uuid,
+        role: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<(Payment, u32)>, DbError> {
+        let tables = self.tables();
+        let payments = tables
+            .user_payments
+            .iter()
+            .filter(|(link, _)| link.user_id == user_id && link.role == role)
+            .filter_map(|(link, _)| tables.payments.get(&link.payment_id))
+            .collect();
+        Ok(page(
+            payments,
+            |a, b| b.created_at.cmp(&a.created_at),
+            limit,
+            offset,
+        ))
+    }
+}
+
+#[async_trait]
+impl AuditRepository for MemoryStore {
+    async fn insert_audit_entry(&self, entry: AdminAuditEntry) -> Result<(), DbError> {
+        self.tables().audit_log.push(entry);
+        Ok(())
+    }
+
+    async fn query_audit_entries(
+        &self,
+        query: &AdminAuditQuery,
+    ) -> Result<Vec<AdminAuditEntry>, DbError> {
+        let tables = self.tables();
+        let mut entries = tables
+            .audit_log
+            .iter()
+            .filter(|entry| {
+                query
+                    .actor
+                    .as_ref()
+                    .is_none_or(|actor| entry.actor == *actor)
+                    && query
+                        .target_id
+                        .is_none_or(|target_id| entry.target_id == Some(target_id))
+                    && query.from.is_none_or(|from| entry.created_at >= from)
+                    && query.to.is_none_or(|to| entry.created_at < to)
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| {
+            b.created_at
+                .cmp(&a.created_at)
+                .then(a.entry_id.cmp(&b.entry_id))
+        });
+        Ok(entries
+            .into_iter()
+            .skip(query.offset.max(0) as usize)
+            .take(query.limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use serde_json::json;
+
+    use super::*;
+    use crate::entities::{v1::UserDataV1, OutboxMessageData, ProcessedWebhook, UserData};
+
+    fn payment(payment_id: Uuid, amount: u32, created_at: DateTime<Utc>) -> Payment {
+        let payment_data = serde_json::from_value(json!({
+            "version": "V2",
+            "payer_full_name": "Payer",
+            "payer_email": "payer@example.com",
+            "payee_full_name": "Payee",
+            "payee_email": "payee@example.com",
+            "amount": amount,
+            "currency": "GBP",
+            "security_question": "question",
+            "security_answer": "answer",
+            "deposit_lock": { "failed_attempts": 0 },
+            "payment_statuses": { "inbound_created_at": created_at },
+        }))
+        .unwrap();
+        Payment {
+            payment_id,
+            payment_data: Json(payment_data),
+        }
+    }
+
+    /// A store of `count` payments whose amounts and creation times repeat,
+    /// so pages often end between payments of the same sort value.
+    fn store(count: u32) -> MemoryStore {
+        let store = MemoryStore::default();
+        let mut tables = store.tables();
+        let start = Utc::now();
+        for i in 0..count {
+            let payment_id = Uuid::new_v4();
+            let created_at = start + Duration::minutes((i / 3).into());
+            tables.payments.insert(
+                payment_id,
+                Stored {
+                    value: payment(payment_id, 100 * (i % 4), created_at),
+                    version: 0,
+                    created_at,
+                    updated_at: created_at + Duration::seconds((i % 5).into()),
+                },
+            );
+            tables
+                .payment_states
+                .insert(payment_id, String::from("inbound_created"));
+        }
+        drop(tables);
+        store
+    }
+
+    /// Every page of `query`, following the cursors until there is none.
+    async fn pages(store: &MemoryStore, mut query: PaymentQuery) -> Vec<Vec<Uuid>> {
+        let mut pages = Vec::new();
+        loop {
+            let page = store.query_payments(&query).await.unwrap();
+            pages.push(
+                page.payments
+                    .into_iter()
+                    .map(|(payment, _)| payment.payment_id)
+                    .collect(),
+            );
+            match page.next {
+                Some(next) => query.after = Some(next),
+                None => return pages,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn keyset_pages_have_no_gaps_or_repeats() {
+        let store = store(23);
+        for sort in [
+            PaymentSort::CreatedAt,
+            PaymentSort::UpdatedAt,
+            PaymentSort::Amount,
+        ] {
+            for direction in [SortDirection::Asc, SortDirection::Desc] {
+                let query = |limit| PaymentQuery {
+                    filter: Default::default(),
+                    sort,
+                    direction,
+                    after: None,
+                    limit,
+                };
+                let all = pages(&store, query(100)).await;
+                assert_eq!(all.len(), 1);
+                assert_eq!(all[0].len(), 23);
+
+                let pages = pages(&store, query(4)).await;
+                assert_eq!(pages.len(), 6, "{sort:?} {direction:?}");
+                assert!(pages.iter().all(|page| !page.is_empty()));
+                assert_eq!(pages.concat(), all[0], "{sort:?} {direction:?}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn keyset_directions_are_reverses() {
+        let store = store(10);
+        let query = |direction| PaymentQuery {
+            filter: Default::default(),
+            sort: PaymentSort::Amount,
+            direction,
+            after: None,
+            limit: 3,
+        };
+        let asc = pages(&store, query(SortDirection::Asc)).await.concat();
+        let mut desc = pages(&store, query(SortDirection::Desc)).await.concat();
+        desc.reverse();
+        assert_eq!(asc, desc);
+    }
+
+    #[tokio::test]
+    async fn a_full_last_page_has_no_next_cursor() {
+        let store = store(8);
+        let pages = pages(
+            &store,
+            PaymentQuery {
+                filter: Default::default(),
+                sort: PaymentSort::CreatedAt,
+                direction: SortDirection::Desc,
+                after: None,
+                limit: 4,
+            },
+        )
+        .await;
+        assert_eq!(pages.iter().map(Vec::len).collect::<Vec<_>>(), [4, 4]);
+    }
+
+    fn settled_event(payment_id: Uuid) -> PaymentEventRecord {
+        PaymentEventRecord {
+            event_id: Uuid::new_v4(),
+            payment_id,
+            event_type: String::from("inbound_settled"),
+            event_source: String::from("webhook"),
+            payload_ref: None,
+            previous_state: None,
+            new_state: String::from("inbound_settled"),
+            created_at: Utc::now(),
+        }
+    }
+
+    fn settle_message(payment_id: Uuid) -> OutboxMessage {
+        OutboxMessage {
+            message_id: Uuid::new_v4(),
+            payment_id: Some(payment_id),
+            status: String::from("pending"),
+            attempts: 0,
+            last_error: None,
+            next_attempt_at: Utc::now(),
+            created_at: Utc::now(),
+            message_data: Json(OutboxMessageData::Settle {
+                payment_id,
+                payer_accounts: Vec::new(),
+            }),
+        }
+    }
+
+    #[tokio::test]
+    async fn a_webhook_updates_a_payment_once() {
+        let store = MemoryStore::default();
+        let payment_id = Uuid::new_v4();
+        let webhook = ProcessedWebhook {
+            event_id: Uuid::new_v4(),
+            event_type: String::from("payment_settled"),
+            event_version: 1,
+            processed_at: Utc::now(),
+        };
+        let writes = || PaymentWrites {
+            outbox: vec![settle_message(payment_id)],
+            webhook: Some(webhook.clone()),
+            ..Default::default()
+        };
+        assert!(!store.is_webhook_processed(webhook.event_id).await.unwrap());
+
+        store
+            .upsert_payment(
+                payment(payment_id, 100, Utc::now()),
+                0,
+                settled_event(payment_id),
+                writes(),
+            )
+            .await
+            .unwrap();
+        assert!(store.is_webhook_processed(webhook.event_id).await.unwrap());
+
+        let err = store
+            .upsert_payment(
+                payment(payment_id, 100, Utc::now()),
+                1,
+                settled_event(payment_id),
+                writes(),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, DbError::DuplicateWebhook(event_id) if event_id == webhook.event_id));
+
+        // nothing of the duplicate was stored
+        let tables = store.tables();
+        assert_eq!(tables.payments[&payment_id].version, 0);
+        assert_eq!(tables.payment_events.len(), 1);
+        assert_eq!(tables.outbox.len(), 1);
+    }
+
+    /// A payment settled at `settled_at` and stored in `state`.
+    async fn store_settled(store: &MemoryStore, settled_at: DateTime<Utc>, state: &str) -> Uuid {
+        let payment_id = Uuid::new_v4();
+        let mut payment = payment(payment_id, 100, settled_at - Duration::hours(1));
+        let PaymentData::V2(data) = &mut payment.payment_data.0 else {
+            unreachable!("payments are stored as V2");
+        };
+        data.payment_statuses.inbound_settled_at = Some(settled_at);
+        if state == "expired" {
+            data.payment_statuses.expired_at = Some(Utc::now());
+        }
+        let event = PaymentEventRecord {
+            new_state: state.into(),
+            ..settled_event(payment_id)
+        };
+        store
+            .upsert_payment(payment, 0, event, PaymentWrites::default())
+            .await
+            .unwrap();
+        payment_id
+    }
+
+    #[tokio::test]
+    async fn unclaimed_payments_are_only_settled_ones() {
+        let store = MemoryStore::default();
+        let settled_at = Utc::now() - Duration::days(2);
+        let unclaimed = store_settled(&store, settled_at, "inbound_settled").await;
+        store_settled(&store, settled_at, "expired").await;
+        store_settled(&store, settled_at, "refund_requested").await;
+        store_settled(&store, Utc::now(), "inbound_settled").await;
+
+        let payments = store
+            .get_unclaimed_payments(Utc::now() - Duration::days(1), 10)
+            .await
+            .unwrap();
+        let ids: Vec<_> = payments
+            .iter()
+            .map(|(payment, _)| payment.payment_id)
+            .collect();
+        assert_eq!(ids, [unclaimed]);
+    }
+
+    #[tokio::test]
+    async fn a_claimed_message_is_leased_until_it_is_due_again() {
+        let store = MemoryStore::default();
+        let message = settle_message(Uuid::new_v4());
+        let message_id = message.message_id;
+        store.insert_outbox_messages(vec![message]).await.unwrap();
+
+        let now = Utc::now();
+        let lease_until = now + Duration::minutes(5);
+        let claimed = store
+            .claim_outbox_messages(now, lease_until, 10)
+            .await
+            .unwrap();
+        assert_eq!(claimed.len(), 1);
+        assert_eq!(claimed[0].message_id, message_id);
+
+        // another dispatcher finds nothing while the lease runs
+        let claimed = store
+            .claim_outbox_messages(now, lease_until, 10)
+            .await
+            .unwrap();
+        assert!(claimed.is_empty());
+
+        let claimed = store
+            .claim_outbox_messages(lease_until, lease_until + Duration::minutes(5), 10)
+            .await
+            .unwrap();
+        assert_eq!(claimed.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn a_payout_id_is_registered_only_with_its_update() {
+        let store = MemoryStore::default();
+        let payment_id = Uuid::new_v4();
+        let payout_id = Uuid::new_v4();
+        let writes = || PaymentWrites {
+            outbox: vec![settle_message(payment_id)],
+            payout_id: Some(payout_id),
+            ..Default::default()
+        };
+        store
+            .upsert_payment(
+                payment(payment_id, 100, Utc::now()),
+                0,
+                settled_event(payment_id),
+                PaymentWrites::default(),
+            )
+            .await
+            .unwrap();
+
+        // a lost version race registers nothing
+        let err = store
+            .upsert_payment(
+                payment(payment_id, 100, Utc::now()),
+                5,
+                settled_event(payment_id),
+                writes(),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, DbError::ConcurrentUpdate));
+        assert!(store
+            .get_payment_by_payout_id(payout_id)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(store.tables().outbox.is_empty());
+
+        store
+            .upsert_payment(
+                payment(payment_id, 100, Utc::now()),
+                1,
+                settled_event(payment_id),
+                writes(),
+            )
+            .await
+            .unwrap();
+        let (found, _) = store
+            .get_payment_by_payout_id(payout_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.payment_id, payment_id);
+
+        // the id cannot be registered for another payment
+        let other_id = Uuid::new_v4();
+        assert!(store
+            .upsert_payment(
+                payment(other_id, 100, Utc::now()),
+                0,
+                settled_event(other_id),
+                writes(),
+            )
+            .await
+            .is_err());
+        assert!(store.get_payment(other_id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn payment_history_keeps_every_stored_update_in_order() {
+        let store = MemoryStore::default();
+        let payment_id = Uuid::new_v4();
+        let event = |previous: Option<&str>, new: &str| PaymentEventRecord {
+            event_type: new.into(),
+            previous_state: previous.map(Into::into),
+            new_state: new.into(),
+            ..settled_event(payment_id)
+        };
+        for (version, (previous, new)) in [
+            (None, "inbound_created"),
+            (Some("inbound_created"), "inbound_authorized"),
+            (Some("inbound_authorized"), "inbound_settled"),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            store
+                .upsert_payment(
+                    payment(payment_id, 100, Utc::now()),
+                    version as u32,
+                    event(previous, new),
+                    PaymentWrites::default(),
+                )
+                .await
+                .unwrap();
+        }
+        // a stale update leaves no history behind
+        assert!(store
+            .upsert_payment(
+                payment(payment_id, 100, Utc::now()),
+                1,
+                event(Some("inbound_created"), "expired"),
+                PaymentWrites::default(),
+            )
+            .await
+            .is_err());
+
+        let history = store.get_payment_events(payment_id).await.unwrap();
+        let states: Vec<_> = history
+            .iter()
+            .map(|(event, version)| (*version, event.new_state.as_str()))
+            .collect();
+        assert_eq!(
+            states,
+            [
+                (0, "inbound_created"),
+                (1, "inbound_authorized"),
+                (2, "inbound_settled")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn payments_are_linked_to_a_user_by_email_once() {
+        let store = MemoryStore::default();
+        for _ in 0..2 {
+            let payment_id = Uuid::new_v4();
+            store
+                .upsert_payment(
+                    payment(payment_id, 100, Utc::now()),
+                    0,
+                    settled_event(payment_id),
+                    PaymentWrites::default(),
+                )
+                .await
+                .unwrap();
+        }
+        let user_id = Uuid::new_v4();
+
+        let linked = store
+            .link_user_payments_by_email(user_id, "Payer@Example.com", "payer", "payee")
+            .await
+            .unwrap();
+        assert_eq!(linked, 2);
+        let linked = store
+            .link_user_payments_by_email(user_id, "payer@example.com", "payer", "payee")
+            .await
+            .unwrap();
+        assert_eq!(linked, 0);
+
+        let sent = store
+            .get_user_payments(user_id, "payer", 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(sent.len(), 2);
+        let received = store
+            .get_user_payments(user_id, "payee", 10, 0)
+            .await
+            .unwrap();
+        assert!(received.is_empty());
+    }
+
+    /// A payment as stored before multi currency support and the deposit
+    /// lock.
+    fn v1_payment(payment_id: Uuid) -> Payment {
+        let payment_data = serde_json::from_value(json!({
+            "version": "V1",
+            "payer_full_name": "Payer",
+            "payer_email": "payer@example.com",
+            "payee_full_name": "Payee",
+            "payee_email": "payee@example.com",
+            "amount": 100,
+            "security_question": "question",
+            "security_answer": "answer",
+            "failed_security_answer_attempts": 2,
+            "payment_statuses": { "inbound_created_at": Utc::now() },
+            "payout_data": null,
+            "refund_data": null,
+        }))
+        .unwrap();
+        Payment {
+            payment_id,
+            payment_data: Json(payment_data),
+        }
+    }
+
+    #[test]
+    fn v1_payments_read_as_the_latest_shape() {
+        let data = v1_payment(Uuid::new_v4()).payment_data.0.into_latest();
+        assert!(matches!(data.currency, Currency::Gbp));
+        assert_eq!(data.amount, 100);
+        assert_eq!(data.deposit_lock.failed_attempts, 2);
+        assert!(data.deposit_lock.locked_until.is_none());
+        assert!(data.failure.is_none());
+    }
+
+    #[tokio::test]
+    async fn upcasting_rewrites_v1_payments_in_batches_without_a_new_version() {
+        let store = MemoryStore::default();
+        for _ in 0..3 {
+            let payment_id = Uuid::new_v4();
+            store
+                .upsert_payment(
+                    v1_payment(payment_id),
+                    0,
+                    settled_event(payment_id),
+                    PaymentWrites::default(),
+                )
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(store.upcast_payments(2).await.unwrap(), 2);
+        assert_eq!(store.upcast_payments(2).await.unwrap(), 1);
+        assert_eq!(store.upcast_payments(2).await.unwrap(), 0);
+        let tables = store.tables();
+        assert!(tables.payments.values().all(|stored| {
+            stored.version == 0 && matches!(stored.value.payment_data.0, PaymentData::V2(_))
+        }));
+    }
+
+    fn user(user_id: Uuid, email: &str, first_name: &str) -> User {
+        User {
+            user_id,
+            email: email.into(),
+            user_data: Json(UserData::V1(UserDataV1::Registered {
+                first_name: first_name.into(),
+                last_name: String::from("Person"),
+                payout_account: None,
+            })),
+        }
+    }
+
+    #[tokio::test]
+    async fn users_are_updated_only_over_their_stored_version() {
+        let store = MemoryStore::default();
+        let user_id = Uuid::new_v4();
+        store
+            .upsert_user(user(user_id, "user@example.com", "First"), 0)
+            .await
+            .unwrap();
+        store
+            .upsert_user(user(user_id, "user@example.com", "Second"), 1)
+            .await
+            .unwrap();
+        let err = store
+            .upsert_user(user(user_id, "user@example.com", "Stale"), 1)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, DbError::ConcurrentUpdate));
+
+        let (found, version) = store
+            .get_user_by_email("User@Example.com")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.user_id, user_id);
+        assert_eq!(version, 1);
+        let UserData::V1(data) = &found.user_data.0;
+        assert_eq!(data.first_name(), "Second");
+        assert!(store
+            .get_user_by_email("other@example.com")
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,159 @@
+use std::time::Instant;
+
+use sha2::{Digest, Sha384};
+
+use crate::{error::DbError, PgClient};
+
+/// The workspace migrations as `(version, description, sql)`, in version order.
+const MIGRATIONS: &[(i64, &str, &str)] = include!(concat!(env!("OUT_DIR"), "/migrations.rs"));
+
+/// Held while migrating so two gateways starting together do not both apply
+/// the same migration.
+const MIGRATION_LOCK_ID: i64 = 0x6574_7261_6e73;
+
+/// Checks the `(version, success, checksum)` of the applied migrations against
+/// `migrations`.
+fn check_applied(
+    migrations: &[(i64, &str, &str)],
+    applied: &[(i64, bool, Vec<u8>)],
+) -> Result<(), DbError> {
+    for (version, success, checksum) in applied {
+        if !success {
+            return Err(DbError::MigrationFailed(*version));
+        }
+        match migrations.iter().find(|(v, ..)| v == version) {
+            Some((_, _, sql)) if Sha384::digest(sql).as_slice() == checksum => {}
+            Some(_) => return Err(DbError::MigrationChanged(*version)),
+            None => return Err(DbError::MigrationMissing(*version)),
+        }
+    }
+    Ok(())
+}
+
+impl PgClient {
+    /// Applies the embedded migrations the database has not seen yet and
+    /// returns their versions. Applied migrations are recorded in the same
+    /// `_sqlx_migrations` table the sqlx cli uses, so databases migrated by
+    /// either stay interchangeable.
+    ///
+    /// Fails without applying anything if an applied migration no longer
+    /// matches its embedded sql, is missing from this build, or previously
+    /// failed part way.
+    pub async fn migrate(&self) -> Result<Vec<i64>, DbError> {
+        let mut client = self.client().await?;
+        client
+            .batch_execute(
+                r#"
+                CREATE TABLE IF NOT EXISTS _sqlx_migrations (
+                    version BIGINT PRIMARY KEY,
+                    description TEXT NOT NULL,
+                    installed_on TIMESTAMPTZ NOT NULL DEFAULT now(),
+                    success BOOLEAN NOT NULL,
+                    checksum BYTEA NOT NULL,
+                    execution_time BIGINT NOT NULL
+                )
+                "#,
+            )
+            .await?;
+        client
+            .execute("SELECT pg_advisory_lock($1)", &[&MIGRATION_LOCK_ID])
+            .await?;
+
+        let result = async {
+            let applied = client
+                .query(
+                    "SELECT version, success, checksum FROM _sqlx_migrations ORDER BY version",
+                    &[],
+                )
+                .await?
+                .into_iter()
+                .map(|row| {
+                    let version: i64 = row.try_get("version")?;
+                    let success: bool = row.try_get("success")?;
+                    let checksum: Vec<u8> = row.try_get("checksum")?;
+                    Ok((version, success, checksum))
+                })
+                .collect::<Result<Vec<_>, DbError>>()?;
+
+            check_applied(MIGRATIONS, &applied)?;
+
+            let mut newly_applied = Vec::new();
+            for (version, description, sql) in MIGRATIONS {
+                if applied.iter().any(|(v, ..)| v == version) {
+                    continue;
+                }
+
+                let started = Instant::now();
+                let transaction = client.transaction().await?;
+                transaction.batch_execute(sql).await?;
+                let execution_time =
+                    i64::try_from(started.elapsed().as_nanos()).unwrap_or(i64::MAX);
+                transaction
+                    .execute(
+                        r#"
+                        INSERT INTO _sqlx_migrations (
+                            version,
+                            description,
+                            success,
+                            checksum,
+                            execution_time
+                        )
+                        VALUES ($1, $2, TRUE, $3, $4)
+                        "#,
+                        &[
+                            version,
+                            description,
+                            &Sha384::digest(sql).as_slice(),
+                            &execution_time,
+                        ],
+                    )
+                    .await?;
+                transaction.commit().await?;
+                newly_applied.push(*version);
+            }
+            Ok(newly_applied)
+        }
+        .await;
+
+        client
+            .execute("SELECT pg_advisory_unlock($1)", &[&MIGRATION_LOCK_ID])
+            .await?;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQL: &str = "CREATE TABLE payments (payment_id UUID PRIMARY KEY)";
+
+    fn applied(version: i64, sql: &str) -> (i64, bool, Vec<u8>) {
+        (version, true, Sha384::digest(sql).to_vec())
+    }
+
+    #[test]
+    fn embedded_migrations_are_in_version_order() {
+        assert!(!MIGRATIONS.is_empty());
+        assert!(MIGRATIONS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn applied_migrations_must_match_their_sql() {
+        let migrations = [(1, "payments", SQL)];
+        assert!(check_applied(&migrations, &[]).is_ok());
+        assert!(check_applied(&migrations, &[applied(1, SQL)]).is_ok());
+        assert!(matches!(
+            check_applied(&migrations, &[applied(1, "CREATE TABLE users ()")]),
+            Err(DbError::MigrationChanged(1))
+        ));
+        assert!(matches!(
+            check_applied(&migrations, &[applied(1, SQL), applied(2, SQL)]),
+            Err(DbError::MigrationMissing(2))
+        ));
+        assert!(matches!(
+            check_applied(&migrations, &[(1, false, Sha384::digest(SQL).to_vec())]),
+            Err(DbError::MigrationFailed(1))
+        ));
+    }
+}
@@ -0,0 +1,1709 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use deadpool_postgres::{
+    Manager, ManagerConfig, Object, Pool, RecyclingMethod, Runtime, Transaction,
+};
+use serde_json::Value;
+use tokio_postgres::{
+    types::{Json, ToSql},
+    Config, NoTls, Row,
+};
+use uuid::Uuid;
+
+use crate::{
+    crypto::Cipher,
+    entities::{
+        AdminAuditEntry, AdminAuditQuery, OutboxMessage, Payment, PaymentCursor, PaymentData,
+        PaymentEventRecord, PaymentPage, PaymentQuery, PaymentRequest, PaymentSort, PaymentTotals,
+        PaymentWrites, ProcessedWebhook, ScheduledTransfer, SortDirection, User, UserPayment,
+    },
+    error::DbError,
+    repository::{AuditRepository, PaymentRepository, UserRepository},
+    DbConfig,
+};
+
+/// A pool of postgres connections. Connections are checked with a test query
+/// before they are handed out, so one dropped by a postgres restart is
+/// replaced rather than failing the query using it.
+pub struct PgClient {
+    inner: Pool,
+    cipher: Cipher,
+}
+
+impl PgClient {
+    pub async fn connect(db_config: DbConfig) -> Result<Self, DbError> {
+        let cipher = Cipher::new(db_config.encryption.as_ref()).context("encryption keys")?;
+
+        let mut pg_config = Config::new();
+        pg_config
+            .dbname(&db_config.name)
+            .host(&db_config.host)
+            .port(db_config.port)
+            .user(&db_config.username)
+            .password(&db_config.password);
+
+        let manager = Manager::from_config(
+            pg_config,
+            NoTls,
+            ManagerConfig {
+                recycling_method: RecyclingMethod::Verified,
+            },
+        );
+        let acquire_timeout = Duration::from_secs(db_config.acquire_timeout_secs);
+        let pool = Pool::builder(manager)
+            .max_size(db_config.pool_size)
+            .wait_timeout(Some(acquire_timeout))
+            .create_timeout(Some(acquire_timeout))
+            .recycle_timeout(Some(acquire_timeout))
+            .runtime(Runtime::Tokio1)
+            .build()
+            .context("postgres pool")?;
+
+        // fail on startup rather than on the first request
+        drop(pool.get().await?);
+
+        Ok(PgClient {
+            inner: pool,
+            cipher,
+        })
+    }
+
+    pub(crate) async fn client(&self) -> Result<Object, DbError> {
+        Ok(self.inner.get().await?)
+    }
+
+    /// Stores `payment_data` over the payment at `version` without bumping
+    /// it, encrypted with the active key. Skipped if the payment was updated
+    /// since it was read.
+    async fn rewrite_payment(
+        &self,
+        client: &Object,
+        payment_id: Uuid,
+        version: u32,
+        payment_data: &Json<PaymentData>,
+    ) -> Result<(), DbError> {
+        let version: i32 = version.try_into().context("version overflow")?;
+        let sealed = seal_payment(&self.cipher, payment_id, payment_data)?;
+        client
+            .execute(
+                r#"
+                UPDATE payments
+                SET payment_data = $3,
+                    pii_key_id = $4,
+                    payer_email_index = $5,
+                    payee_email_index = $6,
+                    legacy_email_index = FALSE
+                WHERE payment_id = $1
+                    AND data_version = $2
+                "#,
+                &[
+                    &payment_id,
+                    &version,
+                    &sealed.payment_data,
+                    &self.cipher.active_key_id(),
+                    &sealed.payer_email_index,
+                    &sealed.payee_email_index,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PaymentRepository for PgClient {
+    async fn upsert_payment(
+        &self,
+        payment: Payment,
+        version: u32,
+        event: PaymentEventRecord,
+        writes: PaymentWrites,
+    ) -> Result<(), DbError> {
+        let version: i32 = version.try_into().context("version overflow")?;
+        let sealed = seal_payment(&self.cipher, payment.payment_id, &payment.payment_data)?;
+        let mut client = self.client().await?;
+        let transaction = client.transaction().await?;
+        let affected_rows = transaction
+            .execute(
+                r#"
+                WITH upserted AS (
+                    INSERT INTO payments (
+                        payment_id,
+                        data_version,
+                        created_at,
+                        updated_at,
+                        payment_data,
+                        state,
+                        pii_key_id,
+                        payer_email_index,
+                        payee_email_index
+                    )
+                    VALUES($1, $2, NOW(), NOW(), $3, $9, $11, $12, $13)
+                    ON CONFLICT (payment_id) DO UPDATE SET
+                        data_version = $2,
+                        payment_data = $3,
+                        state = $9,
+                        pii_key_id = $11,
+                        payer_email_index = $12,
+                        payee_email_index = $13,
+                        legacy_email_index = FALSE,
+                        updated_at = NOW()
+                    WHERE payments.data_version = $2 - 1
+                    RETURNING payment_id, data_version
+                )
+                INSERT INTO payment_events (
+                    event_id,
+                    payment_id,
+                    data_version,
+                    event_type,
+                    event_source,
+                    payload_ref,
+                    previous_state,
+                    new_state,
+                    created_at
+                )
+                SELECT $4::UUID, payment_id, data_version, $5, $6, $7, $8, $9, $10
+                FROM upserted
+                "#,
+                &[
+                    &payment.payment_id,
+                    &version,
+                    &sealed.payment_data,
+                    &event.event_id,
+                    &event.event_type,
+                    &event.event_source,
+                    &event.payload_ref,
+                    &event.previous_state,
+                    &event.new_state,
+                    &event.created_at,
+                    &self.cipher.active_key_id(),
+                    &sealed.payer_email_index,
+                    &sealed.payee_email_index,
+                ],
+            )
+            .await?;
+
+        match affected_rows {
+            0 => return Err(DbError::ConcurrentUpdate),
+            1 => {}
+            n => {
+                return Err(DbError::Unknown(anyhow::anyhow!(
+                    "More than one({}) row was updated",
+                    n
+                )))
+            }
+        }
+
+        if let Some(payout_id) = writes.payout_id {
+            transaction
+                .execute(
+                    r#"
+                    INSERT INTO payout_id_to_payment_id (
+                        payout_id,
+                        payment_id,
+                        created_at
+                    )
+                    VALUES($1, $2, NOW())
+                    "#,
+                    &[&payout_id, &payment.payment_id],
+                )
+                .await?;
+        }
+        if let Some(refund_id) = writes.refund_id {
+            transaction
+                .execute(
+                    r#"
+                    INSERT INTO refund_id_to_payment_id (
+                        refund_id,
+                        payment_id,
+                        created_at
+                    )
+                    VALUES($1, $2, NOW())
+                    "#,
+                    &[&refund_id, &payment.payment_id],
+                )
+                .await?;
+        }
+        if let Some(webhook) = writes.webhook {
+            insert_processed_webhook(&transaction, payment.payment_id, webhook).await?;
+        }
+        for message in writes.outbox {
+            insert_outbox_message(&transaction, message).await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn get_payment_events(
+        &self,
+        payment_id: Uuid,
+    ) -> Result<Vec<(PaymentEventRecord, u32)>, DbError> {
+        let rows = self
+            .client()
+            .await?
+            .query(
+                r#"
+                SELECT
+                    event_id,
+                    payment_id,
+                    data_version,
+                    event_type,
+                    event_source,
+                    payload_ref,
+                    previous_state,
+                    new_state,
+                    created_at
+                FROM payment_events
+                WHERE payment_id = $1
+                ORDER BY data_version, created_at
+                "#,
+                &[&payment_id],
+            )
+            .await?;
+
+        rows.into_iter()
+            .map(payment_event_from_row)
+            .collect::<Result<_, _>>()
+    }
+
+    async fn get_payment(&self, payment_id: Uuid) -> Result<Option<(Payment, u32)>, DbError> {
+        let row = self
+            .client()
+            .await?
+            .query_opt(
+                r#"
+                SELECT
+                    payment_id,
+                    data_version,
+                    payment_data
+                FROM payments
+                WHERE payment_id = $1
+                "#,
+                &[&payment_id],
+            )
+            .await?;
+
+        row.map(|row| payment_from_row(&self.cipher, row))
+            .transpose()
+    }
+
+    async fn get_payment_by_payout_id(
+        &self,
+        payout_id: Uuid,
+    ) -> Result<Option<(Payment, u32)>, DbError> {
+        let row = self
+            .client()
+            .await?
+            .query_opt(
+                r#"
+                SELECT
+                    p.payment_id,
+                    p.data_version,
+                    p.payment_data
+                FROM 
+                    payout_id_to_payment_id pid
+                JOIN 
+                    payments p ON pid.payment_id = p.payment_id
+                WHERE 
+                    pid.payout_id = $1
+                "#,
+                &[&payout_id],
+            )
+            .await?;
+
+        row.map(|row| payment_from_row(&self.cipher, row))
+            .transpose()
+    }
+
+    async fn get_payer_totals(
+        &self,
+        payer_email: &str,
+        currency: &str,
+        since: DateTime<Utc>,
+    ) -> Result<PaymentTotals, DbError> {
+        let row = self
+            .client()
+            .await?
+            .query_one(
+                r#"
+                    SELECT
+                        COUNT(*) AS count,
+                        COALESCE(SUM((payment_data->>'amount')::BIGINT), 0)::BIGINT AS amount
+                    FROM payments
+                    WHERE payer_email_index = ANY($1)
+                        AND created_at >= $3
+                        AND COALESCE(payment_data->>'currency', 'GBP') = $2
+                        AND payment_data->'payment_statuses'->>'inbound_failed_at' IS NULL
+                "#,
+                &[&self.cipher.blind_indexes(payer_email), &currency, &since],
+            )
+            .await?;
+        payment_totals_from_row(row)
+    }
+
+    async fn get_payee_totals(
+        &self,
+        payee_email: &str,
+        currency: &str,
+        since: DateTime<Utc>,
+    ) -> Result<PaymentTotals, DbError> {
+        let row = self
+            .client()
+            .await?
+            .query_one(
+                r#"
+                    SELECT
+                        COUNT(*) AS count,
+                        COALESCE(SUM((payment_data->>'amount')::BIGINT), 0)::BIGINT AS amount
+                    FROM payments
+                    WHERE payee_email_index = ANY($1)
+                        AND created_at >= $3
+                        AND COALESCE(payment_data->>'currency', 'GBP') = $2
+                        AND payment_data->'payment_statuses'->>'inbound_failed_at' IS NULL
+                "#,
+                &[&self.cipher.blind_indexes(payee_email), &currency, &since],
+            )
+            .await?;
+        payment_totals_from_row(row)
+    }
+
+    async fn get_payment_by_refund_id(
+        &self,
+        refund_id: Uuid,
+    ) -> Result<Option<(Payment, u32)>, DbError> {
+        let row = self
+            .client()
+            .await?
+            .query_opt(
+                r#"
+                SELECT
+                    p.payment_id,
+                    p.data_version,
+                    p.payment_data
+                FROM 
+                    refund_id_to_payment_id rid
+                JOIN 
+                    payments p ON rid.payment_id = p.payment_id
+                WHERE 
+                    rid.refund_id = $1
+                "#,
+                &[&refund_id],
+            )
+            .await?;
+
+        row.map(|row| payment_from_row(&self.cipher, row))
+            .transpose()
+    }
+
+    async fn get_unclaimed_payments(
+        &self,
+        settled_before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<(Payment, u32)>, DbError> {
+        let rows = self
+            .client()
+            .await?
+            .query(
+                r#"
+                SELECT
+                    payment_id,
+                    data_version,
+                    payment_data
+                FROM payments
+                WHERE state = 'inbound_settled'
+                    AND (payment_data->'payment_statuses'->>'inbound_settled_at')::TIMESTAMPTZ < $1
+                ORDER BY (payment_data->'payment_statuses'->>'inbound_settled_at')::TIMESTAMPTZ
+                LIMIT $2
+                "#,
+                &[&settled_before, &limit],
+            )
+            .await?;
+
+        rows.into_iter()
+            .map(|row| payment_from_row(&self.cipher, row))
+            .collect::<Result<_, _>>()
+    }
+
+    async fn get_held_payments(
+        &self,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<(Payment, u32)>, DbError> {
+        let rows = self
+            .client()
+            .await?
+            .query(
+                r#"
+                SELECT
+                    payment_id,
+                    data_version,
+                    payment_data
+                FROM payments
+                WHERE payment_data->'payment_statuses'->>'held_for_review_at' IS NOT NULL
+                    AND payment_data->'payment_statuses'->>'review_released_at' IS NULL
+                    AND payment_data->>'refund_data' IS NULL
+                ORDER BY created_at
+                LIMIT $1
+                OFFSET $2
+                "#,
+                &[&limit, &offset],
+            )
+            .await?;
+
+        rows.into_iter()
+            .map(|row| payment_from_row(&self.cipher, row))
+            .collect::<Result<_, _>>()
+    }
+
+    async fn get_payments_to_erase(
+        &self,
+        state: &str,
+        updated_before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<(Payment, u32)>, DbError> {
+        let rows = self
+            .client()
+            .await?
+            .query(
+                r#"
+                SELECT
+                    payment_id,
+                    data_version,
+                    payment_data
+                FROM payments
+                WHERE state = $1
+                    AND updated_at < $2
+                    AND payment_data->>'personal_data_erased_at' IS NULL
+                ORDER BY updated_at
+                LIMIT $3
+                "#,
+                &[&state, &updated_before, &limit],
+            )
+            .await?;
+
+        rows.into_iter()
+            .map(|row| payment_from_row(&self.cipher, row))
+            .collect::<Result<_, _>>()
+    }
+
+    async fn query_payments(&self, query: &PaymentQuery) -> Result<PaymentPage<Payment>, DbError> {
+        let sort_column = match query.sort {
+            PaymentSort::CreatedAt => "created_at",
+            PaymentSort::UpdatedAt => "updated_at",
+            PaymentSort::Amount => "amount_in_minor",
+        };
+        let (order, comparison) = match query.direction {
+            SortDirection::Asc => ("ASC", ">"),
+            SortDirection::Desc => ("DESC", "<"),
+        };
+        let after_time = match (query.sort, query.after) {
+            (PaymentSort::CreatedAt | PaymentSort::UpdatedAt, Some(after)) => Some(
+                DateTime::<Utc>::from_timestamp_micros(after.sort_value)
+                    .context("cursor time out of range")?,
+            ),
+            _ => None,
+        };
+        let limit = query.limit.max(0) + 1;
+
+        let filter = &query.filter;
+        let payer_email_indexes = filter
+            .payer_email
+            .as_deref()
+            .map(|email| self.cipher.blind_indexes(email));
+        let payee_email_indexes = filter
+            .payee_email
+            .as_deref()
+            .map(|email| self.cipher.blind_indexes(email));
+        let mut conditions = Conditions::default();
+        if !filter.states.is_empty() {
+            conditions.push("state = ANY(?)", [&filter.states]);
+        }
+        if let Some(indexes) = &payer_email_indexes {
+            conditions.push("payer_email_index = ANY(?)", [indexes]);
+        }
+        if let Some(indexes) = &payee_email_indexes {
+            conditions.push("payee_email_index = ANY(?)", [indexes]);
+        }
+        if let Some(amount) = &filter.min_amount {
+            conditions.push("amount_in_minor >= ?", [amount]);
+        }
+        if let Some(amount) = &filter.max_amount {
+            conditions.push("amount_in_minor <= ?", [amount]);
+        }
+        if let Some(time) = &filter.created_from {
+            conditions.push("created_at >= ?", [time]);
+        }
+        if let Some(time) = &filter.created_to {
+            conditions.push("created_at < ?", [time]);
+        }
+        if let Some(time) = &filter.updated_from {
+            conditions.push("updated_at >= ?", [time]);
+        }
+        if let Some(time) = &filter.updated_to {
+            conditions.push("updated_at < ?", [time]);
+        }
+        if let Some(after) = &query.after {
+            let condition = format!("({sort_column}, payment_id) {comparison} (?, ?)");
+            match &after_time {
+                Some(time) => conditions.push(&condition, [time, &after.payment_id]),
+                None => conditions.push(&condition, [&after.sort_value, &after.payment_id]),
+            }
+        }
+        let (where_clause, mut params) = conditions.build();
+        params.push(&limit);
+
+        let rows = self
+            .client()
+            .await?
+            .query(
+                &format!(
+                    r#"
+                    SELECT
+                        payment_id,
+                        data_version,
+                        payment_data,
+                        {sort_column}
+                    FROM payments
+                    WHERE {where_clause}
+                    ORDER BY {sort_column} {order}, payment_id {order}
+                    LIMIT ${}
+                    "#,
+                    params.len()
+                ),
+                &params,
+            )
+            .await?;
+
+        let has_next = rows.len() as i64 > query.limit.max(0);
+        let mut payments = Vec::with_capacity(rows.len());
+        let mut last = None;
+        for row in rows.into_iter().take(query.limit.max(0) as usize) {
+            let sort_value = match query.sort {
+                PaymentSort::CreatedAt | PaymentSort::UpdatedAt => {
+                    row.try_get::<_, DateTime<Utc>>(3)?.timestamp_micros()
+                }
+                PaymentSort::Amount => row.try_get(3)?,
+            };
+            let payment = payment_from_row(&self.cipher, row)?;
+            last = Some(PaymentCursor {
+                sort_value,
+                payment_id: payment.0.payment_id,
+            });
+            payments.push(payment);
+        }
+
+        Ok(PaymentPage {
+            next: last.filter(|_| has_next),
+            payments,
+        })
+    }
+
+    async fn upcast_payments(&self, limit: i64) -> Result<usize, DbError> {
+        let client = self.client().await?;
+        let rows = client
+            .query(
+                r#"
+                SELECT
+                    payment_id,
+                    data_version,
+                    payment_data
+                FROM payments
+                WHERE payment_data->>'version' = 'V1'
+                LIMIT $1
+                "#,
+                &[&limit],
+            )
+            .await?;
+
+        let found = rows.len();
+        for row in rows {
+            let (payment, version) = payment_from_row(&self.cipher, row)?;
+            let payment_data = PaymentData::V2(payment.payment_data.0.into_latest());
+            self.rewrite_payment(&client, payment.payment_id, version, &Json(payment_data))
+                .await?;
+        }
+        Ok(found)
+    }
+
+    async fn rotate_payment_keys(&self, limit: i64) -> Result<usize, DbError> {
+        let client = self.client().await?;
+        let rows = client
+            .query(
+                r#"
+                SELECT
+                    payment_id,
+                    data_version,
+                    payment_data
+                FROM payments
+                WHERE pii_key_id IS DISTINCT FROM $1
+                    OR legacy_email_index
+                LIMIT $2
+                "#,
+                &[&self.cipher.active_key_id(), &limit],
+            )
+            .await?;
+
+        let found = rows.len();
+        for row in rows {
+            let (payment, version) = payment_from_row(&self.cipher, row)?;
+            self.rewrite_payment(&client, payment.payment_id, version, &payment.payment_data)
+                .await?;
+        }
+        Ok(found)
+    }
+
+    async fn is_webhook_processed(&self, event_id: Uuid) -> Result<bool, DbError> {
+        let row = self
+            .client()
+            .await?
+            .query_opt(
+                "SELECT 1 FROM processed_webhooks WHERE event_id = $1",
+                &[&event_id],
+            )
+            .await?;
+        Ok(row.is_some())
+    }
+
+    async fn insert_outbox_messages(&self, messages: Vec<OutboxMessage>) -> Result<(), DbError> {
+        let mut client = self.client().await?;
+        let transaction = client.transaction().await?;
+        for message in messages {
+            insert_outbox_message(&transaction, message).await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn claim_outbox_messages(
+        &self,
+        now: DateTime<Utc>,
+        lease_until: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<OutboxMessage>, DbError> {
+        let rows = self
+            .client()
+            .await?
+            .query(
+                r#"
+                UPDATE outbox
+                SET next_attempt_at = $2
+                WHERE message_id IN (
+                    SELECT message_id
+                    FROM outbox
+                    WHERE status = 'pending'
+                        AND next_attempt_at <= $1
+                    ORDER BY next_attempt_at
+                    LIMIT $3
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING
+                    message_id,
+                    payment_id,
+                    status,
+                    attempts,
+                    last_error,
+                    next_attempt_at,
+                    created_at,
+                    message_data
+                "#,
+                &[&now, &lease_until, &limit],
+            )
+            .await?;
+
+        let mut messages = rows
+            .into_iter()
+            .map(outbox_message_from_row)
+            .collect::<Result<Vec<_>, _>>()?;
+        // RETURNING does not keep the order of the subquery
+        messages.sort_by_key(|message| message.created_at);
+        Ok(messages)
+    }
+
+    async fn update_outbox_message(&self, message: OutboxMessage) -> Result<(), DbError> {
+        let attempts: i32 = message.attempts.try_into().context("attempts overflow")?;
+        self.client()
+            .await?
+            .execute(
+                r#"
+                UPDATE outbox
+                SET
+                    status = $2,
+                    attempts = $3,
+                    last_error = $4,
+                    next_attempt_at = $5,
+                    updated_at = NOW()
+                WHERE message_id = $1
+                "#,
+                &[
+                    &message.message_id,
+                    &message.status,
+                    &attempts,
+                    &message.last_error,
+                    &message.next_attempt_at,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn get_outbox_message(&self, message_id: Uuid) -> Result<Option<OutboxMessage>, DbError> {
+        let row = self
+            .client()
+            .await?
+            .query_opt(
+                r#"
+                SELECT
+                    message_id,
+                    payment_id,
+                    status,
+                    attempts,
+                    last_error,
+                    next_attempt_at,
+                    created_at,
+                    message_data
+                FROM outbox
+                WHERE message_id = $1
+                "#,
+                &[&message_id],
+            )
+            .await?;
+
+        row.map(outbox_message_from_row).transpose()
+    }
+
+    async fn get_outbox_messages(
+        &self,
+        status: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<OutboxMessage>, DbError> {
+        let rows = self
+            .client()
+            .await?
+            .query(
+                r#"
+                SELECT
+                    message_id,
+                    payment_id,
+                    status,
+                    attempts,
+                    last_error,
+                    next_attempt_at,
+                    created_at,
+                    message_data
+                FROM outbox
+                WHERE status = $1
+                ORDER BY created_at DESC
+                LIMIT $2
+                OFFSET $3
+                "#,
+                &[&status, &limit, &offset],
+            )
+            .await?;
+
+        rows.into_iter()
+            .map(outbox_message_from_row)
+            .collect::<Result<_, _>>()
+    }
+
+    async fn upsert_payment_request(
+        &self,
+        request: PaymentRequest,
+        version: u32,
+    ) -> Result<(), DbError> {
+        let version: i32 = version.try_into().context("version overflow")?;
+        let affected_rows = self
+            .client()
+            .await?
+            .execute(
+                r#"
+                INSERT INTO payment_requests (
+                    request_id,
+                    user_id,
+                    data_version,
+                    created_at,
+                    updated_at,
+                    request_data
+                )
+                VALUES($1, $2, $3, NOW(), NOW(), $4)
+                ON CONFLICT (request_id) DO UPDATE SET
+                    data_version = $3,
+                    request_data = $4,
+                    updated_at = NOW()
+                WHERE payment_requests.data_version = $3 - 1
+                "#,
+                &[
+                    &request.request_id,
+                    &request.user_id,
+                    &version,
+                    &request.request_data,
+                ],
+            )
+            .await?;
+
+        match affected_rows {
+            0 => Err(DbError::ConcurrentUpdate),
+            1 => Ok(()),
+            n => Err(DbError::Unknown(anyhow::anyhow!(
+                "More than one({}) row was updated",
+                n
+            ))),
+        }
+    }
+
+    async fn get_payment_request(
+        &self,
+        request_id: Uuid,
+    ) -> Result<Option<(PaymentRequest, u32)>, DbError> {
+        let row = self
+            .client()
+            .await?
+            .query_opt(
+                r#"
+                SELECT
+                    request_id,
+                    user_id,
+                    data_version,
+                    request_data
+                FROM payment_requests
+                WHERE request_id = $1
+                "#,
+                &[&request_id],
+            )
+            .await?;
+
+        row.map(payment_request_from_row).transpose()
+    }
+
+    async fn get_user_payment_requests(
+        &self,
+        user_id: Uuid,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<(PaymentRequest, u32)>, DbError> {
+        let rows = self
+            .client()
+            .await?
+            .query(
+                r#"
+                SELECT
+                    request_id,
+                    user_id,
+                    data_version,
+                    request_data
+                FROM payment_requests
+                WHERE user_id = $1
+                ORDER BY created_at DESC
+                LIMIT $2
+                OFFSET $3
+                "#,
+                &[&user_id, &limit, &offset],
+            )
+            .await?;
+
+        rows.into_iter()
+            .map(payment_request_from_row)
+            .collect::<Result<_, _>>()
+    }
+
+    async fn upsert_scheduled_transfer(
+        &self,
+        schedule: ScheduledTransfer,
+        version: u32,
+    ) -> Result<(), DbError> {
+        let version: i32 = version.try_into().context("version overflow")?;
+        let affected_rows = self
+            .client()
+            .await?
+            .execute(
+                r#"
+                INSERT INTO scheduled_transfers (
+                    schedule_id,
+                    user_id,
+                    data_version,
+                    created_at,
+                    updated_at,
+                    next_run_at,
+                    schedule_data
+                )
+                VALUES($1, $2, $3, NOW(), NOW(), $4, $5)
+                ON CONFLICT (schedule_id) DO UPDATE SET
+                    data_version = $3,
+                    next_run_at = $4,
+                    schedule_data = $5,
+                    updated_at = NOW()
+                WHERE scheduled_transfers.data_version = $3 - 1
+                "#,
+                &[
+                    &schedule.schedule_id,
+                    &schedule.user_id,
+                    &version,
+                    &schedule.next_run_at,
+                    &schedule.schedule_data,
+                ],
+            )
+            .await?;
+
+        match affected_rows {
+            0 => Err(DbError::ConcurrentUpdate),
+            1 => Ok(()),
+            n => Err(DbError::Unknown(anyhow::anyhow!(
+                "More than one({}) row was updated",
+                n
+            ))),
+        }
+    }
+
+    async fn get_scheduled_transfer(
+        &self,
+        schedule_id: Uuid,
+    ) -> Result<Option<(ScheduledTransfer, u32)>, DbError> {
+        let row = self
+            .client()
+            .await?
+            .query_opt(
+                r#"
+                SELECT
+                    schedule_id,
+                    user_id,
+                    data_version,
+                    next_run_at,
+                    schedule_data
+                FROM scheduled_transfers
+                WHERE schedule_id = $1
+                "#,
+                &[&schedule_id],
+            )
+            .await?;
+
+        row.map(scheduled_transfer_from_row).transpose()
+    }
+
+    async fn get_user_scheduled_transfers(
+        &self,
+        user_id: Uuid,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<(ScheduledTransfer, u32)>, DbError> {
+        let rows = self
+            .client()
+            .await?
+            .query(
+                r#"
+                SELECT
+                    schedule_id,
+                    user_id,
+                    data_version,
+                    next_run_at,
+                    schedule_data
+                FROM scheduled_transfers
+                WHERE user_id = $1
+                ORDER BY created_at DESC
+                LIMIT $2
+                OFFSET $3
+                "#,
+                &[&user_id, &limit, &offset],
+            )
+            .await?;
+
+        rows.into_iter()
+            .map(scheduled_transfer_from_row)
+            .collect::<Result<_, _>>()
+    }
+
+    async fn get_due_scheduled_transfers(
+        &self,
+        now: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<(ScheduledTransfer, u32)>, DbError> {
+        let rows = self
+            .client()
+            .await?
+            .query(
+                r#"
+                SELECT
+                    schedule_id,
+                    user_id,
+                    data_version,
+                    next_run_at,
+                    schedule_data
+                FROM scheduled_transfers
+                WHERE next_run_at <= $1
+                ORDER BY next_run_at
+                LIMIT $2
+                "#,
+                &[&now, &limit],
+            )
+            .await?;
+
+        rows.into_iter()
+            .map(scheduled_transfer_from_row)
+            .collect::<Result<_, _>>()
+    }
+}
+
+#[async_trait]
+impl UserRepository for PgClient {
+    async fn upsert_user(&self, user: User, version: u32) -> Result<(), DbError> {
+        let version: i32 = version.try_into().context("version overflow")?;
+        let sealed = seal_user(&self.cipher, &user)?;
+        let affected_rows = self
+            .client()
+            .await?
+            .execute(
+                r#"
+                INSERT INTO users (
+                    user_id,
+                    email,
+                    data_version,
+                    created_at,
+                    updated_at,
+                    user_data,
+                    pii_key_id,
+                    email_index
+                )
+                VALUES($1, $2, $3, NOW(), NOW(), $4, $5, $6)
+                ON CONFLICT (user_id) DO UPDATE SET
+                    email = $2,
+                    data_version = $3,
+                    user_data = $4,
+                    pii_key_id = $5,
+                    email_index = $6,
+                    legacy_email_index = FALSE,
+                    updated_at = NOW()
+                WHERE users.data_version = $3 - 1
+                "#,
+                &[
+                    &user.user_id,
+                    &sealed.email,
+                    &version,
+                    &sealed.user_data,
+                    &self.cipher.active_key_id(),
+                    &sealed.email_index,
+                ],
+            )
+            .await?;
+
+        match affected_rows {
+            0 => Err(DbError::ConcurrentUpdate),
+            1 => Ok(()),
+            n => Err(DbError::Unknown(anyhow::anyhow!(
+                "More than one({}) row was updated",
+                n
+            ))),
+        }
+    }
+
+    async fn get_user(&self, user_id: Uuid) -> Result<Option<(User, u32)>, DbError> {
+        let row = self
+            .client()
+            .await?
+            .query_opt(
+                r#"
+                SELECT
+                    user_id,
+                    email,
+                    data_version,
+                    user_data
+                FROM users
+                WHERE user_id = $1
+                "#,
+                &[&user_id],
+            )
+            .await?;
+
+        row.map(|row| user_from_row(&self.cipher, row)).transpose()
+    }
+
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<(User, u32)>, DbError> {
+        let row = self
+            .client()
+            .await?
+            .query_opt(
+                r#"
+                SELECT
+                    user_id,
+                    email,
+                    data_version,
+                    user_data
+                FROM users
+                WHERE email_index = ANY($1)
+                ORDER BY created_at
+                LIMIT 1
+                "#,
+                &[&self.cipher.blind_indexes(email)],
+            )
+            .await?;
+
+        row.map(|row| user_from_row(&self.cipher, row)).transpose()
+    }
+
+    async fn get_users(&self, limit: i64, offset: i64) -> Result<Vec<(User, u32)>, DbError> {
+        let rows = self
+            .client()
+            .await?
+            .query(
+                r#"
+                SELECT
+                    user_id,
+                    email,
+                    data_version,
+                    user_data
+                FROM users
+                LIMIT $1 
+                OFFSET $2
+                "#,
+                &[&limit, &offset],
+            )
+            .await?;
+
+        rows.into_iter()
+            .map(|row| user_from_row(&self.cipher, row))
+            .collect::<Result<_, _>>()
+    }
+
+    async fn rotate_user_keys(&self, limit: i64) -> Result<usize, DbError> {
+        let client = self.client().await?;
+        let rows = client
+            .query(
+                r#"
+                SELECT
+                    user_id,
+                    email,
+                    data_version,
+                    user_data
+                FROM users
+                WHERE pii_key_id IS DISTINCT FROM $1
+                    OR legacy_email_index
+                LIMIT $2
+                "#,
+                &[&self.cipher.active_key_id(), &limit],
+            )
+            .await?;
+
+        let found = rows.len();
+        for row in rows {
+            let (user, version) = user_from_row(&self.cipher, row)?;
+            let version: i32 = version.try_into().context("version overflow")?;
+            let sealed = seal_user(&self.cipher, &user)?;
+            client
+                .execute(
+                    r#"
+                    UPDATE users
+                    SET email = $3,
+                        user_data = $4,
+                        pii_key_id = $5,
+                        email_index = $6,
+                        legacy_email_index = FALSE
+                    WHERE user_id = $1
+                        AND data_version = $2
+                    "#,
+                    &[
+                        &user.user_id,
+                        &version,
+                        &sealed.email,
+                        &sealed.user_data,
+                        &self.cipher.active_key_id(),
+                        &sealed.email_index,
+                    ],
+                )
+                .await?;
+        }
+        Ok(found)
+    }
+
+    async fn link_user_payment(&self, user_payment: UserPayment) -> Result<(), DbError> {
+        self.client()
+            .await?
+            .execute(
+                r#"
+                INSERT INTO user_payments (
+                    payment_id,
+                    user_id,
+                    role,
+                    created_at
+                )
+                VALUES($1, $2, $3, NOW())
+                ON CONFLICT DO NOTHING
+                "#,
+                &[
+                    &user_payment.payment_id,
+                    &user_payment.user_id,
+                    &user_payment.role,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn link_user_payments_by_email(
+        &self,
+        user_id: Uuid,
+        email: &str,
+        payer_role: &str,
+        payee_role: &str,
+    ) -> Result<u64, DbError> {
+        let linked = self
+            .client()
+            .await?
+            .execute(
+                r#"
+                INSERT INTO user_payments (
+                    payment_id,
+                    user_id,
+                    role,
+                    created_at
+                )
+                SELECT payment_id, $1::UUID, $3::VARCHAR, NOW()
+                FROM payments
+                WHERE payer_email_index = ANY($2)
+                UNION ALL
+                SELECT payment_id, $1::UUID, $4::VARCHAR, NOW()
+                FROM payments
+                WHERE payee_email_index = ANY($2)
+                ON CONFLICT DO NOTHING
+                "#,
+                &[
+                    &user_id,
+                    &self.cipher.blind_indexes(email),
+                    &payer_role,
+                    &payee_role,
+                ],
+            )
+            .await?;
+        Ok(linked)
+    }
+
+    async fn get_user_payments(
+        &self,
+        user_id: Uuid,
+        role: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<(Payment, u32)>, DbError> {
+        let rows = self
+            .client()
+            .await?
+            .query(
+                r#"
+                SELECT
+                    p.payment_id,
+                    p.data_version,
+                    p.payment_data
+                FROM user_payments up
+                JOIN payments p ON p.payment_id = up.payment_id
+                WHERE up.user_id = $1
+                    AND up.role = $2
+                ORDER BY p.created_at DESC
+                LIMIT $3
+                OFFSET $4
+                "#,
+                &[&user_id, &role, &limit, &offset],
+            )
+            .await?;
+
+        rows.into_iter()
+            .map(|row| payment_from_row(&self.cipher, row))
+            .collect::<Result<_, _>>()
+    }
+}
+
+#[async_trait]
+impl AuditRepository for PgClient {
+    async fn insert_audit_entry(&self, entry: AdminAuditEntry) -> Result<(), DbError> {
+        self.client()
+            .await?
+            .execute(
+                r#"
+                INSERT INTO admin_audit_log (
+                    entry_id,
+                    actor,
+                    action,
+                    target_type,
+                    target_id,
+                    request,
+                    before,
+                    after,
+                    ip,
+                    created_at
+                )
+                VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                "#,
+                &[
+                    &entry.entry_id,
+                    &entry.actor,
+                    &entry.action,
+                    &entry.target_type,
+                    &entry.target_id,
+                    &entry.request,
+                    &entry.before,
+                    &entry.after,
+                    &entry.ip,
+                    &entry.created_at,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn query_audit_entries(
+        &self,
+        query: &AdminAuditQuery,
+    ) -> Result<Vec<AdminAuditEntry>, DbError> {
+        let mut conditions = Conditions::default();
+        if let Some(actor) = &query.actor {
+            conditions.push("actor = ?", [actor]);
+        }
+        if let Some(target_id) = &query.target_id {
+            conditions.push("target_id = ?", [target_id]);
+        }
+        if let Some(time) = &query.from {
+            conditions.push("created_at >= ?", [time]);
+        }
+        if let Some(time) = &query.to {
+            conditions.push("created_at < ?", [time]);
+        }
+        let (where_clause, mut params) = conditions.build();
+        params.push(&query.limit);
+        params.push(&query.offset);
+
+        let rows = self
+            .client()
+            .await?
+            .query(
+                &format!(
+                    r#"
+                    SELECT
+                        entry_id,
+                        actor,
+                        action,
+                        target_type,
+                        target_id,
+                        request,
+                        before,
+                        after,
+                        ip,
+                        created_at
+                    FROM admin_audit_log
+                    WHERE {where_clause}
+                    ORDER BY created_at DESC, entry_id
+                    LIMIT ${}
+                    OFFSET ${}
+                    "#,
+                    params.len() - 1,
+                    params.len()
+                ),
+                &params,
+            )
+            .await?;
+
+        rows.into_iter()
+            .map(audit_entry_from_row)
+            .collect::<Result<_, _>>()
+    }
+}
+
+/// The fields of a payment's data holding personal data, encrypted at rest.
+const PAYMENT_PII_FIELDS: &[&str] = &[
+    "payer_full_name",
+    "payer_email",
+    "payee_full_name",
+    "payee_email",
+    "payout_destination",
+];
+
+/// The fields of a user's data holding personal data, encrypted at rest. The
+/// email of a user is kept in a column of its own and encrypted as well.
+const USER_PII_FIELDS: &[&str] = &["first_name", "last_name", "payout_account"];
+
+/// A payment's data as stored, with its personal data encrypted, and the
+/// blind indexes of its emails.
+struct SealedPayment {
+    payment_data: Json<Value>,
+    payer_email_index: Vec<u8>,
+    payee_email_index: Vec<u8>,
+}
+
+fn seal_payment(
+    cipher: &Cipher,
+    payment_id: Uuid,
+    payment_data: &Json<PaymentData>,
+) -> Result<SealedPayment, DbError> {
+    let mut data = serde_json::to_value(&payment_data.0).context("payment data")?;
+    let email_index = |field: &str| {
+        data.get(field)
+            .and_then(Value::as_str)
+            .map(|email| cipher.blind_index(email))
+            .with_context(|| format!("payment data without {field}"))
+    };
+    let payer_email_index = email_index("payer_email")?;
+    let payee_email_index = email_index("payee_email")?;
+    cipher.seal_fields(&mut data, payment_id, PAYMENT_PII_FIELDS)?;
+
+    Ok(SealedPayment {
+        payment_data: Json(data),
+        payer_email_index,
+        payee_email_index,
+    })
+}
+
+/// A user as stored, with its personal data encrypted, and the blind index of
+/// its email.
+struct SealedUser {
+    email: Json<Value>,
+    email_index: Vec<u8>,
+    user_data: Json<Value>,
+}
+
+fn seal_user(cipher: &Cipher, user: &User) -> Result<SealedUser, DbError> {
+    let email = cipher.seal(Value::from(user.email.as_str()), user.user_id, "email")?;
+    let mut user_data = serde_json::to_value(&user.user_data.0).context("user data")?;
+    cipher.seal_fields(&mut user_data, user.user_id, USER_PII_FIELDS)?;
+
+    Ok(SealedUser {
+        email: Json(email),
+        email_index: cipher.blind_index(&user.email),
+        user_data: Json(user_data),
+    })
+}
+
+fn user_from_row(cipher: &Cipher, row: Row) -> Result<(User, u32), DbError> {
+    let user_id: Uuid = row.try_get(0)?;
+    let Json(email): Json<Value> = row.try_get(1)?;
+    let email = cipher.open(email, user_id, "email")?;
+    let Json(mut user_data): Json<Value> = row.try_get(3)?;
+    cipher.open_fields(&mut user_data, user_id, USER_PII_FIELDS)?;
+
+    let user = User {
+        user_id,
+        email: serde_json::from_value(email).context("user email")?,
+        user_data: Json(serde_json::from_value(user_data).context("user data")?),
+    };
+    let version: i32 = row.try_get(2)?;
+    Ok((user, version as _))
+}
+
+fn payment_from_row(cipher: &Cipher, row: Row) -> Result<(Payment, u32), DbError> {
+    let payment_id: Uuid = row.try_get(0)?;
+    let Json(mut payment_data): Json<Value> = row.try_get(2)?;
+    cipher.open_fields(&mut payment_data, payment_id, PAYMENT_PII_FIELDS)?;
+
+    let payment = Payment {
+        payment_id,
+        payment_data: Json(serde_json::from_value(payment_data).context("payment data")?),
+    };
+    let version: i32 = row.try_get(1)?;
+    Ok((payment, version as _))
+}
+
+/// Records the webhook, failing with [`DbError::DuplicateWebhook`] if it was
+/// recorded before. A webhook being processed concurrently holds its row
+/// until its transaction ends, so only one of them gets past this.
+async fn insert_processed_webhook(
+    transaction: &Transaction<'_>,
+    payment_id: Uuid,
+    webhook: ProcessedWebhook,
+) -> Result<(), DbError> {
+    let event_version: i32 = webhook
+        .event_version
+        .try_into()
+        .context("event version overflow")?;
+    let affected_rows = transaction
+        .execute(
+            r#"
+            INSERT INTO processed_webhooks (
+                event_id,
+                payment_id,
+                event_type,
+                event_version,
+                processed_at
+            )
+            VALUES($1, $2, $3, $4, $5)
+            ON CONFLICT (event_id) DO NOTHING
+            "#,
+            &[
+                &webhook.event_id,
+                &payment_id,
+                &webhook.event_type,
+                &event_version,
+                &webhook.processed_at,
+            ],
+        )
+        .await?;
+    match affected_rows {
+        0 => Err(DbError::DuplicateWebhook(webhook.event_id)),
+        _ => Ok(()),
+    }
+}
+
+async fn insert_outbox_message(
+    transaction: &Transaction<'_>,
+    message: OutboxMessage,
+) -> Result<(), DbError> {
+    let attempts: i32 = message.attempts.try_into().context("attempts overflow")?;
+    transaction
+        .execute(
+            r#"
+            INSERT INTO outbox (
+                message_id,
+                payment_id,
+                status,
+                attempts,
+                last_error,
+                next_attempt_at,
+                created_at,
+                updated_at,
+                message_data
+            )
+            VALUES($1, $2, $3, $4, $5, $6, $7, NOW(), $8)
+            "#,
+            &[
+                &message.message_id,
+                &message.payment_id,
+                &message.status,
+                &attempts,
+                &message.last_error,
+                &message.next_attempt_at,
+                &message.created_at,
+                &message.message_data,
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+fn outbox_message_from_row(row: Row) -> Result<OutboxMessage, DbError> {
+    let attempts: i32 = row.try_get(3)?;
+    Ok(OutboxMessage {
+        message_id: row.try_get(0)?,
+        payment_id: row.try_get(1)?,
+        status: row.try_get(2)?,
+        attempts: attempts as _,
+        last_error: row.try_get(4)?,
+        next_attempt_at: row.try_get(5)?,
+        created_at: row.try_get(6)?,
+        message_data: row.try_get(7)?,
+    })
+}
+
+fn audit_entry_from_row(row: Row) -> Result<AdminAuditEntry, DbError> {
+    Ok(AdminAuditEntry {
+        entry_id: row.try_get(0)?,
+        actor: row.try_get(1)?,
+        action: row.try_get(2)?,
+        target_type: row.try_get(3)?,
+        target_id: row.try_get(4)?,
+        request: row.try_get(5)?,
+        before: row.try_get(6)?,
+        after: row.try_get(7)?,
+        ip: row.try_get(8)?,
+        created_at: row.try_get(9)?,
+    })
+}
+
+fn payment_event_from_row(row: Row) -> Result<(PaymentEventRecord, u32), DbError> {
+    let event = PaymentEventRecord {
+        event_id: row.try_get(0)?,
+        payment_id: row.try_get(1)?,
+        event_type: row.try_get(3)?,
+        event_source: row.try_get(4)?,
+        payload_ref: row.try_get(5)?,
+        previous_state: row.try_get(6)?,
+        new_state: row.try_get(7)?,
+        created_at: row.try_get(8)?,
+    };
+    let version: i32 = row.try_get(2)?;
+    Ok((event, version as _))
+}
+
+fn payment_request_from_row(row: Row) -> Result<(PaymentRequest, u32), DbError> {
+    let request = PaymentRequest {
+        request_id: row.try_get(0)?,
+        user_id: row.try_get(1)?,
+        request_data: row.try_get(3)?,
+    };
+    let version: i32 = row.try_get(2)?;
+    Ok((request, version as _))
+}
+
+fn scheduled_transfer_from_row(row: Row) -> Result<(ScheduledTransfer, u32), DbError> {
+    let schedule = ScheduledTransfer {
+        schedule_id: row.try_get(0)?,
+        user_id: row.try_get(1)?,
+        next_run_at: row.try_get(3)?,
+        schedule_data: row.try_get(4)?,
+    };
+    let version: i32 = row.try_get(2)?;
+    Ok((schedule, version as _))
+}
+
+fn payment_totals_from_row(row: Row) -> Result<PaymentTotals, DbError> {
+    Ok(PaymentTotals {
+        count: row.try_get(0)?,
+        amount_in_minor: row.try_get(1)?,
+    })
+}
+
+/// The conditions of a `WHERE` clause built up from optional filters. Each is
+/// written with `?` in place of its parameters, which are numbered in the
+/// order they are pushed.
+#[derive(Default)]
+struct Conditions<'a> {
+    clauses: Vec<String>,
+    params: Vec<&'a (dyn ToSql + Sync)>,
+}
+
+impl<'a> Conditions<'a> {
+    fn push<const N: usize>(&mut self, condition: &str, params: [&'a (dyn ToSql + Sync); N]) {
+        let mut clause = condition.to_owned();
+        for param in params {
+            self.params.push(param);
+            clause = clause.replacen('?', &format!("${}", self.params.len()), 1);
+        }
+        self.clauses.push(clause);
+    }
+
+    fn build(self) -> (String, Vec<&'a (dyn ToSql + Sync)>) {
+        let clause = match self.clauses.is_empty() {
+            true => "TRUE".to_owned(),
+            false => self.clauses.join(" AND "),
+        };
+        (clause, self.params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn config() -> DbConfig {
+        serde_json::from_value(json!({
+            "name": "etransfer",
+            "host": "127.0.0.1",
+            "port": 1,
+            "username": "etransfer",
+            "password": "etransfer",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn pool_settings_default_when_left_out() {
+        let config = config();
+        assert_eq!(config.pool_size, 16);
+        assert_eq!(config.acquire_timeout_secs, 5);
+        assert!(config.encryption.is_none());
+    }
+
+    #[tokio::test]
+    async fn connecting_fails_on_startup_without_postgres() {
+        let started = std::time::Instant::now();
+        assert!(PgClient::connect(config()).await.is_err());
+        assert!(started.elapsed() < Duration::from_secs(config().acquire_timeout_secs + 1));
+    }
+}
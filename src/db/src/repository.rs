@@ -0,0 +1,239 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::{
+    entities::{
+        AdminAuditEntry, AdminAuditQuery, OutboxMessage, Payment, PaymentEventRecord, PaymentPage,
+        PaymentQuery, PaymentRequest, PaymentTotals, PaymentWrites, ScheduledTransfer, User,
+        UserPayment,
+    },
+    error::DbError,
+};
+
+/// Storage of payments and of the records made alongside them: their event
+/// history, payout and refund ids, payment requests and scheduled transfers.
+///
+/// Snapshots are versioned. An upsert stores `version` only over no snapshot
+/// or over the snapshot at `version - 1`, and fails with
+/// [`DbError::ConcurrentUpdate`] otherwise, so a writer working from a stale
+/// read cannot overwrite a newer one.
+#[async_trait]
+pub trait PaymentRepository: Send + Sync {
+    /// Stores the payment snapshot, appends `event` to its history and makes
+    /// the `writes` that go with it. All writes happen together, so none is
+    /// kept without the others.
+    async fn upsert_payment(
+        &self,
+        payment: Payment,
+        version: u32,
+        event: PaymentEventRecord,
+        writes: PaymentWrites,
+    ) -> Result<(), DbError>;
+
+    /// The history of a payment, oldest first, with the snapshot version each
+    /// event produced.
+    async fn get_payment_events(
+        &self,
+        payment_id: Uuid,
+    ) -> Result<Vec<(PaymentEventRecord, u32)>, DbError>;
+
+    async fn get_payment(&self, payment_id: Uuid) -> Result<Option<(Payment, u32)>, DbError>;
+
+    async fn get_payment_by_payout_id(
+        &self,
+        payout_id: Uuid,
+    ) -> Result<Option<(Payment, u32)>, DbError>;
+
+    /// Totals of the payments sent by `payer_email` in `currency` since
+    /// `since`, leaving out payments that failed.
+    async fn get_payer_totals(
+        &self,
+        payer_email: &str,
+        currency: &str,
+        since: DateTime<Utc>,
+    ) -> Result<PaymentTotals, DbError>;
+
+    /// Totals of the payments sent to `payee_email` in `currency` since
+    /// `since`, leaving out payments that failed.
+    async fn get_payee_totals(
+        &self,
+        payee_email: &str,
+        currency: &str,
+        since: DateTime<Utc>,
+    ) -> Result<PaymentTotals, DbError>;
+
+    async fn get_payment_by_refund_id(
+        &self,
+        refund_id: Uuid,
+    ) -> Result<Option<(Payment, u32)>, DbError>;
+
+    /// Payments still in the settled state that settled before
+    /// `settled_before`, oldest first. Expired, cancelled and held payments and
+    /// those with a deposit or refund requested are left out.
+    async fn get_unclaimed_payments(
+        &self,
+        settled_before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<(Payment, u32)>, DbError>;
+
+    /// Payments held for review that an admin has neither released nor
+    /// refunded, oldest first.
+    async fn get_held_payments(
+        &self,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<(Payment, u32)>, DbError>;
+
+    /// Payments in `state` last updated before `updated_before` whose personal
+    /// data was not erased yet, oldest first.
+    async fn get_payments_to_erase(
+        &self,
+        state: &str,
+        updated_before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<(Payment, u32)>, DbError>;
+
+    /// A page of the payments matching `query`. The page after it is read by
+    /// passing back its `next` cursor, which keeps its place as payments are
+    /// added or updated.
+    async fn query_payments(&self, query: &PaymentQuery) -> Result<PaymentPage<Payment>, DbError>;
+
+    /// Rewrites up to `limit` payments stored in an older data shape to the
+    /// latest one and returns how many were found. The rewrite leaves the
+    /// data version alone, as the payment itself is unchanged, and skips
+    /// payments updated since they were read.
+    async fn upcast_payments(&self, limit: i64) -> Result<usize, DbError>;
+
+    /// Re-encrypts up to `limit` payments whose personal data is not encrypted
+    /// with the active key, or whose emails still have the plain indexes of
+    /// the 0012 migration, and returns how many were found. Like an upcast it
+    /// leaves the data version alone and skips payments updated since they
+    /// were read, as those were stored with the active key.
+    async fn rotate_payment_keys(&self, limit: i64) -> Result<usize, DbError>;
+
+    /// Whether an update was stored from the webhook event.
+    async fn is_webhook_processed(&self, event_id: Uuid) -> Result<bool, DbError>;
+
+    /// Queues messages that do not come with a payment update.
+    async fn insert_outbox_messages(&self, messages: Vec<OutboxMessage>) -> Result<(), DbError>;
+
+    /// Pending messages due at or before `now`, oldest first. The claimed
+    /// messages are not due again before `lease_until`, so other dispatchers
+    /// leave them alone while they are delivered.
+    async fn claim_outbox_messages(
+        &self,
+        now: DateTime<Utc>,
+        lease_until: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<OutboxMessage>, DbError>;
+
+    /// Stores the delivery status, attempts and next attempt of the message.
+    async fn update_outbox_message(&self, message: OutboxMessage) -> Result<(), DbError>;
+
+    async fn get_outbox_message(&self, message_id: Uuid) -> Result<Option<OutboxMessage>, DbError>;
+
+    /// Messages with the given status, newest first.
+    async fn get_outbox_messages(
+        &self,
+        status: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<OutboxMessage>, DbError>;
+
+    async fn upsert_payment_request(
+        &self,
+        request: PaymentRequest,
+        version: u32,
+    ) -> Result<(), DbError>;
+
+    async fn get_payment_request(
+        &self,
+        request_id: Uuid,
+    ) -> Result<Option<(PaymentRequest, u32)>, DbError>;
+
+    /// Payment requests created by the user, newest first.
+    async fn get_user_payment_requests(
+        &self,
+        user_id: Uuid,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<(PaymentRequest, u32)>, DbError>;
+
+    async fn upsert_scheduled_transfer(
+        &self,
+        schedule: ScheduledTransfer,
+        version: u32,
+    ) -> Result<(), DbError>;
+
+    async fn get_scheduled_transfer(
+        &self,
+        schedule_id: Uuid,
+    ) -> Result<Option<(ScheduledTransfer, u32)>, DbError>;
+
+    async fn get_user_scheduled_transfers(
+        &self,
+        user_id: Uuid,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<(ScheduledTransfer, u32)>, DbError>;
+
+    /// Active schedules due at or before `now`, most overdue first.
+    async fn get_due_scheduled_transfers(
+        &self,
+        now: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<(ScheduledTransfer, u32)>, DbError>;
+}
+
+/// Storage of users and of the links between users and their payments.
+/// Users are versioned like the snapshots of a [`PaymentRepository`].
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn upsert_user(&self, user: User, version: u32) -> Result<(), DbError>;
+
+    async fn get_user(&self, user_id: Uuid) -> Result<Option<(User, u32)>, DbError>;
+
+    /// The user registered with `email`, ignoring case.
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<(User, u32)>, DbError>;
+
+    async fn get_users(&self, limit: i64, offset: i64) -> Result<Vec<(User, u32)>, DbError>;
+
+    /// Re-encrypts up to `limit` users whose personal data is not encrypted
+    /// with the active key, like [`PaymentRepository::rotate_payment_keys`].
+    async fn rotate_user_keys(&self, limit: i64) -> Result<usize, DbError>;
+
+    async fn link_user_payment(&self, user_payment: UserPayment) -> Result<(), DbError>;
+
+    /// Links every existing payment sent from or to `email`, ignoring case, to
+    /// the user, for payments made before the user registered.
+    async fn link_user_payments_by_email(
+        &self,
+        user_id: Uuid,
+        email: &str,
+        payer_role: &str,
+        payee_role: &str,
+    ) -> Result<u64, DbError>;
+
+    /// Payments linked to the user under `role`, newest first.
+    async fn get_user_payments(
+        &self,
+        user_id: Uuid,
+        role: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<(Payment, u32)>, DbError>;
+}
+
+/// The admin audit log. Entries are only ever added, never changed or
+/// removed.
+#[async_trait]
+pub trait AuditRepository: Send + Sync {
+    async fn insert_audit_entry(&self, entry: AdminAuditEntry) -> Result<(), DbError>;
+
+    /// The entries matching `query`, newest first.
+    async fn query_audit_entries(
+        &self,
+        query: &AdminAuditQuery,
+    ) -> Result<Vec<AdminAuditEntry>, DbError>;
+}
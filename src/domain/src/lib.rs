@@ -1,8 +1,9 @@
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr};
 
 use anyhow::Context;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Months, Utc};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -21,6 +22,149 @@ pub struct RefundId(Uuid);
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct UserId(Uuid);
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PaymentRequestId(Uuid);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ScheduledTransferId(Uuid);
+
+impl PaymentId {
+    /// The idempotency key of `operation` on the payment at TrueLayer, the
+    /// same on every attempt, so a retried request cannot create a second
+    /// payout or refund.
+    pub fn idempotency_key(&self, operation: &str) -> Uuid {
+        let digest = Sha256::digest(format!("{}/{operation}", self.0));
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(&digest[..16]);
+        uuid::Builder::from_random_bytes(bytes).into_uuid()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Money
+////////////////////////////////////////////////////////////////////////////////
+
+/// ISO-4217 currencies supported by the merchant accounts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Currency {
+    Gbp,
+    Eur,
+}
+
+impl Currency {
+    pub const ALL: [Currency; 2] = [Currency::Gbp, Currency::Eur];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Currency::Gbp => "GBP",
+            Currency::Eur => "EUR",
+        }
+    }
+
+    pub const fn symbol(self) -> &'static str {
+        match self {
+            Currency::Gbp => "£",
+            Currency::Eur => "€",
+        }
+    }
+
+    /// Number of decimal places between the minor and the major unit.
+    pub const fn minor_unit_exponent(self) -> u32 {
+        match self {
+            Currency::Gbp | Currency::Eur => 2,
+        }
+    }
+}
+
+impl Display for Currency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Currency {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Currency::ALL
+            .into_iter()
+            .find(|c| c.as_str().eq_ignore_ascii_case(s))
+            .with_context(|| format!("Unsupported currency: {s}"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Money {
+    pub amount_in_minor: u32,
+    pub currency: Currency,
+}
+
+impl Money {
+    pub const fn new(amount_in_minor: u32, currency: Currency) -> Self {
+        Self {
+            amount_in_minor,
+            currency,
+        }
+    }
+}
+
+impl Display for Money {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let exponent = self.currency.minor_unit_exponent();
+        let scale = 10u32.pow(exponent);
+        write!(
+            f,
+            "{}{}.{:0width$}",
+            self.currency.symbol(),
+            self.amount_in_minor / scale,
+            self.amount_in_minor % scale,
+            width = exponent as usize
+        )
+    }
+}
+
+/// Reference shown on the payer's and payee's bank statements. Faster
+/// Payments accepts at most 18 characters of `A-Z`, `0-9`, space and
+/// `- . / &`, which SEPA also accepts, so anything else is replaced. Text
+/// with nothing accepted in it gets [`PaymentReference::DEFAULT`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentReference(String);
+
+impl PaymentReference {
+    pub const MAX_LEN: usize = 18;
+    pub const DEFAULT: &'static str = "E-TRANSFER";
+
+    pub fn new(text: &str) -> Self {
+        let mut reference = String::with_capacity(Self::MAX_LEN);
+        for c in text.chars() {
+            if reference.len() == Self::MAX_LEN {
+                break;
+            }
+            match c.to_ascii_uppercase() {
+                c @ ('A'..='Z' | '0'..='9' | '-' | '.' | '/' | '&') => reference.push(c),
+                _ if reference.is_empty() || reference.ends_with(' ') => {}
+                _ => reference.push(' '),
+            }
+        }
+        reference.truncate(reference.trim_end().len());
+        if reference.is_empty() {
+            reference.push_str(Self::DEFAULT);
+        }
+        Self(reference)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for PaymentReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 // Payment Models
 ////////////////////////////////////////////////////////////////////////////////
@@ -32,24 +176,481 @@ pub struct Payment {
     pub payer_email: String,
     pub payee_full_name: String,
     pub payee_email: String,
-    pub amount: u32,
+    pub amount: Money,
     pub security_question: String,
     pub security_answer: String,
+    /// Free-text note from the payer to the payee.
+    pub message: Option<String>,
+    pub deposit_lock: DepositLock,
+    pub cancellation_request: Option<CancellationRequest>,
+    /// Set when the payment was made in answer to a [`PaymentRequest`].
+    pub payment_request_id: Option<PaymentRequestId>,
+    /// The risk assessment made at settlement, set once the payment was held
+    /// for review (see [`PaymentState::HeldForReview`]) or released.
+    pub risk_assessment: Option<RiskAssessment>,
+    /// Why the inbound payment failed, see [`PaymentState::InboundFailed`].
+    pub failure: Option<PaymentFailure>,
     pub payment_statuses: PaymentStatuses,
     pub payout_data: Option<PayoutData>,
     pub refund_data: Option<RefundData>,
+    /// Where the requested payout goes, until it is created, see
+    /// [`PaymentState::PayoutRequested`].
+    pub payout_destination: Option<PayoutDestination>,
+    /// Set once the personal data of the payment was erased.
+    pub personal_data_erased_at: Option<DateTime<Utc>>,
 }
 
 impl Payment {
+    pub const MESSAGE_MAX_LEN: usize = 280;
+
+    /// A new GBP 10.00 payment between example parties, for tests to adjust.
+    #[cfg(any(test, feature = "test-fixtures"))]
+    pub fn test_fixture() -> Self {
+        Payment {
+            payment_id: PaymentId::new(),
+            payer_full_name: String::from("Payer"),
+            payer_email: String::from("payer@example.com"),
+            payee_full_name: String::from("Payee"),
+            payee_email: String::from("payee@example.com"),
+            amount: Money::new(1000, Currency::Gbp),
+            security_question: String::from("question"),
+            security_answer: String::from("answer"),
+            message: None,
+            deposit_lock: DepositLock::default(),
+            cancellation_request: None,
+            payment_request_id: None,
+            risk_assessment: None,
+            failure: None,
+            payment_statuses: PaymentStatuses::new(Utc::now()),
+            payout_data: None,
+            refund_data: None,
+            payout_destination: None,
+            personal_data_erased_at: None,
+        }
+    }
+
     pub fn state(&self) -> PaymentState {
+        let statuses = &self.payment_statuses;
         self.refund_data
             .as_ref()
             .map(RefundData::refund_state)
+            .or_else(|| {
+                statuses
+                    .refund_requested_at
+                    .map(|_| PaymentState::RefundRequested)
+            })
             .or_else(|| self.payout_data.as_ref().map(PayoutData::payout_state))
-            .unwrap_or(self.payment_statuses.payment_state())
+            .or_else(|| {
+                statuses
+                    .payout_requested_at
+                    .map(|_| PaymentState::PayoutRequested)
+            })
+            .unwrap_or(statuses.payment_state())
+    }
+
+    /// Applies `event` like [`Payment::apply`] and returns the history entry
+    /// describing the transition.
+    pub fn transition(
+        &mut self,
+        event: PaymentEvent,
+        source: EventSource,
+    ) -> Result<PaymentEventRecord, InvalidTransition> {
+        let previous_state = self.state();
+        let event_type = event.event_type();
+        let new_state = self.apply(event)?;
+        Ok(PaymentEventRecord::new(
+            self.payment_id,
+            event_type,
+            source,
+            Some(previous_state),
+            new_state,
+        ))
+    }
+
+    /// Moves the payment to the state targeted by `event`, recording its
+    /// timestamp. Fails without touching the payment if the transition is not
+    /// part of the payment state graph.
+    pub fn apply(&mut self, event: PaymentEvent) -> Result<PaymentState, InvalidTransition> {
+        let from = self.state();
+        let to = event.target_state();
+        // a held payment is only settled again by releasing it, and only a
+        // held payment can be released
+        let is_release = matches!(event, PaymentEvent::ReviewReleased { .. });
+        let releases_hold =
+            from == PaymentState::HeldForReview && to == PaymentState::InboundSettled;
+        if !from.can_transition_to(to) || is_release != releases_hold {
+            return Err(InvalidTransition { from, to });
+        }
+
+        match event {
+            PaymentEvent::InboundAuthorized { authorized_at } => {
+                self.payment_statuses.inbound_authorized_at = Some(authorized_at)
+            }
+            PaymentEvent::InboundExecuted { executed_at } => {
+                self.payment_statuses.inbound_executed_at = Some(executed_at)
+            }
+            PaymentEvent::InboundSettled { settled_at } => {
+                self.payment_statuses.inbound_settled_at = Some(settled_at)
+            }
+            PaymentEvent::InboundFailed { failed_at, failure } => {
+                self.payment_statuses.inbound_failed_at = Some(failed_at);
+                self.failure = failure;
+            }
+            PaymentEvent::Expired { expired_at } => {
+                self.payment_statuses.expired_at = Some(expired_at)
+            }
+            PaymentEvent::Cancelled { cancelled_at } => {
+                self.payment_statuses.cancelled_at = Some(cancelled_at);
+                self.cancellation_request = None;
+            }
+            PaymentEvent::HeldForReview { held_at } => {
+                self.payment_statuses.held_for_review_at = Some(held_at);
+                self.payment_statuses.review_released_at = None;
+            }
+            PaymentEvent::ReviewReleased { released_at } => {
+                self.payment_statuses.review_released_at = Some(released_at)
+            }
+            PaymentEvent::PayoutRequested {
+                destination,
+                requested_at,
+            } => {
+                self.payment_statuses.payout_requested_at = Some(requested_at);
+                self.payout_destination = Some(destination);
+            }
+            PaymentEvent::PayoutCreated {
+                payout_id,
+                created_at,
+            } => {
+                self.payout_destination = None;
+                self.payout_data = Some(PayoutData {
+                    payout_id,
+                    payout_statuses: PayoutStatuses {
+                        payout_created_at: created_at,
+                        payout_executed_at: None,
+                        payout_failed_at: None,
+                    },
+                })
+            }
+            PaymentEvent::PayoutExecuted { executed_at } => {
+                if let Some(payout) = self.payout_data.as_mut() {
+                    payout.payout_statuses.payout_executed_at = Some(executed_at)
+                }
+            }
+            PaymentEvent::PayoutFailed { failed_at } => {
+                if let Some(payout) = self.payout_data.as_mut() {
+                    payout.payout_statuses.payout_failed_at = Some(failed_at)
+                }
+            }
+            PaymentEvent::RefundRequested { requested_at } => {
+                self.payment_statuses.refund_requested_at = Some(requested_at)
+            }
+            PaymentEvent::RefundCreated {
+                refund_id,
+                created_at,
+            } => {
+                self.refund_data = Some(RefundData {
+                    refund_id,
+                    refund_statuses: RefundStatuses {
+                        refund_created_at: created_at,
+                        refund_executed_at: None,
+                        refund_failed_at: None,
+                    },
+                })
+            }
+            PaymentEvent::RefundExecuted { executed_at } => {
+                if let Some(refund) = self.refund_data.as_mut() {
+                    refund.refund_statuses.refund_executed_at = Some(executed_at)
+                }
+            }
+            PaymentEvent::RefundFailed { failed_at } => {
+                if let Some(refund) = self.refund_data.as_mut() {
+                    refund.refund_statuses.refund_failed_at = Some(failed_at)
+                }
+            }
+        }
+
+        Ok(self.state())
+    }
+
+    /// Scrubs the names and emails of both parties, the security question and
+    /// answer and the message, keeping the amount, statuses and ids as the
+    /// financial record of the payment. Returns the history entry of the
+    /// erasure, which leaves the state as it is.
+    pub fn erase_personal_data(
+        &mut self,
+        source: EventSource,
+        erased_at: DateTime<Utc>,
+    ) -> PaymentEventRecord {
+        self.payer_full_name.clear();
+        self.payer_email.clear();
+        self.payee_full_name.clear();
+        self.payee_email.clear();
+        self.security_question.clear();
+        self.security_answer.clear();
+        self.message = None;
+        self.cancellation_request = None;
+        self.payout_destination = None;
+        self.personal_data_erased_at = Some(erased_at);
+
+        let state = self.state();
+        PaymentEventRecord::new(
+            self.payment_id,
+            "personal_data_erased",
+            source,
+            Some(state),
+            state,
+        )
     }
 }
 
+/// Something that happened to a payment, moving it to a new [`PaymentState`].
+#[derive(Debug, Clone)]
+pub enum PaymentEvent {
+    InboundAuthorized {
+        authorized_at: DateTime<Utc>,
+    },
+    InboundExecuted {
+        executed_at: DateTime<Utc>,
+    },
+    InboundSettled {
+        settled_at: DateTime<Utc>,
+    },
+    InboundFailed {
+        failed_at: DateTime<Utc>,
+        failure: Option<PaymentFailure>,
+    },
+    Expired {
+        expired_at: DateTime<Utc>,
+    },
+    Cancelled {
+        cancelled_at: DateTime<Utc>,
+    },
+    HeldForReview {
+        held_at: DateTime<Utc>,
+    },
+    /// An admin approved a held payment, it is settled again.
+    ReviewReleased {
+        released_at: DateTime<Utc>,
+    },
+    /// The payee asked for a payout to `destination`, it is created with
+    /// TrueLayer next.
+    PayoutRequested {
+        destination: PayoutDestination,
+        requested_at: DateTime<Utc>,
+    },
+    PayoutCreated {
+        payout_id: PayoutId,
+        created_at: DateTime<Utc>,
+    },
+    PayoutExecuted {
+        executed_at: DateTime<Utc>,
+    },
+    PayoutFailed {
+        failed_at: DateTime<Utc>,
+    },
+    /// The payment is to be refunded, the refund is created with TrueLayer
+    /// next.
+    RefundRequested {
+        requested_at: DateTime<Utc>,
+    },
+    RefundCreated {
+        refund_id: RefundId,
+        created_at: DateTime<Utc>,
+    },
+    RefundExecuted {
+        executed_at: DateTime<Utc>,
+    },
+    RefundFailed {
+        failed_at: DateTime<Utc>,
+    },
+}
+
+impl PaymentEvent {
+    pub const fn target_state(&self) -> PaymentState {
+        match self {
+            PaymentEvent::InboundAuthorized { .. } => PaymentState::InboundAuthorized,
+            PaymentEvent::InboundExecuted { .. } => PaymentState::InboundExecuted,
+            PaymentEvent::InboundSettled { .. } => PaymentState::InboundSettled,
+            PaymentEvent::InboundFailed { .. } => PaymentState::InboundFailed,
+            PaymentEvent::Expired { .. } => PaymentState::Expired,
+            PaymentEvent::Cancelled { .. } => PaymentState::Cancelled,
+            PaymentEvent::HeldForReview { .. } => PaymentState::HeldForReview,
+            PaymentEvent::ReviewReleased { .. } => PaymentState::InboundSettled,
+            PaymentEvent::PayoutRequested { .. } => PaymentState::PayoutRequested,
+            PaymentEvent::PayoutCreated { .. } => PaymentState::PayoutCreated,
+            PaymentEvent::PayoutExecuted { .. } => PaymentState::PayoutExecuted,
+            PaymentEvent::PayoutFailed { .. } => PaymentState::PayoutFailed,
+            PaymentEvent::RefundRequested { .. } => PaymentState::RefundRequested,
+            PaymentEvent::RefundCreated { .. } => PaymentState::RefundCreated,
+            PaymentEvent::RefundExecuted { .. } => PaymentState::RefundExecuted,
+            PaymentEvent::RefundFailed { .. } => PaymentState::RefundFailed,
+        }
+    }
+
+    /// Name of the event in the payment history.
+    pub const fn event_type(&self) -> &'static str {
+        match self {
+            PaymentEvent::ReviewReleased { .. } => "review_released",
+            event => event.target_state().as_str(),
+        }
+    }
+}
+
+/// Who or what caused a change to a payment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventSource {
+    Webhook,
+    Ui,
+    Admin,
+    System,
+}
+
+impl EventSource {
+    pub const ALL: [EventSource; 4] = [
+        EventSource::Webhook,
+        EventSource::Ui,
+        EventSource::Admin,
+        EventSource::System,
+    ];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            EventSource::Webhook => "webhook",
+            EventSource::Ui => "ui",
+            EventSource::Admin => "admin",
+            EventSource::System => "system",
+        }
+    }
+}
+
+impl FromStr for EventSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EventSource::ALL
+            .into_iter()
+            .find(|source| source.as_str() == s)
+            .with_context(|| format!("Unknown event source: {s}"))
+    }
+}
+
+/// An entry in the history of a payment.
+#[derive(Debug, Clone)]
+pub struct PaymentEventRecord {
+    pub event_id: Uuid,
+    pub payment_id: PaymentId,
+    pub event_type: String,
+    pub source: EventSource,
+    /// Reference to the raw payload that caused the event, e.g. the
+    /// TrueLayer webhook `event_id`.
+    pub payload_ref: Option<String>,
+    pub previous_state: Option<PaymentState>,
+    pub new_state: PaymentState,
+    pub created_at: DateTime<Utc>,
+}
+
+impl PaymentEventRecord {
+    pub fn new(
+        payment_id: PaymentId,
+        event_type: impl Into<String>,
+        source: EventSource,
+        previous_state: Option<PaymentState>,
+        new_state: PaymentState,
+    ) -> Self {
+        Self {
+            event_id: Uuid::new_v4(),
+            payment_id,
+            event_type: event_type.into(),
+            source,
+            payload_ref: None,
+            previous_state,
+            new_state,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn with_payload_ref(mut self, payload_ref: impl Into<String>) -> Self {
+        self.payload_ref = Some(payload_ref.into());
+        self
+    }
+}
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("Invalid payment transition: {} -> {}", from.as_str(), to.as_str())]
+pub struct InvalidTransition {
+    pub from: PaymentState,
+    pub to: PaymentState,
+}
+
+/// Guards the security answer of a payment against brute force guessing.
+#[derive(Debug, Clone, Default)]
+pub struct DepositLock {
+    pub failed_attempts: u32,
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LockoutPolicy {
+    pub max_attempts: u32,
+    pub cooldown: chrono::Duration,
+}
+
+impl DepositLock {
+    pub fn is_locked(&self, now: DateTime<Utc>) -> bool {
+        self.locked_until
+            .is_some_and(|locked_until| now < locked_until)
+    }
+
+    /// Counts a wrong security answer. Returns `true` if this attempt locked
+    /// the deposit, in which case the attempt counter starts over once the
+    /// cool-down has passed.
+    pub fn record_failure(&mut self, now: DateTime<Utc>, policy: LockoutPolicy) -> bool {
+        self.failed_attempts += 1;
+        if self.failed_attempts >= policy.max_attempts {
+            self.failed_attempts = 0;
+            self.locked_until = Some(now + policy.cooldown);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = DepositLock::default();
+    }
+}
+
+/// The stage and reason TrueLayer gave for a failed inbound payment.
+#[derive(Debug, Clone)]
+pub struct PaymentFailure {
+    pub stage: String,
+    pub reason: String,
+}
+
+/// The risk score a payment was given when it settled and the rules behind
+/// it.
+#[derive(Debug, Clone)]
+pub struct RiskAssessment {
+    pub score: u32,
+    pub reasons: Vec<String>,
+    pub assessed_at: DateTime<Utc>,
+}
+
+/// A pending payer cancellation, confirmed through a one-time link emailed to
+/// the payer. Only the argon2 hash of the link token is kept.
+#[derive(Debug, Clone)]
+pub struct CancellationRequest {
+    pub token_hash: String,
+    pub requested_at: DateTime<Utc>,
+}
+
+/// The account a payout is sent to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayoutDestination {
+    pub account_holder_name: String,
+    pub account_identifier: AccountIdentifier,
+}
+
 #[derive(Debug, Clone)]
 pub struct PayoutData {
     pub payout_id: PayoutId,
@@ -119,12 +720,42 @@ pub struct PaymentStatuses {
     pub inbound_executed_at: Option<DateTime<Utc>>,
     pub inbound_settled_at: Option<DateTime<Utc>>,
     pub inbound_failed_at: Option<DateTime<Utc>>,
+    pub expired_at: Option<DateTime<Utc>>,
+    pub cancelled_at: Option<DateTime<Utc>>,
+    pub held_for_review_at: Option<DateTime<Utc>>,
+    pub review_released_at: Option<DateTime<Utc>>,
+    pub payout_requested_at: Option<DateTime<Utc>>,
+    pub refund_requested_at: Option<DateTime<Utc>>,
 }
 
 impl PaymentStatuses {
+    /// The statuses of a payment created at `inbound_created_at`.
+    pub const fn new(inbound_created_at: DateTime<Utc>) -> Self {
+        PaymentStatuses {
+            inbound_created_at,
+            inbound_authorized_at: None,
+            inbound_executed_at: None,
+            inbound_settled_at: None,
+            inbound_failed_at: None,
+            expired_at: None,
+            cancelled_at: None,
+            held_for_review_at: None,
+            review_released_at: None,
+            payout_requested_at: None,
+            refund_requested_at: None,
+        }
+    }
+
     pub fn payment_state(&self) -> PaymentState {
         self.inbound_failed_at
             .map(|_| PaymentState::InboundFailed)
+            .or_else(|| self.expired_at.map(|_| PaymentState::Expired))
+            .or_else(|| self.cancelled_at.map(|_| PaymentState::Cancelled))
+            .or_else(|| {
+                self.held_for_review_at
+                    .filter(|_| self.review_released_at.is_none())
+                    .map(|_| PaymentState::HeldForReview)
+            })
             .or_else(|| {
                 self.inbound_settled_at
                     .map(|_| PaymentState::InboundSettled)
@@ -149,17 +780,87 @@ pub enum PaymentState {
     InboundExecuted,
     InboundSettled,
     InboundFailed,
+    // review status
+    HeldForReview,
+    // unclaimed status
+    Expired,
+    Cancelled,
     // outbound status
+    /// A payout was claimed but not created with TrueLayer yet.
+    PayoutRequested,
     PayoutCreated,
     PayoutExecuted,
     PayoutFailed,
     // refund status
+    /// A refund was claimed but not created with TrueLayer yet.
+    RefundRequested,
     RefundCreated,
     RefundExecuted,
     RefundFailed,
 }
 
 impl PaymentState {
+    pub const ALL: [PaymentState; 16] = [
+        PaymentState::InboundCreated,
+        PaymentState::InboundAuthorized,
+        PaymentState::InboundExecuted,
+        PaymentState::InboundSettled,
+        PaymentState::InboundFailed,
+        PaymentState::HeldForReview,
+        PaymentState::Expired,
+        PaymentState::Cancelled,
+        PaymentState::PayoutRequested,
+        PaymentState::PayoutCreated,
+        PaymentState::PayoutExecuted,
+        PaymentState::PayoutFailed,
+        PaymentState::RefundRequested,
+        PaymentState::RefundCreated,
+        PaymentState::RefundExecuted,
+        PaymentState::RefundFailed,
+    ];
+
+    /// The legal edges of the payment state graph.
+    ///
+    /// Inbound webhooks may skip intermediate states (TrueLayer does not
+    /// guarantee every status is delivered) but may never move backwards.
+    /// Payouts and refunds are requested before they are created, so only one
+    /// of them is ever sent to TrueLayer for a payment.
+    pub const fn can_transition_to(self, next: PaymentState) -> bool {
+        use PaymentState::*;
+        matches!(
+            (self, next),
+            (
+                InboundCreated,
+                InboundAuthorized | InboundExecuted | InboundSettled | InboundFailed
+            ) | (
+                InboundAuthorized,
+                InboundExecuted | InboundSettled | InboundFailed
+            ) | (InboundExecuted, InboundSettled)
+                | (
+                    InboundSettled,
+                    HeldForReview | Expired | Cancelled | PayoutRequested | RefundRequested
+                )
+                | (HeldForReview, InboundSettled | RefundRequested)
+                | (Expired | Cancelled, RefundRequested)
+                | (PayoutRequested, PayoutCreated)
+                | (PayoutCreated, PayoutExecuted | PayoutFailed)
+                | (PayoutFailed, RefundRequested)
+                | (RefundRequested, RefundCreated)
+                | (RefundCreated, RefundExecuted | RefundFailed)
+        )
+    }
+
+    /// Whether no transition leaves the state and nothing is owed to either
+    /// party, so the payment needs none of its personal data anymore. The
+    /// funds of a failed refund are still owed to the payer until it is
+    /// resolved by hand.
+    pub fn is_final(self) -> bool {
+        self != PaymentState::RefundFailed
+            && PaymentState::ALL
+                .into_iter()
+                .all(|next| !self.can_transition_to(next))
+    }
+
     pub const fn as_str(self) -> &'static str {
         match self {
             PaymentState::InboundCreated => "inbound_created",
@@ -167,9 +868,14 @@ impl PaymentState {
             PaymentState::InboundExecuted => "inbound_executed",
             PaymentState::InboundSettled => "inbound_settled",
             PaymentState::InboundFailed => "inbound_failed",
+            PaymentState::HeldForReview => "held_for_review",
+            PaymentState::Expired => "expired",
+            PaymentState::Cancelled => "cancelled",
+            PaymentState::PayoutRequested => "payout_requested",
             PaymentState::PayoutCreated => "payout_created",
             PaymentState::PayoutExecuted => "payout_executed",
             PaymentState::PayoutFailed => "payout_failed",
+            PaymentState::RefundRequested => "refund_requested",
             PaymentState::RefundCreated => "refund_created",
             PaymentState::RefundExecuted => "refund_executed",
             PaymentState::RefundFailed => "refund_failed",
@@ -177,78 +883,688 @@ impl PaymentState {
     }
 }
 
+impl FromStr for PaymentState {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PaymentState::ALL
+            .into_iter()
+            .find(|state| state.as_str() == s)
+            .with_context(|| format!("Unknown payment state: {s}"))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Payment Request Models
+////////////////////////////////////////////////////////////////////////////////
+
+/// A registered payee asking `payer_email` for money.
+#[derive(Debug, Clone)]
+pub struct PaymentRequest {
+    pub request_id: PaymentRequestId,
+    pub payee_user_id: UserId,
+    pub payer_email: String,
+    pub amount: Money,
+    pub note: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// The payment currently answering the request, cleared again if it fails.
+    pub payment_id: Option<PaymentId>,
+    pub paid_at: Option<DateTime<Utc>>,
+    pub declined_at: Option<DateTime<Utc>>,
+    /// Set while a payment for the request is being created, so only one
+    /// payer attempt creates one. A claim left behind lapses after
+    /// [`PaymentRequest::CLAIM_MINUTES`].
+    pub claimed_at: Option<DateTime<Utc>>,
+}
+
+impl PaymentRequest {
+    pub const CLAIM_MINUTES: i64 = 15;
+
+    pub fn status(&self, now: DateTime<Utc>) -> PaymentRequestStatus {
+        if self.paid_at.is_some() {
+            PaymentRequestStatus::Paid
+        } else if self.declined_at.is_some() {
+            PaymentRequestStatus::Declined
+        } else if now >= self.expires_at {
+            PaymentRequestStatus::Expired
+        } else {
+            PaymentRequestStatus::Pending
+        }
+    }
+
+    /// Whether the payer can start a payment for this request.
+    pub fn is_payable(&self, now: DateTime<Utc>) -> bool {
+        let is_claimed = self.claimed_at.is_some_and(|claimed_at| {
+            now - claimed_at < chrono::Duration::minutes(Self::CLAIM_MINUTES)
+        });
+        self.status(now) == PaymentRequestStatus::Pending
+            && self.payment_id.is_none()
+            && !is_claimed
+    }
+
+    /// Scrubs the payer email and note, declining the request if it could
+    /// still be paid.
+    pub fn erase_personal_data(&mut self, now: DateTime<Utc>) {
+        if self.is_payable(now) {
+            self.declined_at = Some(now);
+        }
+        self.payer_email.clear();
+        self.note.clear();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaymentRequestStatus {
+    Pending,
+    Paid,
+    Declined,
+    Expired,
+}
+
+impl PaymentRequestStatus {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            PaymentRequestStatus::Pending => "pending",
+            PaymentRequestStatus::Paid => "paid",
+            PaymentRequestStatus::Declined => "declined",
+            PaymentRequestStatus::Expired => "expired",
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Scheduled Transfer Models
+////////////////////////////////////////////////////////////////////////////////
+
+/// A transfer a registered payer sends on a future date, once or repeatedly.
+/// Each occurrence creates a new [`Payment`] the payer authorizes by email.
+#[derive(Debug, Clone)]
+pub struct ScheduledTransfer {
+    pub schedule_id: ScheduledTransferId,
+    pub user_id: UserId,
+    pub payee_full_name: String,
+    pub payee_email: String,
+    pub amount: Money,
+    pub security_question: String,
+    pub security_answer: String,
+    pub frequency: ScheduleFrequency,
+    pub starts_at: DateTime<Utc>,
+    /// Index of the next occurrence, counting from `starts_at`.
+    pub occurrence: u32,
+    pub status: ScheduleStatus,
+    pub last_payment_id: Option<PaymentId>,
+    pub last_run_at: Option<DateTime<Utc>>,
+}
+
+impl ScheduledTransfer {
+    /// When the next payment is due, `None` once the schedule has ended.
+    pub fn next_run_at(&self) -> Option<DateTime<Utc>> {
+        match self.status {
+            ScheduleStatus::Active | ScheduleStatus::Paused => {
+                self.frequency.occurrence(self.starts_at, self.occurrence)
+            }
+            ScheduleStatus::Cancelled | ScheduleStatus::Completed => None,
+        }
+    }
+
+    /// Like [`ScheduledTransfer::next_run_at`] but `None` while paused.
+    pub fn due_at(&self) -> Option<DateTime<Utc>> {
+        match self.status {
+            ScheduleStatus::Active => self.next_run_at(),
+            _ => None,
+        }
+    }
+
+    /// Moves the schedule past the current occurrence, completing it when no
+    /// occurrences are left.
+    pub fn record_run(&mut self, now: DateTime<Utc>) {
+        self.occurrence += 1;
+        self.last_run_at = Some(now);
+        if self.next_run_at().is_none() {
+            self.status = ScheduleStatus::Completed;
+        }
+    }
+
+    pub fn pause(&mut self) {
+        if self.status == ScheduleStatus::Active {
+            self.status = ScheduleStatus::Paused;
+        }
+    }
+
+    /// Resumes a paused schedule. Recurring occurrences missed while paused
+    /// are skipped, a one-off transfer runs as soon as it is resumed.
+    pub fn resume(&mut self, now: DateTime<Utc>) {
+        if self.status != ScheduleStatus::Paused {
+            return;
+        }
+        self.status = ScheduleStatus::Active;
+        if self.frequency != ScheduleFrequency::Once {
+            while self
+                .next_run_at()
+                .is_some_and(|next_run_at| next_run_at < now)
+            {
+                self.occurrence += 1;
+            }
+        }
+    }
+
+    pub fn cancel(&mut self) {
+        if matches!(self.status, ScheduleStatus::Active | ScheduleStatus::Paused) {
+            self.status = ScheduleStatus::Cancelled;
+        }
+    }
+
+    /// Cancels the schedule and scrubs the payee details and security
+    /// question and answer.
+    pub fn erase_personal_data(&mut self) {
+        self.cancel();
+        self.payee_full_name.clear();
+        self.payee_email.clear();
+        self.security_question.clear();
+        self.security_answer.clear();
+    }
+
+    /// Restarts the schedule from `starts_at`, as after an edit.
+    pub fn reschedule(&mut self, starts_at: DateTime<Utc>, frequency: ScheduleFrequency) {
+        self.starts_at = starts_at;
+        self.frequency = frequency;
+        self.occurrence = 0;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleFrequency {
+    Once,
+    Weekly,
+    Monthly,
+}
+
+impl ScheduleFrequency {
+    pub const ALL: [ScheduleFrequency; 3] = [
+        ScheduleFrequency::Once,
+        ScheduleFrequency::Weekly,
+        ScheduleFrequency::Monthly,
+    ];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            ScheduleFrequency::Once => "once",
+            ScheduleFrequency::Weekly => "weekly",
+            ScheduleFrequency::Monthly => "monthly",
+        }
+    }
+
+    /// The `n`th occurrence of a schedule starting at `starts_at`. Monthly
+    /// occurrences keep the day of `starts_at`, clamped to the month's end.
+    pub fn occurrence(self, starts_at: DateTime<Utc>, n: u32) -> Option<DateTime<Utc>> {
+        match self {
+            ScheduleFrequency::Once => (n == 0).then_some(starts_at),
+            ScheduleFrequency::Weekly => Some(starts_at + chrono::Duration::weeks(n.into())),
+            ScheduleFrequency::Monthly => starts_at.checked_add_months(Months::new(n)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleStatus {
+    Active,
+    Paused,
+    Cancelled,
+    Completed,
+}
+
+impl ScheduleStatus {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            ScheduleStatus::Active => "active",
+            ScheduleStatus::Paused => "paused",
+            ScheduleStatus::Cancelled => "cancelled",
+            ScheduleStatus::Completed => "completed",
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Outbox Models
+////////////////////////////////////////////////////////////////////////////////
+
+/// A side effect of a state change, stored with the change and delivered
+/// afterwards, so stopping right after the change does not lose it.
+#[derive(Debug, Clone)]
+pub struct OutboxMessage {
+    pub message_id: Uuid,
+    pub payment_id: Option<PaymentId>,
+    pub effect: OutboxEffect,
+    pub status: OutboxStatus,
+    /// Failed and successful deliveries so far.
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub next_attempt_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl OutboxMessage {
+    pub fn new(payment_id: Option<PaymentId>, effect: OutboxEffect) -> Self {
+        let now = Utc::now();
+        Self {
+            message_id: Uuid::new_v4(),
+            payment_id,
+            effect,
+            status: OutboxStatus::Pending,
+            attempts: 0,
+            last_error: None,
+            next_attempt_at: now,
+            created_at: now,
+        }
+    }
+
+    pub fn record_delivery(&mut self) {
+        self.attempts += 1;
+        self.status = OutboxStatus::Delivered;
+    }
+
+    /// Records a failed delivery, retried at `retry_at` or given up on when
+    /// there is none.
+    pub fn record_failure(&mut self, error: String, retry_at: Option<DateTime<Utc>>) {
+        self.attempts += 1;
+        self.last_error = Some(error);
+        match retry_at {
+            Some(retry_at) => self.next_attempt_at = retry_at,
+            None => self.status = OutboxStatus::Dead,
+        }
+    }
+
+    /// Queues a dead message again with a fresh set of attempts.
+    pub fn retry(&mut self, now: DateTime<Utc>) {
+        if self.status == OutboxStatus::Dead {
+            self.status = OutboxStatus::Pending;
+            self.attempts = 0;
+            self.next_attempt_at = now;
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutboxEffect {
+    Email {
+        to: String,
+        subject: String,
+        body: String,
+    },
+    /// Creates the requested payout of the payment.
+    Payout { payment_id: PaymentId },
+    /// Creates the refund of the payment, requesting it first if it was not.
+    Refund { payment_id: PaymentId },
+    /// Assesses a settled payment and releases it to the payee, or holds it
+    /// for review. `payer_accounts` are the accounts TrueLayer reported the
+    /// payer paid from.
+    Settle {
+        payment_id: PaymentId,
+        payer_accounts: Vec<AccountIdentifier>,
+    },
+}
+
+impl OutboxEffect {
+    pub fn email(to: impl Into<String>, subject: impl Into<String>, body: String) -> Self {
+        OutboxEffect::Email {
+            to: to.into(),
+            subject: subject.into(),
+            body,
+        }
+    }
+
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            OutboxEffect::Email { .. } => "email",
+            OutboxEffect::Payout { .. } => "payout",
+            OutboxEffect::Refund { .. } => "refund",
+            OutboxEffect::Settle { .. } => "settle",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutboxStatus {
+    Pending,
+    Delivered,
+    /// Failed every attempt, left for an admin to look at.
+    Dead,
+}
+
+impl OutboxStatus {
+    pub const ALL: [OutboxStatus; 3] = [
+        OutboxStatus::Pending,
+        OutboxStatus::Delivered,
+        OutboxStatus::Dead,
+    ];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            OutboxStatus::Pending => "pending",
+            OutboxStatus::Delivered => "delivered",
+            OutboxStatus::Dead => "dead",
+        }
+    }
+}
+
+impl FromStr for OutboxStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        OutboxStatus::ALL
+            .into_iter()
+            .find(|status| status.as_str() == s)
+            .with_context(|| format!("Unknown outbox status: {s}"))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// User Models
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone)]
+pub enum User {
+    Registering {
+        user_id: UserId,
+        email: String,
+        first_name: String,
+        last_name: String,
+        code: String,
+        timestamp: DateTime<Utc>,
+    },
+    Registered {
+        user_id: UserId,
+        email: String,
+        first_name: String,
+        last_name: String,
+        payout_account: Option<PayoutAccount>,
+    },
+    /// A user erased on request, of whom only the id is left. Their email
+    /// and names read as empty.
+    Erased {
+        user_id: UserId,
+        erased_at: DateTime<Utc>,
+    },
+}
+
+impl User {
+    pub fn state(&self) -> UserState {
+        match self {
+            User::Registered { .. } => UserState::Registered,
+            User::Registering { .. } => UserState::Registering,
+            User::Erased { .. } => UserState::Erased,
+        }
+    }
+
+    pub fn user_id(&self) -> UserId {
+        match self {
+            User::Registered { user_id, .. } => *user_id,
+            User::Registering { user_id, .. } => *user_id,
+            User::Erased { user_id, .. } => *user_id,
+        }
+    }
+
+    pub fn email(&self) -> &str {
+        match self {
+            User::Registered { email, .. } => email,
+            User::Registering { email, .. } => email,
+            User::Erased { .. } => "",
+        }
+    }
+
+    pub fn first_name(&self) -> &str {
+        match self {
+            User::Registered { first_name, .. } => first_name,
+            User::Registering { first_name, .. } => first_name,
+            User::Erased { .. } => "",
+        }
+    }
+
+    pub fn last_name(&self) -> &str {
+        match self {
+            User::Registered { last_name, .. } => last_name,
+            User::Registering { last_name, .. } => last_name,
+            User::Erased { .. } => "",
+        }
+    }
+
+    pub fn payout_account(&self) -> Option<&PayoutAccount> {
+        match self {
+            User::Registered { payout_account, .. } => payout_account.as_ref(),
+            User::Registering { .. } | User::Erased { .. } => None,
+        }
+    }
+
+    pub fn registration_code(&self) -> Option<(&str, &DateTime<Utc>)> {
+        match self {
+            User::Registered { .. } | User::Erased { .. } => None,
+            User::Registering {
+                timestamp, code, ..
+            } => Some((code, timestamp)),
+        }
+    }
+
+    /// The user with their personal data dropped.
+    pub fn erase(&self, erased_at: DateTime<Utc>) -> User {
+        User::Erased {
+            user_id: self.user_id(),
+            erased_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserState {
+    Registering,
+    Registered,
+    Erased,
+}
+
+impl UserState {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            UserState::Registering => "registering",
+            UserState::Registered => "registered",
+            UserState::Erased => "erased",
+        }
+    }
+}
+
+/// A bank account a registered user verified through the Data API, payouts
+/// of settled payments to them are sent here automatically.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PayoutAccount {
+    pub display_name: String,
+    pub currency: Currency,
+    pub account_identifier: AccountIdentifier,
+    pub verified_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AccountIdentifier {
+    Iban {
+        iban: String,
+    },
+    SortCodeAccountNumber {
+        sort_code: String,
+        account_number: String,
+    },
+}
+
+impl AccountIdentifier {
+    /// The identifier with all but the last four digits hidden.
+    pub fn masked(&self) -> String {
+        let number = match self {
+            AccountIdentifier::Iban { iban } => iban,
+            AccountIdentifier::SortCodeAccountNumber { account_number, .. } => account_number,
+        };
+        let visible = number.len().saturating_sub(4);
+        format!("****{}", number.get(visible..).unwrap_or_default())
+    }
+}
+
+/// Which side of a payment a registered user is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserPaymentRole {
+    Payer,
+    Payee,
+}
+
+impl UserPaymentRole {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            UserPaymentRole::Payer => "payer",
+            UserPaymentRole::Payee => "payee",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct UserPayment {
+    pub payment_id: PaymentId,
+    pub user_id: UserId,
+    pub role: UserPaymentRole,
+}
+
 ////////////////////////////////////////////////////////////////////////////////
-// User Models
+// Admin Audit Models
 ////////////////////////////////////////////////////////////////////////////////
 
+/// An entry of the append-only admin audit log: a request an admin made, or a
+/// change they made with the target before and after it.
 #[derive(Debug, Clone)]
-pub enum User {
-    Registering {
-        user_id: UserId,
-        email: String,
-        first_name: String,
-        last_name: String,
-        code: String,
-        timestamp: DateTime<Utc>,
-    },
-    Registered {
-        user_id: UserId,
-        email: String,
-        first_name: String,
-        last_name: String,
-    },
+pub struct AdminAuditEntry {
+    pub entry_id: Uuid,
+    /// Name the admin signed in with.
+    pub actor: String,
+    pub action: AdminAction,
+    pub target: Option<AuditTarget>,
+    /// Method and path of an [`AdminAction::Request`].
+    pub request: Option<String>,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub ip: Option<String>,
+    pub created_at: DateTime<Utc>,
 }
 
-impl User {
-    pub fn state(&self) -> UserState {
-        match self {
-            User::Registered { .. } => UserState::Registered,
-            User::Registering { .. } => UserState::Registering,
+impl AdminAuditEntry {
+    pub fn new(actor: impl Into<String>, action: AdminAction, ip: Option<String>) -> Self {
+        Self {
+            entry_id: Uuid::new_v4(),
+            actor: actor.into(),
+            action,
+            target: None,
+            request: None,
+            before: None,
+            after: None,
+            ip,
+            created_at: Utc::now(),
         }
     }
 
-    pub fn user_id(&self) -> UserId {
-        match self {
-            User::Registered { user_id, .. } => *user_id,
-            User::Registering { user_id, .. } => *user_id,
-        }
+    pub fn with_target(mut self, target: AuditTarget) -> Self {
+        self.target = Some(target);
+        self
     }
 
-    pub fn email(&self) -> &str {
-        match self {
-            User::Registered { email, .. } => email,
-            User::Registering { email, .. } => email,
-        }
+    pub fn with_request(mut self, request: impl Into<String>) -> Self {
+        self.request = Some(request.into());
+        self
     }
 
-    pub fn first_name(&self) -> &str {
+    pub fn with_change(mut self, before: serde_json::Value, after: serde_json::Value) -> Self {
+        self.before = Some(before);
+        self.after = Some(after);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdminAction {
+    /// Any request to the admin pages.
+    Request,
+    Login,
+    LoginFailed,
+    PaymentUnlock,
+    PaymentApprove,
+    PaymentReject,
+    OutboxRetry,
+    UserErase,
+}
+
+impl AdminAction {
+    pub const ALL: [AdminAction; 8] = [
+        AdminAction::Request,
+        AdminAction::Login,
+        AdminAction::LoginFailed,
+        AdminAction::PaymentUnlock,
+        AdminAction::PaymentApprove,
+        AdminAction::PaymentReject,
+        AdminAction::OutboxRetry,
+        AdminAction::UserErase,
+    ];
+
+    pub const fn as_str(self) -> &'static str {
         match self {
-            User::Registered { first_name, .. } => first_name,
-            User::Registering { first_name, .. } => first_name,
+            AdminAction::Request => "request",
+            AdminAction::Login => "login",
+            AdminAction::LoginFailed => "login_failed",
+            AdminAction::PaymentUnlock => "payment_unlock",
+            AdminAction::PaymentApprove => "payment_approve",
+            AdminAction::PaymentReject => "payment_reject",
+            AdminAction::OutboxRetry => "outbox_retry",
+            AdminAction::UserErase => "user_erase",
         }
     }
+}
 
-    pub fn last_name(&self) -> &str {
+impl FromStr for AdminAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        AdminAction::ALL
+            .into_iter()
+            .find(|action| action.as_str() == s)
+            .with_context(|| format!("Unknown admin action: {s}"))
+    }
+}
+
+/// What an admin looked at or changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditTarget {
+    Payment(PaymentId),
+    User(UserId),
+    OutboxMessage(Uuid),
+}
+
+impl AuditTarget {
+    pub const fn kind(&self) -> &'static str {
         match self {
-            User::Registered { last_name, .. } => last_name,
-            User::Registering { last_name, .. } => last_name,
+            AuditTarget::Payment(_) => "payment",
+            AuditTarget::User(_) => "user",
+            AuditTarget::OutboxMessage(_) => "outbox_message",
         }
     }
 
-    pub fn registration_code(&self) -> Option<(&str, &DateTime<Utc>)> {
+    pub const fn id(&self) -> Uuid {
         match self {
-            User::Registered { .. } => None,
-            User::Registering {
-                timestamp, code, ..
-            } => Some((code, timestamp)),
+            AuditTarget::Payment(payment_id) => payment_id.into_uuid(),
+            AuditTarget::User(user_id) => user_id.into_uuid(),
+            AuditTarget::OutboxMessage(message_id) => *message_id,
         }
     }
-}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum UserState {
-    Registering,
-    Registered,
+    fn from_parts(kind: &str, id: Uuid) -> anyhow::Result<Self> {
+        match kind {
+            "payment" => Ok(AuditTarget::Payment(PaymentId(id))),
+            "user" => Ok(AuditTarget::User(UserId(id))),
+            "outbox_message" => Ok(AuditTarget::OutboxMessage(id)),
+            kind => anyhow::bail!("Unknown audit target: {kind}"),
+        }
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -257,31 +1573,47 @@ pub enum UserState {
 
 impl From<db::entities::Payment> for Payment {
     fn from(value: db::entities::Payment) -> Self {
-        match value.payment_data.0 {
-            db::entities::PaymentData::V1 {
-                payer_full_name,
-                payer_email,
-                payee_full_name,
-                payee_email,
-                amount,
-                security_question,
-                security_answer,
-                payment_statuses,
-                payout_data,
-                refund_data,
-            } => Payment {
-                payment_id: PaymentId::from_uuid(value.payment_id),
-                payer_full_name,
-                payer_email,
-                payee_full_name,
-                payee_email,
-                amount,
-                security_question,
-                security_answer,
-                payment_statuses: PaymentStatuses::from_entity(payment_statuses),
-                payout_data: payout_data.map(PayoutData::from_entity),
-                refund_data: refund_data.map(RefundData::from_entity),
+        let data = value.payment_data.0.into_latest();
+        Payment {
+            payment_id: PaymentId::from_uuid(value.payment_id),
+            payer_full_name: data.payer_full_name,
+            payer_email: data.payer_email,
+            payee_full_name: data.payee_full_name,
+            payee_email: data.payee_email,
+            amount: Money::new(data.amount, Currency::from_entity(data.currency)),
+            security_question: data.security_question,
+            security_answer: data.security_answer,
+            message: data.message,
+            deposit_lock: DepositLock {
+                failed_attempts: data.deposit_lock.failed_attempts,
+                locked_until: data.deposit_lock.locked_until,
             },
+            cancellation_request: data
+                .cancellation_request
+                .map(|request| CancellationRequest {
+                    token_hash: request.token_hash,
+                    requested_at: request.requested_at,
+                }),
+            payment_request_id: data.payment_request_id.map(PaymentRequestId::from_uuid),
+            risk_assessment: data.risk_assessment.map(|assessment| RiskAssessment {
+                score: assessment.score,
+                reasons: assessment.reasons,
+                assessed_at: assessment.assessed_at,
+            }),
+            failure: data.failure.map(|failure| PaymentFailure {
+                stage: failure.stage,
+                reason: failure.reason,
+            }),
+            payment_statuses: PaymentStatuses::from_entity(data.payment_statuses),
+            payout_data: data.payout_data.map(PayoutData::from_entity),
+            refund_data: data.refund_data.map(RefundData::from_entity),
+            payout_destination: data
+                .payout_destination
+                .map(|destination| PayoutDestination {
+                    account_holder_name: destination.account_holder_name,
+                    account_identifier: destination.account_identifier.into(),
+                }),
+            personal_data_erased_at: data.personal_data_erased_at,
         }
     }
 }
@@ -364,18 +1696,69 @@ impl From<Payment> for db::entities::Payment {
     fn from(value: Payment) -> Self {
         db::entities::Payment {
             payment_id: value.payment_id.0,
-            payment_data: db::Json(db::entities::PaymentData::V1 {
-                payer_full_name: value.payer_full_name,
-                payer_email: value.payer_email,
-                payee_full_name: value.payee_full_name,
-                payee_email: value.payee_email,
-                amount: value.amount,
-                security_question: value.security_question,
-                security_answer: value.security_answer,
-                payment_statuses: value.payment_statuses.into_entity(),
-                payout_data: value.payout_data.map(PayoutData::to_entity),
-                refund_data: value.refund_data.map(RefundData::to_entity),
-            }),
+            payment_data: db::Json(db::entities::PaymentData::V2(
+                db::entities::v2::PaymentDataV2 {
+                    payer_full_name: value.payer_full_name,
+                    payer_email: value.payer_email,
+                    payee_full_name: value.payee_full_name,
+                    payee_email: value.payee_email,
+                    amount: value.amount.amount_in_minor,
+                    currency: value.amount.currency.into_entity(),
+                    message: value.message,
+                    security_question: value.security_question,
+                    security_answer: value.security_answer,
+                    deposit_lock: db::entities::v2::DepositLock {
+                        failed_attempts: value.deposit_lock.failed_attempts,
+                        locked_until: value.deposit_lock.locked_until,
+                    },
+                    cancellation_request: value.cancellation_request.map(|request| {
+                        db::entities::CancellationRequest {
+                            token_hash: request.token_hash,
+                            requested_at: request.requested_at,
+                        }
+                    }),
+                    payment_request_id: value.payment_request_id.map(PaymentRequestId::into_uuid),
+                    risk_assessment: value.risk_assessment.map(|assessment| {
+                        db::entities::RiskAssessment {
+                            score: assessment.score,
+                            reasons: assessment.reasons,
+                            assessed_at: assessment.assessed_at,
+                        }
+                    }),
+                    failure: value
+                        .failure
+                        .map(|failure| db::entities::v2::PaymentFailure {
+                            stage: failure.stage,
+                            reason: failure.reason,
+                        }),
+                    payment_statuses: value.payment_statuses.into_entity(),
+                    payout_data: value.payout_data.map(PayoutData::to_entity),
+                    refund_data: value.refund_data.map(RefundData::to_entity),
+                    payout_destination: value.payout_destination.map(|destination| {
+                        db::entities::v2::PayoutDestination {
+                            account_holder_name: destination.account_holder_name,
+                            account_identifier: destination.account_identifier.into(),
+                        }
+                    }),
+                    personal_data_erased_at: value.personal_data_erased_at,
+                },
+            )),
+        }
+    }
+}
+
+impl Currency {
+    const fn from_entity(value: db::entities::Currency) -> Self {
+        match value {
+            db::entities::Currency::Gbp => Currency::Gbp,
+            db::entities::Currency::Eur => Currency::Eur,
+        }
+    }
+
+    const fn into_entity(self) -> db::entities::Currency {
+        match self {
+            Currency::Gbp => db::entities::Currency::Gbp,
+            Currency::Eur => db::entities::Currency::Eur,
         }
     }
 }
@@ -388,6 +1771,12 @@ impl PaymentStatuses {
             inbound_executed_at: value.inbound_executed_at,
             inbound_settled_at: value.inbound_settled_at,
             inbound_failed_at: value.inbound_failed_at,
+            expired_at: value.expired_at,
+            cancelled_at: value.cancelled_at,
+            held_for_review_at: value.held_for_review_at,
+            review_released_at: value.review_released_at,
+            payout_requested_at: value.payout_requested_at,
+            refund_requested_at: value.refund_requested_at,
         }
     }
 
@@ -398,6 +1787,297 @@ impl PaymentStatuses {
             inbound_executed_at: self.inbound_executed_at,
             inbound_settled_at: self.inbound_settled_at,
             inbound_failed_at: self.inbound_failed_at,
+            expired_at: self.expired_at,
+            cancelled_at: self.cancelled_at,
+            held_for_review_at: self.held_for_review_at,
+            review_released_at: self.review_released_at,
+            payout_requested_at: self.payout_requested_at,
+            refund_requested_at: self.refund_requested_at,
+        }
+    }
+}
+
+impl From<db::entities::PaymentEventRecord> for PaymentEventRecord {
+    fn from(value: db::entities::PaymentEventRecord) -> Self {
+        PaymentEventRecord {
+            event_id: value.event_id,
+            payment_id: PaymentId::from_uuid(value.payment_id),
+            event_type: value.event_type,
+            source: value
+                .event_source
+                .parse()
+                .expect("event_source is written from EventSource"),
+            payload_ref: value.payload_ref,
+            previous_state: value
+                .previous_state
+                .map(|state| state.parse().expect("written from PaymentState")),
+            new_state: value
+                .new_state
+                .parse()
+                .expect("new_state is written from PaymentState"),
+            created_at: value.created_at,
+        }
+    }
+}
+
+impl From<PaymentEventRecord> for db::entities::PaymentEventRecord {
+    fn from(value: PaymentEventRecord) -> Self {
+        db::entities::PaymentEventRecord {
+            event_id: value.event_id,
+            payment_id: value.payment_id.into_uuid(),
+            event_type: value.event_type,
+            event_source: value.source.as_str().into(),
+            payload_ref: value.payload_ref,
+            previous_state: value.previous_state.map(|state| state.as_str().into()),
+            new_state: value.new_state.as_str().into(),
+            created_at: value.created_at,
+        }
+    }
+}
+
+impl From<db::entities::OutboxMessage> for OutboxMessage {
+    fn from(value: db::entities::OutboxMessage) -> Self {
+        OutboxMessage {
+            message_id: value.message_id,
+            payment_id: value.payment_id.map(PaymentId::from_uuid),
+            effect: match value.message_data.0 {
+                db::entities::OutboxMessageData::Email { to, subject, body } => {
+                    OutboxEffect::Email { to, subject, body }
+                }
+                db::entities::OutboxMessageData::Payout { payment_id } => OutboxEffect::Payout {
+                    payment_id: PaymentId::from_uuid(payment_id),
+                },
+                db::entities::OutboxMessageData::Refund { payment_id } => OutboxEffect::Refund {
+                    payment_id: PaymentId::from_uuid(payment_id),
+                },
+                db::entities::OutboxMessageData::Settle {
+                    payment_id,
+                    payer_accounts,
+                } => OutboxEffect::Settle {
+                    payment_id: PaymentId::from_uuid(payment_id),
+                    payer_accounts: payer_accounts.into_iter().map(Into::into).collect(),
+                },
+            },
+            status: value
+                .status
+                .parse()
+                .expect("status is written from OutboxStatus"),
+            attempts: value.attempts,
+            last_error: value.last_error,
+            next_attempt_at: value.next_attempt_at,
+            created_at: value.created_at,
+        }
+    }
+}
+
+impl From<OutboxMessage> for db::entities::OutboxMessage {
+    fn from(value: OutboxMessage) -> Self {
+        db::entities::OutboxMessage {
+            message_id: value.message_id,
+            payment_id: value.payment_id.map(PaymentId::into_uuid),
+            status: value.status.as_str().into(),
+            attempts: value.attempts,
+            last_error: value.last_error,
+            next_attempt_at: value.next_attempt_at,
+            created_at: value.created_at,
+            message_data: db::Json(match value.effect {
+                OutboxEffect::Email { to, subject, body } => {
+                    db::entities::OutboxMessageData::Email { to, subject, body }
+                }
+                OutboxEffect::Payout { payment_id } => db::entities::OutboxMessageData::Payout {
+                    payment_id: payment_id.into_uuid(),
+                },
+                OutboxEffect::Refund { payment_id } => db::entities::OutboxMessageData::Refund {
+                    payment_id: payment_id.into_uuid(),
+                },
+                OutboxEffect::Settle {
+                    payment_id,
+                    payer_accounts,
+                } => db::entities::OutboxMessageData::Settle {
+                    payment_id: payment_id.into_uuid(),
+                    payer_accounts: payer_accounts.into_iter().map(Into::into).collect(),
+                },
+            }),
+        }
+    }
+}
+
+impl From<db::entities::AdminAuditEntry> for AdminAuditEntry {
+    fn from(value: db::entities::AdminAuditEntry) -> Self {
+        AdminAuditEntry {
+            entry_id: value.entry_id,
+            actor: value.actor,
+            action: value
+                .action
+                .parse()
+                .expect("action is written from AdminAction"),
+            target: value
+                .target_type
+                .zip(value.target_id)
+                .map(|(kind, id)| AuditTarget::from_parts(&kind, id))
+                .transpose()
+                .expect("target is written from AuditTarget"),
+            request: value.request,
+            before: value.before.map(|before| before.0),
+            after: value.after.map(|after| after.0),
+            ip: value.ip,
+            created_at: value.created_at,
+        }
+    }
+}
+
+impl From<AdminAuditEntry> for db::entities::AdminAuditEntry {
+    fn from(value: AdminAuditEntry) -> Self {
+        db::entities::AdminAuditEntry {
+            entry_id: value.entry_id,
+            actor: value.actor,
+            action: value.action.as_str().into(),
+            target_type: value.target.map(|target| target.kind().into()),
+            target_id: value.target.map(|target| target.id()),
+            request: value.request,
+            before: value.before.map(db::Json),
+            after: value.after.map(db::Json),
+            ip: value.ip,
+            created_at: value.created_at,
+        }
+    }
+}
+
+impl From<db::entities::PaymentRequest> for PaymentRequest {
+    fn from(value: db::entities::PaymentRequest) -> Self {
+        match value.request_data.0 {
+            db::entities::PaymentRequestData::V1 {
+                payer_email,
+                amount,
+                currency,
+                note,
+                created_at,
+                expires_at,
+                payment_id,
+                paid_at,
+                declined_at,
+                claimed_at,
+            } => PaymentRequest {
+                request_id: PaymentRequestId(value.request_id),
+                payee_user_id: UserId(value.user_id),
+                payer_email,
+                amount: Money::new(amount, Currency::from_entity(currency)),
+                note,
+                created_at,
+                expires_at,
+                payment_id: payment_id.map(PaymentId),
+                paid_at,
+                declined_at,
+                claimed_at,
+            },
+        }
+    }
+}
+
+impl From<PaymentRequest> for db::entities::PaymentRequest {
+    fn from(value: PaymentRequest) -> Self {
+        db::entities::PaymentRequest {
+            request_id: value.request_id.0,
+            user_id: value.payee_user_id.0,
+            request_data: db::Json(db::entities::PaymentRequestData::V1 {
+                payer_email: value.payer_email,
+                amount: value.amount.amount_in_minor,
+                currency: value.amount.currency.into_entity(),
+                note: value.note,
+                created_at: value.created_at,
+                expires_at: value.expires_at,
+                payment_id: value.payment_id.map(|payment_id| payment_id.0),
+                paid_at: value.paid_at,
+                declined_at: value.declined_at,
+                claimed_at: value.claimed_at,
+            }),
+        }
+    }
+}
+
+impl From<db::entities::ScheduledTransfer> for ScheduledTransfer {
+    fn from(value: db::entities::ScheduledTransfer) -> Self {
+        match value.schedule_data.0 {
+            db::entities::ScheduledTransferData::V1 {
+                payee_full_name,
+                payee_email,
+                amount,
+                currency,
+                security_question,
+                security_answer,
+                frequency,
+                starts_at,
+                occurrence,
+                status,
+                last_payment_id,
+                last_run_at,
+            } => ScheduledTransfer {
+                schedule_id: ScheduledTransferId(value.schedule_id),
+                user_id: UserId(value.user_id),
+                payee_full_name,
+                payee_email,
+                amount: Money::new(amount, Currency::from_entity(currency)),
+                security_question,
+                security_answer,
+                frequency: match frequency {
+                    db::entities::ScheduleFrequency::Once => ScheduleFrequency::Once,
+                    db::entities::ScheduleFrequency::Weekly => ScheduleFrequency::Weekly,
+                    db::entities::ScheduleFrequency::Monthly => ScheduleFrequency::Monthly,
+                },
+                starts_at,
+                occurrence,
+                status: match status {
+                    db::entities::ScheduleStatus::Active => ScheduleStatus::Active,
+                    db::entities::ScheduleStatus::Paused => ScheduleStatus::Paused,
+                    db::entities::ScheduleStatus::Cancelled => ScheduleStatus::Cancelled,
+                    db::entities::ScheduleStatus::Completed => ScheduleStatus::Completed,
+                },
+                last_payment_id: last_payment_id.map(PaymentId),
+                last_run_at,
+            },
+        }
+    }
+}
+
+impl From<ScheduledTransfer> for db::entities::ScheduledTransfer {
+    fn from(value: ScheduledTransfer) -> Self {
+        db::entities::ScheduledTransfer {
+            schedule_id: value.schedule_id.0,
+            user_id: value.user_id.0,
+            next_run_at: value.due_at(),
+            schedule_data: db::Json(db::entities::ScheduledTransferData::V1 {
+                payee_full_name: value.payee_full_name,
+                payee_email: value.payee_email,
+                amount: value.amount.amount_in_minor,
+                currency: value.amount.currency.into_entity(),
+                security_question: value.security_question,
+                security_answer: value.security_answer,
+                frequency: match value.frequency {
+                    ScheduleFrequency::Once => db::entities::ScheduleFrequency::Once,
+                    ScheduleFrequency::Weekly => db::entities::ScheduleFrequency::Weekly,
+                    ScheduleFrequency::Monthly => db::entities::ScheduleFrequency::Monthly,
+                },
+                starts_at: value.starts_at,
+                occurrence: value.occurrence,
+                status: match value.status {
+                    ScheduleStatus::Active => db::entities::ScheduleStatus::Active,
+                    ScheduleStatus::Paused => db::entities::ScheduleStatus::Paused,
+                    ScheduleStatus::Cancelled => db::entities::ScheduleStatus::Cancelled,
+                    ScheduleStatus::Completed => db::entities::ScheduleStatus::Completed,
+                },
+                last_payment_id: value.last_payment_id.map(|payment_id| payment_id.0),
+                last_run_at: value.last_run_at,
+            }),
+        }
+    }
+}
+
+impl From<UserPayment> for db::entities::UserPayment {
+    fn from(value: UserPayment) -> Self {
+        db::entities::UserPayment {
+            payment_id: value.payment_id.into_uuid(),
+            user_id: value.user_id.into_uuid(),
+            role: value.role.as_str().into(),
         }
     }
 }
@@ -408,11 +2088,13 @@ impl From<db::entities::User> for User {
             db::entities::UserData::V1(db::entities::v1::UserDataV1::Registered {
                 first_name,
                 last_name,
+                payout_account,
             }) => User::Registered {
                 user_id: UserId::from(value.user_id),
                 email: value.email,
                 first_name,
                 last_name,
+                payout_account: payout_account.map(PayoutAccount::from),
             },
             db::entities::UserData::V1(db::entities::v1::UserDataV1::Registering {
                 first_name,
@@ -427,6 +2109,12 @@ impl From<db::entities::User> for User {
                 code,
                 timestamp,
             },
+            db::entities::UserData::V1(db::entities::v1::UserDataV1::Erased { erased_at }) => {
+                User::Erased {
+                    user_id: UserId::from(value.user_id),
+                    erased_at,
+                }
+            }
         }
     }
 }
@@ -458,6 +2146,7 @@ impl From<User> for db::entities::User {
                 email,
                 first_name,
                 last_name,
+                payout_account,
             } => db::entities::User {
                 user_id: user_id.into_uuid(),
                 email,
@@ -465,9 +2154,69 @@ impl From<User> for db::entities::User {
                     db::entities::v1::UserDataV1::Registered {
                         first_name,
                         last_name,
+                        payout_account: payout_account.map(Into::into),
                     },
                 )),
             },
+            User::Erased { user_id, erased_at } => db::entities::User {
+                user_id: user_id.into_uuid(),
+                email: String::new(),
+                user_data: db::Json(db::entities::UserData::V1(
+                    db::entities::v1::UserDataV1::Erased { erased_at },
+                )),
+            },
+        }
+    }
+}
+
+impl From<db::entities::v1::PayoutAccount> for PayoutAccount {
+    fn from(value: db::entities::v1::PayoutAccount) -> Self {
+        PayoutAccount {
+            display_name: value.display_name,
+            currency: Currency::from_entity(value.currency),
+            account_identifier: value.account_identifier.into(),
+            verified_at: value.verified_at,
+        }
+    }
+}
+
+impl From<PayoutAccount> for db::entities::v1::PayoutAccount {
+    fn from(value: PayoutAccount) -> Self {
+        db::entities::v1::PayoutAccount {
+            display_name: value.display_name,
+            currency: value.currency.into_entity(),
+            account_identifier: value.account_identifier.into(),
+            verified_at: value.verified_at,
+        }
+    }
+}
+
+impl From<db::entities::v1::AccountIdentifier> for AccountIdentifier {
+    fn from(value: db::entities::v1::AccountIdentifier) -> Self {
+        match value {
+            db::entities::v1::AccountIdentifier::Iban { iban } => AccountIdentifier::Iban { iban },
+            db::entities::v1::AccountIdentifier::SortCodeAccountNumber {
+                sort_code,
+                account_number,
+            } => AccountIdentifier::SortCodeAccountNumber {
+                sort_code,
+                account_number,
+            },
+        }
+    }
+}
+
+impl From<AccountIdentifier> for db::entities::v1::AccountIdentifier {
+    fn from(value: AccountIdentifier) -> Self {
+        match value {
+            AccountIdentifier::Iban { iban } => db::entities::v1::AccountIdentifier::Iban { iban },
+            AccountIdentifier::SortCodeAccountNumber {
+                sort_code,
+                account_number,
+            } => db::entities::v1::AccountIdentifier::SortCodeAccountNumber {
+                sort_code,
+                account_number,
+            },
         }
     }
 }
@@ -533,3 +2282,330 @@ impl_uuid_ty!(PaymentId);
 impl_uuid_ty!(PayoutId);
 impl_uuid_ty!(RefundId);
 impl_uuid_ty!(UserId);
+impl_uuid_ty!(PaymentRequestId);
+impl_uuid_ty!(ScheduledTransferId);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settled() -> Payment {
+        let mut payment = Payment::test_fixture();
+        payment
+            .apply(PaymentEvent::InboundSettled {
+                settled_at: Utc::now(),
+            })
+            .unwrap();
+        payment
+    }
+
+    fn destination() -> PayoutDestination {
+        PayoutDestination {
+            account_holder_name: String::from("Payee"),
+            account_identifier: AccountIdentifier::Iban {
+                iban: String::from("GB33BUKB20201555555555"),
+            },
+        }
+    }
+
+    fn payment_request(now: DateTime<Utc>) -> PaymentRequest {
+        PaymentRequest {
+            request_id: PaymentRequestId::new(),
+            payee_user_id: UserId::new(),
+            payer_email: String::from("payer@example.com"),
+            amount: Money::new(1_000, Currency::Gbp),
+            note: String::from("dinner"),
+            created_at: now,
+            expires_at: now + chrono::Duration::days(7),
+            payment_id: None,
+            paid_at: None,
+            declined_at: None,
+            claimed_at: None,
+        }
+    }
+
+    #[test]
+    fn a_claimed_request_is_not_payable_until_the_claim_lapses() {
+        let now = Utc::now();
+        let mut request = payment_request(now);
+        assert!(request.is_payable(now));
+
+        request.claimed_at = Some(now);
+        assert!(!request.is_payable(now));
+        let lapsed = now + chrono::Duration::minutes(PaymentRequest::CLAIM_MINUTES);
+        assert!(request.is_payable(lapsed));
+
+        request.payment_id = Some(PaymentId::new());
+        assert!(!request.is_payable(lapsed));
+    }
+
+    #[test]
+    fn request_status_follows_payment_decline_and_expiry() {
+        let now = Utc::now();
+        let request = payment_request(now);
+        assert_eq!(request.status(now), PaymentRequestStatus::Pending);
+        assert_eq!(
+            request.status(request.expires_at),
+            PaymentRequestStatus::Expired
+        );
+        assert!(!request.is_payable(request.expires_at));
+
+        let mut declined = payment_request(now);
+        declined.erase_personal_data(now);
+        assert_eq!(declined.status(now), PaymentRequestStatus::Declined);
+        assert!(declined.payer_email.is_empty());
+
+        let mut paid = payment_request(now);
+        paid.paid_at = Some(now);
+        paid.erase_personal_data(now);
+        assert_eq!(paid.status(now), PaymentRequestStatus::Paid);
+    }
+
+    #[test]
+    fn money_displays_in_major_units() {
+        assert_eq!(Money::new(1_005, Currency::Gbp).to_string(), "£10.05");
+        assert_eq!(Money::new(7, Currency::Eur).to_string(), "€0.07");
+        assert_eq!(Money::new(0, Currency::Gbp).to_string(), "£0.00");
+    }
+
+    #[test]
+    fn currencies_parse_from_their_iso_code() {
+        assert_eq!("gbp".parse::<Currency>().unwrap(), Currency::Gbp);
+        assert_eq!("EUR".parse::<Currency>().unwrap(), Currency::Eur);
+        assert!("USD".parse::<Currency>().is_err());
+    }
+
+    #[test]
+    fn payment_reference_is_never_empty() {
+        assert_eq!(
+            PaymentReference::new("to Zoë O'Brien-Smith jr.").as_str(),
+            "TO ZO O BRIEN-SMIT"
+        );
+        assert_eq!(
+            PaymentReference::new("  ").as_str(),
+            PaymentReference::DEFAULT
+        );
+        assert_eq!(
+            PaymentReference::new("李小龍").as_str(),
+            PaymentReference::DEFAULT
+        );
+    }
+
+    #[test]
+    fn no_state_transitions_to_itself() {
+        for state in PaymentState::ALL {
+            assert!(!state.can_transition_to(state), "{}", state.as_str());
+        }
+    }
+
+    #[test]
+    fn inbound_states_never_move_backwards() {
+        use PaymentState::*;
+        assert!(InboundCreated.can_transition_to(InboundSettled));
+        assert!(InboundAuthorized.can_transition_to(InboundFailed));
+        assert!(!InboundExecuted.can_transition_to(InboundAuthorized));
+        assert!(!InboundSettled.can_transition_to(InboundExecuted));
+        assert!(!InboundExecuted.can_transition_to(InboundFailed));
+    }
+
+    #[test]
+    fn payouts_and_refunds_are_requested_before_created() {
+        use PaymentState::*;
+        for state in PaymentState::ALL {
+            assert_eq!(
+                state.can_transition_to(PayoutCreated),
+                state == PayoutRequested
+            );
+            assert_eq!(
+                state.can_transition_to(RefundCreated),
+                state == RefundRequested
+            );
+        }
+    }
+
+    #[test]
+    fn final_states() {
+        let final_states: Vec<_> = PaymentState::ALL
+            .into_iter()
+            .filter(|state| state.is_final())
+            .collect();
+        assert_eq!(
+            final_states,
+            [
+                PaymentState::InboundFailed,
+                PaymentState::PayoutExecuted,
+                PaymentState::RefundExecuted,
+            ]
+        );
+    }
+
+    #[test]
+    fn apply_moves_through_a_payout() {
+        let mut payment = settled();
+        let state = payment
+            .apply(PaymentEvent::PayoutRequested {
+                destination: destination(),
+                requested_at: Utc::now(),
+            })
+            .unwrap();
+        assert_eq!(state, PaymentState::PayoutRequested);
+        assert_eq!(payment.payout_destination, Some(destination()));
+
+        let state = payment
+            .apply(PaymentEvent::PayoutCreated {
+                payout_id: PayoutId::new(),
+                created_at: Utc::now(),
+            })
+            .unwrap();
+        assert_eq!(state, PaymentState::PayoutCreated);
+        assert_eq!(payment.payout_destination, None);
+
+        let state = payment
+            .apply(PaymentEvent::PayoutFailed {
+                failed_at: Utc::now(),
+            })
+            .unwrap();
+        assert_eq!(state, PaymentState::PayoutFailed);
+
+        let state = payment
+            .apply(PaymentEvent::RefundRequested {
+                requested_at: Utc::now(),
+            })
+            .unwrap();
+        assert_eq!(state, PaymentState::RefundRequested);
+    }
+
+    #[test]
+    fn apply_rejects_an_illegal_transition_untouched() {
+        let mut payment = Payment::test_fixture();
+        let err = payment
+            .apply(PaymentEvent::PayoutCreated {
+                payout_id: PayoutId::new(),
+                created_at: Utc::now(),
+            })
+            .unwrap_err();
+        assert_eq!(
+            err,
+            InvalidTransition {
+                from: PaymentState::InboundCreated,
+                to: PaymentState::PayoutCreated,
+            }
+        );
+        assert!(payment.payout_data.is_none());
+        assert_eq!(payment.state(), PaymentState::InboundCreated);
+    }
+
+    #[test]
+    fn only_a_review_release_settles_a_held_payment() {
+        let mut payment = settled();
+        payment
+            .apply(PaymentEvent::HeldForReview {
+                held_at: Utc::now(),
+            })
+            .unwrap();
+
+        assert!(payment
+            .apply(PaymentEvent::InboundSettled {
+                settled_at: Utc::now(),
+            })
+            .is_err());
+        let state = payment
+            .apply(PaymentEvent::ReviewReleased {
+                released_at: Utc::now(),
+            })
+            .unwrap();
+        assert_eq!(state, PaymentState::InboundSettled);
+
+        // a settled payment that was never held cannot be released
+        assert!(settled()
+            .apply(PaymentEvent::ReviewReleased {
+                released_at: Utc::now(),
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn transition_records_both_states() {
+        let mut payment = settled();
+        let record = payment
+            .transition(
+                PaymentEvent::Cancelled {
+                    cancelled_at: Utc::now(),
+                },
+                EventSource::Ui,
+            )
+            .unwrap();
+        assert_eq!(record.event_type, "cancelled");
+        assert_eq!(record.previous_state, Some(PaymentState::InboundSettled));
+        assert_eq!(record.new_state, PaymentState::Cancelled);
+    }
+
+    fn at(date: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(&format!("{date}T09:30:00Z"))
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn once_occurs_only_at_its_start() {
+        let starts_at = at("2024-03-10");
+        assert_eq!(
+            ScheduleFrequency::Once.occurrence(starts_at, 0),
+            Some(starts_at)
+        );
+        assert_eq!(ScheduleFrequency::Once.occurrence(starts_at, 1), None);
+    }
+
+    #[test]
+    fn weekly_occurrences_keep_the_time_of_day() {
+        let starts_at = at("2024-02-26");
+        assert_eq!(
+            ScheduleFrequency::Weekly.occurrence(starts_at, 1),
+            Some(at("2024-03-04"))
+        );
+        assert_eq!(
+            ScheduleFrequency::Weekly.occurrence(starts_at, 52),
+            Some(at("2025-02-24"))
+        );
+    }
+
+    #[test]
+    fn monthly_occurrences_are_clamped_to_the_month_end() {
+        let starts_at = at("2023-01-31");
+        let occurrences: Vec<_> = (0..4)
+            .map(|n| ScheduleFrequency::Monthly.occurrence(starts_at, n))
+            .collect();
+        assert_eq!(
+            occurrences,
+            [
+                Some(at("2023-01-31")),
+                Some(at("2023-02-28")),
+                Some(at("2023-03-31")),
+                Some(at("2023-04-30")),
+            ]
+        );
+    }
+
+    #[test]
+    fn monthly_occurrences_in_leap_years() {
+        assert_eq!(
+            ScheduleFrequency::Monthly.occurrence(at("2024-01-31"), 1),
+            Some(at("2024-02-29"))
+        );
+        // a schedule starting on a leap day falls on the 28th in other years
+        // and back on the 29th in the next leap year
+        let starts_at = at("2024-02-29");
+        assert_eq!(
+            ScheduleFrequency::Monthly.occurrence(starts_at, 12),
+            Some(at("2025-02-28"))
+        );
+        assert_eq!(
+            ScheduleFrequency::Monthly.occurrence(starts_at, 48),
+            Some(at("2028-02-29"))
+        );
+        assert_eq!(
+            ScheduleFrequency::Monthly.occurrence(starts_at, 1),
+            Some(at("2024-03-29"))
+        );
+    }
+}
@@ -1,11 +1,16 @@
+use actix_session::Session;
 use actix_web::{http::header, post, web, HttpResponse, Responder};
 use argon2::{Argon2, PasswordHash, PasswordVerifier};
-use domain::{Payment, PaymentState};
+use chrono::{DateTime, Utc};
+use db::error::DbError;
+use domain::{
+    EventSource, OutboxEffect, OutboxMessage, Payment, PaymentEventRecord, PaymentId, PaymentState,
+};
 use serde::Deserialize;
 use tracing::instrument;
 use uuid::Uuid;
 
-use crate::AppContext;
+use crate::{app::deposit_flow::allow_deposit, AppContext};
 
 use super::PublicError;
 
@@ -18,51 +23,191 @@ pub struct FormData {
 #[post("/deposit_payment")]
 pub async fn deposit_payment(
     app: web::Data<AppContext>,
+    session: Session,
     form: web::Form<FormData>,
 ) -> Result<impl Responder, PublicError> {
-    execute(app, form.0).await
+    execute(app, session, form.0).await
 }
 
-#[instrument(skip(app))]
+#[instrument(skip(app, session))]
 async fn execute(
     app: web::Data<AppContext>,
+    session: Session,
     request: FormData,
 ) -> Result<impl Responder, PublicError> {
-    let payment = app
-        .db_client
-        .get_payment::<Payment>(request.payment_id)
-        .await?
-        .map(|(p, _)| p)
-        .ok_or(PublicError::Invalid(String::from("test")))?;
-
-    let is_vaild = {
+    let now = Utc::now();
+    let (mut payment, mut version, locks) =
+        match reserve_attempt(&app, request.payment_id, now).await? {
+            Attempt::Reserved {
+                payment,
+                version,
+                locks,
+            } => (*payment, version, locks),
+            // request payments are deposited by the signed in payee instead
+            Attempt::RequestPayment => return Ok(HttpResponse::Unauthorized()),
+            Attempt::Locked => return Ok(HttpResponse::Locked()),
+        };
+
+    let is_correct_answer = {
         let parsed_hash = PasswordHash::new(&payment.security_answer).unwrap();
         Argon2::default()
             .verify_password(request.security_answer.as_bytes(), &parsed_hash)
-            .map_or(false, |_| true)
-            && payment.state() < PaymentState::PayoutCreated
+            .is_ok()
     };
 
+    if !is_correct_answer {
+        if !locks {
+            return Ok(HttpResponse::Unauthorized());
+        }
+        let body = format!(
+            "Too many incorrect security answers were entered for your transfer of {} to {}. \
+            Deposits are paused until {}.",
+            payment.amount,
+            payment.payee_full_name,
+            payment
+                .deposit_lock
+                .locked_until
+                .map(|locked_until| locked_until.to_rfc2822())
+                .unwrap_or_default(),
+        );
+        app.db_client
+            .insert_outbox_messages([OutboxMessage::new(
+                Some(payment.payment_id),
+                OutboxEffect::email(&payment.payer_email, "Deposit locked", body),
+            )])
+            .await?;
+        return Ok(HttpResponse::Locked());
+    }
+
+    // the correct answer clears the reserved attempt and earlier failures
+    loop {
+        payment.deposit_lock.reset();
+        let state = payment.state();
+        let event = PaymentEventRecord::new(
+            payment.payment_id,
+            "security_answer_accepted",
+            EventSource::Ui,
+            Some(state),
+            state,
+        );
+        match app
+            .db_client
+            .upsert_payment(payment.clone(), version + 1, event)
+            .await
+        {
+            Ok(()) => break,
+            Err(DbError::ConcurrentUpdate) => {
+                (payment, version) = app
+                    .db_client
+                    .get_payment::<Payment>(request.payment_id)
+                    .await?
+                    .ok_or(PublicError::InternalServerError)?;
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    let is_vaild = payment
+        .state()
+        .can_transition_to(PaymentState::PayoutRequested);
+
     if is_vaild {
-        let link = UriBuilder::new(&format!(
-            "https://auth.{}/",
-            app.tl_client.enviornment.uri()
-        ))
-        .add_param("response_type", "code")
-        .add_param("client_id", &app.tl_client.client_id)
-        .add_param("scope", "info%20accounts%20balance")
-        .add_param("redirect_uri", &app.tl_client.data_redirect_uri)
-        .add_param("providers", "uk-cs-mock%20uk-ob-all%20uk-oauth-all")
-        .add_param("state", &payment.payment_id.to_string())
-        .build();
+        allow_deposit(&session, payment.payment_id);
         Ok(HttpResponse::SeeOther()
-            .insert_header((header::LOCATION, link))
+            .insert_header((
+                header::LOCATION,
+                deposit_auth_link(&app, payment.payment_id),
+            ))
             .take())
     } else {
         Ok(HttpResponse::Unauthorized())
     }
 }
 
+enum Attempt {
+    /// The attempt was counted, `payment` is stored at `version` with it.
+    Reserved {
+        payment: Box<Payment>,
+        version: u32,
+        /// Whether the attempt locked the deposit.
+        locks: bool,
+    },
+    RequestPayment,
+    Locked,
+}
+
+/// Counts an attempt at the security answer as failed before the answer is
+/// checked, so parallel guesses cannot get past the lockout.
+async fn reserve_attempt(
+    app: &AppContext,
+    payment_id: Uuid,
+    now: DateTime<Utc>,
+) -> Result<Attempt, PublicError> {
+    loop {
+        let (mut payment, version) = app
+            .db_client
+            .get_payment::<Payment>(payment_id)
+            .await?
+            .ok_or(PublicError::Invalid(String::from("test")))?;
+
+        if payment.payment_request_id.is_some() {
+            return Ok(Attempt::RequestPayment);
+        }
+        if payment.deposit_lock.is_locked(now) {
+            return Ok(Attempt::Locked);
+        }
+
+        let locks = payment.deposit_lock.record_failure(now, app.lockout_policy);
+        let event_type = match locks {
+            true => "deposit_locked",
+            false => "security_answer_submitted",
+        };
+        let state = payment.state();
+        let event = PaymentEventRecord::new(
+            payment.payment_id,
+            event_type,
+            EventSource::Ui,
+            Some(state),
+            state,
+        );
+        match app
+            .db_client
+            .upsert_payment(payment.clone(), version + 1, event)
+            .await
+        {
+            Ok(()) => {
+                return Ok(Attempt::Reserved {
+                    payment: Box::new(payment),
+                    version: version + 1,
+                    locks,
+                })
+            }
+            Err(DbError::ConcurrentUpdate) => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+/// TrueLayer data auth link the payee follows to pick the payout account.
+pub fn deposit_auth_link(app: &AppContext, payment_id: PaymentId) -> String {
+    data_auth_link(app, &payment_id.to_string())
+}
+
+/// TrueLayer data auth link, `state` is handed back to the data callback.
+pub fn data_auth_link(app: &AppContext, state: &str) -> String {
+    UriBuilder::new(&format!(
+        "https://auth.{}/",
+        app.tl_client.enviornment.uri()
+    ))
+    .add_param("response_type", "code")
+    .add_param("client_id", &app.tl_client.client_id)
+    .add_param("scope", "info%20accounts%20balance")
+    .add_param("redirect_uri", &app.tl_client.data_redirect_uri)
+    .add_param("providers", "uk-cs-mock%20uk-ob-all%20uk-oauth-all")
+    .add_param("state", state)
+    .build()
+}
+
 struct UriBuilder<'a> {
     path: &'a str,
     params: String,
@@ -89,3 +234,66 @@ impl<'a> UriBuilder<'a> {
         format!("{}{}", self.path, self.params)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use domain::PaymentEvent;
+
+    use super::*;
+
+    async fn reserve(app: &AppContext, payment_id: Uuid, now: DateTime<Utc>) -> Option<bool> {
+        match reserve_attempt(app, payment_id, now).await.unwrap() {
+            Attempt::Reserved { locks, .. } => Some(locks),
+            Attempt::RequestPayment => panic!("not a request payment"),
+            Attempt::Locked => None,
+        }
+    }
+
+    #[actix_web::test]
+    async fn deposits_lock_after_the_max_attempts_until_the_cooldown_ends() {
+        let app = AppContext::for_tests();
+        let mut payment = Payment::test_fixture();
+        let payment_id = payment.payment_id.into_uuid();
+        let event = payment
+            .transition(
+                PaymentEvent::InboundSettled {
+                    settled_at: Utc::now(),
+                },
+                EventSource::Webhook,
+            )
+            .unwrap();
+        app.db_client
+            .upsert_payment(payment, 1, event)
+            .await
+            .unwrap();
+        let now = Utc::now();
+        let policy = app.lockout_policy;
+
+        for _ in 1..policy.max_attempts {
+            assert_eq!(reserve(&app, payment_id, now).await, Some(false));
+        }
+        assert_eq!(reserve(&app, payment_id, now).await, Some(true));
+        assert_eq!(reserve(&app, payment_id, now).await, None);
+        assert_eq!(
+            reserve(
+                &app,
+                payment_id,
+                now + policy.cooldown - Duration::seconds(1)
+            )
+            .await,
+            None
+        );
+
+        // attempts start over once the cooldown has passed
+        let later = now + policy.cooldown;
+        assert_eq!(reserve(&app, payment_id, later).await, Some(false));
+        let (payment, _) = app
+            .db_client
+            .get_payment::<Payment>(payment_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(payment.deposit_lock.failed_attempts, 1);
+    }
+}
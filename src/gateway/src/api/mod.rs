@@ -2,6 +2,7 @@ pub mod deposit_payment;
 pub mod tl_webhooks;
 
 use db::error::DbError;
+use domain::InvalidTransition;
 use serde::Deserialize;
 
 #[derive(thiserror::Error, Debug)]
@@ -30,6 +31,13 @@ impl From<DbError> for PublicError {
     }
 }
 
+impl From<InvalidTransition> for PublicError {
+    #[inline]
+    fn from(err: InvalidTransition) -> Self {
+        PublicError::Invalid(err.to_string())
+    }
+}
+
 pub fn deserialize_body<'de, T>(body: &'de str) -> Result<T, PublicError>
 where
     T: Deserialize<'de>,
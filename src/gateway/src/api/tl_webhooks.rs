@@ -1,16 +1,36 @@
 use actix_web::{post, web, HttpRequest, HttpResponse, Responder};
 use anyhow::{ensure, Context};
-use chrono::{DateTime, Utc};
-use domain::{Payment, PaymentId, PaymentState, PayoutId, RefundId};
+use chrono::{DateTime, Duration, Utc};
+use db::{entities::ProcessedWebhook, error::DbError};
+use domain::{
+    EventSource, OutboxEffect, OutboxMessage, Payment, PaymentEvent, PaymentFailure, PaymentId,
+    PaymentState, PayoutId, RefundId,
+};
 use serde::Deserialize;
-use tracing::warn;
+use tracing::{info, warn};
 use truelayer_signing::Method;
 use uuid::Uuid;
 
-use crate::{api::deserialize_body, log, AppContext};
+use crate::{api::deserialize_body, log, payment_request::sync_payment_request, AppContext};
 
 use super::PublicError;
 
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /// Furthest a webhook's signed timestamp may be from now before it is
+    /// rejected as a replay.
+    pub timestamp_tolerance_secs: i64,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        WebhookConfig {
+            timestamp_tolerance_secs: 300,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum TlWebhook {
@@ -19,14 +39,12 @@ pub enum TlWebhook {
         event_version: u32,
         payment_id: PaymentId,
         authorized_at: DateTime<Utc>,
-        payment_source: Option<PaymentSource>,
     },
     PaymentExecuted {
         event_id: Uuid,
         event_version: u32,
         payment_id: PaymentId,
         executed_at: DateTime<Utc>,
-        payment_source: Option<PaymentSource>,
     },
     PaymentFailed {
         event_id: Uuid,
@@ -35,7 +53,6 @@ pub enum TlWebhook {
         failed_at: DateTime<Utc>,
         failed_stage: String,
         failure_reason: String,
-        payment_source: Option<PaymentSource>,
     },
     PaymentSettled {
         event_id: Uuid,
@@ -43,17 +60,10 @@ pub enum TlWebhook {
         payment_id: PaymentId,
         settled_at: DateTime<Utc>,
         payment_source: Option<PaymentSource>,
-        user_id: String,
     },
     ExternalPaymentReceived {
         event_id: Uuid,
         event_version: u32,
-        transaction_id: Uuid,
-        currency: String,
-        amount_in_minor: String,
-        settled_at: DateTime<Utc>,
-        merchant_account_id: String,
-        remitter: Remitter,
     },
     PayoutExecuted {
         event_id: Uuid,
@@ -71,21 +81,77 @@ pub enum TlWebhook {
         event_id: Uuid,
         event_version: u32,
         refund_id: RefundId,
-        payment_id: Uuid,
         executed_at: DateTime<Utc>,
     },
     RefundFailed {
         event_id: Uuid,
         event_version: u32,
         refund_id: RefundId,
-        payment_id: Uuid,
         failed_at: DateTime<Utc>,
     },
 }
 
+impl TlWebhook {
+    /// The record of this webhook's event being applied now.
+    fn processed(&self) -> ProcessedWebhook {
+        let (event_type, event_id, event_version) = match self {
+            TlWebhook::PaymentAuthorized {
+                event_id,
+                event_version,
+                ..
+            } => ("payment_authorized", event_id, event_version),
+            TlWebhook::PaymentExecuted {
+                event_id,
+                event_version,
+                ..
+            } => ("payment_executed", event_id, event_version),
+            TlWebhook::PaymentFailed {
+                event_id,
+                event_version,
+                ..
+            } => ("payment_failed", event_id, event_version),
+            TlWebhook::PaymentSettled {
+                event_id,
+                event_version,
+                ..
+            } => ("payment_settled", event_id, event_version),
+            TlWebhook::ExternalPaymentReceived {
+                event_id,
+                event_version,
+                ..
+            } => ("external_payment_received", event_id, event_version),
+            TlWebhook::PayoutExecuted {
+                event_id,
+                event_version,
+                ..
+            } => ("payout_executed", event_id, event_version),
+            TlWebhook::PayoutFailed {
+                event_id,
+                event_version,
+                ..
+            } => ("payout_failed", event_id, event_version),
+            TlWebhook::RefundExecuted {
+                event_id,
+                event_version,
+                ..
+            } => ("refund_executed", event_id, event_version),
+            TlWebhook::RefundFailed {
+                event_id,
+                event_version,
+                ..
+            } => ("refund_failed", event_id, event_version),
+        };
+        ProcessedWebhook {
+            event_id: *event_id,
+            event_type: event_type.into(),
+            event_version: *event_version,
+            processed_at: Utc::now(),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct PaymentSource {
-    pub account_holder_name: String,
     pub account_identifiers: Vec<AccountIdentifier>,
 }
 
@@ -101,10 +167,19 @@ pub enum AccountIdentifier {
     },
 }
 
-#[derive(Debug, Deserialize)]
-pub struct Remitter {
-    pub account_holder_name: String,
-    pub account_identifiers: Vec<AccountIdentifier>,
+impl From<AccountIdentifier> for domain::AccountIdentifier {
+    fn from(value: AccountIdentifier) -> Self {
+        match value {
+            AccountIdentifier::Iban { iban } => domain::AccountIdentifier::Iban { iban },
+            AccountIdentifier::SortCodeAccountNumber {
+                sort_code,
+                account_number,
+            } => domain::AccountIdentifier::SortCodeAccountNumber {
+                sort_code,
+                account_number,
+            },
+        }
+    }
 }
 
 #[post("/tl_webhook")]
@@ -113,6 +188,10 @@ pub async fn tl_webhook(
     req: HttpRequest,
     body: String,
 ) -> Result<impl Responder, PublicError> {
+    if let Err(err) = check_timestamp(&req, &app.webhook_config) {
+        warn!("{err}");
+        return Ok(HttpResponse::Unauthorized());
+    }
     if let Err(err) = verify_hook(req, body.as_bytes()).await {
         warn!("{err}");
         return Ok(HttpResponse::Unauthorized());
@@ -120,6 +199,18 @@ pub async fn tl_webhook(
 
     let webhook: TlWebhook = deserialize_body(&body)?;
 
+    // TrueLayer redelivers until acknowledged, so an event already applied is
+    // acknowledged again without applying it
+    let processed = webhook.processed();
+    if app
+        .db_client
+        .is_webhook_processed(processed.event_id)
+        .await?
+    {
+        info!(event_id = %processed.event_id, "webhook already processed");
+        return Ok(HttpResponse::Ok());
+    }
+
     match webhook {
         TlWebhook::PaymentAuthorized {
             payment_id,
@@ -129,14 +220,21 @@ pub async fn tl_webhook(
             log::set_payment_id(payment_id);
             log::set_payment_state(PaymentState::InboundAuthorized);
 
-            let (mut payment, version) = app
+            let (payment, version) = app
                 .db_client
                 .get_payment::<Payment>(payment_id)
                 .await?
                 .ok_or(PublicError::Invalid(String::from("test")))?;
 
-            payment.payment_statuses.inbound_authorized_at = Some(authorized_at);
-            app.db_client.upsert_payment(payment, version + 1).await?;
+            apply_event(
+                &app,
+                processed,
+                payment,
+                version,
+                Vec::new(),
+                PaymentEvent::InboundAuthorized { authorized_at },
+            )
+            .await?;
         }
         TlWebhook::PaymentExecuted {
             payment_id,
@@ -146,48 +244,93 @@ pub async fn tl_webhook(
             log::set_payment_id(payment_id);
             log::set_payment_state(PaymentState::InboundExecuted);
 
-            let (mut payment, version) = app
+            let (payment, version) = app
                 .db_client
                 .get_payment::<Payment>(payment_id)
                 .await?
                 .ok_or(PublicError::Invalid(String::from("test")))?;
 
-            payment.payment_statuses.inbound_executed_at = Some(executed_at);
-            app.db_client.upsert_payment(payment, version + 1).await?;
+            apply_event(
+                &app,
+                processed,
+                payment,
+                version,
+                Vec::new(),
+                PaymentEvent::InboundExecuted { executed_at },
+            )
+            .await?;
         }
         TlWebhook::PaymentSettled {
             payment_id,
             settled_at,
+            payment_source,
             ..
         } => {
             log::set_payment_id(payment_id);
             log::set_payment_state(PaymentState::InboundSettled);
 
-            let (mut payment, version) = app
+            let (payment, version) = app
                 .db_client
                 .get_payment::<Payment>(payment_id)
                 .await?
                 .ok_or(PublicError::Invalid(String::from("test")))?;
 
-            payment.payment_statuses.inbound_settled_at = Some(settled_at);
-            app.db_client.upsert_payment(payment, version + 1).await?;
+            // the payment is assessed and released by the outbox, so a
+            // failure there is retried rather than leaving it settled
+            let payer_accounts = payment_source
+                .map(|source| source.account_identifiers)
+                .unwrap_or_default()
+                .into_iter()
+                .map(Into::into)
+                .collect();
+            let settle = OutboxMessage::new(
+                Some(payment_id),
+                OutboxEffect::Settle {
+                    payment_id,
+                    payer_accounts,
+                },
+            );
+            apply_event(
+                &app,
+                processed,
+                payment,
+                version,
+                vec![settle],
+                PaymentEvent::InboundSettled { settled_at },
+            )
+            .await?;
         }
         TlWebhook::PaymentFailed {
             payment_id,
             failed_at,
+            failed_stage,
+            failure_reason,
             ..
         } => {
             log::set_payment_id(payment_id);
             log::set_payment_state(PaymentState::InboundFailed);
 
-            let (mut payment, version) = app
+            let (payment, version) = app
                 .db_client
                 .get_payment::<Payment>(payment_id)
                 .await?
                 .ok_or(PublicError::Invalid(String::from("test")))?;
 
-            payment.payment_statuses.inbound_failed_at = Some(failed_at);
-            app.db_client.upsert_payment(payment, version + 1).await?;
+            apply_event(
+                &app,
+                processed,
+                payment,
+                version,
+                Vec::new(),
+                PaymentEvent::InboundFailed {
+                    failed_at,
+                    failure: Some(PaymentFailure {
+                        stage: failed_stage,
+                        reason: failure_reason,
+                    }),
+                },
+            )
+            .await?;
         }
         TlWebhook::PayoutExecuted {
             payout_id,
@@ -197,7 +340,7 @@ pub async fn tl_webhook(
             log::set_payout_id(payout_id);
             log::set_payment_state(PaymentState::PayoutExecuted);
 
-            let (mut payment, version) = app
+            let (payment, version) = app
                 .db_client
                 .get_payment_by_payout_id::<Payment>(payout_id)
                 .await?
@@ -205,12 +348,15 @@ pub async fn tl_webhook(
 
             log::set_payment_id(payment.payment_id);
 
-            match payment.payout_data.as_mut() {
-                Some(payout) => payout.payout_statuses.payout_executed_at = Some(executed_at),
-                None => todo!(),
-            }
-
-            app.db_client.upsert_payment(payment, version + 1).await?;
+            apply_event(
+                &app,
+                processed,
+                payment,
+                version,
+                Vec::new(),
+                PaymentEvent::PayoutExecuted { executed_at },
+            )
+            .await?;
         }
         TlWebhook::PayoutFailed {
             payout_id,
@@ -220,7 +366,7 @@ pub async fn tl_webhook(
             log::set_payout_id(payout_id);
             log::set_payment_state(PaymentState::PayoutFailed);
 
-            let (mut payment, version) = app
+            let (payment, version) = app
                 .db_client
                 .get_payment_by_payout_id::<Payment>(payout_id)
                 .await?
@@ -228,12 +374,15 @@ pub async fn tl_webhook(
 
             log::set_payment_id(payment.payment_id);
 
-            match payment.payout_data.as_mut() {
-                Some(payout) => payout.payout_statuses.payout_failed_at = Some(failed_at),
-                None => todo!(),
-            }
-
-            app.db_client.upsert_payment(payment, version + 1).await?;
+            apply_event(
+                &app,
+                processed,
+                payment,
+                version,
+                Vec::new(),
+                PaymentEvent::PayoutFailed { failed_at },
+            )
+            .await?;
         }
         TlWebhook::RefundExecuted {
             refund_id,
@@ -243,7 +392,7 @@ pub async fn tl_webhook(
             log::set_refund_id(refund_id);
             log::set_payment_state(PaymentState::RefundExecuted);
 
-            let (mut payment, version) = app
+            let (payment, version) = app
                 .db_client
                 .get_payment_by_refund_id::<Payment>(refund_id)
                 .await?
@@ -251,12 +400,15 @@ pub async fn tl_webhook(
 
             log::set_payment_id(payment.payment_id);
 
-            match payment.refund_data.as_mut() {
-                Some(refund) => refund.refund_statuses.refund_executed_at = Some(executed_at),
-                None => todo!(),
-            }
-
-            app.db_client.upsert_payment(payment, version + 1).await?;
+            apply_event(
+                &app,
+                processed,
+                payment,
+                version,
+                Vec::new(),
+                PaymentEvent::RefundExecuted { executed_at },
+            )
+            .await?;
         }
         TlWebhook::RefundFailed {
             refund_id,
@@ -266,7 +418,7 @@ pub async fn tl_webhook(
             log::set_refund_id(refund_id);
             log::set_payment_state(PaymentState::RefundFailed);
 
-            let (mut payment, version) = app
+            let (payment, version) = app
                 .db_client
                 .get_payment_by_refund_id::<Payment>(refund_id)
                 .await?
@@ -274,16 +426,85 @@ pub async fn tl_webhook(
 
             log::set_payment_id(payment.payment_id);
 
-            match payment.refund_data.as_mut() {
-                Some(refund) => refund.refund_statuses.refund_failed_at = Some(failed_at),
-                None => todo!(),
+            apply_event(
+                &app,
+                processed,
+                payment,
+                version,
+                Vec::new(),
+                PaymentEvent::RefundFailed { failed_at },
+            )
+            .await?;
+        }
+        TlWebhook::ExternalPaymentReceived { event_id, .. } => {
+            // funds paid into the merchant account outside of a payment are
+            // reconciled by hand
+            info!(%event_id, "external payment received");
+        }
+    }
+    Ok(HttpResponse::Ok())
+}
+
+/// Applies `event` to `payment` and stores the result with the `webhook` it
+/// came from and the `outbox` messages it queues. Events that
+/// are not a legal transition from the payment's current state, or that were
+/// applied by a concurrent delivery of the same webhook, are acknowledged but
+/// dropped, as TrueLayer would otherwise keep redelivering them.
+async fn apply_event(
+    app: &AppContext,
+    webhook: ProcessedWebhook,
+    mut payment: Payment,
+    version: u32,
+    outbox: Vec<OutboxMessage>,
+    event: PaymentEvent,
+) -> Result<(), PublicError> {
+    match payment.transition(event, EventSource::Webhook) {
+        Ok(record) => {
+            let record = record.with_payload_ref(format!("tl_webhook:{}", webhook.event_id));
+            match app
+                .db_client
+                .upsert_payment_from_webhook(payment.clone(), version + 1, record, webhook, outbox)
+                .await
+            {
+                Ok(()) => {}
+                Err(DbError::DuplicateWebhook(event_id)) => {
+                    info!(%event_id, "webhook already processed");
+                    return Ok(());
+                }
+                Err(err) => return Err(err.into()),
             }
 
-            app.db_client.upsert_payment(payment, version + 1).await?;
+            if let Err(err) = sync_payment_request(app, &payment).await {
+                warn!(payment_id = %payment.payment_id, "failed to update payment request: {err:?}");
+            }
+            Ok(())
+        }
+        Err(err) => {
+            warn!(payment_id = %payment.payment_id, "{err}");
+            Ok(())
         }
-        TlWebhook::ExternalPaymentReceived { .. } => unimplemented!(),
     }
-    Ok(HttpResponse::Ok())
+}
+
+/// Rejects webhooks signed further than the configured tolerance from now, so
+/// a captured webhook cannot be replayed later. The timestamp is required to
+/// be part of the signature by [`verify_hook`].
+fn check_timestamp(req: &HttpRequest, config: &WebhookConfig) -> anyhow::Result<()> {
+    let timestamp = req
+        .headers()
+        .get("X-Tl-Webhook-Timestamp")
+        .context("missing X-Tl-Webhook-Timestamp header")?
+        .to_str()
+        .context("invalid non-string X-Tl-Webhook-Timestamp")?;
+    let timestamp = DateTime::parse_from_rfc3339(timestamp)
+        .with_context(|| format!("invalid X-Tl-Webhook-Timestamp {timestamp}"))?;
+
+    let skew = (Utc::now() - timestamp.with_timezone(&Utc)).abs();
+    ensure!(
+        skew <= Duration::seconds(config.timestamp_tolerance_secs),
+        "webhook timestamp {timestamp} is outside the tolerance"
+    );
+    Ok(())
 }
 
 async fn verify_hook(parts: HttpRequest, body: &[u8]) -> anyhow::Result<()> {
@@ -326,9 +547,83 @@ async fn verify_hook(parts: HttpRequest, body: &[u8]) -> anyhow::Result<()> {
                 .iter()
                 .map(|(h, v)| (h.as_str(), v.as_bytes())),
         )
+        .require_header("X-Tl-Webhook-Timestamp")
         .body(body)
         .build_verifier()
         .verify(tl_signature)?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    fn signed_at(timestamp: DateTime<Utc>) -> HttpRequest {
+        TestRequest::default()
+            .insert_header(("X-Tl-Webhook-Timestamp", timestamp.to_rfc3339()))
+            .to_http_request()
+    }
+
+    #[test]
+    fn timestamp_within_the_tolerance_is_accepted() {
+        let config = WebhookConfig::default();
+        for skew in [-299, 0, 299] {
+            let req = signed_at(Utc::now() + Duration::seconds(skew));
+            assert!(check_timestamp(&req, &config).is_ok(), "{skew}");
+        }
+    }
+
+    #[test]
+    fn stale_or_future_timestamp_is_rejected() {
+        let config = WebhookConfig {
+            timestamp_tolerance_secs: 60,
+        };
+        for skew in [-3600, -61, 61] {
+            let req = signed_at(Utc::now() + Duration::seconds(skew));
+            assert!(check_timestamp(&req, &config).is_err(), "{skew}");
+        }
+    }
+
+    #[test]
+    fn missing_or_invalid_timestamp_is_rejected() {
+        let config = WebhookConfig::default();
+        assert!(check_timestamp(&TestRequest::default().to_http_request(), &config).is_err());
+
+        let req = TestRequest::default()
+            .insert_header(("X-Tl-Webhook-Timestamp", "yesterday"))
+            .to_http_request();
+        assert!(check_timestamp(&req, &config).is_err());
+    }
+
+    #[test]
+    fn processed_records_the_event_of_every_webhook() {
+        let event_id = Uuid::new_v4();
+        let webhook: TlWebhook = deserialize_body(&format!(
+            r#"{{
+                "type": "payment_settled",
+                "event_id": "{event_id}",
+                "event_version": 2,
+                "payment_id": "{}",
+                "settled_at": "2024-01-01T00:00:00Z",
+                "payment_source": {{
+                    "account_identifiers": [{{ "type": "iban", "iban": "GB33BUKB20201555555555" }}]
+                }}
+            }}"#,
+            Uuid::new_v4()
+        ))
+        .unwrap();
+        let processed = webhook.processed();
+        assert_eq!(processed.event_id, event_id);
+        assert_eq!(processed.event_type, "payment_settled");
+        assert_eq!(processed.event_version, 2);
+
+        let webhook: TlWebhook = deserialize_body(&format!(
+            r#"{{ "type": "external_payment_received", "event_id": "{event_id}", "event_version": 1 }}"#
+        ))
+        .unwrap();
+        assert_eq!(webhook.processed().event_type, "external_payment_received");
+    }
+}
@@ -0,0 +1,30 @@
+mod payout_account;
+mod select_account;
+
+use actix_web::web;
+use payout_account::{connect_payout_account, payout_account, remove_payout_account};
+use select_account::{save_payout_account, select_payout_account};
+
+pub const ACCOUNT_PAGE: &str = "/app/account";
+pub const ACCOUNT_CONNECT_PAGE: &str = "/app/account/connect";
+pub const ACCOUNT_SELECT_PAGE: &str = "/app/account/select_account";
+pub const ACCOUNT_REMOVE_PAGE: &str = "/app/account/remove";
+
+/// Data API `state` marking a user saving a payout account, as opposed to a
+/// payment id being deposited.
+pub const PAYOUT_ACCOUNT_STATE: &str = "payout_account";
+
+/// Session key holding the accounts the Data API just verified for the user.
+const VERIFIED_ACCOUNTS: &str = "verified_accounts";
+
+pub fn account_scope() -> actix_web::Scope {
+    web::scope("account")
+        .service(web::resource("").get(payout_account))
+        .service(web::resource("connect").get(connect_payout_account))
+        .service(
+            web::resource("select_account")
+                .get(select_payout_account)
+                .post(save_payout_account),
+        )
+        .service(web::resource("remove").post(remove_payout_account))
+}
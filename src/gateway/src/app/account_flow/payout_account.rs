@@ -0,0 +1,116 @@
+use actix_session::Session;
+use actix_web::{http::header, web, HttpResponse};
+use domain::User;
+use leptos::view;
+
+use crate::{
+    api::{deposit_payment::data_auth_link, PublicError},
+    app::{
+        account_flow::{
+            ACCOUNT_CONNECT_PAGE, ACCOUNT_PAGE, ACCOUNT_REMOVE_PAGE, PAYOUT_ACCOUNT_STATE,
+        },
+        component::MyHtml,
+        login_flow::{session_user, LOGIN_PAGE},
+        MY_TRANSFERS_PAGE,
+    },
+    AppContext,
+};
+
+pub async fn payout_account(
+    app: web::Data<AppContext>,
+    session: Session,
+) -> Result<HttpResponse, PublicError> {
+    let Some(user_id) = session_user(&session) else {
+        return Ok(HttpResponse::SeeOther()
+            .insert_header((header::LOCATION, LOGIN_PAGE))
+            .finish());
+    };
+    let (user, _) = app
+        .db_client
+        .get_user::<User>(user_id.into_uuid())
+        .await?
+        .ok_or(PublicError::InternalServerError)?;
+
+    let account = user.payout_account().cloned().map(|account| {
+        view! {
+            <div class="card mb-3">
+                <div class="card-body">
+                    <h5 class="card-title">{account.display_name}</h5>
+                    <p class="card-text mb-1">{account.account_identifier.masked()}</p>
+                    <p class="card-text">
+                        <small>
+                            {format!(
+                                "{} payments are deposited here automatically. Verified {}.",
+                                account.currency,
+                                account.verified_at.format("%Y-%m-%d"),
+                            )}
+                        </small>
+                    </p>
+                    <form action={ACCOUNT_REMOVE_PAGE} method="post">
+                        <input type="submit" class="btn btn-outline-danger" value="Remove"/>
+                    </form>
+                </div>
+            </div>
+        }
+    });
+    let has_account = account.is_some();
+
+    let html = leptos::ssr::render_to_string(move || {
+        view! {
+            <MyHtml>
+                <div class="container-sm form-signin w-100 m-auto text-center" >
+                    <h1 class="text-light mb-3 fw-normal">Payout Account</h1>
+                    {account}
+                    <p hidden={has_account}>
+                        "Verify a bank account to have transfers sent to you deposited automatically."
+                    </p>
+                    <a class="btn btn-success w-100 mb-3" href={ACCOUNT_CONNECT_PAGE}>
+                        {if has_account { "Change Account" } else { "Verify Account" }}
+                    </a>
+                    <a class="btn btn-outline-light w-100" href={MY_TRANSFERS_PAGE}>My Transfers</a>
+                </div>
+            </MyHtml>
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html.to_string()))
+}
+
+/// Sends the user to the Data API to prove they own the account.
+pub async fn connect_payout_account(app: web::Data<AppContext>, session: Session) -> HttpResponse {
+    let location = match session_user(&session) {
+        Some(_) => data_auth_link(&app, PAYOUT_ACCOUNT_STATE),
+        None => LOGIN_PAGE.to_owned(),
+    };
+    HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, location))
+        .finish()
+}
+
+pub async fn remove_payout_account(
+    app: web::Data<AppContext>,
+    session: Session,
+) -> Result<HttpResponse, PublicError> {
+    let Some(user_id) = session_user(&session) else {
+        return Ok(HttpResponse::SeeOther()
+            .insert_header((header::LOCATION, LOGIN_PAGE))
+            .finish());
+    };
+    let (mut user, version) = app
+        .db_client
+        .get_user::<User>(user_id.into_uuid())
+        .await?
+        .ok_or(PublicError::InternalServerError)?;
+
+    if let User::Registered { payout_account, .. } = &mut user {
+        if payout_account.take().is_some() {
+            app.db_client.upsert_user(user, version + 1).await?;
+        }
+    }
+
+    Ok(HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, ACCOUNT_PAGE))
+        .finish())
+}
@@ -0,0 +1,172 @@
+use actix_session::Session;
+use actix_web::{http::header, web, HttpResponse};
+use chrono::Utc;
+use domain::{AccountIdentifier, Currency, PayoutAccount, User};
+use leptos::{component, view, CollectView, IntoView};
+use serde::Deserialize;
+use tracing::{error, instrument};
+
+use crate::{
+    api::PublicError,
+    app::{
+        account_flow::{ACCOUNT_PAGE, ACCOUNT_SELECT_PAGE, VERIFIED_ACCOUNTS},
+        component::MyHtml,
+        login_flow::{session_user, LOGIN_PAGE},
+    },
+    AppContext,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct QueryParams {
+    code: String,
+}
+
+/// Lists the accounts the Data API returned for the user. They are kept in
+/// the session so only one of them can be saved.
+#[instrument(skip_all)]
+pub async fn select_payout_account(
+    app: web::Data<AppContext>,
+    session: Session,
+    query_params: web::Query<QueryParams>,
+) -> Result<HttpResponse, PublicError> {
+    if session_user(&session).is_none() {
+        return Ok(HttpResponse::SeeOther()
+            .insert_header((header::LOCATION, LOGIN_PAGE))
+            .finish());
+    }
+
+    let token = app
+        .tl_client
+        .auth_data(&query_params.code)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to exchange data auth code");
+            PublicError::InternalServerError
+        })?
+        .access_token;
+    let verified_at = Utc::now();
+    let accounts = app
+        .tl_client
+        .get_accounts(&token)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to fetch accounts");
+            PublicError::InternalServerError
+        })?
+        .results
+        .into_iter()
+        .filter_map(|account| {
+            let currency = account.currency.parse::<Currency>().ok()?;
+            let account_identifier = match (
+                currency,
+                account.account_number.sort_code,
+                account.account_number.number,
+            ) {
+                (Currency::Gbp, Some(sort_code), Some(account_number)) => {
+                    AccountIdentifier::SortCodeAccountNumber {
+                        sort_code,
+                        account_number,
+                    }
+                }
+                _ => AccountIdentifier::Iban {
+                    iban: account.account_number.iban,
+                },
+            };
+            Some(PayoutAccount {
+                display_name: account.display_name,
+                currency,
+                account_identifier,
+                verified_at,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    session
+        .insert(VERIFIED_ACCOUNTS, &accounts)
+        .map_err(|_| PublicError::InternalServerError)?;
+
+    let html = leptos::ssr::render_to_string(|| {
+        view! {
+            <MyHtml>
+                <div class="container-sm form-signin w-100 m-auto" >
+                    <h1 class="text-center" >"Select Account"</h1>
+                    <AccountList accounts={accounts} />
+                </div>
+            </MyHtml>
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html.to_string()))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FormData {
+    index: usize,
+}
+
+#[instrument(skip_all)]
+pub async fn save_payout_account(
+    app: web::Data<AppContext>,
+    session: Session,
+    form: web::Form<FormData>,
+) -> Result<HttpResponse, PublicError> {
+    let Some(user_id) = session_user(&session) else {
+        return Ok(HttpResponse::SeeOther()
+            .insert_header((header::LOCATION, LOGIN_PAGE))
+            .finish());
+    };
+    let account = session
+        .remove_as::<Vec<PayoutAccount>>(VERIFIED_ACCOUNTS)
+        .and_then(Result::ok)
+        .and_then(|mut accounts| {
+            (form.index < accounts.len()).then(|| accounts.swap_remove(form.index))
+        })
+        .ok_or(PublicError::Invalid(String::from(
+            "Please verify your account again.",
+        )))?;
+
+    let (mut user, version) = app
+        .db_client
+        .get_user::<User>(user_id.into_uuid())
+        .await?
+        .ok_or(PublicError::InternalServerError)?;
+    let User::Registered { payout_account, .. } = &mut user else {
+        return Err(PublicError::InternalServerError);
+    };
+    *payout_account = Some(account);
+    app.db_client.upsert_user(user, version + 1).await?;
+
+    Ok(HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, ACCOUNT_PAGE))
+        .finish())
+}
+
+#[component]
+fn account_list(accounts: Vec<PayoutAccount>) -> impl IntoView {
+    let accounts_view = accounts
+        .into_iter()
+        .enumerate()
+        .map(|(index, account)| {
+            view! {
+                <form action={ACCOUNT_SELECT_PAGE} method="post" class="list-group-item">
+                    <input type="hidden" name="index" value={index}/>
+                    <div class="d-flex w-100 justify-content-between align-items-center" >
+                        <div>
+                            <h5 class="mb-1" >{account.display_name}</h5>
+                            <small>{format!("{} {}", account.currency, account.account_identifier.masked())}</small>
+                        </div>
+                        <input type="submit" class="btn btn-success" value="Use"/>
+                    </div>
+                </form>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <ul class="list-group list-group-flush">
+            { accounts_view }
+        </ul>
+    }
+}
@@ -0,0 +1,204 @@
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use db::entities::AdminAuditQuery;
+use domain::{AdminAuditEntry, AuditTarget, OutboxMessage, Payment, User};
+use leptos::{component, view, CollectView, IntoView};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{app::component::MyHtml, AppContext};
+
+use super::admin_payments::{start_of_day, value};
+
+const PAGE_SIZE: i64 = 50;
+
+/// The search form, submitted as is. Fields left blank match every entry.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct QueryParams {
+    actor: Option<String>,
+    target_id: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    offset: Option<i64>,
+}
+
+impl QueryParams {
+    fn audit_query(&self) -> anyhow::Result<AdminAuditQuery> {
+        Ok(AdminAuditQuery {
+            actor: value(&self.actor).map(str::to_owned),
+            target_id: value(&self.target_id)
+                .map(|target_id| {
+                    target_id
+                        .parse()
+                        .with_context(|| format!("Invalid target id: {target_id}"))
+                })
+                .transpose()?,
+            from: start_of_day(&self.from, 0)?,
+            to: start_of_day(&self.to, 1)?,
+            limit: PAGE_SIZE,
+            offset: self.offset.unwrap_or_default().max(0),
+        })
+    }
+}
+
+/// Searches the audit log by actor, target and day.
+pub async fn admin_audit_view(
+    app: web::Data<AppContext>,
+    query_params: web::Query<QueryParams>,
+) -> HttpResponse {
+    let query = match query_params.audit_query() {
+        Ok(query) => query,
+        Err(err) => return HttpResponse::BadRequest().body(err.to_string()),
+    };
+    let entries = app
+        .db_client
+        .query_audit_entries::<AdminAuditEntry>(&query)
+        .await
+        .unwrap();
+    let next_offset = (entries.len() as i64 == query.limit).then_some(query.offset + query.limit);
+    let params = query_params.into_inner();
+
+    let html = leptos::ssr::render_to_string(move || {
+        view! {
+            <MyHtml>
+                <div class="container-sm w-75">
+                    <h1 class="">Admin Audit Log</h1>
+                    <AuditSearchView params={params.clone()} />
+                    <AuditListView entries={entries} />
+                    <NextPageView params={params} next_offset={next_offset} />
+                </div>
+            </MyHtml>
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html.to_string())
+}
+
+/// What an admin change to a payment is recorded with, its state and the
+/// fields admins change. Personal data is left out of the log.
+pub fn payment_snapshot(payment: &Payment) -> Value {
+    json!({
+        "state": payment.state().as_str(),
+        "failed_attempts": payment.deposit_lock.failed_attempts,
+        "locked_until": payment.deposit_lock.locked_until,
+    })
+}
+
+pub fn outbox_snapshot(message: &OutboxMessage) -> Value {
+    json!({
+        "status": message.status.as_str(),
+        "attempts": message.attempts,
+        "next_attempt_at": message.next_attempt_at,
+    })
+}
+
+pub fn user_snapshot(user: &User) -> Value {
+    json!({ "state": user.state().as_str() })
+}
+
+#[component]
+fn audit_search_view(params: QueryParams) -> impl IntoView {
+    let input = |input_type: &'static str, name: &'static str, label: &'static str, field| {
+        let field = value(field).unwrap_or_default().to_owned();
+        view! {
+            <div class="col-6">
+                <div class="form-floating mb-3" >
+                    <input type={input_type} id={name} name={name} class="form-control" value={field} />
+                    <label for={name}>{label}</label>
+                </div>
+            </div>
+        }
+    };
+
+    view! {
+        <form method="get" action="/admin/audit" class="row">
+            { input("text", "actor", "Actor", &params.actor) }
+            { input("text", "target_id", "Payment, User or Message Id", &params.target_id) }
+            { input("date", "from", "From", &params.from) }
+            { input("date", "to", "To", &params.to) }
+            <div class="col-12 mb-3">
+                <button class="btn btn-success" type="submit">Search</button>
+            </div>
+        </form>
+    }
+}
+
+#[component]
+fn next_page_view(params: QueryParams, next_offset: Option<i64>) -> impl IntoView {
+    next_offset.map(|next_offset| {
+        let fields = [
+            ("actor", &params.actor),
+            ("target_id", &params.target_id),
+            ("from", &params.from),
+            ("to", &params.to),
+        ]
+        .into_iter()
+        .map(|(name, field)| {
+            let field = value(field).unwrap_or_default().to_owned();
+            view! { <input type="hidden" name={name} value={field}/> }
+        })
+        .collect_view();
+        view! {
+            <form method="get" action="/admin/audit">
+                { fields }
+                <input type="hidden" name="offset" value={next_offset.to_string()}/>
+                <button class="btn btn-outline-success" type="submit">Older</button>
+            </form>
+        }
+    })
+}
+
+#[component]
+fn audit_list_view(entries: Vec<AdminAuditEntry>) -> impl IntoView {
+    let values = entries
+        .into_iter()
+        .map(|entry| {
+            let target = entry.target.map(|target| {
+                let href = match target {
+                    AuditTarget::Payment(payment_id) => {
+                        format!("/admin/payment?payment_id={payment_id}")
+                    }
+                    AuditTarget::User(user_id) => format!("/admin/user?user_id={user_id}"),
+                    AuditTarget::OutboxMessage(_) => String::from("/admin/outbox"),
+                };
+                view! {
+                    <a href={href}>{format!("{} {}", target.kind(), target.id())}</a>
+                }
+            });
+            view! {
+                <tr>
+                    <td>{entry.created_at.to_rfc3339()}</td>
+                    <td>{entry.actor}</td>
+                    <td>{entry.action.as_str()}</td>
+                    <td>{target}</td>
+                    <td>{entry.request.unwrap_or_default()}</td>
+                    <td><code>{entry.before.map(|before| before.to_string())}</code></td>
+                    <td><code>{entry.after.map(|after| after.to_string())}</code></td>
+                    <td>{entry.ip.unwrap_or_default()}</td>
+                </tr>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <table class="table">
+            <thead>
+                <tr>
+                    <th class="" scope="col">Time</th>
+                    <th class="" scope="col">Actor</th>
+                    <th class="" scope="col">Action</th>
+                    <th class="" scope="col">Target</th>
+                    <th class="" scope="col">Request</th>
+                    <th class="" scope="col">Before</th>
+                    <th class="" scope="col">After</th>
+                    <th class="" scope="col">IP</th>
+                </tr>
+            </thead>
+            <tbody class="table-group-divider">
+                { values }
+            </tbody>
+        </table>
+    }
+}
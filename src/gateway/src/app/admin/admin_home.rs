@@ -12,6 +12,9 @@ pub async fn admin_home_view() -> HttpResponse {
 
                     <a class="btn btn-success ms-1" href="/admin/payments" >Payments</a>
                     <a class="btn btn-success ms-1" href="/admin/users" >Users</a>
+                    <a class="btn btn-success ms-1" href="/admin/reviews" >Review Queue</a>
+                    <a class="btn btn-success ms-1" href="/admin/outbox" >Outbox</a>
+                    <a class="btn btn-success ms-1" href="/admin/audit" >Audit Log</a>
 
                 </div>
             </MyHtml>
@@ -1,15 +1,22 @@
 use actix_web::{
     cookie::{time::Duration, Cookie},
     http::header,
-    web, HttpResponse,
+    web, HttpRequest, HttpResponse,
 };
 use argon2::{password_hash::SaltString, Argon2, PasswordHasher};
+use domain::{AdminAction, AdminAuditEntry};
 use leptos::view;
 use serde::Deserialize;
 
-use crate::app::component::{MyHtml, MyInput};
+use crate::{
+    app::component::{MyHtml, MyInput},
+    AppContext,
+};
 
-use super::admin_route_to_unauthorized;
+use super::{
+    admin_route_to_unauthorized,
+    auth::{admin_secret, is_valid_admin_name},
+};
 
 pub async fn admin_login_form() -> HttpResponse {
     let html = leptos::ssr::render_to_string(|| {
@@ -18,6 +25,7 @@ pub async fn admin_login_form() -> HttpResponse {
                 <div class="container-sm form-signin w-100 m-auto text-center" >
                     <form action="/admin/login" method="post" >
                         <h1 class="text-light mb-3 fw-normal">Admin Login</h1>
+                        <MyInput input_type="text" name="admin_name" label="Admin Name" required=true/>
                         <MyInput input_type="password" name="admin_password" label="Admin Password" required=true/>
                     </form>
                 </div>
@@ -32,14 +40,38 @@ pub async fn admin_login_form() -> HttpResponse {
 
 #[derive(Debug, Deserialize)]
 pub struct FormData {
+    admin_name: String,
     admin_password: String,
 }
 
-pub async fn admin_login(form: web::Form<FormData>) -> HttpResponse {
-    if form.admin_password == "admin" {
+/// Signs the admin in under the name they gave, which their requests and
+/// changes are recorded under in the audit log.
+pub async fn admin_login(
+    app: web::Data<AppContext>,
+    req: HttpRequest,
+    form: web::Form<FormData>,
+) -> HttpResponse {
+    let name = form.admin_name.trim();
+    let signed_in = is_valid_admin_name(name) && form.admin_password == "admin";
+    let action = match signed_in {
+        true => AdminAction::Login,
+        false => AdminAction::LoginFailed,
+    };
+    let ip = req
+        .connection_info()
+        .realip_remote_addr()
+        .map(str::to_owned);
+    // names of failed attempts are whatever was typed, keep them short
+    let actor = name.chars().take(64).collect::<String>();
+    app.db_client
+        .insert_audit_entry(AdminAuditEntry::new(actor, action, ip))
+        .await
+        .unwrap();
+
+    if signed_in {
         let salt = SaltString::from_b64("ZXRyYW5zZmVy").unwrap();
         let admin_hash = Argon2::default()
-            .hash_password(b"admin", &salt)
+            .hash_password(admin_secret(name).as_bytes(), &salt)
             .unwrap()
             .to_string();
 
@@ -50,9 +82,17 @@ pub async fn admin_login(form: web::Form<FormData>) -> HttpResponse {
             .max_age(Duration::minutes(5))
             .finish()
             .to_string();
+        let name_cookie = Cookie::build("admin_name", name)
+            .path("/admin")
+            .http_only(true)
+            .secure(true)
+            .max_age(Duration::minutes(5))
+            .finish()
+            .to_string();
 
         HttpResponse::SeeOther()
-            .insert_header((header::SET_COOKIE, cookie))
+            .append_header((header::SET_COOKIE, cookie))
+            .append_header((header::SET_COOKIE, name_cookie))
             .insert_header((header::LOCATION, "/admin/home"))
             .finish()
     } else {
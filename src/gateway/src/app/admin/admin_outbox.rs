@@ -0,0 +1,154 @@
+use actix_web::{http::header, web, HttpResponse};
+use chrono::Utc;
+use domain::{AdminAction, AuditTarget, OutboxMessage, OutboxStatus};
+use leptos::{component, view, CollectView, IntoView};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{app::component::MyHtml, AppContext};
+
+use super::{admin_audit::outbox_snapshot, auth::AdminActor};
+
+#[derive(Debug, Deserialize)]
+pub struct QueryParams {
+    status: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RetryForm {
+    message_id: Uuid,
+}
+
+/// Outbox messages by status, the dead ones by default.
+pub async fn admin_outbox_view(
+    app: web::Data<AppContext>,
+    query_params: web::Query<QueryParams>,
+) -> HttpResponse {
+    let status = query_params
+        .status
+        .as_deref()
+        .map(str::parse)
+        .transpose()
+        .unwrap()
+        .unwrap_or(OutboxStatus::Dead);
+    let messages = app
+        .db_client
+        .get_outbox_messages::<OutboxMessage>(status.as_str(), 50, 0)
+        .await
+        .unwrap();
+
+    let html = leptos::ssr::render_to_string(move || {
+        let tabs = OutboxStatus::ALL
+            .into_iter()
+            .map(|tab| {
+                let class = match tab == status {
+                    true => "btn btn-success ms-1",
+                    false => "btn btn-outline-success ms-1",
+                };
+                view! {
+                    <a class={class} href={format!("/admin/outbox?status={}", tab.as_str())}>
+                        {tab.as_str()}
+                    </a>
+                }
+            })
+            .collect_view();
+        view! {
+            <MyHtml>
+                <div class="container-sm w-75">
+                    <h1 class="">Admin Outbox</h1>
+                    <div class="mb-3">{ tabs }</div>
+                    <OutboxListView messages={messages} />
+                </div>
+            </MyHtml>
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html.to_string())
+}
+
+/// Queues a dead message for delivery again.
+pub async fn admin_outbox_retry(
+    app: web::Data<AppContext>,
+    actor: AdminActor,
+    form: web::Form<RetryForm>,
+) -> HttpResponse {
+    let mut message = app
+        .db_client
+        .get_outbox_message::<OutboxMessage>(form.message_id)
+        .await
+        .unwrap()
+        .unwrap();
+
+    let before = outbox_snapshot(&message);
+    message.retry(Utc::now());
+    let after = outbox_snapshot(&message);
+    app.db_client.update_outbox_message(message).await.unwrap();
+    app.db_client
+        .insert_audit_entry(
+            actor
+                .audit(AdminAction::OutboxRetry)
+                .with_target(AuditTarget::OutboxMessage(form.message_id))
+                .with_change(before, after),
+        )
+        .await
+        .unwrap();
+
+    HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, "/admin/outbox?status=dead"))
+        .finish()
+}
+
+#[component]
+fn outbox_list_view(messages: Vec<OutboxMessage>) -> impl IntoView {
+    let values = messages
+        .into_iter()
+        .map(|message| {
+            let payment_link = message.payment_id.map(|payment_id| {
+                view! {
+                    <a href={format!("/admin/payment?payment_id={}", payment_id)}>
+                        {payment_id.to_string()}
+                    </a>
+                }
+            });
+            view! {
+                <tr>
+                    <th scope="row">{message.message_id.to_string()}</th>
+                    <td>{payment_link}</td>
+                    <td>{message.effect.kind()}</td>
+                    <td>{message.attempts.to_string()}</td>
+                    <td>{message.last_error.unwrap_or_default()}</td>
+                    <td>{message.created_at.to_rfc3339()}</td>
+                    <td>
+                        {(message.status == OutboxStatus::Dead).then(|| view! {
+                            <form method="post" action="/admin/outbox/retry">
+                                <input type="hidden" name="message_id" value={message.message_id.to_string()}/>
+                                <button class="btn btn-outline-success btn-sm" type="submit">Retry</button>
+                            </form>
+                        })}
+                    </td>
+                </tr>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <table class="table">
+            <thead>
+                <tr>
+                    <th class="" scope="col">MessageId</th>
+                    <th class="" scope="col">PaymentId</th>
+                    <th class="" scope="col">Kind</th>
+                    <th class="" scope="col">Attempts</th>
+                    <th class="" scope="col">Last Error</th>
+                    <th class="" scope="col">Created</th>
+                    <th class="" scope="col"></th>
+                </tr>
+            </thead>
+            <tbody class="table-group-divider">
+                { values }
+            </tbody>
+        </table>
+    }
+}
@@ -1,10 +1,19 @@
-use actix_web::{web, HttpResponse};
-use domain::Payment;
+use actix_web::{http::header, web, HttpResponse};
+use chrono::Utc;
+use domain::{
+    AdminAction, AuditTarget, EventSource, OutboxEffect, OutboxMessage, Payment, PaymentEvent,
+    PaymentEventRecord, PaymentId, PaymentState,
+};
 use leptos::{component, view, CollectView, IntoView};
 use serde::Deserialize;
+use serde_json::Value;
 use uuid::Uuid;
 
-use crate::{app::component::MyHtml, AppContext};
+use crate::{
+    app::component::MyHtml, refund::refund_payment, settlement::release_payment, AppContext,
+};
+
+use super::{admin_audit::payment_snapshot, auth::AdminActor};
 
 #[derive(Debug, Deserialize)]
 pub struct QueryParams {
@@ -23,12 +32,22 @@ pub async fn admin_payment_view(
         .map(|(p, _)| p)
         .unwrap();
 
+    let events = app
+        .db_client
+        .get_payment_events::<PaymentEventRecord>(query_params.payment_id)
+        .await
+        .unwrap();
+
     let html = leptos::ssr::render_to_string(|| {
         view! {
             <MyHtml>
                 <div class="container-sm w-50" >
                     <h1 class="">Admin Payment View</h1>
+                    <ReviewView payment={payment.clone()} />
+                    <DepositLockView payment={payment.clone()} />
                     <PaymentView payment={payment} />
+                    <h2 class="">History</h2>
+                    <PaymentHistoryView events={events} />
                 </div>
             </MyHtml>
         }
@@ -39,10 +58,236 @@ pub async fn admin_payment_view(
         .body(html.to_string())
 }
 
+pub async fn admin_payment_unlock(
+    app: web::Data<AppContext>,
+    actor: AdminActor,
+    form: web::Form<QueryParams>,
+) -> HttpResponse {
+    let (mut payment, version) = app
+        .db_client
+        .get_payment::<Payment>(form.payment_id)
+        .await
+        .unwrap()
+        .unwrap();
+    let before = payment_snapshot(&payment);
+
+    payment.deposit_lock.reset();
+    let state = payment.state();
+    let event = PaymentEventRecord::new(
+        payment.payment_id,
+        "deposit_unlocked",
+        EventSource::Admin,
+        Some(state),
+        state,
+    );
+    app.db_client
+        .upsert_payment(payment, version + 1, event)
+        .await
+        .unwrap();
+    audit_payment_change(
+        &app,
+        &actor,
+        AdminAction::PaymentUnlock,
+        form.payment_id,
+        before,
+    )
+    .await;
+
+    redirect_to_payment(form.payment_id)
+}
+
+/// Lets a held payment go on to the payee.
+pub async fn admin_payment_approve(
+    app: web::Data<AppContext>,
+    actor: AdminActor,
+    form: web::Form<QueryParams>,
+) -> HttpResponse {
+    let (mut payment, version) = app
+        .db_client
+        .get_payment::<Payment>(form.payment_id)
+        .await
+        .unwrap()
+        .unwrap();
+    let before = payment_snapshot(&payment);
+
+    let event = payment
+        .transition(
+            PaymentEvent::ReviewReleased {
+                released_at: Utc::now(),
+            },
+            EventSource::Admin,
+        )
+        .unwrap();
+    app.db_client
+        .upsert_payment(payment.clone(), version + 1, event)
+        .await
+        .unwrap();
+    release_payment(&app, payment, version + 1).await.unwrap();
+    audit_payment_change(
+        &app,
+        &actor,
+        AdminAction::PaymentApprove,
+        form.payment_id,
+        before,
+    )
+    .await;
+
+    redirect_to_payment(form.payment_id)
+}
+
+/// Refunds a held payment to the payer.
+pub async fn admin_payment_reject(
+    app: web::Data<AppContext>,
+    actor: AdminActor,
+    form: web::Form<QueryParams>,
+) -> HttpResponse {
+    let (payment, version) = app
+        .db_client
+        .get_payment::<Payment>(form.payment_id)
+        .await
+        .unwrap()
+        .unwrap();
+    let before = payment_snapshot(&payment);
+
+    let email = OutboxMessage::new(
+        Some(payment.payment_id),
+        OutboxEffect::email(
+            &payment.payer_email,
+            "Your e-transfer was refunded",
+            format!(
+                "Your transfer of {} to {} could not be completed and is being refunded to your account.",
+                payment.amount, payment.payee_full_name
+            ),
+        ),
+    );
+    refund_payment(&app, payment, version, EventSource::Admin, vec![email])
+        .await
+        .unwrap();
+    audit_payment_change(
+        &app,
+        &actor,
+        AdminAction::PaymentReject,
+        form.payment_id,
+        before,
+    )
+    .await;
+
+    redirect_to_payment(form.payment_id)
+}
+
+/// Records an admin change to the payment, with the payment as it was
+/// `before` and as it is now.
+async fn audit_payment_change(
+    app: &AppContext,
+    actor: &AdminActor,
+    action: AdminAction,
+    payment_id: Uuid,
+    before: Value,
+) {
+    let after = app
+        .db_client
+        .get_payment::<Payment>(payment_id)
+        .await
+        .unwrap()
+        .map(|(payment, _)| payment_snapshot(&payment))
+        .unwrap_or_default();
+    app.db_client
+        .insert_audit_entry(
+            actor
+                .audit(action)
+                .with_target(AuditTarget::Payment(PaymentId::from_uuid(payment_id)))
+                .with_change(before, after),
+        )
+        .await
+        .unwrap();
+}
+
+fn redirect_to_payment(payment_id: Uuid) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .insert_header((
+            header::LOCATION,
+            format!("/admin/payment?payment_id={}", payment_id),
+        ))
+        .finish()
+}
+
+#[component]
+fn review_view(payment: Payment) -> impl IntoView {
+    let held = payment.state() == PaymentState::HeldForReview;
+    payment.risk_assessment.map(|assessment| {
+        let reasons = assessment
+            .reasons
+            .into_iter()
+            .map(|reason| view! { <li>{reason}</li> })
+            .collect_view();
+        let payment_id = payment.payment_id.to_string();
+
+        view! {
+            <div class="my-3">
+                <span>{format!("Risk score {}, assessed {}", assessment.score, assessment.assessed_at.to_rfc3339())}</span>
+                <ul>{reasons}</ul>
+                {held.then(|| view! {
+                    <div class="d-flex">
+                        <form method="post" action="/admin/payment/approve">
+                            <input type="hidden" name="payment_id" value={payment_id.clone()}/>
+                            <button class="btn btn-outline-success" type="submit">Approve</button>
+                        </form>
+                        <form class="ms-1" method="post" action="/admin/payment/reject">
+                            <input type="hidden" name="payment_id" value={payment_id}/>
+                            <button class="btn btn-outline-danger" type="submit">Reject</button>
+                        </form>
+                    </div>
+                })}
+            </div>
+        }
+    })
+}
+
+#[component]
+fn deposit_lock_view(payment: Payment) -> impl IntoView {
+    let lock = payment.deposit_lock;
+    let status = match lock.locked_until {
+        Some(locked_until) if lock.is_locked(Utc::now()) => {
+            format!("Deposits locked until {}", locked_until.to_rfc3339())
+        }
+        _ => format!(
+            "Deposits unlocked, {} failed security answer attempt(s)",
+            lock.failed_attempts
+        ),
+    };
+    let can_unlock = lock.locked_until.is_some() || lock.failed_attempts > 0;
+
+    view! {
+        <div class="d-flex align-items-center justify-content-between my-3">
+            <span>{status}</span>
+            {can_unlock.then(|| view! {
+                <form method="post" action="/admin/payment/unlock">
+                    <input type="hidden" name="payment_id" value={payment.payment_id.to_string()}/>
+                    <button class="btn btn-outline-danger" type="submit">Unlock</button>
+                </form>
+            })}
+        </div>
+    }
+}
+
 #[component]
 fn payment_view(payment: Payment) -> impl IntoView {
     let feilds_and_values = [
         ("payment_id", payment.payment_id.to_string()),
+        (
+            "amount_in_minor",
+            payment.amount.amount_in_minor.to_string(),
+        ),
+        ("currency", payment.amount.currency.to_string()),
+        ("message", payment.message.clone().unwrap_or_default()),
+        (
+            "payment_request_id",
+            payment
+                .payment_request_id
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+        ),
+        ("amount", payment.amount.to_string()),
         (
             "inbound_created_at",
             payment.payment_statuses.inbound_created_at.to_rfc3339(),
@@ -79,6 +324,74 @@ fn payment_view(payment: Payment) -> impl IntoView {
                 .map(|s| s.to_rfc3339())
                 .unwrap_or_default(),
         ),
+        (
+            "held_for_review_at",
+            payment
+                .payment_statuses
+                .held_for_review_at
+                .map(|s| s.to_rfc3339())
+                .unwrap_or_default(),
+        ),
+        (
+            "review_released_at",
+            payment
+                .payment_statuses
+                .review_released_at
+                .map(|s| s.to_rfc3339())
+                .unwrap_or_default(),
+        ),
+        (
+            "failure",
+            payment
+                .failure
+                .as_ref()
+                .map(|failure| format!("{}: {}", failure.stage, failure.reason))
+                .unwrap_or_default(),
+        ),
+        (
+            "expired_at",
+            payment
+                .payment_statuses
+                .expired_at
+                .map(|s| s.to_rfc3339())
+                .unwrap_or_default(),
+        ),
+        (
+            "cancelled_at",
+            payment
+                .payment_statuses
+                .cancelled_at
+                .map(|s| s.to_rfc3339())
+                .unwrap_or_default(),
+        ),
+        (
+            "payout_requested_at",
+            payment
+                .payment_statuses
+                .payout_requested_at
+                .map(|s| s.to_rfc3339())
+                .unwrap_or_default(),
+        ),
+        (
+            "refund_requested_at",
+            payment
+                .payment_statuses
+                .refund_requested_at
+                .map(|s| s.to_rfc3339())
+                .unwrap_or_default(),
+        ),
+        (
+            "failed_security_answer_attempts",
+            payment.deposit_lock.failed_attempts.to_string(),
+        ),
+        (
+            "deposit_locked_until",
+            payment
+                .deposit_lock
+                .locked_until
+                .map(|s| s.to_rfc3339())
+                .unwrap_or_default(),
+        ),
     ];
 
     let feilds_and_values = feilds_and_values
@@ -107,3 +420,42 @@ fn payment_view(payment: Payment) -> impl IntoView {
         </table>
     }
 }
+
+#[component]
+fn payment_history_view(events: Vec<(PaymentEventRecord, u32)>) -> impl IntoView {
+    let values = events
+        .into_iter()
+        .map(|(event, version)| {
+            view! {
+                <tr>
+                    <th scope="row">{version}</th>
+                    <td>{event.created_at.to_rfc3339()}</td>
+                    <td>{event.event_type}</td>
+                    <td>{event.source.as_str()}</td>
+                    <td>{event.previous_state.map(|s| s.as_str()).unwrap_or_default()}</td>
+                    <td>{event.new_state.as_str()}</td>
+                    <td>{event.payload_ref.unwrap_or_default()}</td>
+                </tr>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <table class="table">
+            <thead>
+                <tr>
+                    <th scope="col">Version</th>
+                    <th scope="col">Timestamp</th>
+                    <th scope="col">Event</th>
+                    <th scope="col">Source</th>
+                    <th scope="col">From</th>
+                    <th scope="col">To</th>
+                    <th scope="col">Payload</th>
+                </tr>
+            </thead>
+            <tbody class="table-group-divider">
+                { values }
+            </tbody>
+        </table>
+    }
+}
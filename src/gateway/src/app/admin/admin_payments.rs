@@ -1,18 +1,154 @@
+use std::str::FromStr;
+
 use actix_web::{web, HttpResponse};
-use domain::Payment;
+use anyhow::{bail, Context};
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use db::entities::{PaymentCursor, PaymentFilter, PaymentQuery, PaymentSort, SortDirection};
+use domain::{Payment, PaymentState};
 use leptos::{component, view, CollectView, IntoView};
+use serde::Deserialize;
 
 use crate::{app::component::MyHtml, AppContext};
 
-pub async fn admin_payments_view(app: web::Data<AppContext>) -> HttpResponse {
-    let payments = app.db_client.get_payments::<Payment>(10, 0).await.unwrap();
+const PAGE_SIZE: i64 = 20;
+
+/// The search form, submitted as is. Fields left blank are sent empty and
+/// match every payment.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct QueryParams {
+    state: Option<String>,
+    payer_email: Option<String>,
+    payee_email: Option<String>,
+    min_amount: Option<String>,
+    max_amount: Option<String>,
+    created_from: Option<String>,
+    created_to: Option<String>,
+    updated_from: Option<String>,
+    updated_to: Option<String>,
+    sort: Option<String>,
+    direction: Option<String>,
+    after: Option<String>,
+}
+
+impl QueryParams {
+    /// The fields carried over to the next page, all but its cursor.
+    fn search_fields(&self) -> [(&'static str, &str); 11] {
+        [
+            ("state", value(&self.state).unwrap_or_default()),
+            ("payer_email", value(&self.payer_email).unwrap_or_default()),
+            ("payee_email", value(&self.payee_email).unwrap_or_default()),
+            ("min_amount", value(&self.min_amount).unwrap_or_default()),
+            ("max_amount", value(&self.max_amount).unwrap_or_default()),
+            (
+                "created_from",
+                value(&self.created_from).unwrap_or_default(),
+            ),
+            ("created_to", value(&self.created_to).unwrap_or_default()),
+            (
+                "updated_from",
+                value(&self.updated_from).unwrap_or_default(),
+            ),
+            ("updated_to", value(&self.updated_to).unwrap_or_default()),
+            ("sort", value(&self.sort).unwrap_or_default()),
+            ("direction", value(&self.direction).unwrap_or_default()),
+        ]
+    }
+
+    fn payment_query(&self) -> anyhow::Result<PaymentQuery> {
+        let filter = PaymentFilter {
+            states: value(&self.state)
+                .map(PaymentState::from_str)
+                .transpose()?
+                .map(|state| state.as_str().to_owned())
+                .into_iter()
+                .collect(),
+            payer_email: value(&self.payer_email).map(str::to_owned),
+            payee_email: value(&self.payee_email).map(str::to_owned),
+            min_amount: amount(&self.min_amount)?,
+            max_amount: amount(&self.max_amount)?,
+            created_from: start_of_day(&self.created_from, 0)?,
+            created_to: start_of_day(&self.created_to, 1)?,
+            updated_from: start_of_day(&self.updated_from, 0)?,
+            updated_to: start_of_day(&self.updated_to, 1)?,
+        };
+        let sort = match value(&self.sort) {
+            None | Some("created_at") => PaymentSort::CreatedAt,
+            Some("updated_at") => PaymentSort::UpdatedAt,
+            Some("amount") => PaymentSort::Amount,
+            Some(sort) => bail!("Unknown sort: {sort}"),
+        };
+        let direction = match value(&self.direction) {
+            None | Some("desc") => SortDirection::Desc,
+            Some("asc") => SortDirection::Asc,
+            Some(direction) => bail!("Unknown sort direction: {direction}"),
+        };
+        Ok(PaymentQuery {
+            filter,
+            sort,
+            direction,
+            after: value(&self.after).map(str::parse).transpose()?,
+            limit: PAGE_SIZE,
+        })
+    }
+}
+
+pub(super) fn value(field: &Option<String>) -> Option<&str> {
+    field
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+fn amount(field: &Option<String>) -> anyhow::Result<Option<i64>> {
+    value(field)
+        .map(|amount| {
+            amount
+                .parse()
+                .with_context(|| format!("Invalid amount: {amount}"))
+        })
+        .transpose()
+}
+
+/// The start of the day `days` after the date in `field`, so a range ending
+/// on a date takes in the whole of that day.
+pub(super) fn start_of_day(
+    field: &Option<String>,
+    days: u64,
+) -> anyhow::Result<Option<DateTime<Utc>>> {
+    value(field)
+        .map(|date| {
+            NaiveDate::from_str(date)
+                .ok()
+                .and_then(|date| date.checked_add_days(Days::new(days)))
+                .map(|date| date.and_time(Default::default()).and_utc())
+                .with_context(|| format!("Invalid date: {date}"))
+        })
+        .transpose()
+}
+
+pub async fn admin_payments_view(
+    app: web::Data<AppContext>,
+    query_params: web::Query<QueryParams>,
+) -> HttpResponse {
+    let query = match query_params.payment_query() {
+        Ok(query) => query,
+        Err(err) => return HttpResponse::BadRequest().body(err.to_string()),
+    };
+    let page = app
+        .db_client
+        .query_payments::<Payment>(&query)
+        .await
+        .unwrap();
+    let params = query_params.into_inner();
 
-    let html = leptos::ssr::render_to_string(|| {
+    let html = leptos::ssr::render_to_string(move || {
         view! {
             <MyHtml>
                 <div class="container-sm w-50">
                     <h1 class="">Admin Payments View</h1>
-                    <PaymentListView payments={payments.iter().map(|(p, _)| p)} />
+                    <PaymentSearchView params={params.clone()} />
+                    <PaymentListView payments={page.payments.iter().map(|(p, _)| p)} />
+                    <NextPageView params={params} next={page.next} />
                 </div>
             </MyHtml>
         }
@@ -23,6 +159,107 @@ pub async fn admin_payments_view(app: web::Data<AppContext>) -> HttpResponse {
         .body(html.to_string())
 }
 
+#[component]
+fn payment_search_view(params: QueryParams) -> impl IntoView {
+    let selected_state = value(&params.state).unwrap_or_default().to_owned();
+    let state_options = PaymentState::ALL
+        .into_iter()
+        .map(|state| {
+            view! {
+                <option value={state.as_str()} selected={selected_state == state.as_str()}>
+                    {state.as_str()}
+                </option>
+            }
+        })
+        .collect_view();
+    let selected_sort = value(&params.sort).unwrap_or("created_at").to_owned();
+    let sort_options = [
+        ("created_at", "Created"),
+        ("updated_at", "Updated"),
+        ("amount", "Amount"),
+    ]
+    .into_iter()
+    .map(|(sort, label)| {
+        view! { <option value={sort} selected={selected_sort == sort}>{label}</option> }
+    })
+    .collect_view();
+    let ascending = value(&params.direction) == Some("asc");
+
+    let input = |input_type: &'static str, name: &'static str, label: &'static str, field| {
+        let field = value(field).unwrap_or_default().to_owned();
+        view! {
+            <div class="col-6">
+                <div class="form-floating mb-3" >
+                    <input type={input_type} id={name} name={name} class="form-control" value={field} />
+                    <label for={name}>{label}</label>
+                </div>
+            </div>
+        }
+    };
+
+    view! {
+        <form method="get" action="/admin/payments" class="row">
+            <div class="col-12">
+                <div class="form-floating mb-3" >
+                    <select class="form-select" id="state" name="state">
+                        <option value="">any</option>
+                        { state_options }
+                    </select>
+                    <label for="state">State</label>
+                </div>
+            </div>
+            { input("email", "payer_email", "Payer Email", &params.payer_email) }
+            { input("email", "payee_email", "Payee Email", &params.payee_email) }
+            { input("number", "min_amount", "Min Amount (minor units)", &params.min_amount) }
+            { input("number", "max_amount", "Max Amount (minor units)", &params.max_amount) }
+            { input("date", "created_from", "Created From", &params.created_from) }
+            { input("date", "created_to", "Created To", &params.created_to) }
+            { input("date", "updated_from", "Updated From", &params.updated_from) }
+            { input("date", "updated_to", "Updated To", &params.updated_to) }
+            <div class="col-6">
+                <div class="form-floating mb-3" >
+                    <select class="form-select" id="sort" name="sort">
+                        { sort_options }
+                    </select>
+                    <label for="sort">Sort By</label>
+                </div>
+            </div>
+            <div class="col-6">
+                <div class="form-floating mb-3" >
+                    <select class="form-select" id="direction" name="direction">
+                        <option value="desc" selected={!ascending}>Descending</option>
+                        <option value="asc" selected={ascending}>Ascending</option>
+                    </select>
+                    <label for="direction">Order</label>
+                </div>
+            </div>
+            <div class="col-12 mb-3">
+                <button class="btn btn-success" type="submit">Search</button>
+            </div>
+        </form>
+    }
+}
+
+#[component]
+fn next_page_view(params: QueryParams, next: Option<PaymentCursor>) -> impl IntoView {
+    next.map(|next| {
+        let fields = params
+            .search_fields()
+            .into_iter()
+            .map(|(name, value)| {
+                view! { <input type="hidden" name={name} value={value.to_owned()}/> }
+            })
+            .collect_view();
+        view! {
+            <form method="get" action="/admin/payments">
+                { fields }
+                <input type="hidden" name="after" value={next.to_string()}/>
+                <button class="btn btn-outline-success" type="submit">Next</button>
+            </form>
+        }
+    })
+}
+
 #[component]
 fn payment_list_view<'a, U>(payments: U) -> impl IntoView
 where
@@ -35,7 +272,7 @@ where
                     <th scope="row">{payment.payment_id.to_string()}</th>
                     <td>{payment.payer_email.clone()}</td>
                     <td>{payment.payee_email.clone()}</td>
-                    <td>{payment.amount}</td>
+                    <td>{payment.amount.to_string()}</td>
                     <td>{payment.state().as_str()}</td>
                 </tr>
             }
@@ -1,16 +1,37 @@
-use actix_web::{web, HttpResponse};
-use domain::User;
+use actix_web::{
+    http::header::{self, ContentDisposition, DispositionParam, DispositionType},
+    web, HttpResponse,
+};
+use domain::{AdminAction, AuditTarget, User, UserId, UserState};
 use leptos::{component, view, CollectView, IntoView};
 use serde::Deserialize;
+use serde_json::json;
+use tracing::error;
 use uuid::Uuid;
 
-use crate::{app::component::MyHtml, AppContext};
+use crate::{
+    app::component::MyHtml,
+    personal_data::{erase_user, export_personal_data},
+    AppContext,
+};
+
+use super::{admin_audit::user_snapshot, auth::AdminActor};
 
 #[derive(Debug, Deserialize)]
 pub struct QueryParams {
     user_id: Uuid,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ExportParams {
+    email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EraseForm {
+    user_id: Uuid,
+}
+
 pub async fn admin_user_view(
     app: web::Data<AppContext>,
     query_params: web::Query<QueryParams>,
@@ -39,6 +60,89 @@ pub async fn admin_user_view(
         .body(html.to_string())
 }
 
+/// All data stored about an email address, as a JSON download.
+pub async fn admin_user_export(
+    app: web::Data<AppContext>,
+    query_params: web::Query<ExportParams>,
+) -> HttpResponse {
+    let email = query_params.email.trim();
+    if email.is_empty() {
+        return HttpResponse::BadRequest().finish();
+    }
+    let export = match export_personal_data(&app, email).await {
+        Ok(export) => export,
+        Err(err) => {
+            error!(?err, "failed to export personal data");
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    HttpResponse::Ok()
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(String::from(
+                "personal-data.json",
+            ))],
+        })
+        .json(export)
+}
+
+/// Erases the user and their personal data, keeping the financial records.
+pub async fn admin_user_erase(
+    app: web::Data<AppContext>,
+    actor: AdminActor,
+    form: web::Form<EraseForm>,
+) -> HttpResponse {
+    let user_id = UserId::from_uuid(form.user_id);
+    let user = match app.db_client.get_user::<User>(form.user_id).await {
+        Ok(Some((user, _))) => user,
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(err) => {
+            error!(?err, %user_id, "failed to get user to erase");
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let report = match erase_user(&app, user_id).await {
+        Ok(report) => report,
+        Err(err) => {
+            error!(?err, %user_id, "failed to erase user");
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let erased = match app.db_client.get_user::<User>(form.user_id).await {
+        Ok(Some((erased, _))) => erased,
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(err) => {
+            error!(?err, %user_id, "failed to get erased user");
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let mut after = user_snapshot(&erased);
+    after["payments_erased"] = json!(report.payments_erased);
+    after["payments_kept"] = json!(report.payments_kept);
+    if let Err(err) = app
+        .db_client
+        .insert_audit_entry(
+            actor
+                .audit(AdminAction::UserErase)
+                .with_target(AuditTarget::User(user_id))
+                .with_change(user_snapshot(&user), after),
+        )
+        .await
+    {
+        error!(?err, %user_id, "failed to audit user erasure");
+        return HttpResponse::InternalServerError().finish();
+    }
+
+    HttpResponse::SeeOther()
+        .insert_header((
+            header::LOCATION,
+            format!("/admin/user?user_id={}", form.user_id),
+        ))
+        .finish()
+}
+
 #[component]
 fn user_view(user: User) -> impl IntoView {
     let feilds_and_values = [
@@ -72,6 +176,25 @@ fn user_view(user: User) -> impl IntoView {
         })
         .collect_view();
 
+    let actions = (user.state() != UserState::Erased).then(|| {
+        view! {
+            <div class="d-flex">
+                <form method="get" action="/admin/user/export">
+                    <input type="hidden" name="email" value={user.email().to_string()}/>
+                    <button class="btn btn-outline-success" type="submit">Export Data</button>
+                </form>
+                <form
+                    method="post"
+                    action="/admin/user/erase"
+                    onsubmit="return confirm('Erase this user and their personal data?')"
+                >
+                    <input type="hidden" name="user_id" value={user.user_id().to_string()}/>
+                    <button class="btn btn-outline-danger ms-1" type="submit">Erase User</button>
+                </form>
+            </div>
+        }
+    });
+
     view! {
         <table class="table">
             <thead>
@@ -84,5 +207,6 @@ fn user_view(user: User) -> impl IntoView {
                 { feilds_and_values }
             </tbody>
         </table>
+        { actions }
     }
 }
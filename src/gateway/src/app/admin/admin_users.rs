@@ -12,6 +12,10 @@ pub async fn admin_users_view(app: web::Data<AppContext>) -> HttpResponse {
             <MyHtml>
                 <div class="container-sm w-50">
                     <h1 class="">Admin Users View</h1>
+                    <form class="d-flex mb-3" method="get" action="/admin/user/export">
+                        <input class="form-control" type="email" name="email" placeholder="Email" required/>
+                        <button class="btn btn-outline-success ms-1 text-nowrap" type="submit">Export Data</button>
+                    </form>
                     <UserListView users={users.iter().map(|(u, _)| u)} />
                 </div>
             </MyHtml>
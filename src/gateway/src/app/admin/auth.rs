@@ -1,20 +1,93 @@
-use std::future::{ready, Ready};
+use std::{
+    future::{ready, Ready},
+    rc::Rc,
+};
 
 use actix_web::{
     body::BoxBody,
     cookie::Cookie,
-    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    error::ErrorUnauthorized,
     http::header,
-    Error, HttpResponse,
+    web, Error, FromRequest, HttpMessage, HttpRequest, HttpResponse,
 };
 use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use domain::{AdminAction, AdminAuditEntry, AuditTarget, PaymentId, UserId};
 use futures::future::LocalBoxFuture;
+use serde::Deserialize;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::AppContext;
+
+/// The admin a request was made by, known once [`AdminAuth`] let it through.
+#[derive(Debug, Clone)]
+pub struct AdminActor {
+    pub name: String,
+    pub ip: Option<String>,
+}
+
+impl AdminActor {
+    /// An audit entry of `action` made by this admin.
+    pub fn audit(&self, action: AdminAction) -> AdminAuditEntry {
+        AdminAuditEntry::new(&self.name, action, self.ip.clone())
+    }
+}
+
+impl FromRequest for AdminActor {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<AdminActor>()
+                .cloned()
+                .ok_or_else(|| ErrorUnauthorized("not signed in as admin")),
+        )
+    }
+}
+
+/// The secret the admin cookie proves, bound to the name the admin signed in
+/// with so the name cannot be swapped for another.
+pub fn admin_secret(name: &str) -> String {
+    format!("admin/{name}")
+}
+
+/// Whether `name` can be used as an admin name, and so in a cookie.
+pub fn is_valid_admin_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '@'))
+}
+
+/// The ids a request is about, read from its query.
+#[derive(Debug, Deserialize)]
+struct RequestTarget {
+    payment_id: Option<Uuid>,
+    user_id: Option<Uuid>,
+    message_id: Option<Uuid>,
+}
+
+impl RequestTarget {
+    fn target(&self) -> Option<AuditTarget> {
+        self.payment_id
+            .map(|payment_id| AuditTarget::Payment(PaymentId::from_uuid(payment_id)))
+            .or_else(|| {
+                self.user_id
+                    .map(|user_id| AuditTarget::User(UserId::from_uuid(user_id)))
+            })
+            .or_else(|| self.message_id.map(AuditTarget::OutboxMessage))
+    }
+}
 
 pub struct AdminAuth;
 
 impl<S> Transform<S, ServiceRequest> for AdminAuth
 where
-    S: Service<ServiceRequest, Response = ServiceResponse<BoxBody>, Error = Error>,
+    S: Service<ServiceRequest, Response = ServiceResponse<BoxBody>, Error = Error> + 'static,
     S::Future: 'static,
 {
     type Response = ServiceResponse<BoxBody>;
@@ -24,17 +97,19 @@ where
     type Future = Ready<Result<Self::Transform, Self::InitError>>;
 
     fn new_transform(&self, service: S) -> Self::Future {
-        ready(Ok(AdminAuthMiddleware { service }))
+        ready(Ok(AdminAuthMiddleware {
+            service: Rc::new(service),
+        }))
     }
 }
 
 pub struct AdminAuthMiddleware<S> {
-    service: S,
+    service: Rc<S>,
 }
 
 impl<S> Service<ServiceRequest> for AdminAuthMiddleware<S>
 where
-    S: Service<ServiceRequest, Response = ServiceResponse<BoxBody>, Error = Error>,
+    S: Service<ServiceRequest, Response = ServiceResponse<BoxBody>, Error = Error> + 'static,
     S::Future: 'static,
 {
     type Response = ServiceResponse<BoxBody>;
@@ -44,42 +119,163 @@ where
     forward_ready!(service);
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
-        let auth_flag = req
-            .headers()
-            .get(header::COOKIE)
-            .and_then(|cookie_header| {
-                cookie_header.to_str().ok().and_then(|cookie_str| {
+        let cookie = |name: &str| {
+            req.headers()
+                .get(header::COOKIE)
+                .and_then(|cookie_header| cookie_header.to_str().ok())
+                .and_then(|cookie_str| {
                     cookie_str
                         .split(';')
-                        .map(|s| Cookie::parse(s.trim()).ok())
-                        .find(|x| x.as_ref().map(|x| x.name() == "admin").unwrap_or(false))
+                        .filter_map(|s| Cookie::parse(s.trim()).ok())
+                        .find(|cookie| cookie.name() == name)
+                        .map(|cookie| cookie.value().to_owned())
                 })
-            })
-            .flatten()
-            .as_ref()
-            .map(|admin_cookie| admin_cookie.value())
-            .map(|auth_value| {
-                let admin_hash = PasswordHash::new(auth_value).unwrap();
-                Argon2::default()
-                    .verify_password(b"admin", &admin_hash)
-                    .map_or(false, |_| true)
-            })
-            .unwrap_or(false);
+        };
+        let actor = cookie("admin_name").filter(|name| {
+            cookie("admin")
+                .and_then(|auth_value| {
+                    let admin_hash = PasswordHash::new(&auth_value).ok()?;
+                    Argon2::default()
+                        .verify_password(admin_secret(name).as_bytes(), &admin_hash)
+                        .ok()
+                })
+                .is_some()
+        });
 
-        if auth_flag {
-            let fut = self.service.call(req);
-            Box::pin(async move {
-                let res = fut.await?;
-                Ok(res)
-            })
-        } else {
-            Box::pin(async {
+        let Some(name) = actor else {
+            return Box::pin(async {
                 Ok(req.into_response(
                     HttpResponse::SeeOther()
                         .insert_header((header::LOCATION, "/admin/unauthorized"))
                         .finish(),
                 ))
-            })
+            });
+        };
+
+        let actor = AdminActor {
+            name,
+            ip: req
+                .connection_info()
+                .realip_remote_addr()
+                .map(str::to_owned),
+        };
+        // the query is left out, it may hold emails searched for, and the
+        // audit log is never erased
+        let mut entry = actor.audit(AdminAction::Request).with_request(format!(
+            "{} {}",
+            req.method(),
+            req.path()
+        ));
+        if let Some(target) = web::Query::<RequestTarget>::from_query(req.query_string())
+            .ok()
+            .and_then(|query| query.target())
+        {
+            entry = entry.with_target(target);
         }
+        req.extensions_mut().insert(actor);
+
+        let app = req
+            .app_data::<web::Data<AppContext>>()
+            .cloned()
+            .expect("app context is registered");
+        let service = self.service.clone();
+        Box::pin(async move {
+            // a request that cannot be audited is not served
+            if let Err(err) = app.db_client.insert_audit_entry(entry).await {
+                error!("insert_audit_entry: {err:?}");
+                return Ok(req.into_response(HttpResponse::InternalServerError().finish()));
+            }
+            service.call(req).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test, App};
+    use argon2::{
+        password_hash::{rand_core::OsRng, SaltString},
+        PasswordHasher,
+    };
+    use db::entities::AdminAuditQuery;
+
+    use super::*;
+
+    fn admin_cookies(name: &str) -> String {
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = Argon2::default()
+            .hash_password(admin_secret(name).as_bytes(), &salt)
+            .unwrap();
+        format!("admin_name={name}; admin={hash}")
+    }
+
+    async fn audit_log(app: &AppContext) -> Vec<AdminAuditEntry> {
+        app.db_client
+            .query_audit_entries(&AdminAuditQuery {
+                limit: 10,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn admin_requests_are_audited_without_their_query() {
+        let app_context = web::Data::new(AppContext::for_tests());
+        let service = test::init_service(
+            App::new().app_data(app_context.clone()).service(
+                web::scope("/admin")
+                    .wrap(AdminAuth)
+                    .route("/payment", web::get().to(HttpResponse::Ok)),
+            ),
+        )
+        .await;
+        let payment_id = Uuid::new_v4();
+
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/admin/payment?payment_id={payment_id}&email=payer%40example.com"
+            ))
+            .insert_header((header::COOKIE, admin_cookies("alice")))
+            .to_request();
+        assert!(test::call_service(&service, req)
+            .await
+            .status()
+            .is_success());
+
+        let entries = audit_log(&app_context).await;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].actor, "alice");
+        assert_eq!(entries[0].action, AdminAction::Request);
+        assert_eq!(entries[0].request.as_deref(), Some("GET /admin/payment"));
+        assert_eq!(
+            entries[0].target,
+            Some(AuditTarget::Payment(PaymentId::from_uuid(payment_id)))
+        );
+    }
+
+    #[actix_web::test]
+    async fn requests_without_an_admin_are_turned_away_unaudited() {
+        let app_context = web::Data::new(AppContext::for_tests());
+        let service = test::init_service(
+            App::new().app_data(app_context.clone()).service(
+                web::scope("/admin")
+                    .wrap(AdminAuth)
+                    .route("/payment", web::get().to(HttpResponse::Ok)),
+            ),
+        )
+        .await;
+
+        let forged = format!(
+            "admin_name=mallory; {}",
+            admin_cookies("alice").split("; ").nth(1).unwrap()
+        );
+        let req = test::TestRequest::get()
+            .uri("/admin/payment")
+            .insert_header((header::COOKIE, forged))
+            .to_request();
+        let res = test::call_service(&service, req).await;
+        assert_eq!(res.status(), actix_web::http::StatusCode::SEE_OTHER);
+        assert!(audit_log(&app_context).await.is_empty());
     }
 }
@@ -1,17 +1,25 @@
+mod admin_audit;
 mod admin_home;
 mod admin_login_;
+mod admin_outbox;
 mod admin_payment;
 mod admin_payments;
+mod admin_reviews;
 mod admin_user;
 mod admin_users;
 mod auth;
 
 use actix_web::{http::header, web, HttpResponse};
+use admin_audit::admin_audit_view;
 use admin_home::admin_home_view;
 use admin_login_::{admin_login, admin_login_form};
-use admin_payment::admin_payment_view;
+use admin_outbox::{admin_outbox_retry, admin_outbox_view};
+use admin_payment::{
+    admin_payment_approve, admin_payment_reject, admin_payment_unlock, admin_payment_view,
+};
 use admin_payments::admin_payments_view;
-use admin_user::admin_user_view;
+use admin_reviews::admin_reviews_view;
+use admin_user::{admin_user_erase, admin_user_export, admin_user_view};
 use admin_users::admin_users_view;
 use auth::AdminAuth;
 use leptos::view;
@@ -29,10 +37,19 @@ pub fn admin_scope() -> actix_web::Scope {
         .service(
             web::scope("")
                 .wrap(AdminAuth)
+                .service(web::resource("audit").get(admin_audit_view))
                 .service(web::resource("home").get(admin_home_view))
+                .service(web::resource("outbox").get(admin_outbox_view))
+                .service(web::resource("outbox/retry").post(admin_outbox_retry))
                 .service(web::resource("payment").get(admin_payment_view))
+                .service(web::resource("payment/unlock").post(admin_payment_unlock))
+                .service(web::resource("payment/approve").post(admin_payment_approve))
+                .service(web::resource("payment/reject").post(admin_payment_reject))
                 .service(web::resource("payments").get(admin_payments_view))
+                .service(web::resource("reviews").get(admin_reviews_view))
                 .service(web::resource("user").get(admin_user_view))
+                .service(web::resource("user/export").get(admin_user_export))
+                .service(web::resource("user/erase").post(admin_user_erase))
                 .service(web::resource("users").get(admin_users_view)),
         )
         .default_service(web::to(admin_route_to_unauthorized))
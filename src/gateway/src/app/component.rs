@@ -1,4 +1,5 @@
-use leptos::{component, view, Children, IntoView};
+use domain::{Currency, Payment};
+use leptos::{component, view, Children, CollectView, IntoView};
 
 #[component]
 pub fn my_input(
@@ -22,6 +23,51 @@ pub fn my_input(
     }
 }
 
+/// Optional note from the payer to the payee.
+#[component]
+pub fn message_input() -> impl IntoView {
+    view! {
+        <div class="form-floating mb-3" >
+            <textarea
+                id="message"
+                name="message"
+                class="form-control"
+                style="height: 6rem"
+                maxlength={Payment::MESSAGE_MAX_LEN}
+                data-1p-ignore
+            ></textarea>
+            <label for="message">"Message (optional)"</label>
+        </div>
+    }
+}
+
+#[component]
+pub fn currency_select(
+    name: &'static str,
+    label: &'static str,
+    #[prop(optional_no_strip)] selected: Option<Currency>,
+) -> impl IntoView {
+    let options = Currency::ALL
+        .into_iter()
+        .map(|currency| {
+            view! {
+                <option value={currency.as_str()} selected={selected == Some(currency)}>
+                    {currency.as_str()}
+                </option>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <div class="form-floating mb-3" >
+            <select class="form-select" id={name} name={name} required=true>
+                { options }
+            </select>
+            <label for={name}>{label}</label>
+        </div>
+    }
+}
+
 #[component]
 pub fn navbar() -> impl IntoView {
     view! {
@@ -6,13 +6,22 @@ use base64::{
     Engine,
 };
 use chrono::Utc;
-use domain::{Payment, PaymentId, PaymentState, PayoutData, PayoutId, PayoutStatuses};
+use domain::{
+    AccountIdentifier, EventSource, Payment, PaymentId, PaymentRequest, PaymentState,
+    PayoutDestination,
+};
 use serde::Deserialize;
+use tracing::error;
 
 use crate::{
     api::PublicError,
-    app::deposit_flow::{DESPOSIT_STATUS_PAGE, PAYOUT_COOKIE},
-    log, AppContext,
+    app::{
+        deposit_flow::{is_deposit_allowed, DESPOSIT_STATUS_PAGE, PAYOUT_COOKIE},
+        login_flow::session_user,
+    },
+    log,
+    payout::payout_payment,
+    AppContext,
 };
 
 #[derive(Debug, Deserialize)]
@@ -27,6 +36,9 @@ pub async fn create_payout(
     query_params: web::Query<QueryParams>,
 ) -> Result<HttpResponse, PublicError> {
     let payment_id = query_params.payment_id;
+    if !is_deposit_allowed(&session, payment_id) {
+        return Ok(HttpResponse::Unauthorized().finish());
+    }
 
     let salt_b64 = STANDARD_NO_PAD.encode(query_params.payment_id.as_uuid());
     let salt = SaltString::from_b64(salt_b64.as_str()).unwrap();
@@ -38,47 +50,60 @@ pub async fn create_payout(
 
     log::set_payment_id(payment_id);
 
-    let valid_ibans: String = session.get(PAYOUT_COOKIE).unwrap().unwrap();
+    let valid_ibans: String = session
+        .get(PAYOUT_COOKIE)
+        .ok()
+        .flatten()
+        .unwrap_or_default();
     if !valid_ibans.contains(hash_iban.as_str()) {
-        todo!()
+        return Ok(HttpResponse::Unauthorized().finish());
     }
 
-    let (mut payment, version) = app
+    let (payment, version) = app
         .db_client
         .get_payment::<Payment>(payment_id)
         .await?
         .ok_or(PublicError::InternalServerError)?;
 
-    if payment.state() >= PaymentState::PayoutCreated {
+    if payment.deposit_lock.is_locked(Utc::now()) {
+        return Ok(HttpResponse::Locked().finish());
+    }
+    // request payments only ever go to the signed in requester
+    if let Some(request_id) = payment.payment_request_id {
+        let (request, _) = app
+            .db_client
+            .get_payment_request::<PaymentRequest>(request_id)
+            .await?
+            .ok_or(PublicError::InternalServerError)?;
+        if session_user(&session) != Some(request.payee_user_id) {
+            return Ok(HttpResponse::Unauthorized().finish());
+        }
+    }
+    if !payment
+        .state()
+        .can_transition_to(PaymentState::PayoutRequested)
+    {
         return Err(PublicError::InternalServerError);
     }
 
-    let payout = app
-        .tl_client
-        .create_payout(
-            &payment.payee_full_name,
-            iban.as_str(),
-            payment.amount,
-            "ref",
-        )
-        .await
-        .map_err(|_| PublicError::InternalServerError)?;
-
-    let payout_id = PayoutId::from_uuid(payout.payout_id);
-    log::set_payout_id(payout_id);
-    log::set_payment_state(PaymentState::PayoutCreated);
-
-    payment.payout_data = Some(PayoutData {
-        payout_id,
-        payout_statuses: PayoutStatuses {
-            payout_created_at: Utc::now(),
-            payout_executed_at: None,
-            payout_failed_at: None,
-        },
-    });
-
     let link = format!("{}?payment_id={}", DESPOSIT_STATUS_PAGE, payment.payment_id);
-    app.db_client.upsert_payment(payment, version + 1).await?;
+    let destination = PayoutDestination {
+        account_holder_name: payment.payee_full_name.clone(),
+        account_identifier: AccountIdentifier::Iban { iban },
+    };
+    payout_payment(
+        &app,
+        payment,
+        version,
+        destination,
+        EventSource::Ui,
+        Vec::new(),
+    )
+    .await
+    .map_err(|err| {
+        error!(?err, "failed to request payout");
+        PublicError::InternalServerError
+    })?;
 
     Ok(HttpResponse::SeeOther()
         .insert_header((header::LOCATION, link))
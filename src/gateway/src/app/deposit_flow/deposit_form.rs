@@ -1,11 +1,14 @@
 use actix_web::{web, HttpResponse};
-use domain::Payment;
-use leptos::view;
+use domain::{Payment, PaymentState};
+use leptos::{view, IntoView};
 use serde::Deserialize;
 use uuid::Uuid;
 
 use crate::{
-    app::component::{MyHtml, MyInput},
+    app::{
+        component::{MyHtml, MyInput},
+        deposit_flow::DEPOSIT_REQUEST_PAGE,
+    },
     AppContext,
 };
 
@@ -27,10 +30,26 @@ pub async fn deposit_form(
         _ => return HttpResponse::ServiceUnavailable().body("ummm"),
     };
 
+    if payment.state() == PaymentState::HeldForReview {
+        return deposit_under_review();
+    }
+
+    if !payment
+        .state()
+        .can_transition_to(PaymentState::PayoutRequested)
+    {
+        return deposit_unavailable();
+    }
+
+    if payment.payment_request_id.is_some() {
+        return deposit_request_form(payment);
+    }
+
     let from = payment.payer_full_name;
     let payment_id = payment.payment_id.to_string();
-    let amount = payment.amount;
+    let amount = payment.amount.to_string();
     let security_question = payment.security_question;
+    let message = payment.message.map(message_view);
 
     let html = leptos::ssr::render_to_string(move || {
         view! {
@@ -53,7 +72,7 @@ pub async fn deposit_form(
 
                         <div class="form-floating mb-3" >
                             <input
-                                type="number"
+                                type="text"
                                 readonly
                                 class="form-control"
                                 id="amount_test"
@@ -62,6 +81,8 @@ pub async fn deposit_form(
                             <label for="amount_test">Amount</label>
                         </div>
 
+                        {message}
+
                         <div class="form-floating mb-3" >
                             <input
                                 type="text"
@@ -100,3 +121,84 @@ pub async fn deposit_form(
         .content_type("text/html; charset=utf-8")
         .body(html.to_string())
 }
+
+/// Payments answering a money request skip the security question, the
+/// requesting payee deposits them while signed in.
+fn deposit_request_form(payment: Payment) -> HttpResponse {
+    let from = payment.payer_full_name;
+    let payment_id = payment.payment_id.to_string();
+    let amount = payment.amount.to_string();
+    let message = payment.message.map(message_view);
+
+    let html = leptos::ssr::render_to_string(move || {
+        view! {
+            <MyHtml>
+                <div class="container-sm form-signin w-100 m-auto text-center" >
+                    <form action={DEPOSIT_REQUEST_PAGE} method="post" >
+                        <h1 class="text-light mb-3 fw-normal">Deposit Payment</h1>
+                        <p>{format!("{} paid your request for {}.", from, amount)}</p>
+                        {message}
+                        <p>"Sign in with the account that requested the money to deposit it."</p>
+                        <input type="hidden" name="payment_id" value={payment_id}/>
+                        <div class="input-group mb-3">
+                            <input
+                                type="submit"
+                                class="form-control btn btn-success"
+                                value="DEPOSIT"
+                            />
+                        </div>
+                    </form>
+                </div>
+            </MyHtml>
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html.to_string())
+}
+
+fn message_view(message: String) -> impl IntoView {
+    view! {
+        <div class="form-floating mb-3" >
+            <textarea readonly class="form-control" id="message" style="height: 6rem">
+                {message}
+            </textarea>
+            <label for="message">Message</label>
+        </div>
+    }
+}
+
+fn deposit_unavailable() -> HttpResponse {
+    let html = leptos::ssr::render_to_string(|| {
+        view! {
+            <MyHtml>
+                <div class="container-sm form-signin w-100 m-auto text-center" >
+                    <h1 class="text-light mb-3 fw-normal">"Deposit Unavailable"</h1>
+                    <p>"This transfer can no longer be deposited."</p>
+                </div>
+            </MyHtml>
+        }
+    });
+
+    HttpResponse::Gone()
+        .content_type("text/html; charset=utf-8")
+        .body(html.to_string())
+}
+
+fn deposit_under_review() -> HttpResponse {
+    let html = leptos::ssr::render_to_string(|| {
+        view! {
+            <MyHtml>
+                <div class="container-sm form-signin w-100 m-auto text-center" >
+                    <h1 class="text-light mb-3 fw-normal">"Deposit Under Review"</h1>
+                    <p>"This transfer is being reviewed. You will be able to deposit it once the review is complete."</p>
+                </div>
+            </MyHtml>
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html.to_string())
+}
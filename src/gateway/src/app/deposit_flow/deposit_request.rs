@@ -0,0 +1,66 @@
+use actix_session::Session;
+use actix_web::{http::header, web, HttpResponse};
+use domain::{Payment, PaymentRequest, PaymentState};
+use serde::Deserialize;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{
+    api::{deposit_payment::deposit_auth_link, PublicError},
+    app::{
+        deposit_flow::allow_deposit,
+        login_flow::{session_user, LOGIN_PAGE},
+    },
+    AppContext,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct FormData {
+    payment_id: Uuid,
+}
+
+/// Deposits a payment made in answer to a money request. The requesting
+/// payee proves who they are by being signed in, in place of a security
+/// answer.
+#[instrument(skip(app, session))]
+pub async fn deposit_request(
+    app: web::Data<AppContext>,
+    session: Session,
+    form: web::Form<FormData>,
+) -> Result<HttpResponse, PublicError> {
+    let Some(user_id) = session_user(&session) else {
+        return Ok(HttpResponse::SeeOther()
+            .insert_header((header::LOCATION, LOGIN_PAGE))
+            .finish());
+    };
+
+    let (payment, _) = app
+        .db_client
+        .get_payment::<Payment>(form.payment_id)
+        .await?
+        .ok_or(PublicError::Invalid(String::from("Unknown payment.")))?;
+    let request_id = payment
+        .payment_request_id
+        .ok_or(PublicError::Invalid(String::from("Unknown payment.")))?;
+    let (request, _) = app
+        .db_client
+        .get_payment_request::<PaymentRequest>(request_id)
+        .await?
+        .ok_or(PublicError::InternalServerError)?;
+
+    if request.payee_user_id != user_id
+        || !payment
+            .state()
+            .can_transition_to(PaymentState::PayoutRequested)
+    {
+        return Ok(HttpResponse::Unauthorized().finish());
+    }
+
+    allow_deposit(&session, payment.payment_id);
+    Ok(HttpResponse::SeeOther()
+        .insert_header((
+            header::LOCATION,
+            deposit_auth_link(&app, payment.payment_id),
+        ))
+        .finish())
+}
@@ -53,9 +53,11 @@ pub async fn deposit_status_update(
         )))?;
 
     let status = match payment.state() {
+        PaymentState::PayoutRequested => "Payment Deposit Requested...",
         PaymentState::PayoutCreated => "Payment Deposit Created...",
         PaymentState::PayoutExecuted => "Payment Deposit Executed",
-        PaymentState::RefundCreated
+        PaymentState::RefundRequested
+        | PaymentState::RefundCreated
         | PaymentState::RefundExecuted
         | PaymentState::RefundFailed
         | PaymentState::PayoutFailed => "Payment Deposit Failed...",
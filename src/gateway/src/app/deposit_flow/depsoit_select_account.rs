@@ -14,7 +14,7 @@ use truelayer::model::AccountBalance;
 use crate::{
     app::{
         component::MyHtml,
-        deposit_flow::{DEPOSIT_CREATE_PAYOUT, PAYOUT_COOKIE},
+        deposit_flow::{is_deposit_allowed, DEPOSIT_CREATE_PAYOUT, PAYOUT_COOKIE},
     },
     AppContext,
 };
@@ -31,6 +31,10 @@ pub async fn deposit_select_account(
     session: Session,
     query_params: web::Query<QueryParams>,
 ) -> HttpResponse {
+    if !is_deposit_allowed(&session, query_params.payment_id) {
+        return HttpResponse::Unauthorized().finish();
+    }
+
     let token = app
         .tl_client
         .auth_data(&query_params.code)
@@ -53,7 +57,7 @@ pub async fn deposit_select_account(
         .await
         .into_iter()
         .map(|a: Result<AccountBalance, _>| a.unwrap())
-        .zip(accounts.into_iter())
+        .zip(accounts)
         .map(|(b, a)| Account {
             name: a.display_name,
             balance: b.current,
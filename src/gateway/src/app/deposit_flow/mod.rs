@@ -1,14 +1,18 @@
 mod create_payout;
 mod deposit_form;
+mod deposit_request;
 mod deposit_status;
 mod depsoit_select_account;
 mod tl_despoit_callback;
 
+use actix_session::Session;
 use actix_web::web;
 use create_payout::create_payout;
 use deposit_form::deposit_form;
+use deposit_request::deposit_request;
 use deposit_status::{deposit_status, deposit_status_update};
 use depsoit_select_account::deposit_select_account;
+use domain::PaymentId;
 use tl_despoit_callback::tl_deposit_callback;
 
 pub const DESPOSIT_CREATE_PAGE: &str = "/app/deposit";
@@ -16,15 +20,30 @@ pub const DESPOSIT_SELECT_ACCOUNT_PAGE: &str = "/app/deposit/select_account";
 pub const DESPOSIT_STATUS_PAGE: &str = "/app/deposit/status";
 pub const DESPOSIT_STATUS_UPDATE_PAGE: &str = "/app/deposit/status_update";
 pub const DEPOSIT_CREATE_PAYOUT: &str = "/app/deposit/create_payout";
+pub const DEPOSIT_REQUEST_PAGE: &str = "/app/deposit/request";
 #[allow(unused)]
 pub const DESPOSIT_TL_CALLBACK_PAGE: &str = "/app/deposit/tl_callback";
 
 pub const PAYOUT_COOKIE: &str = "payout_init";
+const DEPOSIT_ALLOWED: &str = "deposit_allowed";
+
+/// Remembers that this session proved it is the payee of the payment, by its
+/// security answer or by being signed in as the requester, so the rest of the
+/// deposit flow may pay it out.
+pub fn allow_deposit(session: &Session, payment_id: PaymentId) {
+    session.insert(DEPOSIT_ALLOWED, payment_id).unwrap();
+}
+
+/// Whether [`allow_deposit`] was called for the payment in this session.
+pub fn is_deposit_allowed(session: &Session, payment_id: PaymentId) -> bool {
+    session.get::<PaymentId>(DEPOSIT_ALLOWED).ok().flatten() == Some(payment_id)
+}
 
 pub fn deposit_scope() -> actix_web::Scope {
     web::scope("deposit")
         .service(web::resource("").to(deposit_form))
         .service(web::resource("create_payout").get(create_payout))
+        .service(web::resource("request").post(deposit_request))
         .service(web::resource("select_account").to(deposit_select_account))
         .service(web::resource("status").to(deposit_status))
         .service(web::resource("status_update").to(deposit_status_update))
@@ -2,7 +2,8 @@ use actix_web::{HttpRequest, HttpResponse};
 use leptos::view;
 
 use crate::app::{
-    component::MyHtml, payment_flow::PAYMENT_FORM_PAGE, registration_flow::REGISTER_PAGE,
+    component::MyHtml, login_flow::LOGIN_PAGE, payment_flow::PAYMENT_FORM_PAGE,
+    registration_flow::REGISTER_PAGE,
 };
 
 pub async fn home(_req: HttpRequest) -> HttpResponse {
@@ -12,7 +13,7 @@ pub async fn home(_req: HttpRequest) -> HttpResponse {
                 <div class="container text-light text-center pt-4" >
                     <h1 class="text-center" >"Welcome to e-transfer"</h1>
                     <a class="btn btn-success" href={PAYMENT_FORM_PAGE} >Move Money</a>
-                    <a class="btn btn-success ms-1" href="/login" >Sign In</a>
+                    <a class="btn btn-success ms-1" href={LOGIN_PAGE} >Sign In</a>
                     <a class="btn btn-success ms-1" href={REGISTER_PAGE} >Register</a>
                 </div>
             </MyHtml>
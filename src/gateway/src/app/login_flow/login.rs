@@ -0,0 +1,74 @@
+use actix_session::Session;
+use actix_web::{http::header, web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use domain::{User, UserId};
+use rand::distributions::{Alphanumeric, DistString};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::{api::PublicError, app::login_flow::LOGIN_CODE_PAGE, AppContext};
+
+pub const LOGIN_CODE_SESSION: &str = "login_code";
+
+/// A login code emailed to the user, kept in the (encrypted) session cookie
+/// until it is confirmed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginCode {
+    pub user_id: UserId,
+    pub code: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FormData {
+    email: String,
+}
+
+pub async fn login(
+    app: web::Data<AppContext>,
+    session: Session,
+    form: web::Form<FormData>,
+) -> Result<impl Responder, PublicError> {
+    execute(app, session, form.into_inner()).await
+}
+
+#[instrument(skip(app, session))]
+async fn execute(
+    app: web::Data<AppContext>,
+    session: Session,
+    request: FormData,
+) -> Result<impl Responder, PublicError> {
+    let user_id = match app.db_client.get_user_by_email(&request.email).await? {
+        Some((User::Registered { user_id, .. }, _)) => user_id,
+        _ => {
+            return Err(PublicError::Invalid(String::from(
+                "No registered user with that email address.",
+            )))
+        }
+    };
+
+    let code = Alphanumeric.sample_string(&mut rand::thread_rng(), 8);
+    app.notifier
+        .send(
+            &request.email,
+            "Your e-transfer sign in code",
+            format!("Your sign in code is {}", code),
+        )
+        .await
+        .map_err(|_| PublicError::InternalServerError)?;
+
+    session
+        .insert(
+            LOGIN_CODE_SESSION,
+            LoginCode {
+                user_id,
+                code,
+                timestamp: Utc::now(),
+            },
+        )
+        .map_err(|_| PublicError::InternalServerError)?;
+
+    Ok(HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, LOGIN_CODE_PAGE))
+        .finish())
+}
@@ -0,0 +1,70 @@
+use actix_session::Session;
+use actix_web::{http::header, HttpResponse};
+use chrono::{Duration, Utc};
+use leptos::view;
+use serde::Deserialize;
+
+use crate::{
+    api::PublicError,
+    app::{
+        component::{MyHtml, MyInput},
+        login_flow::{
+            login::{LoginCode, LOGIN_CODE_SESSION},
+            sign_in, EMAIL_CODE_TTL_MINUTES, LOGIN_CODE_PAGE,
+        },
+        MY_TRANSFERS_PAGE,
+    },
+};
+
+pub async fn login_code_form() -> HttpResponse {
+    let html = leptos::ssr::render_to_string(|| {
+        view! {
+            <MyHtml>
+                <div class="container-sm form-signin w-100 m-auto text-center">
+                    <form action={LOGIN_CODE_PAGE} method="post">
+                        <h1 class="text-light mb-3 fw-normal">Confirm Code</h1>
+                        <MyInput input_type="text" name="code" label="Email Code" required=true/>
+                        <div class="input-group mb-3">
+                            <input
+                                type="submit"
+                                class="form-control btn btn-success"
+                                value="SIGN IN"
+                            />
+                        </div>
+                    </form>
+                </div>
+            </MyHtml>
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html.to_string())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FormData {
+    code: String,
+}
+
+pub async fn login_code_confirm(
+    session: Session,
+    form: actix_web::web::Form<FormData>,
+) -> Result<HttpResponse, PublicError> {
+    let login_code = session
+        .remove_as::<LoginCode>(LOGIN_CODE_SESSION)
+        .and_then(Result::ok)
+        .filter(|login_code| {
+            login_code.code == form.code
+                && Utc::now() - login_code.timestamp < Duration::minutes(EMAIL_CODE_TTL_MINUTES)
+        })
+        .ok_or(PublicError::Invalid(String::from(
+            "Invalid or expired code.",
+        )))?;
+
+    sign_in(&session, login_code.user_id);
+
+    Ok(HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, MY_TRANSFERS_PAGE))
+        .finish())
+}
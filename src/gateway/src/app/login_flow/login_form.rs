@@ -0,0 +1,33 @@
+use actix_web::HttpResponse;
+use leptos::view;
+
+use crate::app::{
+    component::{MyHtml, MyInput},
+    login_flow::LOGIN_PAGE,
+};
+
+pub async fn login_form() -> HttpResponse {
+    let html = leptos::ssr::render_to_string(|| {
+        view! {
+            <MyHtml>
+                <div class="container-sm form-signin w-100 m-auto text-center">
+                    <form action={LOGIN_PAGE} method="post">
+                        <h1 class="text-light mb-3 fw-normal">Sign In</h1>
+                        <MyInput input_type="email" name="email" label="Email Address" required=true/>
+                        <div class="input-group mb-3">
+                            <input
+                                type="submit"
+                                class="form-control btn btn-success"
+                                value="SEND CODE"
+                            />
+                        </div>
+                    </form>
+                </div>
+            </MyHtml>
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html.to_string())
+}
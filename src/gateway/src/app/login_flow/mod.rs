@@ -0,0 +1,46 @@
+mod login;
+mod login_code;
+mod login_form;
+
+use actix_session::Session;
+use actix_web::{http::header, web, HttpResponse};
+use domain::UserId;
+use login::login;
+use login_code::{login_code_confirm, login_code_form};
+use login_form::login_form;
+
+pub const LOGIN_PAGE: &str = "/app/login";
+pub const LOGIN_CODE_PAGE: &str = "/app/login/code";
+pub const LOGOUT_PAGE: &str = "/app/logout";
+
+/// How long an emailed registration or login code stays valid.
+pub const EMAIL_CODE_TTL_MINUTES: i64 = 15;
+
+const USER_SESSION: &str = "user_id";
+
+pub fn login_scope() -> actix_web::Scope {
+    web::scope("login")
+        .service(web::resource("").get(login_form).post(login))
+        .service(
+            web::resource("code")
+                .get(login_code_form)
+                .post(login_code_confirm),
+        )
+}
+
+/// The registered user signed in to this session, if any.
+pub fn session_user(session: &Session) -> Option<UserId> {
+    session.get(USER_SESSION).ok().flatten()
+}
+
+pub fn sign_in(session: &Session, user_id: UserId) {
+    session.renew();
+    session.insert(USER_SESSION, user_id).unwrap();
+}
+
+pub async fn logout(session: Session) -> HttpResponse {
+    session.purge();
+    HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, "/app"))
+        .finish()
+}
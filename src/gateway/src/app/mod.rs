@@ -2,11 +2,16 @@ mod component;
 mod home;
 mod not_found;
 mod tl_data_callback;
+mod transfers;
 
+pub mod account_flow;
 pub mod admin;
 pub mod deposit_flow;
+pub mod login_flow;
 pub mod payment_flow;
 pub mod registration_flow;
+pub mod request_flow;
+pub mod schedule_flow;
 
 use actix_session::{config::PersistentSession, storage::CookieSessionStore, SessionMiddleware};
 use actix_web::{
@@ -17,18 +22,30 @@ use actix_web::{
 pub use home::home;
 pub use not_found::not_found;
 pub use tl_data_callback::tl_data_callback;
+use transfers::my_transfers;
 
 pub const APP_ROOT: &str = "/app";
+pub const MY_TRANSFERS_PAGE: &str = "/app/transfers";
+
+/// The cookie session of the app, shared by the `/app` pages and the `/api`
+/// endpoints they post to.
+pub fn session_middleware(secret_key: Key) -> SessionMiddleware<CookieSessionStore> {
+    SessionMiddleware::builder(CookieSessionStore::default(), secret_key)
+        .session_lifecycle(PersistentSession::default().session_ttl(Duration::minutes(15)))
+        .build()
+}
 
 pub fn app_scope(secret_key: Key) -> impl HttpServiceFactory + 'static {
     Scope::new("app")
-        .wrap(
-            SessionMiddleware::builder(CookieSessionStore::default(), secret_key.clone())
-                .session_lifecycle(PersistentSession::default().session_ttl(Duration::minutes(15)))
-                .build(),
-        )
+        .wrap(session_middleware(secret_key))
         .service(web::resource("").get(home))
         .service(payment_flow::payment_scope())
         .service(deposit_flow::deposit_scope())
         .service(registration_flow::register_scope())
+        .service(login_flow::login_scope())
+        .service(request_flow::request_scope())
+        .service(schedule_flow::schedule_scope())
+        .service(account_flow::account_scope())
+        .service(web::resource("logout").get(login_flow::logout))
+        .service(web::resource("transfers").get(my_transfers))
 }
@@ -0,0 +1,323 @@
+use actix_web::{http::header, web, HttpResponse};
+use argon2::{
+    password_hash::{rand_core::OsRng, SaltString},
+    Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
+};
+use chrono::{Duration, Utc};
+use domain::{
+    CancellationRequest, EventSource, OutboxEffect, OutboxMessage, Payment, PaymentEvent,
+    PaymentEventRecord, PaymentState,
+};
+use leptos::view;
+use rand::distributions::{Alphanumeric, DistString};
+use serde::Deserialize;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{
+    api::PublicError,
+    app::{
+        component::MyHtml,
+        payment_flow::{PAYMENT_CANCEL_PAGE, PAYMENT_STATUS_PAGE},
+    },
+    refund::refund_message,
+    AppContext,
+};
+
+/// How long an emailed cancellation link stays valid.
+const CANCEL_LINK_TTL_HOURS: i64 = 24;
+
+#[derive(Debug, Deserialize)]
+pub struct CancelRequestForm {
+    payment_id: Uuid,
+}
+
+/// Emails the payer a one-time link to cancel the payment. Nothing is sent
+/// while an earlier link is still valid, so the form cannot flood the payer
+/// with emails or replace the token they were sent.
+#[instrument(skip(app))]
+pub async fn cancel_request(
+    app: web::Data<AppContext>,
+    form: web::Form<CancelRequestForm>,
+) -> Result<HttpResponse, PublicError> {
+    let (mut payment, version) = app
+        .db_client
+        .get_payment::<Payment>(form.payment_id)
+        .await?
+        .ok_or(PublicError::Invalid(String::from("Unknown payment.")))?;
+
+    if !payment.state().can_transition_to(PaymentState::Cancelled) {
+        return Ok(cancel_unavailable());
+    }
+    if payment
+        .cancellation_request
+        .as_ref()
+        .is_some_and(|request| !is_expired(request))
+    {
+        return Ok(cancel_link_sent());
+    }
+
+    let token = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+    let salt = SaltString::generate(&mut OsRng);
+    let token_hash = Argon2::default()
+        .hash_password(token.as_bytes(), &salt)
+        .map_err(|_| PublicError::InternalServerError)?
+        .to_string();
+
+    payment.cancellation_request = Some(CancellationRequest {
+        token_hash,
+        requested_at: Utc::now(),
+    });
+    let state = payment.state();
+    let event = PaymentEventRecord::new(
+        payment.payment_id,
+        "cancellation_requested",
+        EventSource::Ui,
+        Some(state),
+        state,
+    );
+    let link = app.public_link(&format!(
+        "{}?payment_id={}&token={}",
+        PAYMENT_CANCEL_PAGE, payment.payment_id, token
+    ));
+    let email = OutboxMessage::new(
+        Some(payment.payment_id),
+        OutboxEffect::email(
+            &payment.payer_email,
+            "Cancel your e-transfer",
+            format!(
+                "Use the link below within {} hours to cancel your transfer of {} to {}. \
+                The funds will be refunded to your account.\n\n{}",
+                CANCEL_LINK_TTL_HOURS, payment.amount, payment.payee_full_name, link
+            ),
+        ),
+    );
+    app.db_client
+        .upsert_payment_with_outbox(payment, version + 1, event, [email])
+        .await?;
+
+    Ok(cancel_link_sent())
+}
+
+fn cancel_link_sent() -> HttpResponse {
+    let html = leptos::ssr::render_to_string(|| {
+        view! {
+            <MyHtml>
+                <div class="container-sm form-signin w-100 m-auto text-center" >
+                    <h1 class="text-light mb-3 fw-normal">"Check Your Email"</h1>
+                    <p>"We sent a cancellation link to the payer's email address."</p>
+                </div>
+            </MyHtml>
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html.to_string())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CancelParams {
+    payment_id: Uuid,
+    token: String,
+}
+
+/// The page behind the emailed link. Cancelling needs an explicit POST so
+/// link previews cannot cancel a payment.
+pub async fn cancel_form(
+    app: web::Data<AppContext>,
+    query_params: web::Query<CancelParams>,
+) -> Result<HttpResponse, PublicError> {
+    let (payment, _) = app
+        .db_client
+        .get_payment::<Payment>(query_params.payment_id)
+        .await?
+        .ok_or(PublicError::Invalid(String::from("Unknown payment.")))?;
+
+    if !is_valid_cancel_token(&payment, &query_params.token) {
+        return Ok(cancel_unavailable());
+    }
+
+    let summary = format!("{} to {}", payment.amount, payment.payee_full_name);
+    let payment_id = query_params.payment_id.to_string();
+    let token = query_params.into_inner().token;
+    let html = leptos::ssr::render_to_string(|| {
+        view! {
+            <MyHtml>
+                <div class="container-sm form-signin w-100 m-auto text-center" >
+                    <form action={PAYMENT_CANCEL_PAGE} method="post">
+                        <h1 class="text-light mb-3 fw-normal">"Cancel Transfer"</h1>
+                        <p>{summary}</p>
+                        <input type="hidden" name="payment_id" value={payment_id}/>
+                        <input type="hidden" name="token" value={token}/>
+                        <div class="input-group mb-3" >
+                            <input
+                                type="submit"
+                                class="form-control btn btn-danger"
+                                value="CANCEL TRANSFER"
+                            />
+                        </div>
+                    </form>
+                </div>
+            </MyHtml>
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html.to_string()))
+}
+
+#[instrument(skip(app, form), fields(payment_id = %form.payment_id))]
+pub async fn cancel_payment(
+    app: web::Data<AppContext>,
+    form: web::Form<CancelParams>,
+) -> Result<HttpResponse, PublicError> {
+    let (mut payment, version) = app
+        .db_client
+        .get_payment::<Payment>(form.payment_id)
+        .await?
+        .ok_or(PublicError::Invalid(String::from("Unknown payment.")))?;
+
+    if !is_valid_cancel_token(&payment, &form.token) {
+        return Ok(cancel_unavailable());
+    }
+
+    let payment_id = payment.payment_id;
+    let event = payment.transition(
+        PaymentEvent::Cancelled {
+            cancelled_at: Utc::now(),
+        },
+        EventSource::Ui,
+    )?;
+    app.db_client
+        .upsert_payment_with_outbox(
+            payment,
+            version + 1,
+            event,
+            vec![refund_message(payment_id)],
+        )
+        .await?;
+
+    Ok(HttpResponse::SeeOther()
+        .insert_header((
+            header::LOCATION,
+            format!("{}?payment_id={}", PAYMENT_STATUS_PAGE, form.payment_id),
+        ))
+        .finish())
+}
+
+fn is_valid_cancel_token(payment: &Payment, token: &str) -> bool {
+    let Some(request) = payment.cancellation_request.as_ref() else {
+        return false;
+    };
+    let Ok(token_hash) = PasswordHash::new(&request.token_hash) else {
+        return false;
+    };
+
+    payment.state().can_transition_to(PaymentState::Cancelled)
+        && !is_expired(request)
+        && Argon2::default()
+            .verify_password(token.as_bytes(), &token_hash)
+            .is_ok()
+}
+
+fn is_expired(request: &CancellationRequest) -> bool {
+    Utc::now() - request.requested_at >= Duration::hours(CANCEL_LINK_TTL_HOURS)
+}
+
+fn cancel_unavailable() -> HttpResponse {
+    let html = leptos::ssr::render_to_string(|| {
+        view! {
+            <MyHtml>
+                <div class="container-sm form-signin w-100 m-auto text-center" >
+                    <h1 class="text-light mb-3 fw-normal">"Cancellation Unavailable"</h1>
+                    <p>"This transfer can no longer be cancelled, or the link has expired."</p>
+                </div>
+            </MyHtml>
+        }
+    });
+
+    HttpResponse::Gone()
+        .content_type("text/html; charset=utf-8")
+        .body(html.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use domain::{OutboxMessage, OutboxStatus, PaymentId};
+
+    use super::*;
+
+    async fn request_cancel(app: &web::Data<AppContext>, payment_id: PaymentId) {
+        let form = CancelRequestForm {
+            payment_id: *payment_id.as_uuid(),
+        };
+        let res = cancel_request(app.clone(), web::Form(form)).await.unwrap();
+        assert!(res.status().is_success());
+    }
+
+    async fn pending_emails(app: &AppContext) -> usize {
+        app.db_client
+            .get_outbox_messages::<OutboxMessage>(OutboxStatus::Pending.as_str(), 10, 0)
+            .await
+            .unwrap()
+            .len()
+    }
+
+    #[actix_web::test]
+    async fn a_pending_cancel_link_is_not_sent_again() {
+        let app = web::Data::new(AppContext::for_tests());
+        let mut payment = Payment::test_fixture();
+        let payment_id = payment.payment_id;
+        let event = payment
+            .transition(
+                PaymentEvent::InboundSettled {
+                    settled_at: Utc::now(),
+                },
+                EventSource::Webhook,
+            )
+            .unwrap();
+        app.db_client
+            .upsert_payment(payment, 1, event)
+            .await
+            .unwrap();
+
+        request_cancel(&app, payment_id).await;
+        let (sent, version) = app
+            .db_client
+            .get_payment::<Payment>(payment_id)
+            .await
+            .unwrap()
+            .unwrap();
+        request_cancel(&app, payment_id).await;
+
+        let (payment, _) = app
+            .db_client
+            .get_payment::<Payment>(payment_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(pending_emails(&app).await, 1);
+        assert_eq!(
+            payment.cancellation_request.as_ref().unwrap().token_hash,
+            sent.cancellation_request.unwrap().token_hash
+        );
+
+        // an expired link is replaced
+        let mut expired = payment;
+        expired.cancellation_request = Some(CancellationRequest {
+            token_hash: String::new(),
+            requested_at: Utc::now() - Duration::hours(CANCEL_LINK_TTL_HOURS),
+        });
+        let state = expired.state();
+        let event =
+            PaymentEventRecord::new(payment_id, "test", EventSource::Ui, Some(state), state);
+        app.db_client
+            .upsert_payment(expired, version + 1, event)
+            .await
+            .unwrap();
+        request_cancel(&app, payment_id).await;
+        assert_eq!(pending_emails(&app).await, 2);
+    }
+}
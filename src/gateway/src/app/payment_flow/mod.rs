@@ -1,9 +1,11 @@
+mod cancel_payment;
 mod z01_payment_form;
 mod z02_create_payment;
 mod z03_tl_payment_callback;
 mod z04_payment_status;
 
 use actix_web::web;
+use cancel_payment::{cancel_form, cancel_payment, cancel_request};
 use concat_const::concat;
 use z01_payment_form::{payment_form, validation::validation_scope};
 use z02_create_payment::create_payment;
@@ -18,6 +20,8 @@ pub const PAYMENT_FORM_PAGE: &str = concat!(APP_ROOT, PAYMENT_ROOT);
 pub const PAYMENT_CREATE_PAGE: &str = concat!(APP_ROOT, PAYMENT_ROOT, "/create_payout");
 pub const PAYMENT_STATUS_PAGE: &str = concat!(APP_ROOT, PAYMENT_ROOT, "/status");
 pub const PAYMENT_STATUS_UPDATE_PAGE: &str = concat!(APP_ROOT, PAYMENT_ROOT, "/status_update");
+pub const PAYMENT_CANCEL_REQUEST_PAGE: &str = concat!(APP_ROOT, PAYMENT_ROOT, "/cancel_request");
+pub const PAYMENT_CANCEL_PAGE: &str = concat!(APP_ROOT, PAYMENT_ROOT, "/cancel");
 #[allow(unused)]
 pub const PAYMENT_TL_CALLBACK_PAGE: &str = concat!(APP_ROOT, PAYMENT_ROOT, "/tl_callback");
 
@@ -28,5 +32,11 @@ pub fn payment_scope() -> actix_web::Scope {
         .service(web::resource("status").get(payment_status))
         .service(web::resource("status_update").get(payment_status_update))
         .service(web::resource("tl_callback").to(tl_payment_callback))
+        .service(web::resource("cancel_request").post(cancel_request))
+        .service(
+            web::resource("cancel")
+                .get(cancel_form)
+                .post(cancel_payment),
+        )
         .service(validation_scope())
 }
@@ -1,26 +1,108 @@
-use actix_web::{HttpRequest, HttpResponse};
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use domain::{PaymentRequest, User};
 use leptos::{component, view, IntoView};
+use serde::Deserialize;
+use uuid::Uuid;
 use validation::{VLIDATE_AMOUNT, VLIDATE_PAYEE_EMAIL, VLIDATE_PAYER_EMAIL};
 
-use crate::app::{
-    component::{MyHtml, MyInput},
-    payment_flow::PAYMENT_CREATE_PAGE,
+use crate::{
+    api::PublicError,
+    app::{
+        component::{CurrencySelect, MessageInput, MyHtml, MyInput},
+        payment_flow::PAYMENT_CREATE_PAGE,
+    },
+    AppContext,
 };
 
-pub async fn payment_form(_req: HttpRequest) -> HttpResponse {
-    let html = leptos::ssr::render_to_string(|| {
+#[derive(Debug, Deserialize)]
+pub struct QueryParams {
+    request_id: Option<Uuid>,
+}
+
+pub async fn payment_form(
+    app: web::Data<AppContext>,
+    query_params: web::Query<QueryParams>,
+) -> Result<HttpResponse, PublicError> {
+    if let Some(request_id) = query_params.request_id {
+        return request_payment_form(app, request_id).await;
+    }
+
+    let min_amount = app.limits.min_amount();
+    let html = leptos::ssr::render_to_string(move || {
         view! {
             <MyHtml>
                 <div class="container-sm form-signin w-100 m-auto text-center" >
-                    <form action={PAYMENT_CREATE_PAGE} method="post" >
+                    <form action={PAYMENT_CREATE_PAGE} method="post" hx-post={PAYMENT_CREATE_PAGE} hx-target="#form-errors" >
                         <h1 class="text-light mb-3 fw-normal">Create Payment</h1>
                         <MyInput input_type="text" name="payer_full_name" label="Benefactor Name" required=true/>
                         <EmailInput name="payer_email" label="Benefactor Email" email={None} check=false endpoint={VLIDATE_PAYER_EMAIL}/>
                         <MyInput input_type="text" name="payee_full_name" label="Recipiant Name" required=true/>
                         <EmailInput name="payee_email" label="Recipiant Email" email={None} check=false endpoint={VLIDATE_PAYEE_EMAIL}/>
-                        <AmountInput name="amount" label="Amount" amount={None} check=false endpoint={VLIDATE_AMOUNT}/>
+                        <AmountInput name="amount" label="Amount" amount={None} min_amount={min_amount} check=false endpoint={VLIDATE_AMOUNT}/>
+                        <CurrencySelect name="currency" label="Currency"/>
                         <MyInput input_type="text" name="security_question" label="Security Question" required=true/>
                         <MyInput input_type="text" name="security_answer" label="Security Answer" required=true/>
+                        <MessageInput/>
+                        <div id="form-errors"></div>
+                        <div class="input-group mb-3" >
+                            <input
+                                type="submit"
+                                class="form-control btn btn-success"
+                                value="CONTINUE"
+                            />
+                        </div>
+                    </form>
+                </div>
+            </MyHtml>
+        }
+    });
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html.to_string()))
+}
+
+/// The payment form pre-filled from a [`PaymentRequest`]. No security
+/// question is asked as the requesting payee deposits while signed in.
+async fn request_payment_form(
+    app: web::Data<AppContext>,
+    request_id: Uuid,
+) -> Result<HttpResponse, PublicError> {
+    let Some((request, _)) = app
+        .db_client
+        .get_payment_request::<PaymentRequest>(request_id)
+        .await?
+        .filter(|(request, _)| request.is_payable(Utc::now()))
+    else {
+        return Ok(request_unavailable());
+    };
+
+    let (payee, _) = app
+        .db_client
+        .get_user::<User>(request.payee_user_id.into_uuid())
+        .await?
+        .ok_or(PublicError::InternalServerError)?;
+
+    let payee_name = format!("{} {}", payee.first_name(), payee.last_name());
+    let amount = request.amount.to_string();
+    let note = request.note;
+    let payer_email = request.payer_email;
+    let request_id = request_id.to_string();
+
+    let html = leptos::ssr::render_to_string(move || {
+        view! {
+            <MyHtml>
+                <div class="container-sm form-signin w-100 m-auto text-center" >
+                    <form action={PAYMENT_CREATE_PAGE} method="post" hx-post={PAYMENT_CREATE_PAGE} hx-target="#form-errors" >
+                        <h1 class="text-light mb-3 fw-normal">Pay Request</h1>
+                        <ReadonlyField name="payee" label="Requested By" value={payee_name}/>
+                        <ReadonlyField name="request_amount" label="Amount" value={amount}/>
+                        <ReadonlyField name="note" label="Note" value={note}/>
+                        <MyInput input_type="text" name="payer_full_name" label="Benefactor Name" required=true/>
+                        <EmailInput name="payer_email" label="Benefactor Email" email={Some(payer_email)} check=false endpoint={VLIDATE_PAYER_EMAIL}/>
+                        <MessageInput/>
+                        <input type="hidden" name="request_id" value={request_id}/>
+                        <div id="form-errors"></div>
                         <div class="input-group mb-3" >
                             <input
                                 type="submit"
@@ -33,20 +115,56 @@ pub async fn payment_form(_req: HttpRequest) -> HttpResponse {
             </MyHtml>
         }
     });
-    HttpResponse::Ok()
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html.to_string()))
+}
+
+fn request_unavailable() -> HttpResponse {
+    let html = leptos::ssr::render_to_string(|| {
+        view! {
+            <MyHtml>
+                <div class="container-sm form-signin w-100 m-auto text-center" >
+                    <h1 class="text-light mb-3 fw-normal">"Request Unavailable"</h1>
+                    <p>"This money request was already paid, declined or has expired."</p>
+                </div>
+            </MyHtml>
+        }
+    });
+
+    HttpResponse::Gone()
         .content_type("text/html; charset=utf-8")
         .body(html.to_string())
 }
 
+#[component]
+fn readonly_field(name: &'static str, label: &'static str, value: String) -> impl IntoView {
+    view! {
+        <div class="form-floating mb-3" >
+            <input
+                type="text"
+                readonly
+                class="form-control"
+                id={name}
+                value={value}
+            />
+            <label for={name}>{label}</label>
+        </div>
+    }
+}
+
 pub mod validation {
     use actix_web::{web, HttpResponse};
     use concat_const::concat;
     use leptos::view;
     use serde::Deserialize;
 
-    use crate::app::payment_flow::{
-        z01_payment_form::{AmountInput, EmailInput},
-        PAYMENT_FORM_PAGE,
+    use crate::{
+        app::payment_flow::{
+            z01_payment_form::{AmountInput, EmailInput},
+            PAYMENT_FORM_PAGE,
+        },
+        AppContext,
     };
 
     pub const VLIDATE_PAYER_EMAIL: &str = concat!(PAYMENT_FORM_PAGE, "/validate/payer_email");
@@ -101,11 +219,12 @@ pub mod validation {
         amount: u32,
     }
 
-    async fn amount(form: web::Form<AmountFormData>) -> HttpResponse {
+    async fn amount(app: web::Data<AppContext>, form: web::Form<AmountFormData>) -> HttpResponse {
         let amount = form.0.amount;
+        let min_amount = app.limits.min_amount();
         let html = leptos::ssr::render_to_string(move || {
             view! {
-                <AmountInput name="amount" label="Amount" amount={Some(amount)} check=true endpoint={VLIDATE_AMOUNT}/>
+                <AmountInput name="amount" label="Amount" amount={Some(amount)} min_amount={min_amount} check=true endpoint={VLIDATE_AMOUNT}/>
             }
         });
 
@@ -120,13 +239,17 @@ fn amount_input(
     name: &'static str,
     label: &'static str,
     amount: Option<u32>,
+    min_amount: u32,
     check: bool,
     endpoint: &'static str,
 ) -> impl IntoView {
-    let (class, err_msg) = match (check, amount.unwrap_or_default() >= 100) {
-        (true, false) => ("form-control is-invalid", "A minimum of 100 is required..."),
-        (false, false) => ("form-control", ""),
-        (_, _) => ("form-control is-valid", ""),
+    let (class, err_msg) = match (check, amount.unwrap_or_default() >= min_amount) {
+        (true, false) => (
+            "form-control is-invalid",
+            format!("A minimum of {} is required...", min_amount),
+        ),
+        (false, false) => ("form-control", String::new()),
+        (_, _) => ("form-control is-valid", String::new()),
     };
 
     view! {
@@ -1,87 +1,224 @@
-use actix_web::{http::header, web, HttpResponse};
-use argon2::{
-    password_hash::{rand_core::OsRng, SaltString},
-    Argon2, PasswordHasher,
-};
+use actix_session::Session;
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
 use chrono::Utc;
-use domain::{Payment, PaymentId, PaymentState, PaymentStatuses};
+use db::{error::DbError, normalize_email};
+use domain::{Currency, EventSource, Money, Payment, PaymentRequest, User};
+use leptos::view;
 use serde::Deserialize;
+use tracing::{error, warn};
+use uuid::Uuid;
 
-use crate::{api::PublicError, log, AppContext};
+use crate::{
+    api::PublicError,
+    app::login_flow::session_user,
+    limits::{LimitError, PayerTier},
+    payment::{create_inbound_payment, hash_security_answer, NewPayment},
+    AppContext,
+};
 
+/// Answers to the payment form. Payee, amount and security fields are absent
+/// when the payment answers a money request, as they come from the request.
 #[derive(Debug, Deserialize)]
 pub struct FormData {
     payer_full_name: String,
     payer_email: String,
+    #[serde(default)]
     payee_full_name: String,
+    #[serde(default)]
     payee_email: String,
+    #[serde(default)]
     amount: u32,
+    currency: Option<Currency>,
+    #[serde(default)]
     security_question: String,
+    #[serde(default)]
     security_answer: String,
+    #[serde(default)]
+    message: String,
+    request_id: Option<Uuid>,
 }
 
 pub async fn create_payment(
     app: web::Data<AppContext>,
+    session: Session,
+    request: HttpRequest,
     form: web::Form<FormData>,
 ) -> Result<HttpResponse, PublicError> {
-    let form = form.0;
+    let is_htmx = request.headers().contains_key("HX-Request");
+    match execute(&app, &session, form.0).await {
+        Ok(auth_link) if is_htmx => Ok(HttpResponse::Ok()
+            .insert_header(("HX-Redirect", auth_link))
+            .finish()),
+        Ok(auth_link) => Ok(HttpResponse::SeeOther()
+            .insert_header((header::LOCATION, auth_link))
+            .finish()),
+        // htmx only swaps in successful responses
+        Err(err) if is_htmx => Ok(form_error(err.to_string())),
+        Err(err) => Err(err),
+    }
+}
 
-    let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-    let security_answer = argon2
-        .hash_password(form.security_answer.as_bytes(), &salt)
-        .map_err(|_| PublicError::InternalServerError)?
-        .to_string();
+/// Creates the payment and returns the link where the payer authorizes it.
+async fn execute(
+    app: &AppContext,
+    session: &Session,
+    mut form: FormData,
+) -> Result<String, PublicError> {
+    let payment_request = match form.request_id {
+        Some(request_id) => {
+            let (request, version) = app
+                .db_client
+                .get_payment_request::<PaymentRequest>(request_id)
+                .await?
+                .filter(|(request, _)| request.is_payable(Utc::now()))
+                .ok_or(PublicError::Invalid(String::from(
+                    "This money request can no longer be paid.",
+                )))?;
+            let (payee, _) = app
+                .db_client
+                .get_user::<User>(request.payee_user_id.into_uuid())
+                .await?
+                .ok_or(PublicError::InternalServerError)?;
 
-    let payment = app
-        .tl_client
-        .create_ma_payment(
-            &form.payer_full_name,
+            form.payee_full_name = format!("{} {}", payee.first_name(), payee.last_name());
+            form.payee_email = payee.email().to_owned();
+            form.amount = request.amount.amount_in_minor;
+            form.currency = Some(request.amount.currency);
+            Some((request, version))
+        }
+        None => None,
+    };
+    let currency = form
+        .currency
+        .ok_or(PublicError::Invalid(String::from("Missing currency.")))?;
+    let message = form.message.trim();
+    if message.chars().count() > Payment::MESSAGE_MAX_LEN {
+        return Err(PublicError::Invalid(format!(
+            "Messages are limited to {} characters.",
+            Payment::MESSAGE_MAX_LEN
+        )));
+    }
+    let message = (!message.is_empty()).then(|| message.to_owned());
+    let amount = Money::new(form.amount, currency);
+
+    let tier = payer_tier(app, session, &form.payer_email).await?;
+    app.limits
+        .check(
+            &app.db_client,
+            tier,
             &form.payer_email,
-            None,
-            form.amount,
-            "test",
+            &form.payee_email,
+            amount,
         )
         .await
-        .map_err(|_| PublicError::InternalServerError)?;
+        .map_err(|err| match err {
+            LimitError::Db(err) => PublicError::from(err),
+            err => PublicError::Invalid(err.to_string()),
+        })?;
 
-    let payment_id = PaymentId::from_uuid(payment.payment_id);
-    log::set_payment_id(payment_id);
-    log::set_payment_state(PaymentState::InboundCreated);
+    // the request is claimed before its payment is created, so a second
+    // attempt cannot create another payment for it
+    let payment_request = match payment_request {
+        Some((mut request, version)) => {
+            request.claimed_at = Some(Utc::now());
+            match app
+                .db_client
+                .upsert_payment_request(request.clone(), version + 1)
+                .await
+            {
+                Ok(()) => Some((request, version + 1)),
+                Err(DbError::ConcurrentUpdate) => {
+                    return Err(PublicError::Invalid(String::from(
+                        "This money request can no longer be paid.",
+                    )))
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        None => None,
+    };
 
-    app.db_client
-        .upsert_payment(
-            Payment {
-                payment_id,
-                payer_full_name: form.payer_full_name,
-                payer_email: form.payer_email,
-                payee_full_name: form.payee_full_name,
-                payee_email: form.payee_email,
-                amount: form.amount,
-                security_question: form.security_question,
-                security_answer,
-                payment_statuses: PaymentStatuses {
-                    inbound_created_at: Utc::now(),
-                    inbound_authorized_at: None,
-                    inbound_executed_at: None,
-                    inbound_settled_at: None,
-                    inbound_failed_at: None,
-                },
-                payout_data: None,
-                refund_data: None,
-            },
-            0,
-        )
-        .await?;
+    // request payments are deposited by the signed in payee instead
+    let security_answer = match payment_request {
+        Some(_) => String::new(),
+        None => hash_security_answer(&form.security_answer)
+            .map_err(|_| PublicError::InternalServerError)?,
+    };
 
-    let auth_link = format!(
-        "https://payment.truelayer-sandbox.com/payments#payment_id={}&resource_token={}&return_uri={}", 
-        payment.payment_id,
-        payment.resource_token,
-        app.tl_client.return_uri()
-    );
+    let created = create_inbound_payment(
+        app,
+        NewPayment {
+            payer_full_name: form.payer_full_name,
+            payer_email: form.payer_email,
+            payee_full_name: form.payee_full_name,
+            payee_email: form.payee_email,
+            amount,
+            security_question: form.security_question,
+            security_answer,
+            message,
+            payment_request_id: payment_request
+                .as_ref()
+                .map(|(request, _)| request.request_id),
+        },
+        EventSource::Ui,
+    )
+    .await;
+    let created = match (created, payment_request) {
+        (Ok(created), Some((mut request, version))) => {
+            request.payment_id = Some(created.payment_id);
+            request.claimed_at = None;
+            app.db_client
+                .upsert_payment_request(request, version + 1)
+                .await?;
+            created
+        }
+        (Ok(created), None) => created,
+        (Err(err), payment_request) => {
+            error!(?err, "failed to create payment");
+            if let Some((mut request, version)) = payment_request {
+                // left in place the claim lapses on its own
+                request.claimed_at = None;
+                if let Err(err) = app
+                    .db_client
+                    .upsert_payment_request(request, version + 1)
+                    .await
+                {
+                    warn!(?err, "failed to release payment request claim");
+                }
+            }
+            return Err(PublicError::InternalServerError);
+        }
+    };
+
+    Ok(created.auth_link)
+}
+
+/// Payers signed in to the account their email belongs to get the
+/// registered limits.
+async fn payer_tier(
+    app: &AppContext,
+    session: &Session,
+    payer_email: &str,
+) -> Result<PayerTier, PublicError> {
+    let Some(user_id) = session_user(session) else {
+        return Ok(PayerTier::Anonymous);
+    };
+    let tier = match app.db_client.get_user::<User>(user_id.into_uuid()).await? {
+        Some((user, _)) if normalize_email(user.email()) == normalize_email(payer_email) => {
+            PayerTier::Registered
+        }
+        _ => PayerTier::Anonymous,
+    };
+    Ok(tier)
+}
 
-    Ok(HttpResponse::SeeOther()
-        .insert_header((header::LOCATION, auth_link))
-        .finish())
+fn form_error(message: String) -> HttpResponse {
+    let html = leptos::ssr::render_to_string(move || {
+        view! {
+            <div class="alert alert-danger" role="alert">{message}</div>
+        }
+    });
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html.to_string())
 }
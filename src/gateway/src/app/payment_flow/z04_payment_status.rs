@@ -6,8 +6,9 @@ use uuid::Uuid;
 
 use crate::{
     app::{
-        component::MyHtml, deposit_flow::DESPOSIT_CREATE_PAGE,
-        payment_flow::PAYMENT_STATUS_UPDATE_PAGE,
+        component::MyHtml,
+        deposit_flow::DESPOSIT_CREATE_PAGE,
+        payment_flow::{PAYMENT_CANCEL_REQUEST_PAGE, PAYMENT_STATUS_UPDATE_PAGE},
     },
     AppContext,
 };
@@ -26,6 +27,7 @@ pub async fn payment_status(query_params: web::Query<QueryParams>) -> HttpRespon
         "{}?payment_id={}",
         PAYMENT_STATUS_UPDATE_PAGE, query_params.payment_id
     );
+    let payment_id = query_params.payment_id.to_string();
 
     let html = leptos::ssr::render_to_string(|| {
         view! {
@@ -36,6 +38,10 @@ pub async fn payment_status(query_params: web::Query<QueryParams>) -> HttpRespon
                         <p>loading status...</p>
                     </div>
                     <a class="btn btn-success" href={deposit_link} >Deposit</a>
+                    <form class="mt-3" action={PAYMENT_CANCEL_REQUEST_PAGE} method="post">
+                        <input type="hidden" name="payment_id" value={payment_id}/>
+                        <input type="submit" class="btn btn-outline-danger" value="Cancel Transfer"/>
+                    </form>
                 </div>
             </MyHtml>
         }
@@ -66,9 +72,14 @@ pub async fn payment_status_update(
         PaymentState::InboundExecuted => "Payment Executed...",
         PaymentState::InboundSettled => "Payment Settled Into Landing Account",
         PaymentState::InboundFailed => "Payment Failed",
+        PaymentState::HeldForReview => "Payment Settled, Under Review",
+        PaymentState::Expired => "Payment Expired, Refund Pending...",
+        PaymentState::Cancelled => "Payment Cancelled, Refund Pending...",
+        PaymentState::PayoutRequested => "Payment Deposit Requested...",
         PaymentState::PayoutCreated => "Payment Deposit Created...",
         PaymentState::PayoutExecuted => "Payment Deposit Executed",
         PaymentState::PayoutFailed => "Payment Deposit Failed...",
+        PaymentState::RefundRequested => "Payment Refund Requested...",
         PaymentState::RefundCreated => "Payment Refund Created...",
         PaymentState::RefundExecuted => "Payment Refunded",
         PaymentState::RefundFailed => "Payment Refund Failed",
@@ -86,6 +97,7 @@ pub async fn payment_status_update(
             | PaymentState::RefundExecuted
             | PaymentState::PayoutExecuted
             | PaymentState::InboundSettled
+            | PaymentState::HeldForReview
     ) {
         res.status(StatusCode::from_u16(286).unwrap());
     }
@@ -1,12 +1,20 @@
-use actix_web::{web, HttpResponse};
-use domain::User;
+use actix_session::Session;
+use actix_web::{http::header, web, HttpResponse};
+use chrono::{Duration, Utc};
+use domain::{User, UserPaymentRole};
 use leptos::view;
 use serde::Deserialize;
+use tracing::instrument;
 use uuid::Uuid;
 
 use crate::{
     api::PublicError,
-    app::component::{MyHtml, MyInput},
+    app::{
+        component::{MyHtml, MyInput},
+        login_flow::{sign_in, EMAIL_CODE_TTL_MINUTES},
+        registration_flow::REGISTER_EMAIL_CODE_PAGE,
+        MY_TRANSFERS_PAGE,
+    },
     AppContext,
 };
 
@@ -21,7 +29,7 @@ pub async fn email_code_confirm(
 ) -> Result<HttpResponse, PublicError> {
     let email = match app.db_client.get_user::<User>(query_params.user_id).await? {
         Some((User::Registering { email, .. }, _)) => email,
-        Some((User::Registered { .. }, _)) => {
+        Some((User::Registered { .. } | User::Erased { .. }, _)) => {
             return Err(PublicError::InternalServerError);
         }
         None => {
@@ -29,12 +37,14 @@ pub async fn email_code_confirm(
         }
     };
 
+    let user_id = query_params.user_id.to_string();
     let html = leptos::ssr::render_to_string(|| {
         view! {
             <MyHtml>
                 <div class="container-sm form-signin w-100 m-auto text-center" >
-                    <form>
+                    <form action={REGISTER_EMAIL_CODE_PAGE} method="post">
                         <h1 class="text-light mb-3 fw-normal">Confirm Code</h1>
+                        <input type="hidden" name="user_id" value={user_id}/>
 
                         <div class="form-floating mb-3" >
                             <input
@@ -66,3 +76,55 @@ pub async fn email_code_confirm(
         .content_type("text/html; charset=utf-8")
         .body(html.to_string()))
 }
+
+#[derive(Debug, Deserialize)]
+pub struct FormData {
+    user_id: Uuid,
+    email_code: String,
+}
+
+#[instrument(skip(app, session))]
+pub async fn email_code_verify(
+    app: web::Data<AppContext>,
+    session: Session,
+    form: web::Form<FormData>,
+) -> Result<HttpResponse, PublicError> {
+    let (user, version) = app
+        .db_client
+        .get_user::<User>(form.user_id)
+        .await?
+        .ok_or(PublicError::InternalServerError)?;
+
+    let is_valid = user.registration_code().is_some_and(|(code, timestamp)| {
+        code == form.email_code
+            && Utc::now() - *timestamp < Duration::minutes(EMAIL_CODE_TTL_MINUTES)
+    });
+    if !is_valid {
+        return Err(PublicError::Invalid(String::from(
+            "Invalid or expired code.",
+        )));
+    }
+
+    let user = User::Registered {
+        user_id: user.user_id(),
+        email: user.email().to_owned(),
+        first_name: user.first_name().to_owned(),
+        last_name: user.last_name().to_owned(),
+        payout_account: None,
+    };
+    app.db_client.upsert_user(user.clone(), version + 1).await?;
+    app.db_client
+        .link_user_payments_by_email(
+            user.user_id(),
+            user.email(),
+            UserPaymentRole::Payer.as_str(),
+            UserPaymentRole::Payee.as_str(),
+        )
+        .await?;
+
+    sign_in(&session, user.user_id());
+
+    Ok(HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, MY_TRANSFERS_PAGE))
+        .finish())
+}
@@ -38,18 +38,18 @@ async fn execute(
                 email,
                 first_name: request.first_name,
                 last_name: request.last_name,
-                code,
+                code: code.clone(),
                 timestamp: Utc::now(),
             },
             v + 1,
         ),
-        None => (
+        Some((User::Erased { .. }, _)) | None => (
             User::Registering {
                 user_id: UserId::new(),
                 email: request.email.clone(),
                 first_name: request.first_name,
                 last_name: request.last_name,
-                code,
+                code: code.clone(),
                 timestamp: Utc::now(),
             },
             0,
@@ -59,6 +59,15 @@ async fn execute(
     let link = format!("{}?user_id={}", REGISTER_EMAIL_CODE_PAGE, user.user_id());
     app.db_client.upsert_user(user, user_version).await?;
 
+    app.notifier
+        .send(
+            &request.email,
+            "Your e-transfer registration code",
+            format!("Your registration code is {}", code),
+        )
+        .await
+        .map_err(|_| PublicError::InternalServerError)?;
+
     Ok(HttpResponse::SeeOther()
         .insert_header((header::LOCATION, link))
         .finish())
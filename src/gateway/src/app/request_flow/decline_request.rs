@@ -0,0 +1,127 @@
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use domain::{PaymentRequest, User};
+use leptos::view;
+use serde::Deserialize;
+use tracing::{instrument, warn};
+use uuid::Uuid;
+
+use crate::{
+    api::PublicError,
+    app::{component::MyHtml, request_flow::REQUEST_DECLINE_PAGE},
+    AppContext,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct DeclineParams {
+    request_id: Uuid,
+}
+
+/// The page behind the emailed decline link. Declining needs an explicit POST
+/// so link previews cannot decline a request.
+pub async fn decline_request_form(
+    app: web::Data<AppContext>,
+    query_params: web::Query<DeclineParams>,
+) -> Result<HttpResponse, PublicError> {
+    let Some((request, _)) = app
+        .db_client
+        .get_payment_request::<PaymentRequest>(query_params.request_id)
+        .await?
+        .filter(|(request, _)| request.is_payable(Utc::now()))
+    else {
+        return Ok(message_page(
+            "Request Unavailable",
+            "This money request was already paid, declined or has expired.",
+        ));
+    };
+
+    let summary = format!("{} requested from {}", request.amount, request.payer_email);
+    let request_id = query_params.request_id.to_string();
+    let html = leptos::ssr::render_to_string(|| {
+        view! {
+            <MyHtml>
+                <div class="container-sm form-signin w-100 m-auto text-center" >
+                    <form action={REQUEST_DECLINE_PAGE} method="post">
+                        <h1 class="text-light mb-3 fw-normal">"Decline Request"</h1>
+                        <p>{summary}</p>
+                        <input type="hidden" name="request_id" value={request_id}/>
+                        <div class="input-group mb-3" >
+                            <input
+                                type="submit"
+                                class="form-control btn btn-danger"
+                                value="DECLINE"
+                            />
+                        </div>
+                    </form>
+                </div>
+            </MyHtml>
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html.to_string()))
+}
+
+#[instrument(skip(app))]
+pub async fn decline_request(
+    app: web::Data<AppContext>,
+    form: web::Form<DeclineParams>,
+) -> Result<HttpResponse, PublicError> {
+    let Some((mut request, version)) = app
+        .db_client
+        .get_payment_request::<PaymentRequest>(form.request_id)
+        .await?
+        .filter(|(request, _)| request.is_payable(Utc::now()))
+    else {
+        return Ok(message_page(
+            "Request Unavailable",
+            "This money request was already paid, declined or has expired.",
+        ));
+    };
+
+    request.declined_at = Some(Utc::now());
+    app.db_client
+        .upsert_payment_request(request.clone(), version + 1)
+        .await?;
+
+    if let Some((payee, _)) = app
+        .db_client
+        .get_user::<User>(request.payee_user_id.into_uuid())
+        .await?
+    {
+        let body = format!(
+            "{} declined your request for {}.",
+            request.payer_email, request.amount
+        );
+        if let Err(err) = app
+            .notifier
+            .send(payee.email(), "Your money request was declined", body)
+            .await
+        {
+            warn!(?err, "failed to notify payee of declined request");
+        }
+    }
+
+    Ok(message_page(
+        "Request Declined",
+        "The requester has been notified.",
+    ))
+}
+
+fn message_page(title: &'static str, message: &'static str) -> HttpResponse {
+    let html = leptos::ssr::render_to_string(move || {
+        view! {
+            <MyHtml>
+                <div class="container-sm form-signin w-100 m-auto text-center" >
+                    <h1 class="text-light mb-3 fw-normal">{title}</h1>
+                    <p>{message}</p>
+                </div>
+            </MyHtml>
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html.to_string())
+}
@@ -0,0 +1,26 @@
+mod decline_request;
+mod payment_requests;
+
+use actix_web::web;
+use decline_request::{decline_request, decline_request_form};
+use payment_requests::{create_payment_request, payment_requests};
+
+pub const REQUESTS_PAGE: &str = "/app/requests";
+pub const REQUEST_DECLINE_PAGE: &str = "/app/requests/decline";
+
+/// Days a money request can be paid before it expires.
+pub const PAYMENT_REQUEST_TTL_DAYS: i64 = 14;
+
+pub fn request_scope() -> actix_web::Scope {
+    web::scope("requests")
+        .service(
+            web::resource("")
+                .get(payment_requests)
+                .post(create_payment_request),
+        )
+        .service(
+            web::resource("decline")
+                .get(decline_request_form)
+                .post(decline_request),
+        )
+}
@@ -0,0 +1,193 @@
+use actix_session::Session;
+use actix_web::{http::header, web, HttpResponse};
+use chrono::{Duration, Utc};
+use domain::{Currency, Money, PaymentRequest, PaymentRequestId, User};
+use leptos::{component, view, CollectView, IntoView};
+use serde::Deserialize;
+use tracing::instrument;
+
+use crate::{
+    api::PublicError,
+    app::{
+        component::{CurrencySelect, MyHtml, MyInput},
+        login_flow::{session_user, LOGIN_PAGE},
+        payment_flow::PAYMENT_FORM_PAGE,
+        request_flow::{PAYMENT_REQUEST_TTL_DAYS, REQUESTS_PAGE, REQUEST_DECLINE_PAGE},
+        MY_TRANSFERS_PAGE,
+    },
+    AppContext,
+};
+
+const REQUESTS_PAGE_SIZE: i64 = 50;
+
+pub async fn payment_requests(
+    app: web::Data<AppContext>,
+    session: Session,
+) -> Result<HttpResponse, PublicError> {
+    let Some(user_id) = session_user(&session) else {
+        return Ok(redirect_to(LOGIN_PAGE));
+    };
+
+    let requests = app
+        .db_client
+        .get_user_payment_requests::<PaymentRequest>(user_id, REQUESTS_PAGE_SIZE, 0)
+        .await?;
+
+    let html = leptos::ssr::render_to_string(|| {
+        view! {
+            <MyHtml>
+                <div class="container-sm w-75 pt-4" >
+                    <div class="d-flex justify-content-between align-items-center">
+                        <h1>Money Requests</h1>
+                        <a class="btn btn-outline-light" href={MY_TRANSFERS_PAGE}>My Transfers</a>
+                    </div>
+                    <form class="form-signin mx-auto my-3" action={REQUESTS_PAGE} method="post">
+                        <h2 class="mb-3 fw-normal">Request Money</h2>
+                        <MyInput input_type="email" name="payer_email" label="Payer Email" required=true/>
+                        <MyInput input_type="number" name="amount" label="Amount" required=true/>
+                        <CurrencySelect name="currency" label="Currency"/>
+                        <MyInput input_type="text" name="note" label="Note" required=false/>
+                        <div class="input-group mb-3" >
+                            <input
+                                type="submit"
+                                class="form-control btn btn-success"
+                                value="SEND REQUEST"
+                            />
+                        </div>
+                    </form>
+                    <RequestsTable requests={requests} />
+                </div>
+            </MyHtml>
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html.to_string()))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FormData {
+    payer_email: String,
+    amount: u32,
+    currency: Currency,
+    #[serde(default)]
+    note: String,
+}
+
+#[instrument(skip(app, session))]
+pub async fn create_payment_request(
+    app: web::Data<AppContext>,
+    session: Session,
+    form: web::Form<FormData>,
+) -> Result<HttpResponse, PublicError> {
+    let Some(user_id) = session_user(&session) else {
+        return Ok(redirect_to(LOGIN_PAGE));
+    };
+    let form = form.into_inner();
+
+    if !email_address::EmailAddress::is_valid(&form.payer_email) {
+        return Err(PublicError::Invalid(String::from(
+            "Please enter a valid email address.",
+        )));
+    }
+    if form.amount < 100 {
+        return Err(PublicError::Invalid(String::from(
+            "A minimum of 100 is required.",
+        )));
+    }
+
+    let (payee, _) = app
+        .db_client
+        .get_user::<User>(user_id.into_uuid())
+        .await?
+        .ok_or(PublicError::InternalServerError)?;
+
+    let now = Utc::now();
+    let request = PaymentRequest {
+        request_id: PaymentRequestId::new(),
+        payee_user_id: user_id,
+        payer_email: form.payer_email,
+        amount: Money::new(form.amount, form.currency),
+        note: form.note,
+        created_at: now,
+        expires_at: now + Duration::days(PAYMENT_REQUEST_TTL_DAYS),
+        payment_id: None,
+        paid_at: None,
+        declined_at: None,
+        claimed_at: None,
+    };
+    app.db_client
+        .upsert_payment_request(request.clone(), 0)
+        .await?;
+
+    let pay_link = app.public_link(&format!(
+        "{}?request_id={}",
+        PAYMENT_FORM_PAGE, request.request_id
+    ));
+    let decline_link = app.public_link(&format!(
+        "{}?request_id={}",
+        REQUEST_DECLINE_PAGE, request.request_id
+    ));
+    app.notifier
+        .send(
+            &request.payer_email,
+            "You have a new money request",
+            format!(
+                "{} {} requested {} from you.\n\n{}\n\nPay: {}\nDecline: {}",
+                payee.first_name(),
+                payee.last_name(),
+                request.amount,
+                request.note,
+                pay_link,
+                decline_link,
+            ),
+        )
+        .await
+        .map_err(|_| PublicError::InternalServerError)?;
+
+    Ok(redirect_to(REQUESTS_PAGE))
+}
+
+fn redirect_to(location: &str) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, location))
+        .finish()
+}
+
+#[component]
+fn requests_table(requests: Vec<(PaymentRequest, u32)>) -> impl IntoView {
+    let now = Utc::now();
+    let rows = requests
+        .into_iter()
+        .map(|(request, _)| {
+            let status = request.status(now).as_str();
+            view! {
+                <tr>
+                    <td>{request.created_at.format("%Y-%m-%d %H:%M").to_string()}</td>
+                    <td>{request.payer_email}</td>
+                    <td>{request.amount.to_string()}</td>
+                    <td>{request.note}</td>
+                    <td>{status}</td>
+                </tr>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <table class="table">
+            <thead>
+                <tr>
+                    <th scope="col">Date</th>
+                    <th scope="col">Payer</th>
+                    <th scope="col">Amount</th>
+                    <th scope="col">Note</th>
+                    <th scope="col">Status</th>
+                </tr>
+            </thead>
+            <tbody>
+                { rows }
+            </tbody>
+        </table>
+    }
+}
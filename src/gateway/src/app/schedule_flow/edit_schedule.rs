@@ -0,0 +1,107 @@
+use actix_session::Session;
+use actix_web::{web, HttpResponse};
+use domain::{Money, ScheduleStatus};
+use leptos::view;
+use tracing::{error, instrument};
+
+use crate::{
+    api::PublicError,
+    app::{
+        component::MyHtml,
+        schedule_flow::{
+            owned_schedule, redirect_to, require_user, ScheduleFields, ScheduleFormData,
+            ScheduleParams, SCHEDULES_PAGE, SCHEDULE_EDIT_PAGE,
+        },
+    },
+    payment::hash_security_answer,
+    AppContext,
+};
+
+pub async fn edit_schedule_form(
+    app: web::Data<AppContext>,
+    session: Session,
+    query_params: web::Query<ScheduleParams>,
+) -> Result<HttpResponse, PublicError> {
+    let user_id = match require_user(&session) {
+        Ok(user_id) => user_id,
+        Err(redirect) => return Ok(redirect),
+    };
+    let Some((schedule, _)) = owned_schedule(&app, user_id, query_params.schedule_id).await? else {
+        return Ok(redirect_to(SCHEDULES_PAGE));
+    };
+
+    let action = format!(
+        "{}?schedule_id={}",
+        SCHEDULE_EDIT_PAGE, query_params.schedule_id
+    );
+    let html = leptos::ssr::render_to_string(move || {
+        view! {
+            <MyHtml>
+                <div class="container-sm form-signin w-100 m-auto text-center" >
+                    <form action={action} method="post">
+                        <h1 class="text-light mb-3 fw-normal">Edit Scheduled Transfer</h1>
+                        <ScheduleFields schedule={Some(schedule)} />
+                        <div class="input-group mb-3" >
+                            <input
+                                type="submit"
+                                class="form-control btn btn-success"
+                                value="SAVE"
+                            />
+                        </div>
+                    </form>
+                </div>
+            </MyHtml>
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html.to_string()))
+}
+
+#[instrument(skip(app, session, form))]
+pub async fn edit_schedule(
+    app: web::Data<AppContext>,
+    session: Session,
+    query_params: web::Query<ScheduleParams>,
+    form: web::Form<ScheduleFormData>,
+) -> Result<HttpResponse, PublicError> {
+    let user_id = match require_user(&session) {
+        Ok(user_id) => user_id,
+        Err(redirect) => return Ok(redirect),
+    };
+    let Some((mut schedule, version)) =
+        owned_schedule(&app, user_id, query_params.schedule_id).await?
+    else {
+        return Ok(redirect_to(SCHEDULES_PAGE));
+    };
+    if !matches!(
+        schedule.status,
+        ScheduleStatus::Active | ScheduleStatus::Paused
+    ) {
+        return Err(PublicError::Invalid(String::from(
+            "This scheduled transfer has ended.",
+        )));
+    }
+
+    let form = form.into_inner();
+    form.validate()?;
+
+    schedule.payee_full_name = form.payee_full_name.clone();
+    schedule.payee_email = form.payee_email.clone();
+    schedule.amount = Money::new(form.amount, form.currency);
+    schedule.security_question = form.security_question.clone();
+    if !form.security_answer.is_empty() {
+        schedule.security_answer = hash_security_answer(&form.security_answer).map_err(|err| {
+            error!(?err, "failed to hash security answer");
+            PublicError::InternalServerError
+        })?;
+    }
+    schedule.reschedule(form.starts_at(), form.frequency);
+
+    app.db_client
+        .upsert_scheduled_transfer(schedule, version + 1)
+        .await?;
+
+    Ok(redirect_to(SCHEDULES_PAGE))
+}
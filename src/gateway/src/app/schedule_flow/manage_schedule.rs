@@ -0,0 +1,58 @@
+use actix_session::Session;
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use domain::ScheduledTransfer;
+
+use crate::{
+    api::PublicError,
+    app::schedule_flow::{
+        owned_schedule, redirect_to, require_user, ScheduleParams, SCHEDULES_PAGE,
+    },
+    AppContext,
+};
+
+pub async fn pause_schedule(
+    app: web::Data<AppContext>,
+    session: Session,
+    form: web::Form<ScheduleParams>,
+) -> Result<HttpResponse, PublicError> {
+    update_schedule(app, session, form.schedule_id, ScheduledTransfer::pause).await
+}
+
+pub async fn resume_schedule(
+    app: web::Data<AppContext>,
+    session: Session,
+    form: web::Form<ScheduleParams>,
+) -> Result<HttpResponse, PublicError> {
+    update_schedule(app, session, form.schedule_id, |schedule| {
+        schedule.resume(Utc::now())
+    })
+    .await
+}
+
+pub async fn cancel_schedule(
+    app: web::Data<AppContext>,
+    session: Session,
+    form: web::Form<ScheduleParams>,
+) -> Result<HttpResponse, PublicError> {
+    update_schedule(app, session, form.schedule_id, ScheduledTransfer::cancel).await
+}
+
+async fn update_schedule(
+    app: web::Data<AppContext>,
+    session: Session,
+    schedule_id: uuid::Uuid,
+    update: impl FnOnce(&mut ScheduledTransfer),
+) -> Result<HttpResponse, PublicError> {
+    let user_id = match require_user(&session) {
+        Ok(user_id) => user_id,
+        Err(redirect) => return Ok(redirect),
+    };
+    if let Some((mut schedule, version)) = owned_schedule(&app, user_id, schedule_id).await? {
+        update(&mut schedule);
+        app.db_client
+            .upsert_scheduled_transfer(schedule, version + 1)
+            .await?;
+    }
+    Ok(redirect_to(SCHEDULES_PAGE))
+}
@@ -0,0 +1,175 @@
+mod edit_schedule;
+mod manage_schedule;
+mod schedules;
+
+use actix_session::Session;
+use actix_web::{http::header, web, HttpResponse};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use domain::{Currency, ScheduleFrequency, ScheduledTransfer, UserId};
+use edit_schedule::{edit_schedule, edit_schedule_form};
+use leptos::{component, view, CollectView, IntoView};
+use manage_schedule::{cancel_schedule, pause_schedule, resume_schedule};
+use schedules::{create_schedule, schedules};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    api::PublicError,
+    app::{component::CurrencySelect, login_flow::LOGIN_PAGE},
+    AppContext,
+};
+
+pub const SCHEDULES_PAGE: &str = "/app/schedules";
+pub const SCHEDULE_EDIT_PAGE: &str = "/app/schedules/edit";
+pub const SCHEDULE_PAUSE_PAGE: &str = "/app/schedules/pause";
+pub const SCHEDULE_RESUME_PAGE: &str = "/app/schedules/resume";
+pub const SCHEDULE_CANCEL_PAGE: &str = "/app/schedules/cancel";
+
+/// Time of day, in UTC, scheduled transfers are sent on their due date.
+const SCHEDULE_RUN_TIME: NaiveTime = match NaiveTime::from_hms_opt(9, 0, 0) {
+    Some(time) => time,
+    None => unreachable!(),
+};
+
+pub fn schedule_scope() -> actix_web::Scope {
+    web::scope("schedules")
+        .service(web::resource("").get(schedules).post(create_schedule))
+        .service(
+            web::resource("edit")
+                .get(edit_schedule_form)
+                .post(edit_schedule),
+        )
+        .service(web::resource("pause").post(pause_schedule))
+        .service(web::resource("resume").post(resume_schedule))
+        .service(web::resource("cancel").post(cancel_schedule))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScheduleParams {
+    schedule_id: Uuid,
+}
+
+/// Fields shared by the create and edit schedule forms.
+#[derive(Debug, Deserialize)]
+pub struct ScheduleFormData {
+    payee_full_name: String,
+    payee_email: String,
+    amount: u32,
+    currency: Currency,
+    frequency: ScheduleFrequency,
+    start_date: NaiveDate,
+    #[serde(default)]
+    security_question: String,
+    #[serde(default)]
+    security_answer: String,
+}
+
+impl ScheduleFormData {
+    fn validate(&self) -> Result<(), PublicError> {
+        if !email_address::EmailAddress::is_valid(&self.payee_email) {
+            return Err(PublicError::Invalid(String::from(
+                "Please enter a valid email address.",
+            )));
+        }
+        if self.amount < 100 {
+            return Err(PublicError::Invalid(String::from(
+                "A minimum of 100 is required.",
+            )));
+        }
+        if self.start_date < Utc::now().date_naive() {
+            return Err(PublicError::Invalid(String::from(
+                "The start date cannot be in the past.",
+            )));
+        }
+        Ok(())
+    }
+
+    fn starts_at(&self) -> DateTime<Utc> {
+        self.start_date.and_time(SCHEDULE_RUN_TIME).and_utc()
+    }
+}
+
+/// The signed in user's schedule, or `None` if it belongs to someone else.
+async fn owned_schedule(
+    app: &AppContext,
+    user_id: UserId,
+    schedule_id: Uuid,
+) -> Result<Option<(ScheduledTransfer, u32)>, PublicError> {
+    Ok(app
+        .db_client
+        .get_scheduled_transfer::<ScheduledTransfer>(schedule_id)
+        .await?
+        .filter(|(schedule, _)| schedule.user_id == user_id))
+}
+
+fn redirect_to(location: &str) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, location))
+        .finish()
+}
+
+fn require_user(session: &Session) -> Result<UserId, HttpResponse> {
+    crate::app::login_flow::session_user(session).ok_or_else(|| redirect_to(LOGIN_PAGE))
+}
+
+#[component]
+fn schedule_fields(schedule: Option<ScheduledTransfer>) -> impl IntoView {
+    let payee_full_name = schedule.as_ref().map(|s| s.payee_full_name.clone());
+    let payee_email = schedule.as_ref().map(|s| s.payee_email.clone());
+    let amount = schedule.as_ref().map(|s| s.amount.amount_in_minor);
+    let currency = schedule.as_ref().map(|s| s.amount.currency);
+    let start_date = schedule
+        .as_ref()
+        .and_then(ScheduledTransfer::next_run_at)
+        .map(|next_run_at| next_run_at.date_naive().to_string());
+    let selected = schedule.as_ref().map(|s| s.frequency);
+    let security_question = schedule.as_ref().map(|s| s.security_question.clone());
+    let is_new = schedule.is_none();
+
+    let frequencies = ScheduleFrequency::ALL
+        .into_iter()
+        .map(|frequency| {
+            view! {
+                <option value={frequency.as_str()} selected={selected == Some(frequency)}>
+                    {frequency.as_str()}
+                </option>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <div class="form-floating mb-3">
+            <input type="text" class="form-control" id="payee_full_name" name="payee_full_name" value={payee_full_name} required/>
+            <label for="payee_full_name">Recipiant Name</label>
+        </div>
+        <div class="form-floating mb-3">
+            <input type="email" class="form-control" id="payee_email" name="payee_email" value={payee_email} required/>
+            <label for="payee_email">Recipiant Email</label>
+        </div>
+        <div class="form-floating mb-3">
+            <input type="text" inputmode="numeric" pattern="[0-9]*" class="form-control" id="amount" name="amount" value={amount} required/>
+            <label for="amount">Amount</label>
+        </div>
+        <CurrencySelect name="currency" label="Currency" selected={currency}/>
+        <div class="form-floating mb-3">
+            <select class="form-select" id="frequency" name="frequency" required>
+                { frequencies }
+            </select>
+            <label for="frequency">Frequency</label>
+        </div>
+        <div class="form-floating mb-3">
+            <input type="date" class="form-control" id="start_date" name="start_date" value={start_date} required/>
+            <label for="start_date">Send On</label>
+        </div>
+        <div class="form-floating mb-3">
+            <input type="text" class="form-control" id="security_question" name="security_question" value={security_question} required/>
+            <label for="security_question">Security Question</label>
+        </div>
+        <div class="form-floating mb-3">
+            <input type="text" class="form-control" id="security_answer" name="security_answer" required={is_new} data-1p-ignore/>
+            <label for="security_answer">
+                {if is_new { "Security Answer" } else { "Security Answer (leave empty to keep)" }}
+            </label>
+        </div>
+    }
+}
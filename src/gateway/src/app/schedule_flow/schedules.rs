@@ -0,0 +1,178 @@
+use actix_session::Session;
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use domain::{Money, ScheduleStatus, ScheduledTransfer, ScheduledTransferId};
+use leptos::{component, view, CollectView, IntoView};
+use tracing::{error, instrument};
+
+use crate::{
+    api::PublicError,
+    app::{
+        component::MyHtml,
+        schedule_flow::{
+            redirect_to, require_user, ScheduleFields, ScheduleFormData, SCHEDULES_PAGE,
+            SCHEDULE_CANCEL_PAGE, SCHEDULE_EDIT_PAGE, SCHEDULE_PAUSE_PAGE, SCHEDULE_RESUME_PAGE,
+        },
+        MY_TRANSFERS_PAGE,
+    },
+    payment::hash_security_answer,
+    AppContext,
+};
+
+const SCHEDULES_PAGE_SIZE: i64 = 50;
+
+pub async fn schedules(
+    app: web::Data<AppContext>,
+    session: Session,
+) -> Result<HttpResponse, PublicError> {
+    let user_id = match require_user(&session) {
+        Ok(user_id) => user_id,
+        Err(redirect) => return Ok(redirect),
+    };
+
+    let schedules = app
+        .db_client
+        .get_user_scheduled_transfers::<ScheduledTransfer>(user_id, SCHEDULES_PAGE_SIZE, 0)
+        .await?;
+
+    let html = leptos::ssr::render_to_string(|| {
+        view! {
+            <MyHtml>
+                <div class="container-sm w-75 pt-4" >
+                    <div class="d-flex justify-content-between align-items-center">
+                        <h1>Scheduled Transfers</h1>
+                        <a class="btn btn-outline-light" href={MY_TRANSFERS_PAGE}>My Transfers</a>
+                    </div>
+                    <SchedulesTable schedules={schedules} />
+                    <form class="form-signin mx-auto my-3" action={SCHEDULES_PAGE} method="post">
+                        <h2 class="mb-3 fw-normal">New Scheduled Transfer</h2>
+                        <ScheduleFields schedule={None} />
+                        <div class="input-group mb-3" >
+                            <input
+                                type="submit"
+                                class="form-control btn btn-success"
+                                value="SCHEDULE"
+                            />
+                        </div>
+                    </form>
+                </div>
+            </MyHtml>
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html.to_string()))
+}
+
+#[instrument(skip(app, session, form))]
+pub async fn create_schedule(
+    app: web::Data<AppContext>,
+    session: Session,
+    form: web::Form<ScheduleFormData>,
+) -> Result<HttpResponse, PublicError> {
+    let user_id = match require_user(&session) {
+        Ok(user_id) => user_id,
+        Err(redirect) => return Ok(redirect),
+    };
+    let form = form.into_inner();
+    form.validate()?;
+    if form.security_answer.is_empty() {
+        return Err(PublicError::Invalid(String::from(
+            "A security answer is required.",
+        )));
+    }
+
+    let security_answer = hash_security_answer(&form.security_answer).map_err(|err| {
+        error!(?err, "failed to hash security answer");
+        PublicError::InternalServerError
+    })?;
+
+    let schedule = ScheduledTransfer {
+        schedule_id: ScheduledTransferId::new(),
+        user_id,
+        payee_full_name: form.payee_full_name.clone(),
+        payee_email: form.payee_email.clone(),
+        amount: Money::new(form.amount, form.currency),
+        security_question: form.security_question.clone(),
+        security_answer,
+        frequency: form.frequency,
+        starts_at: form.starts_at(),
+        occurrence: 0,
+        status: ScheduleStatus::Active,
+        last_payment_id: None,
+        last_run_at: None,
+    };
+    app.db_client.upsert_scheduled_transfer(schedule, 0).await?;
+
+    Ok(redirect_to(SCHEDULES_PAGE))
+}
+
+#[component]
+fn schedules_table(schedules: Vec<(ScheduledTransfer, u32)>) -> impl IntoView {
+    let now = Utc::now();
+    let rows = schedules
+        .into_iter()
+        .map(|(schedule, _)| {
+            let schedule_id = schedule.schedule_id.to_string();
+            let next_run_at = schedule
+                .next_run_at()
+                .map(|next_run_at| next_run_at.format("%Y-%m-%d").to_string())
+                .unwrap_or_default();
+            let is_open = matches!(
+                schedule.status,
+                ScheduleStatus::Active | ScheduleStatus::Paused
+            );
+            let (toggle_page, toggle_label) = match schedule.status {
+                ScheduleStatus::Paused => (SCHEDULE_RESUME_PAGE, "Resume"),
+                _ => (SCHEDULE_PAUSE_PAGE, "Pause"),
+            };
+            let actions = is_open.then(|| {
+                let edit_link = format!("{}?schedule_id={}", SCHEDULE_EDIT_PAGE, schedule_id);
+                view! {
+                    <div class="d-flex gap-1">
+                        <a class="btn btn-sm btn-outline-light" href={edit_link}>Edit</a>
+                        <form action={toggle_page} method="post">
+                            <input type="hidden" name="schedule_id" value={schedule_id.clone()}/>
+                            <input type="submit" class="btn btn-sm btn-outline-warning" value={toggle_label}/>
+                        </form>
+                        <form action={SCHEDULE_CANCEL_PAGE} method="post">
+                            <input type="hidden" name="schedule_id" value={schedule_id.clone()}/>
+                            <input type="submit" class="btn btn-sm btn-outline-danger" value="Cancel"/>
+                        </form>
+                    </div>
+                }
+            });
+            let overdue = schedule.due_at().is_some_and(|due_at| due_at <= now);
+
+            view! {
+                <tr>
+                    <td>{schedule.payee_full_name}</td>
+                    <td>{schedule.amount.to_string()}</td>
+                    <td>{schedule.frequency.as_str()}</td>
+                    <td>{if overdue { String::from("sending...") } else { next_run_at }}</td>
+                    <td>{schedule.status.as_str()}</td>
+                    <td>{actions}</td>
+                </tr>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <table class="table">
+            <thead>
+                <tr>
+                    <th scope="col">To</th>
+                    <th scope="col">Amount</th>
+                    <th scope="col">Frequency</th>
+                    <th scope="col">Next</th>
+                    <th scope="col">Status</th>
+                    <th scope="col"></th>
+                </tr>
+            </thead>
+            <tbody>
+                { rows }
+            </tbody>
+        </table>
+    }
+}
@@ -1,34 +1,59 @@
 use actix_web::{http::header, web, HttpResponse};
+use chrono::Utc;
 use domain::{Payment, PaymentState};
 use serde::Deserialize;
 use uuid::Uuid;
 
-use crate::{app::deposit_flow::DESPOSIT_SELECT_ACCOUNT_PAGE, AppContext};
+use crate::{
+    app::{
+        account_flow::{ACCOUNT_SELECT_PAGE, PAYOUT_ACCOUNT_STATE},
+        deposit_flow::DESPOSIT_SELECT_ACCOUNT_PAGE,
+    },
+    AppContext,
+};
 
 #[derive(Debug, Deserialize)]
 pub struct QueryParams {
     code: String,
-    #[serde(rename = "state")]
-    payment_id: Uuid,
+    state: String,
 }
 
+/// Sends the Data API redirect on to the flow named by `state`: either the
+/// id of the payment being deposited, or a user saving a payout account. The
+/// deposit flow itself checks the session answered for the payment.
 pub async fn tl_data_callback(
     app: web::Data<AppContext>,
     query_params: web::Query<QueryParams>,
 ) -> HttpResponse {
-    let payment = app
-        .db_client
-        .get_payment::<Payment>(query_params.payment_id)
-        .await;
+    if query_params.state == PAYOUT_ACCOUNT_STATE {
+        return HttpResponse::SeeOther()
+            .insert_header((
+                header::LOCATION,
+                format!("{}?code={}", ACCOUNT_SELECT_PAGE, query_params.code),
+            ))
+            .finish();
+    }
+
+    let Ok(payment_id) = Uuid::parse_str(&query_params.state) else {
+        return HttpResponse::SeeOther()
+            .insert_header((header::LOCATION, "/error"))
+            .finish();
+    };
+    let payment = app.db_client.get_payment::<Payment>(payment_id).await;
 
     match payment {
-        Ok(Some((payment, _))) if payment.state() >= PaymentState::InboundCreated => {
+        Ok(Some((payment, _)))
+            if !payment.deposit_lock.is_locked(Utc::now())
+                && payment
+                    .state()
+                    .can_transition_to(PaymentState::PayoutRequested) =>
+        {
             HttpResponse::SeeOther()
                 .insert_header((
                     header::LOCATION,
                     format!(
                         "{}?payment_id={}&code={}",
-                        DESPOSIT_SELECT_ACCOUNT_PAGE, query_params.payment_id, query_params.code
+                        DESPOSIT_SELECT_ACCOUNT_PAGE, payment_id, query_params.code
                     ),
                 ))
                 .finish()
@@ -0,0 +1,127 @@
+use actix_session::Session;
+use actix_web::{http::header, web, HttpResponse};
+use domain::{Payment, PaymentState, UserPaymentRole};
+use leptos::{component, view, CollectView, IntoView};
+
+use crate::{
+    api::PublicError,
+    app::{
+        account_flow::ACCOUNT_PAGE,
+        component::MyHtml,
+        deposit_flow::DESPOSIT_CREATE_PAGE,
+        login_flow::{session_user, LOGIN_PAGE, LOGOUT_PAGE},
+        request_flow::REQUESTS_PAGE,
+        schedule_flow::SCHEDULES_PAGE,
+    },
+    AppContext,
+};
+
+const TRANSFERS_PAGE_SIZE: i64 = 50;
+
+pub async fn my_transfers(
+    app: web::Data<AppContext>,
+    session: Session,
+) -> Result<HttpResponse, PublicError> {
+    let Some(user_id) = session_user(&session) else {
+        return Ok(HttpResponse::SeeOther()
+            .insert_header((header::LOCATION, LOGIN_PAGE))
+            .finish());
+    };
+
+    let sent = app
+        .db_client
+        .get_user_payments::<Payment>(
+            user_id,
+            UserPaymentRole::Payer.as_str(),
+            TRANSFERS_PAGE_SIZE,
+            0,
+        )
+        .await?;
+    let received = app
+        .db_client
+        .get_user_payments::<Payment>(
+            user_id,
+            UserPaymentRole::Payee.as_str(),
+            TRANSFERS_PAGE_SIZE,
+            0,
+        )
+        .await?;
+
+    let html = leptos::ssr::render_to_string(|| {
+        view! {
+            <MyHtml>
+                <div class="container-sm w-75 pt-4" >
+                    <div class="d-flex justify-content-between align-items-center">
+                        <h1>My Transfers</h1>
+                        <div>
+                            <a class="btn btn-success" href={REQUESTS_PAGE}>Request Money</a>
+                            <a class="btn btn-success ms-1" href={SCHEDULES_PAGE}>Scheduled Transfers</a>
+                            <a class="btn btn-outline-light ms-1" href={ACCOUNT_PAGE}>Payout Account</a>
+                            <a class="btn btn-outline-light ms-1" href={LOGOUT_PAGE}>Sign Out</a>
+                        </div>
+                    </div>
+                    <h2 class="mt-3">Sent</h2>
+                    <TransfersTable payments={sent} role={UserPaymentRole::Payer} />
+                    <h2 class="mt-3">Received</h2>
+                    <TransfersTable payments={received} role={UserPaymentRole::Payee} />
+                </div>
+            </MyHtml>
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html.to_string()))
+}
+
+#[component]
+fn transfers_table(payments: Vec<(Payment, u32)>, role: UserPaymentRole) -> impl IntoView {
+    let counterparty_label = match role {
+        UserPaymentRole::Payer => "To",
+        UserPaymentRole::Payee => "From",
+    };
+
+    let rows = payments
+        .into_iter()
+        .map(|(payment, _)| {
+            let counterparty = match role {
+                UserPaymentRole::Payer => payment.payee_full_name.clone(),
+                UserPaymentRole::Payee => payment.payer_full_name.clone(),
+            };
+            let deposit_link = (role == UserPaymentRole::Payee
+                && payment
+                    .state()
+                    .can_transition_to(PaymentState::PayoutRequested))
+            .then(|| {
+                let href = format!("{}?payment_id={}", DESPOSIT_CREATE_PAGE, payment.payment_id);
+                view! { <a class="btn btn-sm btn-success" href={href}>Deposit</a> }
+            });
+            view! {
+                <tr>
+                    <td>{payment.payment_statuses.inbound_created_at.format("%Y-%m-%d %H:%M").to_string()}</td>
+                    <td>{counterparty}</td>
+                    <td>{payment.amount.to_string()}</td>
+                    <td>{payment.state().as_str()}</td>
+                    <td>{deposit_link}</td>
+                </tr>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <table class="table">
+            <thead>
+                <tr>
+                    <th scope="col">Date</th>
+                    <th scope="col">{counterparty_label}</th>
+                    <th scope="col">Amount</th>
+                    <th scope="col">Status</th>
+                    <th scope="col"></th>
+                </tr>
+            </thead>
+            <tbody>
+                { rows }
+            </tbody>
+        </table>
+    }
+}
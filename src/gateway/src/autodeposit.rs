@@ -0,0 +1,148 @@
+use domain::{
+    EventSource, OutboxEffect, OutboxMessage, Payment, PaymentState, PayoutDestination, User,
+    UserState,
+};
+use tracing::info;
+
+use crate::{payment::message_note, payout::payout_payment, AppContext};
+
+/// Pays a settled payment straight out to the payee when they are a
+/// registered user with a saved payout account in the payment's currency.
+/// Skipping the security question needs the payee's email verified by a
+/// finished registration. Anyone else deposits through the security question
+/// as before. Returns whether the payout was requested.
+pub async fn autodeposit(app: &AppContext, payment: Payment, version: u32) -> anyhow::Result<bool> {
+    if payment.state() != PaymentState::InboundSettled {
+        return Ok(false);
+    }
+    let Some((user, _)) = app
+        .db_client
+        .get_user_by_email::<User>(&payment.payee_email)
+        .await?
+    else {
+        return Ok(false);
+    };
+    if user.state() != UserState::Registered {
+        return Ok(false);
+    }
+    let Some(account) = user
+        .payout_account()
+        .filter(|account| account.currency == payment.amount.currency)
+    else {
+        return Ok(false);
+    };
+
+    let payment_id = payment.payment_id;
+    let body = format!(
+        "{} sent you {}. It was deposited automatically to your account {} ({}).{}",
+        payment.payer_full_name,
+        payment.amount,
+        account.display_name,
+        account.account_identifier.masked(),
+        message_note(&payment),
+    );
+    let email = OutboxMessage::new(
+        Some(payment_id),
+        OutboxEffect::email(&payment.payee_email, "Your e-transfer was deposited", body),
+    );
+    let destination = PayoutDestination {
+        account_holder_name: format!("{} {}", user.first_name(), user.last_name()),
+        account_identifier: account.account_identifier.clone(),
+    };
+    payout_payment(
+        app,
+        payment,
+        version,
+        destination,
+        EventSource::System,
+        vec![email],
+    )
+    .await?;
+    info!(%payment_id, "payment autodeposit requested");
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use domain::{
+        AccountIdentifier, Currency, PaymentEvent, PaymentEventRecord, PayoutAccount, UserId,
+    };
+
+    use super::*;
+
+    fn settled_payment() -> Payment {
+        let mut payment = Payment::test_fixture();
+        payment
+            .apply(PaymentEvent::InboundSettled {
+                settled_at: Utc::now(),
+            })
+            .unwrap();
+        payment
+    }
+
+    fn gbp_account() -> PayoutAccount {
+        PayoutAccount {
+            display_name: String::from("Current account"),
+            currency: Currency::Gbp,
+            account_identifier: AccountIdentifier::SortCodeAccountNumber {
+                sort_code: String::from("040004"),
+                account_number: String::from("12345678"),
+            },
+            verified_at: Utc::now(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn only_registered_payees_are_paid_out() {
+        let app = AppContext::for_tests();
+        let payment = settled_payment();
+        let registering = User::Registering {
+            user_id: UserId::new(),
+            email: payment.payee_email.clone(),
+            first_name: String::from("Payee"),
+            last_name: String::from("Person"),
+            code: String::from("123456"),
+            timestamp: Utc::now(),
+        };
+        app.db_client.upsert_user(registering, 0).await.unwrap();
+
+        assert!(!autodeposit(&app, payment, 1).await.unwrap());
+    }
+
+    #[actix_web::test]
+    async fn a_registered_payee_with_an_account_is_paid_out() {
+        let app = AppContext::for_tests();
+        let payment = settled_payment();
+        let payment_id = payment.payment_id;
+        let registered = User::Registered {
+            user_id: UserId::new(),
+            email: payment.payee_email.clone(),
+            first_name: String::from("Payee"),
+            last_name: String::from("Person"),
+            payout_account: Some(gbp_account()),
+        };
+        app.db_client.upsert_user(registered, 0).await.unwrap();
+        let state = payment.state();
+        let event = PaymentEventRecord::new(
+            payment_id,
+            state.as_str(),
+            EventSource::Webhook,
+            None,
+            state,
+        );
+        app.db_client
+            .upsert_payment(payment.clone(), 1, event)
+            .await
+            .unwrap();
+
+        assert!(autodeposit(&app, payment, 1).await.unwrap());
+        let (payment, _) = app
+            .db_client
+            .get_payment::<Payment>(payment_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(payment.state(), PaymentState::PayoutRequested);
+    }
+}
@@ -0,0 +1,185 @@
+use std::time::Duration;
+
+use actix_web::web;
+use chrono::Utc;
+use domain::{OutboxEffect, OutboxMessage, OutboxStatus};
+use serde::Deserialize;
+use tracing::{error, info, instrument, warn};
+
+use crate::{
+    payout::create_requested_payout, refund::create_requested_refund,
+    settlement::settle_queued_payment, AppContext,
+};
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct OutboxConfig {
+    pub interval_secs: u64,
+    pub batch_size: i64,
+    /// Attempts before a message is marked dead and left for an admin.
+    pub max_attempts: u32,
+    /// Wait after the first failure, doubled after every further one.
+    pub base_backoff_secs: u64,
+    pub max_backoff_secs: u64,
+    /// How long a claimed message is left alone before another dispatcher
+    /// may pick it up, should this one stop while delivering it.
+    pub lease_secs: u64,
+}
+
+impl Default for OutboxConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 5,
+            batch_size: 50,
+            max_attempts: 8,
+            base_backoff_secs: 30,
+            max_backoff_secs: 3600,
+            lease_secs: 300,
+        }
+    }
+}
+
+impl OutboxConfig {
+    /// The wait before retrying a message that failed `attempts` times.
+    fn backoff(&self, attempts: u32) -> chrono::Duration {
+        let secs = self
+            .base_backoff_secs
+            .saturating_mul(1 << attempts.saturating_sub(1).min(32))
+            .min(self.max_backoff_secs);
+        chrono::Duration::seconds(secs.try_into().unwrap_or(i64::MAX))
+    }
+}
+
+/// Periodically delivers the due messages of the outbox. A message whose
+/// delivery fails is retried with backoff until it runs out of attempts.
+pub async fn run(app: web::Data<AppContext>, config: OutboxConfig) {
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval_secs));
+    loop {
+        interval.tick().await;
+        if let Err(err) = dispatch_outbox(&app, &config).await {
+            error!("dispatch_outbox: {err:?}");
+        }
+    }
+}
+
+#[instrument(skip_all)]
+async fn dispatch_outbox(app: &AppContext, config: &OutboxConfig) -> anyhow::Result<()> {
+    let now = Utc::now();
+    let lease_until = now + chrono::Duration::seconds(config.lease_secs.try_into()?);
+    let messages = app
+        .db_client
+        .claim_outbox_messages::<OutboxMessage>(now, lease_until, config.batch_size)
+        .await?;
+
+    for mut message in messages {
+        let message_id = message.message_id;
+        match deliver(app, &message.effect).await {
+            Ok(()) => message.record_delivery(),
+            Err(err) => {
+                let retry_at = (message.attempts + 1 < config.max_attempts)
+                    .then(|| Utc::now() + config.backoff(message.attempts + 1));
+                message.record_failure(format!("{err:#}"), retry_at);
+                warn!(%message_id, attempts = message.attempts, "failed to deliver outbox message: {err:?}");
+            }
+        }
+        if message.status == OutboxStatus::Dead {
+            error!(%message_id, "outbox message is dead after {} attempts", message.attempts);
+        }
+        app.db_client.update_outbox_message(message).await?;
+    }
+    Ok(())
+}
+
+async fn deliver(app: &AppContext, effect: &OutboxEffect) -> anyhow::Result<()> {
+    match effect {
+        OutboxEffect::Email { to, subject, body } => {
+            app.notifier.send(to, subject, body.clone()).await?;
+            info!(%subject, "outbox email sent");
+        }
+        OutboxEffect::Payout { payment_id } => {
+            create_requested_payout(app, *payment_id).await?;
+            info!(%payment_id, "outbox payout created");
+        }
+        OutboxEffect::Refund { payment_id } => {
+            create_requested_refund(app, *payment_id).await?;
+            info!(%payment_id, "outbox refund created");
+        }
+        OutboxEffect::Settle {
+            payment_id,
+            payer_accounts,
+        } => {
+            settle_queued_payment(app, *payment_id, payer_accounts).await?;
+            info!(%payment_id, "outbox payment settled");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use domain::PaymentId;
+
+    use super::*;
+
+    async fn message(app: &AppContext, message_id: uuid::Uuid) -> OutboxMessage {
+        app.db_client
+            .get_outbox_message(message_id)
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_max() {
+        let config = OutboxConfig::default();
+        assert_eq!(config.backoff(1), chrono::Duration::seconds(30));
+        assert_eq!(config.backoff(2), chrono::Duration::seconds(60));
+        assert_eq!(config.backoff(4), chrono::Duration::seconds(240));
+        assert_eq!(config.backoff(20), chrono::Duration::seconds(3600));
+        assert_eq!(config.backoff(u32::MAX), chrono::Duration::seconds(3600));
+    }
+
+    #[actix_web::test]
+    async fn failed_messages_are_retried_later_then_given_up_on() {
+        let app = AppContext::for_tests();
+        let config = OutboxConfig {
+            max_attempts: 2,
+            ..OutboxConfig::default()
+        };
+        let email = OutboxMessage::new(
+            None,
+            OutboxEffect::email("payee@example.com", "Hello", String::from("Hi")),
+        );
+        // the payment of the refund does not exist, so it always fails
+        let payment_id = PaymentId::new();
+        let refund = OutboxMessage::new(Some(payment_id), OutboxEffect::Refund { payment_id });
+        let (email_id, refund_id) = (email.message_id, refund.message_id);
+        app.db_client
+            .insert_outbox_messages([email, refund])
+            .await
+            .unwrap();
+
+        dispatch_outbox(&app, &config).await.unwrap();
+        assert_eq!(
+            message(&app, email_id).await.status,
+            OutboxStatus::Delivered
+        );
+        let failed = message(&app, refund_id).await;
+        assert_eq!(failed.status, OutboxStatus::Pending);
+        assert_eq!(failed.attempts, 1);
+        assert!(failed.last_error.is_some());
+        assert!(failed.next_attempt_at > Utc::now() + chrono::Duration::seconds(20));
+
+        // not due again yet
+        dispatch_outbox(&app, &config).await.unwrap();
+        assert_eq!(message(&app, refund_id).await.attempts, 1);
+
+        let mut due = message(&app, refund_id).await;
+        due.next_attempt_at = Utc::now();
+        app.db_client.update_outbox_message(due).await.unwrap();
+        dispatch_outbox(&app, &config).await.unwrap();
+        let dead = message(&app, refund_id).await;
+        assert_eq!(dead.status, OutboxStatus::Dead);
+        assert_eq!(dead.attempts, 2);
+    }
+}
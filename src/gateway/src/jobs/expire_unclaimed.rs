@@ -0,0 +1,140 @@
+use std::time::Duration;
+
+use actix_web::web;
+use chrono::Utc;
+use domain::{EventSource, Payment, PaymentEvent};
+use serde::Deserialize;
+use tracing::{error, info, instrument, warn};
+
+use crate::{refund::refund_message, AppContext};
+
+const BATCH_SIZE: i64 = 50;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ExpiryConfig {
+    /// Days a settled payment can be deposited before it is refunded.
+    pub claim_window_days: u32,
+    pub interval_secs: u64,
+}
+
+impl Default for ExpiryConfig {
+    fn default() -> Self {
+        Self {
+            claim_window_days: 30,
+            interval_secs: 3600,
+        }
+    }
+}
+
+/// Periodically expires settled payments that were not deposited within the
+/// claim window and refunds them to the payer.
+pub async fn run(app: web::Data<AppContext>, config: ExpiryConfig) {
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval_secs));
+    loop {
+        interval.tick().await;
+        if let Err(err) = expire_unclaimed_payments(&app, &config).await {
+            error!("expire_unclaimed_payments: {err:?}");
+        }
+    }
+}
+
+#[instrument(skip_all)]
+async fn expire_unclaimed_payments(app: &AppContext, config: &ExpiryConfig) -> anyhow::Result<()> {
+    let settled_before = Utc::now() - chrono::Duration::days(config.claim_window_days.into());
+    let payments = app
+        .db_client
+        .get_unclaimed_payments::<Payment>(settled_before, BATCH_SIZE)
+        .await?;
+
+    for (payment, version) in payments {
+        let payment_id = payment.payment_id;
+        match expire_and_refund(app, payment, version).await {
+            Ok(()) => info!(%payment_id, "unclaimed payment expired, refund queued"),
+            Err(err) => warn!(%payment_id, "failed to expire payment: {err:?}"),
+        }
+    }
+    Ok(())
+}
+
+async fn expire_and_refund(
+    app: &AppContext,
+    mut payment: Payment,
+    version: u32,
+) -> anyhow::Result<()> {
+    let payment_id = payment.payment_id;
+    let event = payment.transition(
+        PaymentEvent::Expired {
+            expired_at: Utc::now(),
+        },
+        EventSource::System,
+    )?;
+    app.db_client
+        .upsert_payment_with_outbox(
+            payment,
+            version + 1,
+            event,
+            vec![refund_message(payment_id)],
+        )
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use domain::{OutboxEffect, OutboxMessage, OutboxStatus, PaymentId, PaymentState};
+
+    use super::*;
+
+    async fn store_settled(app: &AppContext, settled_at: DateTime<Utc>) -> PaymentId {
+        let mut payment = Payment::test_fixture();
+        let payment_id = payment.payment_id;
+        let event = payment
+            .transition(
+                PaymentEvent::InboundSettled { settled_at },
+                EventSource::Webhook,
+            )
+            .unwrap();
+        app.db_client
+            .upsert_payment(payment, 1, event)
+            .await
+            .unwrap();
+        payment_id
+    }
+
+    async fn state(app: &AppContext, payment_id: PaymentId) -> PaymentState {
+        let (payment, _) = app
+            .db_client
+            .get_payment::<Payment>(payment_id)
+            .await
+            .unwrap()
+            .unwrap();
+        payment.state()
+    }
+
+    #[actix_web::test]
+    async fn unclaimed_payments_expire_with_one_refund_queued() {
+        let app = AppContext::for_tests();
+        let config = ExpiryConfig::default();
+        let window = chrono::Duration::days(config.claim_window_days.into());
+        let unclaimed = store_settled(&app, Utc::now() - window - chrono::Duration::hours(1)).await;
+        let recent = store_settled(&app, Utc::now()).await;
+
+        expire_unclaimed_payments(&app, &config).await.unwrap();
+        expire_unclaimed_payments(&app, &config).await.unwrap();
+
+        assert_eq!(state(&app, unclaimed).await, PaymentState::Expired);
+        assert_eq!(state(&app, recent).await, PaymentState::InboundSettled);
+        let messages: Vec<OutboxMessage> = app
+            .db_client
+            .get_outbox_messages(OutboxStatus::Pending.as_str(), 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(messages.len(), 1);
+        assert!(matches!(
+            messages[0].effect,
+            OutboxEffect::Refund { payment_id } if payment_id == unclaimed
+        ));
+    }
+}
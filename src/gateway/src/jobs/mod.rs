@@ -0,0 +1,13 @@
+pub mod dispatch_outbox;
+pub mod expire_unclaimed;
+pub mod retention;
+pub mod rotate_keys;
+pub mod scheduled_transfers;
+pub mod upcast_payments;
+
+pub use dispatch_outbox::OutboxConfig;
+pub use expire_unclaimed::ExpiryConfig;
+pub use retention::RetentionConfig;
+pub use rotate_keys::KeyRotationConfig;
+pub use scheduled_transfers::SchedulerConfig;
+pub use upcast_payments::UpcastConfig;
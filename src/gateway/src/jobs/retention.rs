@@ -0,0 +1,156 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use actix_web::web;
+use chrono::Utc;
+use domain::{EventSource, Payment, PaymentState};
+use serde::Deserialize;
+use tracing::{error, info, instrument, warn};
+
+use crate::AppContext;
+
+const DAYS_PER_YEAR: u32 = 365;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RetentionConfig {
+    pub enabled: bool,
+    pub interval_secs: u64,
+    pub batch_size: i64,
+    /// Days a payment is kept in a final state before its personal data is
+    /// erased, by state. Payments in states left out are kept as they are.
+    pub retention_days: BTreeMap<PaymentState, u32>,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 3600,
+            batch_size: 100,
+            retention_days: BTreeMap::from([
+                (PaymentState::InboundFailed, DAYS_PER_YEAR),
+                (PaymentState::PayoutExecuted, 6 * DAYS_PER_YEAR),
+                (PaymentState::RefundExecuted, 6 * DAYS_PER_YEAR),
+            ]),
+        }
+    }
+}
+
+/// Periodically erases the personal data of payments that have been in a
+/// final state for longer than its retention period, keeping their amounts,
+/// statuses and history.
+pub async fn run(app: web::Data<AppContext>, config: RetentionConfig) {
+    if !config.enabled {
+        return;
+    }
+    for state in config.retention_days.keys() {
+        if !state.is_final() {
+            warn!(
+                state = state.as_str(),
+                "retention period of a state that is not final is ignored"
+            );
+        }
+    }
+
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval_secs));
+    loop {
+        interval.tick().await;
+        if let Err(err) = erase_expired_payments(&app, &config).await {
+            error!("erase_expired_payments: {err:?}");
+        }
+    }
+}
+
+#[instrument(skip_all)]
+async fn erase_expired_payments(app: &AppContext, config: &RetentionConfig) -> anyhow::Result<()> {
+    for (state, days) in &config.retention_days {
+        if !state.is_final() {
+            continue;
+        }
+        let now = Utc::now();
+        let updated_before = now - chrono::Duration::days((*days).into());
+        let payments = app
+            .db_client
+            .get_payments_to_erase::<Payment>(state.as_str(), updated_before, config.batch_size)
+            .await?;
+
+        for (mut payment, version) in payments {
+            let payment_id = payment.payment_id;
+            let event = payment.erase_personal_data(EventSource::System, now);
+            match app
+                .db_client
+                .upsert_payment(payment, version + 1, event)
+                .await
+            {
+                Ok(()) => info!(%payment_id, "personal data of payment erased"),
+                Err(err) => warn!(%payment_id, "failed to erase payment: {err:?}"),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use domain::PaymentEvent;
+
+    use super::*;
+
+    async fn store_payment(app: &AppContext, event: PaymentEvent) -> Payment {
+        let mut payment = Payment::test_fixture();
+        let event = payment.transition(event, EventSource::Webhook).unwrap();
+        app.db_client
+            .upsert_payment(payment.clone(), 1, event)
+            .await
+            .unwrap();
+        payment
+    }
+
+    async fn is_erased(app: &AppContext, payment: &Payment) -> bool {
+        let (payment, _) = app
+            .db_client
+            .get_payment::<Payment>(payment.payment_id)
+            .await
+            .unwrap()
+            .unwrap();
+        payment.personal_data_erased_at.is_some()
+    }
+
+    #[actix_web::test]
+    async fn only_final_payments_past_retention_are_erased() {
+        let app = AppContext::for_tests();
+        let failed = store_payment(
+            &app,
+            PaymentEvent::InboundFailed {
+                failed_at: Utc::now(),
+                failure: None,
+            },
+        )
+        .await;
+        let settled = store_payment(
+            &app,
+            PaymentEvent::InboundSettled {
+                settled_at: Utc::now(),
+            },
+        )
+        .await;
+
+        erase_expired_payments(&app, &RetentionConfig::default())
+            .await
+            .unwrap();
+        assert!(!is_erased(&app, &failed).await);
+
+        // a retention period of none leaves every final payment due, and
+        // states that are not final are never erased
+        let config = RetentionConfig {
+            retention_days: BTreeMap::from([
+                (PaymentState::InboundFailed, 0),
+                (PaymentState::InboundSettled, 0),
+            ]),
+            ..RetentionConfig::default()
+        };
+        erase_expired_payments(&app, &config).await.unwrap();
+        assert!(is_erased(&app, &failed).await);
+        assert!(!is_erased(&app, &settled).await);
+    }
+}
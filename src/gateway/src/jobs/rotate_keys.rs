@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use actix_web::web;
+use serde::Deserialize;
+use tracing::{error, info};
+
+use crate::AppContext;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct KeyRotationConfig {
+    pub enabled: bool,
+    pub batch_size: i64,
+    /// Pause between batches, to keep the rewrite from crowding out requests.
+    pub pause_millis: u64,
+}
+
+impl Default for KeyRotationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            batch_size: 100,
+            pause_millis: 200,
+        }
+    }
+}
+
+/// Re-encrypts the personal data of payments and users that is not encrypted
+/// with the active key, batch by batch, and stops once none is left. Switching
+/// the active key and restarting rotates every row to it, after which the
+/// retired key can be dropped from the config.
+pub async fn run(app: web::Data<AppContext>, config: KeyRotationConfig) {
+    if !config.enabled {
+        return;
+    }
+
+    let mut payments = 0;
+    loop {
+        match app.db_client.rotate_payment_keys(config.batch_size).await {
+            Ok(0) => break,
+            Ok(count) => payments += count,
+            Err(err) => {
+                error!("rotate_payment_keys: {err:?}");
+                return;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(config.pause_millis)).await;
+    }
+
+    let mut users = 0;
+    loop {
+        match app.db_client.rotate_user_keys(config.batch_size).await {
+            Ok(0) => break,
+            Ok(count) => users += count,
+            Err(err) => {
+                error!("rotate_user_keys: {err:?}");
+                return;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(config.pause_millis)).await;
+    }
+
+    if payments + users > 0 {
+        info!(payments, users, "rows re-encrypted with the active key");
+    }
+}
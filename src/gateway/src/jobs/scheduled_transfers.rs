@@ -0,0 +1,152 @@
+use std::time::Duration;
+
+use actix_web::web;
+use anyhow::Context;
+use chrono::Utc;
+use domain::{EventSource, OutboxEffect, OutboxMessage, ScheduledTransfer, User};
+use serde::Deserialize;
+use tracing::{error, info, instrument, warn};
+
+use crate::{
+    app::schedule_flow::SCHEDULES_PAGE,
+    limits::{LimitError, PayerTier},
+    payment::{create_inbound_payment, NewPayment},
+    AppContext,
+};
+
+const BATCH_SIZE: i64 = 50;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SchedulerConfig {
+    pub interval_secs: u64,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self { interval_secs: 60 }
+    }
+}
+
+/// Periodically creates the payments of due scheduled transfers and emails
+/// the payer the link to authorize them.
+///
+/// Recurring transfers are sent as individual single payments, TrueLayer
+/// mandates are not supported by [`truelayer::TlClient`] yet.
+pub async fn run(app: web::Data<AppContext>, config: SchedulerConfig) {
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval_secs));
+    loop {
+        interval.tick().await;
+        if let Err(err) = run_due_schedules(&app).await {
+            error!("run_due_schedules: {err:?}");
+        }
+    }
+}
+
+#[instrument(skip_all)]
+async fn run_due_schedules(app: &AppContext) -> anyhow::Result<()> {
+    let schedules = app
+        .db_client
+        .get_due_scheduled_transfers::<ScheduledTransfer>(Utc::now(), BATCH_SIZE)
+        .await?;
+
+    for (schedule, version) in schedules {
+        let schedule_id = schedule.schedule_id;
+        match run_schedule(app, schedule, version).await {
+            Ok(()) => info!(%schedule_id, "scheduled transfer sent"),
+            Err(err) => warn!(%schedule_id, "failed to send scheduled transfer: {err:?}"),
+        }
+    }
+    Ok(())
+}
+
+async fn run_schedule(
+    app: &AppContext,
+    mut schedule: ScheduledTransfer,
+    mut version: u32,
+) -> anyhow::Result<()> {
+    let (payer, _) = app
+        .db_client
+        .get_user::<User>(schedule.user_id.into_uuid())
+        .await?
+        .context("payer not found")?;
+
+    // claim the occurrence before paying so a failure skips it rather than
+    // sending the transfer twice
+    schedule.record_run(Utc::now());
+    version += 1;
+    app.db_client
+        .upsert_scheduled_transfer(schedule.clone(), version)
+        .await?;
+
+    let payer_email = payer.email().to_owned();
+    if let Err(err) = app
+        .limits
+        .check(
+            &app.db_client,
+            PayerTier::Registered,
+            &payer_email,
+            &schedule.payee_email,
+            schedule.amount,
+        )
+        .await
+    {
+        if let LimitError::Db(err) = err {
+            return Err(err.into());
+        }
+        let body = format!(
+            "Your scheduled transfer of {} to {} was skipped this time. {}\n\n\
+            Manage your scheduled transfers at {}",
+            schedule.amount,
+            schedule.payee_full_name,
+            err,
+            app.public_link(SCHEDULES_PAGE),
+        );
+        app.db_client
+            .insert_outbox_messages([OutboxMessage::new(
+                None,
+                OutboxEffect::email(&payer_email, "Scheduled transfer skipped", body),
+            )])
+            .await?;
+        return Err(err.into());
+    }
+
+    let created = create_inbound_payment(
+        app,
+        NewPayment {
+            payer_full_name: format!("{} {}", payer.first_name(), payer.last_name()),
+            payer_email,
+            payee_full_name: schedule.payee_full_name.clone(),
+            payee_email: schedule.payee_email.clone(),
+            amount: schedule.amount,
+            security_question: schedule.security_question.clone(),
+            security_answer: schedule.security_answer.clone(),
+            message: None,
+            payment_request_id: None,
+        },
+        EventSource::System,
+    )
+    .await
+    .context("occurrence skipped, payment not created")?;
+
+    schedule.last_payment_id = Some(created.payment_id);
+    app.db_client
+        .upsert_scheduled_transfer(schedule.clone(), version + 1)
+        .await?;
+
+    let body = format!(
+        "Your scheduled transfer of {} to {} is ready. Authorize it here:\n\n{}\n\n\
+        Manage your scheduled transfers at {}",
+        schedule.amount,
+        schedule.payee_full_name,
+        created.auth_link,
+        app.public_link(SCHEDULES_PAGE),
+    );
+    app.db_client
+        .insert_outbox_messages([OutboxMessage::new(
+            Some(created.payment_id),
+            OutboxEffect::email(payer.email(), "Authorize your scheduled transfer", body),
+        )])
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+use actix_web::web;
+use serde::Deserialize;
+use tracing::{error, info};
+
+use crate::AppContext;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct UpcastConfig {
+    pub enabled: bool,
+    pub batch_size: i64,
+    /// Pause between batches, to keep the rewrite from crowding out requests.
+    pub pause_millis: u64,
+}
+
+impl Default for UpcastConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            batch_size: 100,
+            pause_millis: 200,
+        }
+    }
+}
+
+/// Rewrites payments still stored in an older data shape to the latest one,
+/// batch by batch, and stops once none are left. Payments are read in either
+/// shape, so this only saves upcasting them on every read.
+pub async fn run(app: web::Data<AppContext>, config: UpcastConfig) {
+    if !config.enabled {
+        return;
+    }
+
+    let mut total = 0;
+    loop {
+        match app.db_client.upcast_payments(config.batch_size).await {
+            Ok(0) => break,
+            Ok(count) => total += count,
+            Err(err) => {
+                error!("upcast_payments: {err:?}");
+                return;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(config.pause_millis)).await;
+    }
+
+    if total > 0 {
+        info!(total, "payments rewritten to the latest data shape");
+    }
+}
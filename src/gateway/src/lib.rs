@@ -1,49 +1,233 @@
 mod api;
 mod app;
+mod autodeposit;
+mod jobs;
+mod limits;
 pub mod log;
+mod notify;
+mod payment;
+mod payment_request;
+mod payout;
+mod personal_data;
+mod refund;
+mod risk;
+mod settlement;
 
 use actix_web::{
     cookie::Key, http::header, middleware::Logger, web, App, HttpResponse, HttpServer,
 };
 use actix_web_opentelemetry::RequestTracing;
 use anyhow::Context;
-use db::DbClient;
+use db::{BlindIndex, PgClient};
+use domain::LockoutPolicy;
 use log::DomainRootSpanBuilder;
+use notify::Notifier;
+use risk::RiskEngine;
 use serde::Deserialize;
+use tracing::info;
 use tracing_actix_web::TracingLogger;
 
-pub use db::DbConfig;
+pub use api::tl_webhooks::WebhookConfig;
+pub use db::{DbClient, DbConfig, EncryptionConfig};
+pub use jobs::{
+    ExpiryConfig, KeyRotationConfig, OutboxConfig, RetentionConfig, SchedulerConfig, UpcastConfig,
+};
+pub use limits::LimitsConfig;
+pub use notify::EmailConfig;
+pub use risk::RiskConfig;
 pub use truelayer::{TlClient, TlConfig, TlEnviorment};
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct AppConfig {
     pub http_port: u16,
+    /// Number of http worker threads, all sharing the database pool.
+    #[serde(default = "default_http_workers")]
+    pub http_workers: usize,
+    /// Base url of the app used in emailed links, defaults to localhost.
+    #[serde(default)]
+    pub public_url: Option<String>,
     pub db_config: DbConfig,
+    /// Apply pending database migrations before serving, refusing to start
+    /// if the applied ones no longer match this build.
+    #[serde(default = "default_migrate_on_start")]
+    pub migrate_on_start: bool,
     pub tl_config: TlConfig,
+    #[serde(default)]
+    pub expiry_config: ExpiryConfig,
+    #[serde(default)]
+    pub scheduler_config: SchedulerConfig,
+    #[serde(default)]
+    pub upcast_config: UpcastConfig,
+    #[serde(default)]
+    pub outbox_config: OutboxConfig,
+    #[serde(default)]
+    pub key_rotation_config: KeyRotationConfig,
+    #[serde(default)]
+    pub retention_config: RetentionConfig,
+    #[serde(default)]
+    pub webhook_config: WebhookConfig,
+    #[serde(default)]
+    pub email_config: Option<EmailConfig>,
+    #[serde(default)]
+    pub lockout_config: LockoutConfig,
+    #[serde(default)]
+    pub limits_config: LimitsConfig,
+    #[serde(default)]
+    pub risk_config: RiskConfig,
+}
+
+fn default_http_workers() -> usize {
+    1
+}
+
+fn default_migrate_on_start() -> bool {
+    true
+}
+
+/// How many wrong security answers a deposit tolerates before it is locked,
+/// and for how long.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LockoutConfig {
+    pub max_attempts: u32,
+    pub cooldown_minutes: i64,
+}
+
+impl Default for LockoutConfig {
+    fn default() -> Self {
+        LockoutConfig {
+            max_attempts: 5,
+            cooldown_minutes: 30,
+        }
+    }
+}
+
+impl LockoutConfig {
+    pub fn policy(&self) -> LockoutPolicy {
+        LockoutPolicy {
+            max_attempts: self.max_attempts,
+            cooldown: chrono::Duration::minutes(self.cooldown_minutes),
+        }
+    }
 }
 
 pub struct AppContext {
+    public_url: String,
     db_client: DbClient,
     tl_client: TlClient,
+    notifier: Notifier,
+    lockout_policy: LockoutPolicy,
+    limits: LimitsConfig,
+    risk_engine: RiskEngine,
+    webhook_config: WebhookConfig,
 }
 
 impl AppContext {
+    /// The app backed by the postgres of `config`, migrated first unless
+    /// `migrate_on_start` is off.
     pub async fn init(config: AppConfig) -> anyhow::Result<Self> {
+        let pg_client = PgClient::connect(config.db_config.clone())
+            .await
+            .context("postgres connection")?;
+        if config.migrate_on_start {
+            let applied = pg_client.migrate().await.context("database migrations")?;
+            info!(?applied, "database migrated");
+        }
+        Self::with_db_client(config, DbClient::postgres(pg_client)).await
+    }
+
+    /// The app backed by `db_client`, such as [`DbClient::in_memory`], leaving
+    /// the connection settings of `config` unused.
+    pub async fn with_db_client(config: AppConfig, db_client: DbClient) -> anyhow::Result<Self> {
         Ok(AppContext {
-            db_client: DbClient::connect(config.db_config)
-                .await
-                .context("postgres connection")?,
+            public_url: config
+                .public_url
+                .unwrap_or_else(|| format!("http://localhost:{}", config.http_port)),
+            db_client,
             tl_client: TlClient::new(config.tl_config)
                 .await
                 .context("truelayer connection")?,
+            notifier: Notifier::new(
+                config.email_config,
+                BlindIndex::new(config.db_config.encryption.as_ref()).context("index key")?,
+            )
+            .context("email notifier")?,
+            lockout_policy: config.lockout_config.policy(),
+            limits: config.limits_config,
+            risk_engine: RiskEngine::from_config(&config.risk_config),
+            webhook_config: config.webhook_config,
         })
     }
+
+    /// An in-memory app with default settings, its truelayer client never
+    /// reaching a real environment.
+    #[cfg(test)]
+    pub(crate) fn for_tests() -> Self {
+        AppContext {
+            public_url: String::from("http://localhost:8080"),
+            db_client: DbClient::in_memory(),
+            tl_client: TlClient::unauthenticated(TlConfig {
+                enviornment: TlEnviorment::Mock {
+                    url: String::from("tl.invalid"),
+                },
+                client_id: String::new(),
+                client_secret: String::new(),
+                kid: String::new(),
+                private_key: String::new(),
+                redirect_uri: String::new(),
+                data_redirect_uri: String::new(),
+                merchant_account_id: uuid::Uuid::nil(),
+                eur_merchant_account_id: None,
+            }),
+            notifier: Notifier::new(None, BlindIndex::default()).expect("default notifier"),
+            lockout_policy: LockoutConfig::default().policy(),
+            limits: LimitsConfig::default(),
+            risk_engine: RiskEngine::from_config(&RiskConfig::default()),
+            webhook_config: WebhookConfig::default(),
+        }
+    }
+
+    /// Absolute url of an app `path`, for links sent outside the browser.
+    fn public_link(&self, path: &str) -> String {
+        format!("{}{}", self.public_url.trim_end_matches('/'), path)
+    }
 }
 
 pub async fn start(config: AppConfig) -> anyhow::Result<()> {
-    let app_context = web::Data::new(AppContext::init(config.clone()).await?);
+    let app_context = AppContext::init(config.clone()).await?;
+    serve(config, app_context).await
+}
+
+/// Runs the background jobs and http server of `app_context`.
+pub async fn serve(config: AppConfig, app_context: AppContext) -> anyhow::Result<()> {
+    let app_context = web::Data::new(app_context);
     let secret_key = Key::generate();
 
+    actix_web::rt::spawn(jobs::expire_unclaimed::run(
+        app_context.clone(),
+        config.expiry_config.clone(),
+    ));
+    actix_web::rt::spawn(jobs::scheduled_transfers::run(
+        app_context.clone(),
+        config.scheduler_config.clone(),
+    ));
+    actix_web::rt::spawn(jobs::upcast_payments::run(
+        app_context.clone(),
+        config.upcast_config.clone(),
+    ));
+    actix_web::rt::spawn(jobs::dispatch_outbox::run(
+        app_context.clone(),
+        config.outbox_config.clone(),
+    ));
+    actix_web::rt::spawn(jobs::rotate_keys::run(
+        app_context.clone(),
+        config.key_rotation_config.clone(),
+    ));
+    actix_web::rt::spawn(jobs::retention::run(
+        app_context.clone(),
+        config.retention_config.clone(),
+    ));
+
     let http_server = HttpServer::new(move || {
         App::new()
             .app_data(app_context.clone())
@@ -57,13 +241,14 @@ pub async fn start(config: AppConfig) -> anyhow::Result<()> {
             .service(app::admin::admin_scope())
             .service(
                 web::scope("/api")
+                    .wrap(app::session_middleware(secret_key.clone()))
                     .service(api::deposit_payment::deposit_payment)
                     .service(api::tl_webhooks::tl_webhook),
             )
             .default_service(web::to(app::not_found))
     })
     .bind(("0.0.0.0", config.http_port))?
-    .workers(1)
+    .workers(config.http_workers)
     .run();
 
     http_server.await.context("http_server")
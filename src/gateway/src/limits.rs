@@ -0,0 +1,362 @@
+use chrono::{Duration, Utc};
+use db::{error::DbError, DbClient};
+use domain::Money;
+use serde::Deserialize;
+
+/// Server-side transfer limits. Amounts are in minor units of the payment's
+/// currency and totals are only summed over payments in that currency.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LimitsConfig {
+    /// Payers who are not signed in to an account with their email.
+    pub anonymous: PayerLimits,
+    pub registered: PayerLimits,
+    /// Applied to everything received by a single payee email.
+    pub payee: PeriodLimits,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        LimitsConfig {
+            anonymous: PayerLimits {
+                min_amount: 100,
+                max_amount: 100_000,
+                limits: PeriodLimits {
+                    daily: VelocityLimit {
+                        max_total: 200_000,
+                        max_count: 5,
+                    },
+                    weekly: VelocityLimit {
+                        max_total: 500_000,
+                        max_count: 15,
+                    },
+                },
+            },
+            registered: PayerLimits {
+                min_amount: 100,
+                max_amount: 500_000,
+                limits: PeriodLimits {
+                    daily: VelocityLimit {
+                        max_total: 1_000_000,
+                        max_count: 20,
+                    },
+                    weekly: VelocityLimit {
+                        max_total: 2_500_000,
+                        max_count: 50,
+                    },
+                },
+            },
+            payee: PeriodLimits {
+                daily: VelocityLimit {
+                    max_total: 1_000_000,
+                    max_count: 20,
+                },
+                weekly: VelocityLimit {
+                    max_total: 2_500_000,
+                    max_count: 50,
+                },
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct PayerLimits {
+    pub min_amount: u32,
+    pub max_amount: u32,
+    #[serde(flatten)]
+    pub limits: PeriodLimits,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct PeriodLimits {
+    pub daily: VelocityLimit,
+    pub weekly: VelocityLimit,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct VelocityLimit {
+    pub max_total: u32,
+    pub max_count: u32,
+}
+
+/// Which limits a payer falls under.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayerTier {
+    Anonymous,
+    Registered,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum LimitError {
+    #[error("The minimum transfer is {0}.")]
+    BelowMinimum(Money),
+    #[error("The maximum transfer is {0}.")]
+    AboveMaximum(Money),
+    #[error("This transfer would take you over your {period} limit of {max}.")]
+    PayerTotal { period: &'static str, max: Money },
+    #[error("You can send at most {max} transfers per {period}.")]
+    PayerCount { period: &'static str, max: u32 },
+    #[error("The recipient cannot receive more than {max} per {period}.")]
+    PayeeTotal { period: &'static str, max: Money },
+    #[error("The recipient cannot receive more than {max} transfers per {period}.")]
+    PayeeCount { period: &'static str, max: u32 },
+    #[error(transparent)]
+    Db(#[from] DbError),
+}
+
+impl LimitsConfig {
+    /// The lowest amount any payer may send, for hints on the payment form.
+    pub fn min_amount(&self) -> u32 {
+        self.anonymous.min_amount.min(self.registered.min_amount)
+    }
+
+    /// Checks a new payment of `amount` from `payer_email` to `payee_email`
+    /// against the limits of the payer's tier and of the payee.
+    pub async fn check(
+        &self,
+        db_client: &DbClient,
+        tier: PayerTier,
+        payer_email: &str,
+        payee_email: &str,
+        amount: Money,
+    ) -> Result<(), LimitError> {
+        let payer = match tier {
+            PayerTier::Anonymous => self.anonymous,
+            PayerTier::Registered => self.registered,
+        };
+        let money = |amount_in_minor| Money::new(amount_in_minor, amount.currency);
+        if amount.amount_in_minor < payer.min_amount {
+            return Err(LimitError::BelowMinimum(money(payer.min_amount)));
+        }
+        if amount.amount_in_minor > payer.max_amount {
+            return Err(LimitError::AboveMaximum(money(payer.max_amount)));
+        }
+
+        let now = Utc::now();
+        let currency = amount.currency.as_str();
+        for (period, since, payer_limit, payee_limit) in [
+            (
+                "day",
+                now - Duration::days(1),
+                payer.limits.daily,
+                self.payee.daily,
+            ),
+            (
+                "week",
+                now - Duration::weeks(1),
+                payer.limits.weekly,
+                self.payee.weekly,
+            ),
+        ] {
+            let totals = db_client
+                .get_payer_totals(payer_email, currency, since)
+                .await?;
+            if totals.count + 1 > i64::from(payer_limit.max_count) {
+                return Err(LimitError::PayerCount {
+                    period,
+                    max: payer_limit.max_count,
+                });
+            }
+            if totals.amount_in_minor + i64::from(amount.amount_in_minor)
+                > i64::from(payer_limit.max_total)
+            {
+                return Err(LimitError::PayerTotal {
+                    period,
+                    max: money(payer_limit.max_total),
+                });
+            }
+
+            let totals = db_client
+                .get_payee_totals(payee_email, currency, since)
+                .await?;
+            if totals.count + 1 > i64::from(payee_limit.max_count) {
+                return Err(LimitError::PayeeCount {
+                    period,
+                    max: payee_limit.max_count,
+                });
+            }
+            if totals.amount_in_minor + i64::from(amount.amount_in_minor)
+                > i64::from(payee_limit.max_total)
+            {
+                return Err(LimitError::PayeeTotal {
+                    period,
+                    max: money(payee_limit.max_total),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use domain::{Currency, EventSource, Payment, PaymentEventRecord, PaymentState};
+
+    use super::*;
+
+    const PAYER: &str = "payer@example.com";
+    const PAYEE: &str = "payee@example.com";
+
+    fn config() -> LimitsConfig {
+        let limit = |max_total, max_count| VelocityLimit {
+            max_total,
+            max_count,
+        };
+        LimitsConfig {
+            anonymous: PayerLimits {
+                min_amount: 100,
+                max_amount: 1_000,
+                limits: PeriodLimits {
+                    daily: limit(2_000, 3),
+                    weekly: limit(5_000, 10),
+                },
+            },
+            registered: PayerLimits {
+                min_amount: 100,
+                max_amount: 5_000,
+                limits: PeriodLimits {
+                    daily: limit(10_000, 10),
+                    weekly: limit(20_000, 20),
+                },
+            },
+            payee: PeriodLimits {
+                daily: limit(8_000, 6),
+                weekly: limit(20_000, 20),
+            },
+        }
+    }
+
+    fn gbp(amount_in_minor: u32) -> Money {
+        Money::new(amount_in_minor, Currency::Gbp)
+    }
+
+    async fn store_payment(
+        db_client: &DbClient,
+        payer_email: &str,
+        payee_email: &str,
+        amount: Money,
+    ) {
+        let payment = Payment {
+            payer_email: payer_email.into(),
+            payee_email: payee_email.into(),
+            amount,
+            ..Payment::test_fixture()
+        };
+        let payment_id = payment.payment_id;
+        let event = PaymentEventRecord::new(
+            payment_id,
+            PaymentState::InboundCreated.as_str(),
+            EventSource::Ui,
+            None,
+            PaymentState::InboundCreated,
+        );
+        db_client.upsert_payment(payment, 0, event).await.unwrap();
+    }
+
+    async fn check(db_client: &DbClient, tier: PayerTier, amount: Money) -> Result<(), LimitError> {
+        config().check(db_client, tier, PAYER, PAYEE, amount).await
+    }
+
+    #[actix_web::test]
+    async fn amount_is_within_the_tier_bounds() {
+        let db_client = DbClient::in_memory();
+        assert!(matches!(
+            check(&db_client, PayerTier::Anonymous, gbp(99)).await,
+            Err(LimitError::BelowMinimum(_))
+        ));
+        assert!(matches!(
+            check(&db_client, PayerTier::Anonymous, gbp(1_001)).await,
+            Err(LimitError::AboveMaximum(_))
+        ));
+        assert!(check(&db_client, PayerTier::Anonymous, gbp(1_000))
+            .await
+            .is_ok());
+        assert!(check(&db_client, PayerTier::Registered, gbp(5_000))
+            .await
+            .is_ok());
+    }
+
+    #[actix_web::test]
+    async fn payer_count_depends_on_the_tier() {
+        let db_client = DbClient::in_memory();
+        for _ in 0..3 {
+            store_payment(&db_client, PAYER, "other@example.com", gbp(100)).await;
+        }
+        assert!(matches!(
+            check(&db_client, PayerTier::Anonymous, gbp(100)).await,
+            Err(LimitError::PayerCount {
+                period: "day",
+                max: 3
+            })
+        ));
+        assert!(check(&db_client, PayerTier::Registered, gbp(100))
+            .await
+            .is_ok());
+    }
+
+    #[actix_web::test]
+    async fn payer_total_includes_the_new_payment() {
+        let db_client = DbClient::in_memory();
+        store_payment(&db_client, PAYER, "other@example.com", gbp(1_000)).await;
+        assert!(check(&db_client, PayerTier::Anonymous, gbp(1_000))
+            .await
+            .is_ok());
+        store_payment(&db_client, PAYER, "other@example.com", gbp(500)).await;
+        assert!(matches!(
+            check(&db_client, PayerTier::Anonymous, gbp(501)).await,
+            Err(LimitError::PayerTotal { period: "day", .. })
+        ));
+    }
+
+    #[actix_web::test]
+    async fn payee_limits_count_every_payer() {
+        let db_client = DbClient::in_memory();
+        for i in 0..6 {
+            store_payment(
+                &db_client,
+                &format!("payer{i}@example.com"),
+                PAYEE,
+                gbp(100),
+            )
+            .await;
+        }
+        assert!(matches!(
+            check(&db_client, PayerTier::Registered, gbp(100)).await,
+            Err(LimitError::PayeeCount {
+                period: "day",
+                max: 6
+            })
+        ));
+    }
+
+    #[actix_web::test]
+    async fn totals_match_emails_ignoring_case_and_other_currencies() {
+        let db_client = DbClient::in_memory();
+        store_payment(
+            &db_client,
+            "Payer@Example.com",
+            "other@example.com",
+            gbp(1_500),
+        )
+        .await;
+        store_payment(
+            &db_client,
+            PAYER,
+            "other@example.com",
+            Money::new(1_000, Currency::Eur),
+        )
+        .await;
+        assert!(matches!(
+            check(&db_client, PayerTier::Anonymous, gbp(600)).await,
+            Err(LimitError::PayerTotal { period: "day", .. })
+        ));
+        assert!(check(
+            &db_client,
+            PayerTier::Anonymous,
+            Money::new(1_000, Currency::Eur)
+        )
+        .await
+        .is_ok());
+    }
+}
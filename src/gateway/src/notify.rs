@@ -0,0 +1,83 @@
+use anyhow::Context;
+use db::BlindIndex;
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+use serde::Deserialize;
+use tracing::{info, instrument};
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct EmailConfig {
+    pub smtp_host: String,
+    pub smtp_username: String,
+    pub smtp_password: String,
+    pub from: String,
+}
+
+/// Sends emails to payers and payees. Without an [`EmailConfig`] emails are
+/// only logged, which is what local and test environments use. Logs name the
+/// email by its subject and the recipient by the blind index of their email,
+/// never the address or the body.
+pub struct Notifier {
+    transport: Option<AsyncSmtpTransport<Tokio1Executor>>,
+    from: Mailbox,
+    recipient_index: BlindIndex,
+}
+
+impl Notifier {
+    const DEFAULT_FROM: &'static str = "e-transfer <no-reply@localhost>";
+
+    pub fn new(
+        email_config: Option<EmailConfig>,
+        recipient_index: BlindIndex,
+    ) -> anyhow::Result<Self> {
+        match email_config {
+            Some(email_config) => Ok(Notifier {
+                transport: Some(
+                    AsyncSmtpTransport::<Tokio1Executor>::relay(&email_config.smtp_host)
+                        .context("smtp relay")?
+                        .credentials(Credentials::new(
+                            email_config.smtp_username,
+                            email_config.smtp_password,
+                        ))
+                        .build(),
+                ),
+                from: email_config.from.parse().context("invalid from address")?,
+                recipient_index,
+            }),
+            None => Ok(Notifier {
+                transport: None,
+                from: Self::DEFAULT_FROM.parse().expect("valid default from"),
+                recipient_index,
+            }),
+        }
+    }
+
+    #[instrument(skip(self, to, body), fields(recipient = self.recipient(to)))]
+    pub async fn send(&self, to: &str, subject: &str, body: String) -> anyhow::Result<()> {
+        let Some(transport) = self.transport.as_ref() else {
+            info!("email not sent, no smtp transport configured");
+            return Ok(());
+        };
+
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to.parse().context("invalid recipient address")?)
+            .subject(subject)
+            .body(body)
+            .context("email message")?;
+
+        transport.send(message).await.context("smtp send")?;
+        Ok(())
+    }
+
+    /// The blind index of the recipient `to`, in hex.
+    fn recipient(&self, to: &str) -> String {
+        self.recipient_index
+            .of(to)
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+}
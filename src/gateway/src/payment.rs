@@ -0,0 +1,179 @@
+use anyhow::{anyhow, Context};
+use argon2::{
+    password_hash::{rand_core::OsRng, SaltString},
+    Argon2, PasswordHasher,
+};
+use chrono::Utc;
+use domain::{
+    DepositLock, EventSource, Money, OutboxEffect, OutboxMessage, Payment, PaymentEventRecord,
+    PaymentId, PaymentReference, PaymentRequestId, PaymentState, PaymentStatuses, User,
+    UserPayment, UserPaymentRole,
+};
+use tracing::error;
+
+use crate::{app::deposit_flow::DESPOSIT_CREATE_PAGE, log, AppContext};
+
+/// Everything needed to start a new inbound payment.
+#[derive(Debug)]
+pub struct NewPayment {
+    pub payer_full_name: String,
+    pub payer_email: String,
+    pub payee_full_name: String,
+    pub payee_email: String,
+    pub amount: Money,
+    pub security_question: String,
+    /// Argon2 hash of the answer, see [`hash_security_answer`].
+    pub security_answer: String,
+    pub message: Option<String>,
+    pub payment_request_id: Option<PaymentRequestId>,
+}
+
+pub struct CreatedPayment {
+    pub payment_id: PaymentId,
+    /// Hosted payment page where the payer authorizes the payment.
+    pub auth_link: String,
+}
+
+pub fn hash_security_answer(security_answer: &str) -> anyhow::Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(security_answer.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|err| anyhow!("hash security answer: {err}"))
+}
+
+/// The payer's message set apart for the end of an email, empty without one.
+pub fn message_note(payment: &Payment) -> String {
+    payment
+        .message
+        .as_ref()
+        .map(|message| format!("\n\nMessage from {}:\n{}", payment.payer_full_name, message))
+        .unwrap_or_default()
+}
+
+/// Creates the payment with TrueLayer, stores it and links it to the
+/// registered users it is sent from or to.
+pub async fn create_inbound_payment(
+    app: &AppContext,
+    new_payment: NewPayment,
+    source: EventSource,
+) -> anyhow::Result<CreatedPayment> {
+    let reference = PaymentReference::new(&format!("TO {}", new_payment.payee_full_name));
+    let payment = app
+        .tl_client
+        .create_ma_payment(
+            &new_payment.payer_full_name,
+            &new_payment.payer_email,
+            None,
+            new_payment.amount.amount_in_minor,
+            new_payment.amount.currency.as_str(),
+            reference.as_str(),
+        )
+        .await
+        .context("create truelayer payment")?;
+
+    let payment_id = PaymentId::from_uuid(payment.payment_id);
+    log::set_payment_id(payment_id);
+    log::set_payment_state(PaymentState::InboundCreated);
+
+    let payer_email = new_payment.payer_email.clone();
+    let payee_email = new_payment.payee_email.clone();
+    app.db_client
+        .upsert_payment(
+            Payment {
+                payment_id,
+                payer_full_name: new_payment.payer_full_name,
+                payer_email: new_payment.payer_email,
+                payee_full_name: new_payment.payee_full_name,
+                payee_email: new_payment.payee_email,
+                amount: new_payment.amount,
+                security_question: new_payment.security_question,
+                security_answer: new_payment.security_answer,
+                message: new_payment.message,
+                deposit_lock: DepositLock::default(),
+                cancellation_request: None,
+                payment_request_id: new_payment.payment_request_id,
+                risk_assessment: None,
+                failure: None,
+                payment_statuses: PaymentStatuses::new(Utc::now()),
+                payout_data: None,
+                refund_data: None,
+                payout_destination: None,
+                personal_data_erased_at: None,
+            },
+            0,
+            PaymentEventRecord::new(
+                payment_id,
+                PaymentState::InboundCreated.as_str(),
+                source,
+                None,
+                PaymentState::InboundCreated,
+            ),
+        )
+        .await?;
+
+    for (email, role) in [
+        (payer_email, UserPaymentRole::Payer),
+        (payee_email, UserPaymentRole::Payee),
+    ] {
+        if let Err(err) = link_registered_user(app, payment_id, &email, role).await {
+            error!(?err, role = role.as_str(), "failed to link payment to user");
+        }
+    }
+
+    let auth_link = format!(
+        "https://payment.truelayer-sandbox.com/payments#payment_id={}&resource_token={}&return_uri={}",
+        payment.payment_id,
+        payment.resource_token,
+        app.tl_client.return_uri()
+    );
+
+    Ok(CreatedPayment {
+        payment_id,
+        auth_link,
+    })
+}
+
+/// The email telling the payee a settled payment is waiting for them to
+/// deposit, sent through the outbox so it is retried if it fails.
+pub fn payment_received_message(app: &AppContext, payment: &Payment) -> OutboxMessage {
+    let link = app.public_link(&format!(
+        "{}?payment_id={}",
+        DESPOSIT_CREATE_PAGE, payment.payment_id
+    ));
+    OutboxMessage::new(
+        Some(payment.payment_id),
+        OutboxEffect::email(
+            &payment.payee_email,
+            "You received an e-transfer",
+            format!(
+                "{} sent you {}. Answer their security question to deposit it:\n\n{}{}",
+                payment.payer_full_name,
+                payment.amount,
+                link,
+                message_note(payment)
+            ),
+        ),
+    )
+}
+
+/// Records the payment against the registered user with `email`, if any.
+async fn link_registered_user(
+    app: &AppContext,
+    payment_id: PaymentId,
+    email: &str,
+    role: UserPaymentRole,
+) -> Result<(), db::error::DbError> {
+    if let Some((User::Registered { user_id, .. }, _)) =
+        app.db_client.get_user_by_email::<User>(email).await?
+    {
+        app.db_client
+            .link_user_payment(UserPayment {
+                payment_id,
+                user_id,
+                role,
+            })
+            .await?;
+    }
+    Ok(())
+}
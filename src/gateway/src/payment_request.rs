@@ -0,0 +1,129 @@
+use anyhow::Context;
+use domain::{Payment, PaymentRequest, PaymentState};
+use tracing::info;
+
+use crate::{payment::message_note, AppContext};
+
+/// Carries the outcome of a payment over to the [`PaymentRequest`] it
+/// answers: a settled payment pays the request, a failed one frees it for
+/// another attempt.
+pub async fn sync_payment_request(app: &AppContext, payment: &Payment) -> anyhow::Result<()> {
+    let Some(request_id) = payment.payment_request_id else {
+        return Ok(());
+    };
+    let (mut request, version) = app
+        .db_client
+        .get_payment_request::<PaymentRequest>(request_id)
+        .await?
+        .with_context(|| format!("payment request {request_id} not found"))?;
+
+    match payment.state() {
+        PaymentState::InboundSettled if request.paid_at.is_none() => {
+            request.payment_id = Some(payment.payment_id);
+            request.paid_at = payment.payment_statuses.inbound_settled_at;
+            app.db_client
+                .upsert_payment_request(request.clone(), version + 1)
+                .await?;
+            info!(%request_id, "payment request paid");
+
+            app.notifier
+                .send(
+                    &payment.payee_email,
+                    "Your money request was paid",
+                    format!(
+                        "{} paid your request for {}. You can deposit it from your transfers.{}",
+                        payment.payer_full_name,
+                        request.amount,
+                        message_note(payment)
+                    ),
+                )
+                .await?;
+        }
+        PaymentState::InboundFailed if request.payment_id == Some(payment.payment_id) => {
+            request.payment_id = None;
+            app.db_client
+                .upsert_payment_request(request, version + 1)
+                .await?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use domain::{EventSource, PaymentEvent, PaymentRequestId, UserId};
+
+    use super::*;
+
+    async fn store_request(app: &AppContext, payment: &Payment) -> PaymentRequestId {
+        let now = Utc::now();
+        let request = PaymentRequest {
+            request_id: payment.payment_request_id.unwrap(),
+            payee_user_id: UserId::new(),
+            payer_email: payment.payer_email.clone(),
+            amount: payment.amount,
+            note: String::new(),
+            created_at: now,
+            expires_at: now + chrono::Duration::days(7),
+            payment_id: Some(payment.payment_id),
+            paid_at: None,
+            declined_at: None,
+            claimed_at: None,
+        };
+        app.db_client
+            .upsert_payment_request(request, 0)
+            .await
+            .unwrap();
+        payment.payment_request_id.unwrap()
+    }
+
+    async fn request(app: &AppContext, request_id: PaymentRequestId) -> PaymentRequest {
+        app.db_client
+            .get_payment_request(request_id)
+            .await
+            .unwrap()
+            .unwrap()
+            .0
+    }
+
+    fn request_payment(event: PaymentEvent) -> Payment {
+        let mut payment = Payment {
+            payment_request_id: Some(PaymentRequestId::new()),
+            ..Payment::test_fixture()
+        };
+        payment.transition(event, EventSource::Webhook).unwrap();
+        payment
+    }
+
+    #[actix_web::test]
+    async fn a_settled_payment_pays_its_request() {
+        let app = AppContext::for_tests();
+        let payment = request_payment(PaymentEvent::InboundSettled {
+            settled_at: Utc::now(),
+        });
+        let request_id = store_request(&app, &payment).await;
+
+        sync_payment_request(&app, &payment).await.unwrap();
+        let paid = request(&app, request_id).await;
+        assert_eq!(paid.paid_at, payment.payment_statuses.inbound_settled_at);
+        assert_eq!(paid.payment_id, Some(payment.payment_id));
+    }
+
+    #[actix_web::test]
+    async fn a_failed_payment_frees_its_request() {
+        let app = AppContext::for_tests();
+        let payment = request_payment(PaymentEvent::InboundFailed {
+            failed_at: Utc::now(),
+            failure: None,
+        });
+        let request_id = store_request(&app, &payment).await;
+
+        sync_payment_request(&app, &payment).await.unwrap();
+        let freed = request(&app, request_id).await;
+        assert!(freed.payment_id.is_none());
+        assert!(freed.paid_at.is_none());
+        assert!(freed.is_payable(Utc::now()));
+    }
+}
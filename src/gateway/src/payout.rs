@@ -0,0 +1,108 @@
+use anyhow::Context;
+use chrono::Utc;
+use domain::{
+    AccountIdentifier, EventSource, OutboxEffect, OutboxMessage, Payment, PaymentEvent, PaymentId,
+    PaymentReference, PaymentState, PayoutDestination, PayoutId,
+};
+
+use crate::{log, AppContext};
+
+/// Claims `payment` for a payout to `destination` and queues the payout with
+/// the claim, together with `outbox`. The payout is created with TrueLayer by
+/// [`create_requested_payout`] once the queue delivers it.
+pub async fn payout_payment(
+    app: &AppContext,
+    mut payment: Payment,
+    version: u32,
+    destination: PayoutDestination,
+    source: EventSource,
+    mut outbox: Vec<OutboxMessage>,
+) -> anyhow::Result<()> {
+    let payment_id = payment.payment_id;
+    let event = payment.transition(
+        PaymentEvent::PayoutRequested {
+            destination,
+            requested_at: Utc::now(),
+        },
+        source,
+    )?;
+    log::set_payment_state(PaymentState::PayoutRequested);
+
+    outbox.push(OutboxMessage::new(
+        Some(payment_id),
+        OutboxEffect::Payout { payment_id },
+    ));
+    app.db_client
+        .upsert_payment_with_outbox(payment, version + 1, event, outbox)
+        .await?;
+
+    Ok(())
+}
+
+/// Sends the funds of a payment claimed by [`payout_payment`] to the payee's
+/// account and records the payout against the payment. TrueLayer sees the
+/// same idempotency key on every attempt, so retrying after a failure never
+/// pays out twice, and nothing is done once the payout is recorded.
+pub async fn create_requested_payout(
+    app: &AppContext,
+    payment_id: PaymentId,
+) -> anyhow::Result<()> {
+    let (mut payment, version) = app
+        .db_client
+        .get_payment::<Payment>(payment_id)
+        .await?
+        .with_context(|| format!("payment {payment_id} not found"))?;
+    if payment.state() != PaymentState::PayoutRequested {
+        return Ok(());
+    }
+
+    let destination = payment
+        .payout_destination
+        .clone()
+        .with_context(|| format!("payment {payment_id} has no payout destination"))?;
+    let account_identifier = match destination.account_identifier {
+        AccountIdentifier::Iban { iban } => truelayer::model::AccountIdentifier::Iban { iban },
+        AccountIdentifier::SortCodeAccountNumber {
+            sort_code,
+            account_number,
+        } => truelayer::model::AccountIdentifier::SortCodeAccountNumber {
+            sort_code,
+            account_number,
+        },
+    };
+    let reference = PaymentReference::new(&format!("FROM {}", payment.payer_full_name));
+    let payout = app
+        .tl_client
+        .create_payout(
+            &destination.account_holder_name,
+            &account_identifier,
+            payment.amount.amount_in_minor,
+            payment.amount.currency.as_str(),
+            reference.as_str(),
+            payment_id.idempotency_key("payout"),
+        )
+        .await?;
+
+    let payout_id = PayoutId::from_uuid(payout.payout_id);
+    log::set_payout_id(payout_id);
+    log::set_payment_state(PaymentState::PayoutCreated);
+
+    let event = payment.transition(
+        PaymentEvent::PayoutCreated {
+            payout_id,
+            created_at: Utc::now(),
+        },
+        EventSource::System,
+    )?;
+    app.db_client
+        .upsert_payment_with_payout(
+            payment,
+            version + 1,
+            event,
+            payout_id,
+            Vec::<OutboxMessage>::new(),
+        )
+        .await?;
+
+    Ok(())
+}
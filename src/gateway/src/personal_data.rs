@@ -0,0 +1,404 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use chrono::Utc;
+use db::entities::{PaymentFilter, PaymentQuery, PaymentSort, SortDirection};
+use domain::{
+    EventSource, Payment, PaymentEventRecord, PaymentRequest, ScheduledTransfer, User, UserId,
+    UserPaymentRole,
+};
+use serde_json::{json, Value};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::AppContext;
+
+const PAGE_SIZE: i64 = 100;
+
+/// What erasing a user did to the payments they were part of.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ErasureReport {
+    pub payments_erased: usize,
+    /// Payments still in progress, erased by the retention job once they
+    /// reach a final state and their retention period ends.
+    pub payments_kept: usize,
+}
+
+/// Everything stored about `email`: the user registered with it, the payments
+/// sent from or to it with their history, and the payment requests and
+/// scheduled transfers of the user. Security answers are left out, as the
+/// payer sets them for the payee to prove who they are.
+pub async fn export_personal_data(app: &AppContext, email: &str) -> anyhow::Result<Value> {
+    let user = app
+        .db_client
+        .get_user_by_email::<User>(email)
+        .await?
+        .map(|(user, _)| user);
+    let user_id = user.as_ref().map(User::user_id);
+
+    let mut payments = Vec::new();
+    for (payment, _) in payments_of(app, email, user_id).await? {
+        let events = app
+            .db_client
+            .get_payment_events::<PaymentEventRecord>(payment.payment_id)
+            .await?;
+        payments.push(payment_json(
+            &payment,
+            events.iter().map(|(event, _)| event),
+        ));
+    }
+
+    let (payment_requests, scheduled_transfers) = match user_id {
+        Some(user_id) => (
+            payment_requests_of(app, user_id)
+                .await?
+                .iter()
+                .map(|(request, _)| payment_request_json(request))
+                .collect(),
+            scheduled_transfers_of(app, user_id)
+                .await?
+                .iter()
+                .map(|(schedule, _)| scheduled_transfer_json(schedule))
+                .collect(),
+        ),
+        None => (Vec::new(), Vec::new()),
+    };
+
+    Ok(json!({
+        "email": email,
+        "exported_at": Utc::now(),
+        "user": user.as_ref().map(user_json),
+        "payments": payments,
+        "payment_requests": payment_requests,
+        "scheduled_transfers": scheduled_transfers,
+    }))
+}
+
+/// Erases the user and the personal data of the payments, payment requests
+/// and scheduled transfers they were part of. Amounts, states and ids are
+/// kept, as are payments still in progress. Scheduled transfers are
+/// cancelled and pending payment requests declined.
+pub async fn erase_user(app: &AppContext, user_id: UserId) -> anyhow::Result<ErasureReport> {
+    let (user, version) = app
+        .db_client
+        .get_user::<User>(user_id.into_uuid())
+        .await?
+        .context("user not found")?;
+    let now = Utc::now();
+
+    let mut report = ErasureReport::default();
+    for (mut payment, version) in payments_of(app, user.email(), Some(user_id)).await? {
+        if payment.personal_data_erased_at.is_some() {
+            continue;
+        }
+        if !payment.state().is_final() {
+            report.payments_kept += 1;
+            continue;
+        }
+        let event = payment.erase_personal_data(EventSource::Admin, now);
+        app.db_client
+            .upsert_payment(payment, version + 1, event)
+            .await?;
+        report.payments_erased += 1;
+    }
+
+    for (mut request, version) in payment_requests_of(app, user_id).await? {
+        request.erase_personal_data(now);
+        app.db_client
+            .upsert_payment_request(request, version + 1)
+            .await?;
+    }
+
+    for (mut schedule, version) in scheduled_transfers_of(app, user_id).await? {
+        schedule.erase_personal_data();
+        app.db_client
+            .upsert_scheduled_transfer(schedule, version + 1)
+            .await?;
+    }
+
+    app.db_client
+        .upsert_user(user.erase(now), version + 1)
+        .await?;
+
+    info!(
+        %user_id,
+        payments_erased = report.payments_erased,
+        payments_kept = report.payments_kept,
+        "user erased"
+    );
+    Ok(report)
+}
+
+/// The payments sent from or to `email` and those linked to the user, each
+/// once.
+async fn payments_of(
+    app: &AppContext,
+    email: &str,
+    user_id: Option<UserId>,
+) -> anyhow::Result<Vec<(Payment, u32)>> {
+    let mut payments = BTreeMap::<Uuid, (Payment, u32)>::new();
+    let mut add = |page: Vec<(Payment, u32)>| {
+        for (payment, version) in page {
+            payments.insert(payment.payment_id.into_uuid(), (payment, version));
+        }
+    };
+
+    if !email.is_empty() {
+        let filters = [
+            PaymentFilter {
+                payer_email: Some(email.to_owned()),
+                ..Default::default()
+            },
+            PaymentFilter {
+                payee_email: Some(email.to_owned()),
+                ..Default::default()
+            },
+        ];
+        for filter in filters {
+            let mut query = PaymentQuery {
+                filter,
+                sort: PaymentSort::CreatedAt,
+                direction: SortDirection::Asc,
+                after: None,
+                limit: PAGE_SIZE,
+            };
+            loop {
+                let page = app.db_client.query_payments::<Payment>(&query).await?;
+                add(page.payments);
+                match page.next {
+                    Some(next) => query.after = Some(next),
+                    None => break,
+                }
+            }
+        }
+    }
+
+    if let Some(user_id) = user_id {
+        for role in [UserPaymentRole::Payer, UserPaymentRole::Payee] {
+            let mut offset = 0;
+            loop {
+                let page = app
+                    .db_client
+                    .get_user_payments::<Payment>(user_id, role.as_str(), PAGE_SIZE, offset)
+                    .await?;
+                let len = page.len() as i64;
+                add(page);
+                if len < PAGE_SIZE {
+                    break;
+                }
+                offset += len;
+            }
+        }
+    }
+
+    Ok(payments.into_values().collect())
+}
+
+async fn payment_requests_of(
+    app: &AppContext,
+    user_id: UserId,
+) -> anyhow::Result<Vec<(PaymentRequest, u32)>> {
+    let mut requests = Vec::new();
+    loop {
+        let page = app
+            .db_client
+            .get_user_payment_requests::<PaymentRequest>(user_id, PAGE_SIZE, requests.len() as i64)
+            .await?;
+        let len = page.len() as i64;
+        requests.extend(page);
+        if len < PAGE_SIZE {
+            return Ok(requests);
+        }
+    }
+}
+
+async fn scheduled_transfers_of(
+    app: &AppContext,
+    user_id: UserId,
+) -> anyhow::Result<Vec<(ScheduledTransfer, u32)>> {
+    let mut schedules = Vec::new();
+    loop {
+        let page = app
+            .db_client
+            .get_user_scheduled_transfers::<ScheduledTransfer>(
+                user_id,
+                PAGE_SIZE,
+                schedules.len() as i64,
+            )
+            .await?;
+        let len = page.len() as i64;
+        schedules.extend(page);
+        if len < PAGE_SIZE {
+            return Ok(schedules);
+        }
+    }
+}
+
+fn user_json(user: &User) -> Value {
+    json!({
+        "user_id": user.user_id().to_string(),
+        "email": user.email(),
+        "first_name": user.first_name(),
+        "last_name": user.last_name(),
+        "registration_code_sent_at": user.registration_code().map(|(_, sent_at)| sent_at),
+        "payout_account": user.payout_account(),
+    })
+}
+
+fn payment_json<'a>(
+    payment: &Payment,
+    events: impl Iterator<Item = &'a PaymentEventRecord>,
+) -> Value {
+    let events = events
+        .map(|event| {
+            json!({
+                "event_type": event.event_type,
+                "source": event.source.as_str(),
+                "previous_state": event.previous_state.map(|state| state.as_str()),
+                "new_state": event.new_state.as_str(),
+                "created_at": event.created_at,
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "payment_id": payment.payment_id.to_string(),
+        "state": payment.state().as_str(),
+        "payer_full_name": payment.payer_full_name,
+        "payer_email": payment.payer_email,
+        "payee_full_name": payment.payee_full_name,
+        "payee_email": payment.payee_email,
+        "amount_in_minor": payment.amount.amount_in_minor,
+        "currency": payment.amount.currency.as_str(),
+        "security_question": payment.security_question,
+        "message": payment.message,
+        "created_at": payment.payment_statuses.inbound_created_at,
+        "personal_data_erased_at": payment.personal_data_erased_at,
+        "events": events,
+    })
+}
+
+fn payment_request_json(request: &PaymentRequest) -> Value {
+    json!({
+        "request_id": request.request_id.to_string(),
+        "payer_email": request.payer_email,
+        "amount_in_minor": request.amount.amount_in_minor,
+        "currency": request.amount.currency.as_str(),
+        "note": request.note,
+        "status": request.status(Utc::now()).as_str(),
+        "created_at": request.created_at,
+        "expires_at": request.expires_at,
+        "payment_id": request.payment_id.map(|payment_id| payment_id.to_string()),
+    })
+}
+
+fn scheduled_transfer_json(schedule: &ScheduledTransfer) -> Value {
+    json!({
+        "schedule_id": schedule.schedule_id.to_string(),
+        "payee_full_name": schedule.payee_full_name,
+        "payee_email": schedule.payee_email,
+        "amount_in_minor": schedule.amount.amount_in_minor,
+        "currency": schedule.amount.currency.as_str(),
+        "security_question": schedule.security_question,
+        "frequency": schedule.frequency.as_str(),
+        "status": schedule.status.as_str(),
+        "starts_at": schedule.starts_at,
+        "last_run_at": schedule.last_run_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use domain::{PaymentEvent, PaymentState, UserState};
+
+    use super::*;
+
+    const EMAIL: &str = "payer@example.com";
+
+    async fn store_payment(app: &AppContext, event: PaymentEvent) -> Payment {
+        let mut payment = Payment {
+            security_answer: String::from("correct horse"),
+            ..Payment::test_fixture()
+        };
+        let event = payment.transition(event, EventSource::Webhook).unwrap();
+        app.db_client
+            .upsert_payment(payment.clone(), 1, event)
+            .await
+            .unwrap();
+        payment
+    }
+
+    async fn stored(app: &AppContext, payment: &Payment) -> Payment {
+        app.db_client
+            .get_payment::<Payment>(payment.payment_id)
+            .await
+            .unwrap()
+            .unwrap()
+            .0
+    }
+
+    #[actix_web::test]
+    async fn erasure_keeps_payments_in_progress() {
+        let app = AppContext::for_tests();
+        let user = User::Registered {
+            user_id: UserId::new(),
+            email: String::from(EMAIL),
+            first_name: String::from("Payer"),
+            last_name: String::from("Person"),
+            payout_account: None,
+        };
+        let user_id = user.user_id();
+        app.db_client.upsert_user(user, 0).await.unwrap();
+        let failed = store_payment(
+            &app,
+            PaymentEvent::InboundFailed {
+                failed_at: Utc::now(),
+                failure: None,
+            },
+        )
+        .await;
+        let settled = store_payment(
+            &app,
+            PaymentEvent::InboundSettled {
+                settled_at: Utc::now(),
+            },
+        )
+        .await;
+
+        let report = erase_user(&app, user_id).await.unwrap();
+        assert_eq!(report.payments_erased, 1);
+        assert_eq!(report.payments_kept, 1);
+
+        let (user, _) = app
+            .db_client
+            .get_user::<User>(user_id.into_uuid())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.state(), UserState::Erased);
+        let failed = stored(&app, &failed).await;
+        assert!(failed.payer_email.is_empty());
+        assert!(failed.personal_data_erased_at.is_some());
+        assert_eq!(failed.state(), PaymentState::InboundFailed);
+        assert_eq!(failed.amount, settled.amount);
+        let settled = stored(&app, &settled).await;
+        assert_eq!(settled.payer_email, EMAIL);
+        assert!(settled.personal_data_erased_at.is_none());
+    }
+
+    #[actix_web::test]
+    async fn exports_leave_out_security_answers() {
+        let app = AppContext::for_tests();
+        let payment = store_payment(
+            &app,
+            PaymentEvent::InboundSettled {
+                settled_at: Utc::now(),
+            },
+        )
+        .await;
+
+        let export = export_personal_data(&app, EMAIL).await.unwrap();
+        assert_eq!(export["payments"].as_array().unwrap().len(), 1);
+        assert!(export.to_string().contains(&payment.payment_id.to_string()));
+        assert!(!export.to_string().contains(&payment.security_answer));
+    }
+}
@@ -0,0 +1,144 @@
+use anyhow::Context;
+use chrono::Utc;
+use domain::{
+    EventSource, OutboxEffect, OutboxMessage, Payment, PaymentEvent, PaymentId, PaymentState,
+    RefundId,
+};
+
+use crate::{log, AppContext};
+
+const REFUND_REFERENCE: &str = "ETRANSFER REFUND";
+
+/// Claims `payment` for a refund and queues the refund with the claim,
+/// together with `outbox`. The refund is created with TrueLayer by
+/// [`create_requested_refund`] once the queue delivers it.
+pub async fn refund_payment(
+    app: &AppContext,
+    mut payment: Payment,
+    version: u32,
+    source: EventSource,
+    mut outbox: Vec<OutboxMessage>,
+) -> anyhow::Result<()> {
+    let payment_id = payment.payment_id;
+    let event = payment.transition(
+        PaymentEvent::RefundRequested {
+            requested_at: Utc::now(),
+        },
+        source,
+    )?;
+    log::set_payment_state(PaymentState::RefundRequested);
+
+    outbox.push(refund_message(payment_id));
+    app.db_client
+        .upsert_payment_with_outbox(payment, version + 1, event, outbox)
+        .await?;
+
+    Ok(())
+}
+
+/// The outbox message creating the refund of the payment. Expired and
+/// cancelled payments queue it with the change, it claims them first.
+pub fn refund_message(payment_id: PaymentId) -> OutboxMessage {
+    OutboxMessage::new(Some(payment_id), OutboxEffect::Refund { payment_id })
+}
+
+/// Returns the funds of a payment claimed for a refund to the payer and
+/// records the refund against the payment. TrueLayer sees the same
+/// idempotency key on every attempt, so retrying after a failure never
+/// refunds twice, and nothing is done once the refund is recorded.
+pub async fn create_requested_refund(
+    app: &AppContext,
+    payment_id: PaymentId,
+) -> anyhow::Result<()> {
+    let (mut payment, mut version) = app
+        .db_client
+        .get_payment::<Payment>(payment_id)
+        .await?
+        .with_context(|| format!("payment {payment_id} not found"))?;
+    if matches!(
+        payment.state(),
+        PaymentState::Expired | PaymentState::Cancelled
+    ) {
+        let event = payment.transition(
+            PaymentEvent::RefundRequested {
+                requested_at: Utc::now(),
+            },
+            EventSource::System,
+        )?;
+        version += 1;
+        app.db_client
+            .upsert_payment(payment.clone(), version, event)
+            .await?;
+    }
+    if payment.state() != PaymentState::RefundRequested {
+        return Ok(());
+    }
+
+    let refund = app
+        .tl_client
+        .create_refund(
+            payment_id.into_uuid(),
+            payment.amount.amount_in_minor,
+            REFUND_REFERENCE,
+            payment_id.idempotency_key("refund"),
+        )
+        .await?;
+
+    let refund_id = RefundId::from_uuid(refund.refund_id);
+    log::set_refund_id(refund_id);
+    log::set_payment_state(PaymentState::RefundCreated);
+
+    let event = payment.transition(
+        PaymentEvent::RefundCreated {
+            refund_id,
+            created_at: Utc::now(),
+        },
+        EventSource::System,
+    )?;
+    app.db_client
+        .upsert_payment_with_refund(
+            payment,
+            version + 1,
+            event,
+            refund_id,
+            Vec::<OutboxMessage>::new(),
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn only_expired_or_cancelled_payments_are_refunded() {
+        let app = AppContext::for_tests();
+        let mut payment = Payment::test_fixture();
+        let payment_id = payment.payment_id;
+        let event = payment
+            .transition(
+                PaymentEvent::InboundSettled {
+                    settled_at: Utc::now(),
+                },
+                EventSource::Webhook,
+            )
+            .unwrap();
+        app.db_client
+            .upsert_payment(payment, 1, event)
+            .await
+            .unwrap();
+
+        // a payee can still deposit it, so truelayer is never asked
+        create_requested_refund(&app, payment_id).await.unwrap();
+        let (payment, version) = app
+            .db_client
+            .get_payment::<Payment>(payment_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(payment.state(), PaymentState::InboundSettled);
+        assert_eq!(version, 1);
+    }
+}
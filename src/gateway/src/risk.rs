@@ -0,0 +1,250 @@
+use chrono::{DateTime, Duration, Utc};
+use db::DbClient;
+use domain::{AccountIdentifier, Payment, RiskAssessment, User};
+use serde::Deserialize;
+
+/// Thresholds and weights of the built-in risk rules. A settled payment
+/// whose rule scores add up to `hold_score` is held for review.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RiskConfig {
+    pub enabled: bool,
+    pub hold_score: u32,
+    pub first_time_payer_score: u32,
+    /// Amount in minor units from which a payment counts as large.
+    pub large_amount: u32,
+    pub large_amount_score: u32,
+    pub payee_email_domains: Vec<String>,
+    pub payee_email_domain_score: u32,
+    pub same_account_score: u32,
+    /// Payments to one payee within a day from which the payee looks busy.
+    pub payee_daily_count: u32,
+    pub payee_daily_count_score: u32,
+}
+
+impl Default for RiskConfig {
+    fn default() -> Self {
+        RiskConfig {
+            enabled: true,
+            hold_score: 100,
+            first_time_payer_score: 30,
+            large_amount: 100_000,
+            large_amount_score: 50,
+            payee_email_domains: vec![
+                String::from("mailinator.com"),
+                String::from("guerrillamail.com"),
+                String::from("10minutemail.com"),
+            ],
+            payee_email_domain_score: 50,
+            same_account_score: 100,
+            payee_daily_count: 5,
+            payee_daily_count_score: 50,
+        }
+    }
+}
+
+/// What the rules get to look at, gathered once per payment so rules stay
+/// plain functions of it.
+pub struct RiskContext<'a> {
+    pub payment: &'a Payment,
+    /// Payments the payer made before this one.
+    pub payer_previous_payments: i64,
+    /// Payments the payee received over the last day, this one included.
+    pub payee_daily_payments: i64,
+    /// Accounts TrueLayer reported the payer paid from.
+    pub payer_accounts: &'a [AccountIdentifier],
+    /// The payee's saved payout account, if they are registered.
+    pub payee_account: Option<&'a AccountIdentifier>,
+}
+
+pub struct RiskSignal {
+    pub score: u32,
+    pub reason: String,
+}
+
+/// A check contributing to the risk score of a settled payment.
+pub trait RiskRule: Send + Sync {
+    fn evaluate(&self, context: &RiskContext<'_>) -> Option<RiskSignal>;
+}
+
+pub struct RiskEngine {
+    enabled: bool,
+    hold_score: u32,
+    rules: Vec<Box<dyn RiskRule>>,
+}
+
+impl RiskEngine {
+    pub fn new(hold_score: u32) -> Self {
+        RiskEngine {
+            enabled: true,
+            hold_score,
+            rules: Vec::new(),
+        }
+    }
+
+    pub fn with_rule(mut self, rule: impl RiskRule + 'static) -> Self {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    /// The engine with the built-in rules weighted by `config`.
+    pub fn from_config(config: &RiskConfig) -> Self {
+        let mut engine = RiskEngine::new(config.hold_score)
+            .with_rule(FirstTimePayer {
+                score: config.first_time_payer_score,
+            })
+            .with_rule(LargeAmount {
+                threshold: config.large_amount,
+                score: config.large_amount_score,
+            })
+            .with_rule(PayeeEmailDomain {
+                domains: config.payee_email_domains.clone(),
+                score: config.payee_email_domain_score,
+            })
+            .with_rule(SameAccount {
+                score: config.same_account_score,
+            })
+            .with_rule(PayeeVelocity {
+                max_daily_payments: config.payee_daily_count,
+                score: config.payee_daily_count_score,
+            });
+        engine.enabled = config.enabled;
+        engine
+    }
+
+    pub async fn assess(
+        &self,
+        db_client: &DbClient,
+        payment: &Payment,
+        payer_accounts: &[AccountIdentifier],
+    ) -> anyhow::Result<RiskAssessment> {
+        let now = Utc::now();
+        if !self.enabled {
+            return Ok(RiskAssessment {
+                score: 0,
+                reasons: Vec::new(),
+                assessed_at: now,
+            });
+        }
+
+        let currency = payment.amount.currency.as_str();
+        let payer_totals = db_client
+            .get_payer_totals(&payment.payer_email, currency, DateTime::UNIX_EPOCH)
+            .await?;
+        let payee_totals = db_client
+            .get_payee_totals(&payment.payee_email, currency, now - Duration::days(1))
+            .await?;
+        let payee = db_client
+            .get_user_by_email::<User>(&payment.payee_email)
+            .await?;
+
+        let context = RiskContext {
+            payment,
+            payer_previous_payments: payer_totals.count - 1,
+            payee_daily_payments: payee_totals.count,
+            payer_accounts,
+            payee_account: payee
+                .as_ref()
+                .and_then(|(user, _)| user.payout_account())
+                .map(|account| &account.account_identifier),
+        };
+        let (score, reasons) = self
+            .rules
+            .iter()
+            .filter_map(|rule| rule.evaluate(&context))
+            .fold((0, Vec::new()), |(score, mut reasons), signal| {
+                reasons.push(signal.reason);
+                (score + signal.score, reasons)
+            });
+
+        Ok(RiskAssessment {
+            score,
+            reasons,
+            assessed_at: now,
+        })
+    }
+
+    pub fn should_hold(&self, assessment: &RiskAssessment) -> bool {
+        self.enabled && assessment.score >= self.hold_score
+    }
+}
+
+pub struct FirstTimePayer {
+    pub score: u32,
+}
+
+impl RiskRule for FirstTimePayer {
+    fn evaluate(&self, context: &RiskContext<'_>) -> Option<RiskSignal> {
+        (context.payer_previous_payments <= 0).then(|| RiskSignal {
+            score: self.score,
+            reason: String::from("first payment from this payer"),
+        })
+    }
+}
+
+pub struct LargeAmount {
+    pub threshold: u32,
+    pub score: u32,
+}
+
+impl RiskRule for LargeAmount {
+    fn evaluate(&self, context: &RiskContext<'_>) -> Option<RiskSignal> {
+        (context.payment.amount.amount_in_minor >= self.threshold).then(|| RiskSignal {
+            score: self.score,
+            reason: format!("large amount of {}", context.payment.amount),
+        })
+    }
+}
+
+pub struct PayeeEmailDomain {
+    pub domains: Vec<String>,
+    pub score: u32,
+}
+
+impl RiskRule for PayeeEmailDomain {
+    fn evaluate(&self, context: &RiskContext<'_>) -> Option<RiskSignal> {
+        let (_, domain) = context.payment.payee_email.rsplit_once('@')?;
+        self.domains
+            .iter()
+            .any(|risky| risky.eq_ignore_ascii_case(domain))
+            .then(|| RiskSignal {
+                score: self.score,
+                reason: format!("payee email domain {domain}"),
+            })
+    }
+}
+
+/// The payee deposits to the same account the payer paid from.
+pub struct SameAccount {
+    pub score: u32,
+}
+
+impl RiskRule for SameAccount {
+    fn evaluate(&self, context: &RiskContext<'_>) -> Option<RiskSignal> {
+        let payee_account = context.payee_account?;
+        context
+            .payer_accounts
+            .contains(payee_account)
+            .then(|| RiskSignal {
+                score: self.score,
+                reason: String::from("payee account is the payer's source account"),
+            })
+    }
+}
+
+pub struct PayeeVelocity {
+    pub max_daily_payments: u32,
+    pub score: u32,
+}
+
+impl RiskRule for PayeeVelocity {
+    fn evaluate(&self, context: &RiskContext<'_>) -> Option<RiskSignal> {
+        (context.payee_daily_payments > i64::from(self.max_daily_payments)).then(|| RiskSignal {
+            score: self.score,
+            reason: format!(
+                "{} payments to this payee in a day",
+                context.payee_daily_payments
+            ),
+        })
+    }
+}
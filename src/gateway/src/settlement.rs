@@ -0,0 +1,164 @@
+use anyhow::Context;
+use chrono::Utc;
+use domain::{
+    AccountIdentifier, EventSource, Payment, PaymentEvent, PaymentEventRecord, PaymentId,
+    PaymentState, RiskAssessment,
+};
+use tracing::{info, warn};
+
+use crate::{autodeposit::autodeposit, payment::payment_received_message, AppContext};
+
+/// Settles a payment queued for settlement with its settled state. Nothing is
+/// done once the payment has moved on, so a redelivery does not assess or
+/// release it twice.
+pub async fn settle_queued_payment(
+    app: &AppContext,
+    payment_id: PaymentId,
+    payer_accounts: &[AccountIdentifier],
+) -> anyhow::Result<()> {
+    let (payment, version) = app
+        .db_client
+        .get_payment::<Payment>(payment_id)
+        .await?
+        .with_context(|| format!("payment {payment_id} not found"))?;
+    if payment.state() != PaymentState::InboundSettled || payment.risk_assessment.is_some() {
+        return Ok(());
+    }
+    settle_payment(app, payment, version, payer_accounts).await
+}
+
+/// Runs once a payment's funds reach the merchant account: holds it for
+/// review if it looks risky, otherwise releases it to the payee.
+async fn settle_payment(
+    app: &AppContext,
+    mut payment: Payment,
+    version: u32,
+    payer_accounts: &[AccountIdentifier],
+) -> anyhow::Result<()> {
+    let assessment = app
+        .risk_engine
+        .assess(&app.db_client, &payment, payer_accounts)
+        .await
+        .unwrap_or_else(|err| {
+            // hold rather than let an unscreened payment out
+            warn!(payment_id = %payment.payment_id, "failed to assess payment risk: {err:?}");
+            RiskAssessment {
+                score: u32::MAX,
+                reasons: vec![String::from("risk assessment failed")],
+                assessed_at: Utc::now(),
+            }
+        });
+
+    // kept on a released payment too, marking it as settled for redeliveries
+    let hold = app.risk_engine.should_hold(&assessment);
+    payment.risk_assessment = Some(assessment);
+    if !hold {
+        return release_payment(app, payment, version).await;
+    }
+
+    let event = payment.transition(
+        PaymentEvent::HeldForReview {
+            held_at: Utc::now(),
+        },
+        EventSource::System,
+    )?;
+    let payment_id = payment.payment_id;
+    app.db_client
+        .upsert_payment(payment, version + 1, event)
+        .await?;
+    info!(%payment_id, "payment held for review");
+    Ok(())
+}
+
+/// Hands a settled payment to the payee: straight to their saved account,
+/// or an email with the deposit link, queued with the payment saved as
+/// released.
+pub async fn release_payment(
+    app: &AppContext,
+    payment: Payment,
+    version: u32,
+) -> anyhow::Result<()> {
+    let deposited = autodeposit(app, payment.clone(), version)
+        .await
+        .unwrap_or_else(|err| {
+            warn!(payment_id = %payment.payment_id, "failed to autodeposit payment: {err:?}");
+            false
+        });
+
+    if deposited {
+        return Ok(());
+    }
+
+    // request payments were announced to the payee by the request
+    let email = payment
+        .payment_request_id
+        .is_none()
+        .then(|| payment_received_message(app, &payment));
+    let state = payment.state();
+    let event = PaymentEventRecord::new(
+        payment.payment_id,
+        "payment_released",
+        EventSource::System,
+        Some(state),
+        state,
+    );
+    app.db_client
+        .upsert_payment_with_outbox(payment, version + 1, event, email)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use domain::{OutboxEffect, OutboxMessage, OutboxStatus};
+
+    use super::*;
+
+    async fn store_settled_payment(app: &AppContext) -> PaymentId {
+        let mut payment = Payment::test_fixture();
+        let payment_id = payment.payment_id;
+        let now = Utc::now();
+        let mut version = 0;
+        for event in [
+            PaymentEvent::InboundAuthorized { authorized_at: now },
+            PaymentEvent::InboundExecuted { executed_at: now },
+            PaymentEvent::InboundSettled { settled_at: now },
+        ] {
+            let event = payment.transition(event, EventSource::Webhook).unwrap();
+            version += 1;
+            app.db_client
+                .upsert_payment(payment.clone(), version, event)
+                .await
+                .unwrap();
+        }
+        payment_id
+    }
+
+    #[actix_web::test]
+    async fn a_redelivered_settle_notifies_the_payee_once() {
+        let app = AppContext::for_tests();
+        let payment_id = store_settled_payment(&app).await;
+
+        settle_queued_payment(&app, payment_id, &[]).await.unwrap();
+        settle_queued_payment(&app, payment_id, &[]).await.unwrap();
+
+        let emails: Vec<OutboxMessage> = app
+            .db_client
+            .get_outbox_messages(OutboxStatus::Pending.as_str(), 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(emails.len(), 1);
+        assert!(matches!(
+            &emails[0].effect,
+            OutboxEffect::Email { to, .. } if to == "payee@example.com"
+        ));
+        let (payment, _) = app
+            .db_client
+            .get_payment::<Payment>(payment_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(payment.state(), PaymentState::InboundSettled);
+        assert!(payment.risk_assessment.is_some());
+    }
+}
@@ -23,7 +23,9 @@ pub struct Beneficiary {
 #[derive(Debug, Deserialize)]
 pub struct AccountTdentifier {
     r#type: String,
-    iban: String,
+    iban: Option<String>,
+    sort_code: Option<String>,
+    account_number: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -56,7 +58,7 @@ async fn execute(
 ) -> Result<impl Responder, PublicError> {
     let payout_id = match request.beneficiary.r#type.as_str() {
         "external_account" => match request.beneficiary.account_identifier.r#type.as_str() {
-            "iban" => app
+            "iban" | "sort_code_account_number" => app
                 .state
                 .create_payout(
                     client_id,
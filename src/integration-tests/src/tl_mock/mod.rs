@@ -1,6 +1,7 @@
 mod auth;
 mod create_payment;
 mod create_payout;
+mod refunds;
 mod state;
 
 use std::{net::TcpListener, thread::JoinHandle, time::Duration};
@@ -45,6 +46,9 @@ impl TlMock {
                             .service(auth::auth)
                             .service(create_payment::create_payment)
                             .service(create_payout::create_payout)
+                            .service(refunds::create_refund)
+                            .service(refunds::get_refund)
+                            .service(refunds::list_refunds)
                     })
                     .bind(("0.0.0.0", http_port))?
                     .run();
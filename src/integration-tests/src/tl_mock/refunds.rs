@@ -0,0 +1,110 @@
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{deserialize_body, state, AppContext, PublicError};
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    amount_in_minor: u32,
+    reference: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Response {
+    id: Uuid,
+}
+
+#[derive(Debug, Serialize)]
+struct Refund {
+    id: Uuid,
+    amount_in_minor: u64,
+    currency: String,
+    reference: String,
+    status: String,
+    created_at: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ListRefunds {
+    items: Vec<Refund>,
+}
+
+impl From<state::Refund> for Refund {
+    fn from(refund: state::Refund) -> Self {
+        Refund {
+            id: refund.refund_id,
+            amount_in_minor: refund.amount,
+            currency: refund.currency,
+            reference: refund.reference,
+            status: "pending".into(),
+            created_at: "2024-01-01T00:00:00Z".into(),
+        }
+    }
+}
+
+#[post("/v3/payments/{payment_id}/refunds")]
+pub async fn create_refund(
+    app: web::Data<AppContext>,
+    request: HttpRequest,
+    payment_id: web::Path<Uuid>,
+    body: String,
+) -> Result<impl Responder, PublicError> {
+    let client_id = client_id(&request);
+    let request: Request = deserialize_body(&body)?;
+
+    let refund_id = app
+        .state
+        .create_refund(
+            client_id,
+            payment_id.into_inner(),
+            request.amount_in_minor as _,
+            request.reference,
+        )
+        .ok_or_else(|| PublicError::Invalid("Invalid payment_id or refund amount".into()))?;
+
+    Ok(HttpResponse::Created().json(Response { id: refund_id }))
+}
+
+#[get("/v3/payments/{payment_id}/refunds/{refund_id}")]
+pub async fn get_refund(
+    app: web::Data<AppContext>,
+    request: HttpRequest,
+    path: web::Path<(Uuid, Uuid)>,
+) -> Result<impl Responder, PublicError> {
+    let (payment_id, refund_id) = path.into_inner();
+    let refund = app
+        .state
+        .get_refunds(client_id(&request), payment_id)
+        .into_iter()
+        .find(|refund| refund.refund_id == refund_id)
+        .ok_or_else(|| PublicError::Invalid("Unknown refund_id".into()))?;
+
+    Ok(HttpResponse::Ok().json(Refund::from(refund)))
+}
+
+#[get("/v3/payments/{payment_id}/refunds")]
+pub async fn list_refunds(
+    app: web::Data<AppContext>,
+    request: HttpRequest,
+    payment_id: web::Path<Uuid>,
+) -> Result<impl Responder, PublicError> {
+    let items = app
+        .state
+        .get_refunds(client_id(&request), payment_id.into_inner())
+        .into_iter()
+        .map(Refund::from)
+        .collect();
+
+    Ok(HttpResponse::Ok().json(ListRefunds { items }))
+}
+
+fn client_id(request: &HttpRequest) -> &str {
+    request
+        .headers()
+        .get("authorization")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .trim_start_matches("Bearer ")
+}
@@ -24,6 +24,16 @@ pub struct MerchantAccount {
 pub enum Transaction {
     Inbound { payment_id: Uuid, amount: u64 },
     Outbound { payout_id: Uuid, amount: u64 },
+    Refund(Refund),
+}
+
+#[derive(Debug, Clone)]
+pub struct Refund {
+    pub refund_id: Uuid,
+    pub payment_id: Uuid,
+    pub amount: u64,
+    pub currency: String,
+    pub reference: String,
 }
 
 #[derive(Debug, Clone)]
@@ -130,4 +140,64 @@ impl State {
                 })
         })
     }
+
+    pub fn create_refund(
+        &self,
+        client_id: &str,
+        payment_id: Uuid,
+        amount: u64,
+        reference: String,
+    ) -> Option<Uuid> {
+        let mut state = self.inner.lock().unwrap();
+        state.get_mut(client_id).and_then(|tl_client| {
+            tl_client.merchant_accounts.iter_mut().find_map(|ma| {
+                let paid = ma.transactions.iter().find_map(|t| match t {
+                    Transaction::Inbound {
+                        payment_id: id,
+                        amount,
+                    } if *id == payment_id => Some(*amount),
+                    _ => None,
+                })?;
+                let refunded: u64 = ma.refunds(payment_id).map(|refund| refund.amount).sum();
+
+                if refunded + amount <= paid && ma.balance >= amount {
+                    let refund_id = Uuid::new_v4();
+                    ma.balance -= amount;
+                    ma.transactions.push(Transaction::Refund(Refund {
+                        refund_id,
+                        payment_id,
+                        amount,
+                        currency: ma.currency.clone(),
+                        reference: reference.clone(),
+                    }));
+                    Some(refund_id)
+                } else {
+                    None
+                }
+            })
+        })
+    }
+
+    pub fn get_refunds(&self, client_id: &str, payment_id: Uuid) -> Vec<Refund> {
+        let state = self.inner.lock().unwrap();
+        state
+            .get(client_id)
+            .map(|tl_client| {
+                tl_client
+                    .merchant_accounts
+                    .iter()
+                    .flat_map(|ma| ma.refunds(payment_id).cloned())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl MerchantAccount {
+    fn refunds(&self, payment_id: Uuid) -> impl Iterator<Item = &Refund> {
+        self.transactions.iter().filter_map(move |t| match t {
+            Transaction::Refund(refund) if refund.payment_id == payment_id => Some(refund),
+            _ => None,
+        })
+    }
 }
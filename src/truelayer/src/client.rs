@@ -16,7 +16,11 @@ use uuid::Uuid;
 use crate::{TlConfig, TlEnviorment};
 
 use super::{
-    model::{AccountBalance, AuthResponse, CreatePayoutResponse, GetAccountBalance, GetAccounts},
+    model::{
+        AccountBalance, AccountIdentifier, AuthResponse, CreatePayoutRequest, CreatePayoutResponse,
+        CreateRefundRequest, CreateRefundResponse, GetAccountBalance, GetAccounts, ListRefunds,
+        PayoutBeneficiary, Refund,
+    },
     CreatePaymentResponse, TlError,
 };
 
@@ -31,12 +35,20 @@ pub struct TlClient {
     pub redirect_uri: String,
     pub data_redirect_uri: String,
     merchant_account_id: Uuid,
+    eur_merchant_account_id: Option<Uuid>,
 }
 
 impl TlClient {
     const TIMEOUT: u64 = 2500;
 
     pub async fn new(tl_config: TlConfig) -> Result<TlClient, TlError> {
+        let res = Self::unauthenticated(tl_config);
+        res.get_auth_token().await?;
+        Ok(res)
+    }
+
+    /// A client that authenticates on its first call rather than up front.
+    pub fn unauthenticated(tl_config: TlConfig) -> TlClient {
         let raw_client = ClientBuilder::new()
             .timeout(Duration::from_millis(Self::TIMEOUT))
             .build()
@@ -51,7 +63,7 @@ impl TlClient {
             ))
             .build();
 
-        let res = Self {
+        Self {
             client,
             enviornment: tl_config.enviornment,
             client_id: tl_config.client_id,
@@ -63,16 +75,24 @@ impl TlClient {
             data_redirect_uri: tl_config.data_redirect_uri,
 
             merchant_account_id: tl_config.merchant_account_id,
-        };
-
-        res.get_auth_token().await?;
-        Ok(res)
+            eur_merchant_account_id: tl_config.eur_merchant_account_id,
+        }
     }
 
     pub fn return_uri(&self) -> &str {
         &self.redirect_uri
     }
 
+    /// Merchant account holding funds in the given ISO-4217 `currency`.
+    pub fn merchant_account_id(&self, currency: &str) -> Result<Uuid, TlError> {
+        match currency {
+            "GBP" => Some(self.merchant_account_id),
+            "EUR" => self.eur_merchant_account_id,
+            _ => None,
+        }
+        .ok_or_else(|| TlError::UnsupportedCurrency(currency.into()))
+    }
+
     //
     // PAYMENTS V3 API
     //
@@ -105,12 +125,10 @@ impl TlClient {
         let res = self.client.execute(req).await?;
         match res.status() {
             StatusCode::OK => res.json().await.map_err(TlError::Response),
-            StatusCode::BAD_REQUEST | StatusCode::INTERNAL_SERVER_ERROR => {
-                let err: serde_json::Value = res.json().await.map_err(TlError::Response)?;
-                println!("\nTlClient::auth_payments_v3 ERROR:\n{:?}", err);
-                unimplemented!()
-            }
-            _ => todo!(),
+            status => Err(TlError::Api {
+                status,
+                body: res.json().await.map_err(TlError::Response)?,
+            }),
         }
     }
 
@@ -120,10 +138,12 @@ impl TlClient {
         payer_full_name: &str,
         payer_email: &str,
         payer_phonenumber: Option<&str>,
-        amount: u32,
+        amount_in_minor: u32,
+        currency: &str,
         reference: &str,
     ) -> Result<CreatePaymentResponse, TlError> {
         let endpoint = format!("https://api.{}/v3/payments", self.enviornment.uri());
+        let merchant_account_id = self.merchant_account_id(currency)?;
         let access_token = self.get_auth_token().await?;
 
         let idempotency_key = Uuid::new_v4().to_string();
@@ -131,7 +151,7 @@ impl TlClient {
             r#"
                 {{
                     "amount_in_minor": {},
-                    "currency": "GBP",
+                    "currency": "{}",
                     "payment_method": {{
                         "type": "bank_transfer",
                         "provider_selection": {{
@@ -154,8 +174,9 @@ impl TlClient {
                     }}
                 }}
             "#,
-            amount,
-            self.merchant_account_id,
+            amount_in_minor,
+            currency,
+            merchant_account_id,
             reference,
             payer_full_name,
             payer_email,
@@ -191,44 +212,39 @@ impl TlClient {
                 .json::<CreatePaymentResponse>()
                 .await
                 .map_err(TlError::Response),
-            _ => {
-                let err: serde_json::Value = res.json().await.map_err(TlError::Response)?;
-                println!("\nTlClient::create_ma_payment Error:\n{}", err);
-                unimplemented!()
-            }
+            status => Err(TlError::Api {
+                status,
+                body: res.json().await.map_err(TlError::Response)?,
+            }),
         }
     }
 
+    /// Creates a payout, at most once per `idempotency_key`.
     #[instrument(skip_all)]
     pub async fn create_payout(
         &self,
         payee_full_name: &str,
-        payee_iban: &str,
-        amount: u32,
+        account_identifier: &AccountIdentifier,
+        amount_in_minor: u32,
+        currency: &str,
         reference: &str,
+        idempotency_key: Uuid,
     ) -> Result<CreatePayoutResponse, TlError> {
         let endpoint = format!("https://api.{}/v3/payouts", self.enviornment.uri());
+        let merchant_account_id = self.merchant_account_id(currency)?;
         let access_token = self.get_auth_token().await?;
-        let idempotency_key = Uuid::new_v4().to_string();
-        let body = format!(
-            r#"
-                {{
-                    "amount_in_minor": {},
-                    "merchant_account_id": "{}",
-                    "currency": "GBP",
-                    "beneficiary": {{
-                        "type": "external_account",
-                        "reference": "{}",
-                        "account_holder_name": "{}",
-                        "account_identifier": {{
-                            "type": "iban",
-                            "iban": "{}"
-                        }}
-                    }}
-                }}
-            "#,
-            amount, self.merchant_account_id, reference, payee_full_name, payee_iban
-        );
+        let idempotency_key = idempotency_key.to_string();
+        let body = serde_json::to_string(&CreatePayoutRequest {
+            amount_in_minor,
+            merchant_account_id,
+            currency,
+            beneficiary: PayoutBeneficiary::ExternalAccount {
+                reference,
+                account_holder_name: payee_full_name,
+                account_identifier,
+            },
+        })
+        .expect("CreatePayoutRequest serializes");
 
         let tl_signature =
             truelayer_signing::sign_with_pem(self.kid.as_str(), self.private_key.as_bytes())
@@ -258,11 +274,119 @@ impl TlClient {
         let res = self.client.execute(req).await?;
         match res.status() {
             StatusCode::ACCEPTED => res.json().await.map_err(TlError::Response),
-            _ => {
-                let err: serde_json::Value = res.json().await.map_err(TlError::Response)?;
-                println!("\nTlClient::create_payout Error:\n{}", err);
-                unimplemented!()
-            }
+            status => Err(TlError::Api {
+                status,
+                body: res.json().await.map_err(TlError::Response)?,
+            }),
+        }
+    }
+
+    /// Refunds a payment, at most once per `idempotency_key`.
+    #[instrument(skip_all)]
+    pub async fn create_refund(
+        &self,
+        payment_id: Uuid,
+        amount_in_minor: u32,
+        reference: &str,
+        idempotency_key: Uuid,
+    ) -> Result<CreateRefundResponse, TlError> {
+        let path = format!("/v3/payments/{}/refunds", payment_id);
+        let endpoint = format!("https://api.{}{}", self.enviornment.uri(), path);
+        let access_token = self.get_auth_token().await?;
+        let idempotency_key = idempotency_key.to_string();
+        let body = serde_json::to_string(&CreateRefundRequest {
+            amount_in_minor,
+            reference,
+        })
+        .expect("CreateRefundRequest serializes");
+
+        let tl_signature =
+            truelayer_signing::sign_with_pem(self.kid.as_str(), self.private_key.as_bytes())
+                .method(Method::Post)
+                .path(&path)
+                .header("Idempotency-Key", idempotency_key.as_bytes())
+                .body(body.as_bytes())
+                .build_signer()
+                .sign()
+                .unwrap();
+
+        let req = self
+            .client
+            .post(endpoint)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::AUTHORIZATION, format!("Bearer {}", access_token))
+            .header("Idempotency-Key", idempotency_key)
+            .header("Tl-Signature", tl_signature)
+            .body(body)
+            .build()
+            .map_err(reqwest_middleware::Error::Reqwest)?;
+
+        let res = self.client.execute(req).await?;
+        match res.status() {
+            StatusCode::CREATED => res.json().await.map_err(TlError::Response),
+            status => Err(TlError::Api {
+                status,
+                body: res.json().await.map_err(TlError::Response)?,
+            }),
+        }
+    }
+
+    #[instrument(skip_all)]
+    pub async fn get_refund(&self, payment_id: Uuid, refund_id: Uuid) -> Result<Refund, TlError> {
+        let endpoint = format!(
+            "https://api.{}/v3/payments/{}/refunds/{}",
+            self.enviornment.uri(),
+            payment_id,
+            refund_id
+        );
+        let access_token = self.get_auth_token().await?;
+
+        let req = self
+            .client
+            .get(endpoint)
+            .header(header::ACCEPT, "application/json")
+            .header(header::AUTHORIZATION, format!("Bearer {}", access_token))
+            .build()
+            .map_err(reqwest_middleware::Error::Reqwest)?;
+
+        let res = self.client.execute(req).await?;
+        match res.status() {
+            StatusCode::OK => res.json().await.map_err(TlError::Response),
+            status => Err(TlError::Api {
+                status,
+                body: res.json().await.map_err(TlError::Response)?,
+            }),
+        }
+    }
+
+    #[instrument(skip_all)]
+    pub async fn list_refunds(&self, payment_id: Uuid) -> Result<Vec<Refund>, TlError> {
+        let endpoint = format!(
+            "https://api.{}/v3/payments/{}/refunds",
+            self.enviornment.uri(),
+            payment_id
+        );
+        let access_token = self.get_auth_token().await?;
+
+        let req = self
+            .client
+            .get(endpoint)
+            .header(header::ACCEPT, "application/json")
+            .header(header::AUTHORIZATION, format!("Bearer {}", access_token))
+            .build()
+            .map_err(reqwest_middleware::Error::Reqwest)?;
+
+        let res = self.client.execute(req).await?;
+        match res.status() {
+            StatusCode::OK => res
+                .json::<ListRefunds>()
+                .await
+                .map(|refunds| refunds.items)
+                .map_err(TlError::Response),
+            status => Err(TlError::Api {
+                status,
+                body: res.json().await.map_err(TlError::Response)?,
+            }),
         }
     }
 
@@ -365,3 +489,47 @@ impl TlClient {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(eur_merchant_account_id: Option<Uuid>) -> TlClient {
+        TlClient::unauthenticated(TlConfig {
+            enviornment: TlEnviorment::Mock {
+                url: String::from("tl.invalid"),
+            },
+            client_id: String::new(),
+            client_secret: String::new(),
+            kid: String::new(),
+            private_key: String::new(),
+            redirect_uri: String::new(),
+            data_redirect_uri: String::new(),
+            merchant_account_id: Uuid::from_u128(1),
+            eur_merchant_account_id,
+        })
+    }
+
+    #[test]
+    fn payments_go_to_the_merchant_account_of_their_currency() {
+        let eur_account = Uuid::from_u128(2);
+        let client = client(Some(eur_account));
+        assert_eq!(
+            client.merchant_account_id("GBP").unwrap(),
+            Uuid::from_u128(1)
+        );
+        assert_eq!(client.merchant_account_id("EUR").unwrap(), eur_account);
+        assert!(matches!(
+            client.merchant_account_id("USD"),
+            Err(TlError::UnsupportedCurrency(currency)) if currency == "USD"
+        ));
+    }
+
+    #[test]
+    fn euro_payments_need_a_euro_merchant_account() {
+        assert!(matches!(
+            client(None).merchant_account_id("EUR"),
+            Err(TlError::UnsupportedCurrency(_))
+        ));
+    }
+}
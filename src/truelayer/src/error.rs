@@ -4,4 +4,11 @@ pub enum TlError {
     Response(#[from] reqwest::Error),
     #[error("Request error: {0}")]
     Reqwest(#[from] reqwest_middleware::Error),
+    #[error("Unexpected response {status}: {body}")]
+    Api {
+        status: reqwest::StatusCode,
+        body: serde_json::Value,
+    },
+    #[error("No merchant account configured for currency: {0}")]
+    UnsupportedCurrency(String),
 }
@@ -16,7 +16,10 @@ pub struct TlConfig {
     pub private_key: String,
     pub redirect_uri: String,
     pub data_redirect_uri: String,
+    /// GBP merchant account.
     pub merchant_account_id: Uuid,
+    #[serde(default)]
+    pub eur_merchant_account_id: Option<Uuid>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -1,4 +1,5 @@
-use serde::Deserialize;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 #[derive(Debug, Deserialize)]
@@ -47,12 +48,85 @@ pub struct AuthErrorResponse {
     pub error_description: String,
 }
 
+#[derive(Debug, Serialize)]
+pub struct CreatePayoutRequest<'a> {
+    pub amount_in_minor: u32,
+    pub merchant_account_id: Uuid,
+    pub currency: &'a str,
+    pub beneficiary: PayoutBeneficiary<'a>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PayoutBeneficiary<'a> {
+    ExternalAccount {
+        reference: &'a str,
+        account_holder_name: &'a str,
+        account_identifier: &'a AccountIdentifier,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AccountIdentifier {
+    Iban {
+        iban: String,
+    },
+    SortCodeAccountNumber {
+        sort_code: String,
+        account_number: String,
+    },
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CreatePayoutResponse {
     #[serde(rename = "id")]
     pub payout_id: Uuid,
 }
 
+#[derive(Debug, Serialize)]
+pub struct CreateRefundRequest<'a> {
+    pub amount_in_minor: u32,
+    pub reference: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRefundResponse {
+    #[serde(rename = "id")]
+    pub refund_id: Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Refund {
+    #[serde(rename = "id")]
+    pub refund_id: Uuid,
+    pub amount_in_minor: u32,
+    pub currency: String,
+    pub reference: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub status: RefundStatus,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum RefundStatus {
+    Pending,
+    Authorized,
+    Executed {
+        executed_at: DateTime<Utc>,
+    },
+    Failed {
+        failed_at: DateTime<Utc>,
+        failure_reason: String,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListRefunds {
+    pub items: Vec<Refund>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct GetAccounts {
     pub results: Vec<Account>,
@@ -84,3 +158,70 @@ pub struct AccountBalance {
     pub currency: String,
     pub current: f32,
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn refunds_read_with_their_status() {
+        let refunds: ListRefunds = serde_json::from_value(json!({
+            "items": [
+                {
+                    "id": "3bb7a6a0-0ed4-4bd6-a4e0-84f2a8d8ab42",
+                    "amount_in_minor": 1000,
+                    "currency": "GBP",
+                    "reference": "ETRANSFER REFUND",
+                    "created_at": "2024-01-02T10:00:00Z",
+                    "status": "executed",
+                    "executed_at": "2024-01-02T10:05:00Z"
+                },
+                {
+                    "id": "c1f1b2a4-9a1e-4f4e-9d84-7a3a2c1f4e55",
+                    "amount_in_minor": 500,
+                    "currency": "EUR",
+                    "reference": "ETRANSFER REFUND",
+                    "created_at": "2024-01-03T10:00:00Z",
+                    "status": "failed",
+                    "failed_at": "2024-01-03T10:01:00Z",
+                    "failure_reason": "insufficient_funds"
+                },
+                {
+                    "id": "0f6c2f56-3f3c-4b57-b6a5-0c0b2c8e6b77",
+                    "amount_in_minor": 200,
+                    "currency": "GBP",
+                    "reference": "ETRANSFER REFUND",
+                    "created_at": "2024-01-04T10:00:00Z",
+                    "status": "pending"
+                }
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(refunds.items.len(), 3);
+        assert!(matches!(
+            refunds.items[0].status,
+            RefundStatus::Executed { .. }
+        ));
+        assert!(matches!(
+            &refunds.items[1].status,
+            RefundStatus::Failed { failure_reason, .. } if failure_reason == "insufficient_funds"
+        ));
+        assert!(matches!(refunds.items[2].status, RefundStatus::Pending));
+        assert_eq!(refunds.items[1].currency, "EUR");
+    }
+
+    #[test]
+    fn refund_requests_carry_amount_and_reference() {
+        let request = CreateRefundRequest {
+            amount_in_minor: 1000,
+            reference: "ETRANSFER REFUND",
+        };
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({ "amount_in_minor": 1000, "reference": "ETRANSFER REFUND" })
+        );
+    }
+}